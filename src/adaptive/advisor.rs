@@ -0,0 +1,390 @@
+//! Workload-driven index advisor. Every executed predicate, join key and
+//! write is recorded per table. From that workload the advisor recommends
+//! secondary indexes whose estimated benefit for lookups outweighs what they
+//! cost to maintain under the observed writes, and dropping the indexes it
+//! created that have not been used for a quiet period. In autonomous mode
+//! the advice is applied periodically.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::catalogue::{Catalogue, Index};
+use crate::queryprocessing::dml::{DMLOperator, DMLPredicate, DMLTarget};
+use crate::queryprocessing::plan::{self, TableSize};
+use crate::storage;
+use crate::Database;
+
+/// Indexes that have not been used for this long are dropped.
+const UNUSED_INDEX_QUIET_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// How often the advice is applied in autonomous mode.
+const AUTONOMOUS_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct ColumnUsage {
+    equality_lookups: u64,
+    range_lookups: u64,
+    /// Joins on the column, each of which could look its rows up by key.
+    join_lookups: u64,
+}
+
+#[derive(Default)]
+struct TableWorkload {
+    columns: HashMap<String, ColumnUsage>,
+    inserts: u64,
+    updates: u64,
+    deletes: u64,
+}
+
+impl TableWorkload {
+    fn writes(&self) -> u64 {
+        self.inserts + self.updates + self.deletes
+    }
+}
+
+#[derive(Default)]
+struct Workload {
+    tables: HashMap<String, TableWorkload>,
+    /// When each index was last used, keyed by table and index name.
+    index_last_used: HashMap<(String, String), Instant>,
+}
+
+/// The workload observed by the index advisor of a database.
+pub struct Advisor {
+    workload: Mutex<Workload>,
+    autonomous: bool,
+    /// Time the advisor started observing; indexes that have never been
+    /// used count as idle since then.
+    started: Instant,
+}
+
+pub enum Write {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Advisor {
+    /// Starts observing the workload.
+    pub fn new(autonomous: bool) -> Advisor {
+        Advisor {
+            workload: Mutex::new(Workload::default()),
+            autonomous,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn record_predicates(&self, table: &str, predicates: &[DMLPredicate]) {
+        let mut workload = self.workload.lock().unwrap();
+        let table_workload = workload.tables.entry(table.to_string()).or_default();
+        // Only comparisons of whole values can use a secondary index.
+        for predicate in predicates
+            .iter()
+            .filter(|p| p.target == DMLTarget::Value && p.operator.is_comparison())
+        {
+            let usage = table_workload
+                .columns
+                .entry(predicate.column.clone())
+                .or_default();
+            match predicate.operator {
+                DMLOperator::Equals => usage.equality_lookups += 1,
+                DMLOperator::NotEquals => {}
+                _ => usage.range_lookups += 1,
+            }
+        }
+    }
+
+    /// Records a join on `column` of `table`.
+    pub fn record_join(&self, table: &str, column: &str) {
+        let mut workload = self.workload.lock().unwrap();
+        let table_workload = workload.tables.entry(table.to_string()).or_default();
+        table_workload
+            .columns
+            .entry(column.to_string())
+            .or_default()
+            .join_lookups += 1;
+    }
+
+    pub fn record_write(&self, table: &str, write: Write, rows: usize) {
+        let mut workload = self.workload.lock().unwrap();
+        let table_workload = workload.tables.entry(table.to_string()).or_default();
+        let rows = rows as u64;
+        match write {
+            Write::Insert => table_workload.inserts += rows,
+            Write::Update => table_workload.updates += rows,
+            Write::Delete => table_workload.deletes += rows,
+        }
+    }
+
+    pub fn record_index_use(&self, table: &str, index: &str) {
+        let mut workload = self.workload.lock().unwrap();
+        workload
+            .index_last_used
+            .insert((table.to_string(), index.to_string()), Instant::now());
+    }
+
+    pub fn is_autonomous(&self) -> bool {
+        self.autonomous
+    }
+}
+
+#[derive(Serialize)]
+pub struct IndexRecommendation {
+    pub table: String,
+    pub column: String,
+    pub index: String,
+    pub equality_lookups: u64,
+    pub range_lookups: u64,
+    pub join_lookups: u64,
+    pub writes: u64,
+    pub estimated_benefit: f64,
+    pub estimated_maintenance_cost: f64,
+}
+
+#[derive(Serialize)]
+pub struct UnusedIndex {
+    pub table: String,
+    pub column: String,
+    pub index: String,
+    pub idle_seconds: u64,
+}
+
+#[derive(Serialize)]
+pub struct Advice {
+    pub autonomous: bool,
+    pub create: Vec<IndexRecommendation>,
+    pub drop: Vec<UnusedIndex>,
+}
+
+pub fn index_name(table: &str, column: &str) -> String {
+    format!("idx_{}_{}", table, column).to_lowercase()
+}
+
+/// Recommends indexes to create and to drop for the workload seen so far.
+pub fn advise(db: &Database, catalogue: &Catalogue) -> Advice {
+    let workload = db.advisor.workload.lock().unwrap();
+    let mut create = Vec::new();
+    let mut drop = Vec::new();
+
+    for table in catalogue.tables() {
+        let table_data = db
+            .storage
+            .scan(table.name())
+            .unwrap_or_else(|_| storage::TableData::new(table.name().to_string()));
+        let size = TableSize::of(&table_data);
+
+        if let Some(table_workload) = workload.tables.get(table.name()) {
+            let seq_scan_cost = plan::seq_scan_cost(&size);
+            let equality_benefit = seq_scan_cost
+                - plan::index_scan_cost(&size, plan::estimate_rows(&size, &[DMLOperator::Equals]));
+            let range_benefit = seq_scan_cost
+                - plan::index_scan_cost(&size, plan::estimate_rows(&size, &[DMLOperator::LessThan]));
+            let writes = table_workload.writes();
+            let maintenance_cost = writes as f64 * plan::index_maintenance_cost(&size);
+
+            for (column_name, usage) in table_workload.columns.iter() {
+                let Some(column) = table.get_column(column_name) else {
+                    continue;
+                };
+                // Adaptive columns index themselves through cracking.
+                if column.is_adaptive() || table.index_on(column_name).is_some() {
+                    continue;
+                }
+                let benefit = (usage.equality_lookups + usage.join_lookups) as f64
+                    * equality_benefit.max(0.0)
+                    + usage.range_lookups as f64 * range_benefit.max(0.0);
+                if benefit > maintenance_cost {
+                    create.push(IndexRecommendation {
+                        table: table.name().to_string(),
+                        column: column_name.clone(),
+                        index: index_name(table.name(), column_name),
+                        equality_lookups: usage.equality_lookups,
+                        range_lookups: usage.range_lookups,
+                        join_lookups: usage.join_lookups,
+                        writes,
+                        estimated_benefit: benefit,
+                        estimated_maintenance_cost: maintenance_cost,
+                    });
+                }
+            }
+        }
+
+        // Indexes declared in the schema stay, however rarely they are used.
+        for index in table.indexes().iter().filter(|i| i.is_advised()) {
+            let last_used = workload
+                .index_last_used
+                .get(&(table.name().to_string(), index.name().to_string()))
+                .copied()
+                .unwrap_or(db.advisor.started);
+            let idle = last_used.elapsed();
+            if idle >= UNUSED_INDEX_QUIET_PERIOD {
+                drop.push(UnusedIndex {
+                    table: table.name().to_string(),
+                    column: index.column().to_string(),
+                    index: index.name().to_string(),
+                    idle_seconds: idle.as_secs(),
+                });
+            }
+        }
+    }
+
+    create.sort_by(|a, b| b.estimated_benefit.total_cmp(&a.estimated_benefit));
+    Advice {
+        autonomous: db.advisor.is_autonomous(),
+        create,
+        drop,
+    }
+}
+
+/// Creates and drops the indexes in `advice`.
+pub fn apply(
+    db: &Database,
+    catalogue: &mut Catalogue,
+    advice: &Advice,
+) -> Result<(), std::io::Error> {
+    if advice.create.is_empty() && advice.drop.is_empty() {
+        return Ok(());
+    }
+
+    for recommendation in advice.create.iter() {
+        let Some(table) = catalogue.get_table_mut(&recommendation.table) else {
+            continue;
+        };
+        info!(
+            "Creating index {} on {}({})",
+            recommendation.index, recommendation.table, recommendation.column
+        );
+        table.add_index(
+            Index::new(recommendation.index.clone(), recommendation.column.clone()).advised(),
+        );
+        // A new index gets a full quiet period to prove itself.
+        db.advisor
+            .record_index_use(&recommendation.table, &recommendation.index);
+    }
+
+    for unused in advice.drop.iter() {
+        let Some(table) = catalogue.get_table_mut(&unused.table) else {
+            continue;
+        };
+        info!("Dropping unused index {} on {}", unused.index, unused.table);
+        table.drop_index(&unused.index);
+        db.indexes.drop_index(&unused.table, &unused.column);
+        db.advisor
+            .workload
+            .lock()
+            .unwrap()
+            .index_last_used
+            .remove(&(unused.table.clone(), unused.index.clone()));
+    }
+
+    catalogue.save(&db.storage)
+}
+
+/// Periodically applies the advice while the server runs in autonomous mode.
+pub async fn run_autonomous(db: Arc<Database>) {
+    let mut interval = tokio::time::interval(AUTONOMOUS_INTERVAL);
+    loop {
+        interval.tick().await;
+        let mut catalogue = db.catalogue.lock().await;
+        let advice = advise(&db, &catalogue);
+        if let Err(error) = apply(&db, &mut catalogue, &advice) {
+            tracing::error!("Applying index advice failed: {}", error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queryprocessing::dml::DMLValue;
+
+    #[test]
+    fn test_table_workload_writes() {
+        let advisor = Advisor::new(false);
+        let table = "AdvisorTestTable";
+        advisor.record_write(table, Write::Insert, 3);
+        advisor.record_write(table, Write::Update, 2);
+        advisor.record_write(table, Write::Delete, 1);
+        let predicates = vec![
+            DMLPredicate {
+                column: "a".to_string(),
+                target: DMLTarget::Value,
+                operator: DMLOperator::Equals,
+                value: DMLValue::Literal("1".to_string()),
+            },
+            DMLPredicate {
+                column: "a".to_string(),
+                target: DMLTarget::Value,
+                operator: DMLOperator::LessThan,
+                value: DMLValue::Literal("1".to_string()),
+            },
+        ];
+        advisor.record_predicates(table, &predicates);
+
+        let workload = advisor.workload.lock().unwrap();
+        let table_workload = &workload.tables[table];
+        assert_eq!(table_workload.writes(), 6);
+        assert_eq!(table_workload.columns["a"].equality_lookups, 1);
+        assert_eq!(table_workload.columns["a"].range_lookups, 1);
+    }
+
+    #[tokio::test]
+    async fn test_advise_drops_only_advised_indexes() {
+        let mut db = Database::temporary("advisor_drop");
+        db.apply_schema(
+            "model Item { ItemID Int @id\n Name String\n Price Int\n @@index(Name) }\nmodel Sale { SaleID Int @id\n Price Int }",
+        )
+        .await
+        .unwrap();
+        db.query(
+            "select * from Sale join Item on Sale.Price = Item.Price;",
+            &[],
+        )
+        .await
+        .unwrap();
+        {
+            let workload = db.advisor.workload.lock().unwrap();
+            assert_eq!(workload.tables["Item"].columns["Price"].join_lookups, 1);
+            assert_eq!(workload.tables["Sale"].columns["Price"].join_lookups, 1);
+        }
+        let mut catalogue = db.catalogue.lock().await;
+        let advice = Advice {
+            autonomous: true,
+            create: vec![IndexRecommendation {
+                table: "Item".to_string(),
+                column: "Price".to_string(),
+                index: index_name("Item", "Price"),
+                equality_lookups: 0,
+                range_lookups: 0,
+                join_lookups: 1,
+                writes: 0,
+                estimated_benefit: 1.0,
+                estimated_maintenance_cost: 0.0,
+            }],
+            drop: Vec::new(),
+        };
+        apply(&db, &mut catalogue, &advice).unwrap();
+        let table = catalogue.get_table("Item").unwrap();
+        assert!(!table.index_on("Name").unwrap().is_advised());
+        assert!(table.index_on("Price").unwrap().is_advised());
+        drop(catalogue);
+
+        // Both indexes have gone unused for the quiet period.
+        let Some(started) = Instant::now().checked_sub(UNUSED_INDEX_QUIET_PERIOD) else {
+            return;
+        };
+        db.advisor.started = started;
+        db.advisor.workload.lock().unwrap().index_last_used.clear();
+        let advice = db.index_advice().await;
+        let dropped: Vec<&str> = advice
+            .drop
+            .iter()
+            .map(|unused| unused.index.as_str())
+            .collect();
+        assert_eq!(dropped, [index_name("Item", "Price")]);
+    }
+}
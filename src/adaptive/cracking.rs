@@ -0,0 +1,386 @@
+//! Database cracking: an adaptive index that is built as a side effect of
+//! range queries. Every query on a cracked column partitions (cracks) the
+//! piece of the cracker column that contains its bounds, so the column
+//! gradually becomes sorted where the workload looks at it.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::catalogue::basic_types::BasicType;
+use crate::storage::{index::Range, Row};
+
+/// A position in the value domain. Everything before the crack is below the
+/// pivot, or at most the pivot if the crack is inclusive.
+#[derive(Debug, Clone, Serialize)]
+pub struct Crack {
+    pub pivot: String,
+    pub inclusive: bool,
+    pub position: usize,
+}
+
+/// A copy of one column, reorganized by the range queries run against it.
+pub struct CrackerColumn {
+    data_type: BasicType,
+    /// Column values paired with the index of the row they belong to.
+    entries: Vec<(String, usize)>,
+    /// Cracks ordered by pivot.
+    cracks: Vec<Crack>,
+    /// Number of table rows that have been copied into the cracker column.
+    rows_seen: usize,
+    /// Version of the table the rows were copied from.
+    version: u64,
+}
+
+/// Result of a cracker selection.
+pub struct CrackerSelection {
+    pub row_indices: Vec<usize>,
+    /// Number of values the selection had to look at while cracking.
+    pub values_touched: usize,
+}
+
+#[derive(Serialize)]
+pub struct CrackerState {
+    pub table: String,
+    pub column: String,
+    pub values: usize,
+    pub pieces: usize,
+    pub cracks: Vec<Crack>,
+}
+
+impl CrackerColumn {
+    pub fn new(data_type: BasicType) -> CrackerColumn {
+        CrackerColumn {
+            data_type,
+            entries: Vec::new(),
+            cracks: Vec::new(),
+            rows_seen: 0,
+            version: 0,
+        }
+    }
+
+    /// Copies rows that were appended to the table since the last selection
+    /// into the piece their value belongs to. Any other change to the table
+    /// moves its `version` on, and the cracker column starts over.
+    pub fn refresh(&mut self, column: &str, rows: &[Row], version: u64) {
+        if version != self.version || rows.len() < self.rows_seen {
+            *self = CrackerColumn::new(self.data_type.clone());
+            self.version = version;
+        }
+        for (row_index, row) in rows.iter().enumerate().skip(self.rows_seen) {
+            // NULLs and values that are not of the column type never match
+            // a range, so they are left out of the cracker column.
+            let Some(value) = row.get(column).and_then(|value| value.as_str()) else {
+                continue;
+            };
+            if self.data_type.compare(value, value).is_some() {
+                self.insert(value.to_string(), row_index);
+            }
+        }
+        self.rows_seen = rows.len();
+    }
+
+    fn insert(&mut self, value: String, row_index: usize) {
+        // The value goes at the end of the first piece whose upper crack it
+        // falls below; every later crack moves one position to the right.
+        let piece = self
+            .cracks
+            .iter()
+            .position(|crack| self.below(&value, &crack.pivot, crack.inclusive))
+            .unwrap_or(self.cracks.len());
+        let position = match self.cracks.get(piece) {
+            Some(crack) => crack.position,
+            None => self.entries.len(),
+        };
+        self.entries.insert(position, (value, row_index));
+        for crack in self.cracks.iter_mut().skip(piece) {
+            crack.position += 1;
+        }
+    }
+
+    /// Whether `value` belongs left of a crack at `pivot`.
+    fn below(&self, value: &str, pivot: &str, inclusive: bool) -> bool {
+        match self.data_type.compare(value, pivot) {
+            Some(Ordering::Less) => true,
+            Some(Ordering::Equal) => inclusive,
+            _ => false,
+        }
+    }
+
+    fn compare_cracks(&self, pivot: &str, inclusive: bool, crack: &Crack) -> Ordering {
+        match self.data_type.compare(&crack.pivot, pivot) {
+            Some(Ordering::Equal) | None => crack.inclusive.cmp(&inclusive),
+            Some(ordering) => ordering,
+        }
+    }
+
+    /// Cracks the column at a pivot and returns the position of the crack.
+    fn crack(&mut self, pivot: &str, inclusive: bool, values_touched: &mut usize) -> usize {
+        let search = self
+            .cracks
+            .binary_search_by(|crack| self.compare_cracks(pivot, inclusive, crack));
+        let index = match search {
+            Ok(index) => return self.cracks[index].position,
+            Err(index) => index,
+        };
+
+        let start = if index == 0 {
+            0
+        } else {
+            self.cracks[index - 1].position
+        };
+        let end = match self.cracks.get(index) {
+            Some(crack) => crack.position,
+            None => self.entries.len(),
+        };
+        *values_touched += end - start;
+
+        // Partition the piece in place, moving values below the pivot left.
+        let mut left = start;
+        for i in start..end {
+            if self.below(&self.entries[i].0, pivot, inclusive) {
+                self.entries.swap(left, i);
+                left += 1;
+            }
+        }
+
+        self.cracks.insert(
+            index,
+            Crack {
+                pivot: pivot.to_string(),
+                inclusive,
+                position: left,
+            },
+        );
+        left
+    }
+
+    /// Selects the rows whose value lies in `range`, cracking on its bounds.
+    pub fn select(&mut self, range: &Range) -> CrackerSelection {
+        let mut values_touched = 0;
+        let start = match &range.lower {
+            // `x >= v` starts after everything below v, `x > v` after
+            // everything up to and including v.
+            Some((value, included)) => self.crack(value, !included, &mut values_touched),
+            None => 0,
+        };
+        let end = match &range.upper {
+            Some((value, included)) => self.crack(value, *included, &mut values_touched),
+            None => self.entries.len(),
+        };
+        let row_indices = if start < end {
+            self.entries[start..end].iter().map(|(_, row)| *row).collect()
+        } else {
+            Vec::new()
+        };
+        CrackerSelection {
+            row_indices,
+            values_touched,
+        }
+    }
+
+    /// Number of values in the piece that a crack at `pivot` would partition.
+    pub fn piece_size(&self, pivot: &str, inclusive: bool) -> usize {
+        let index = match self
+            .cracks
+            .binary_search_by(|crack| self.compare_cracks(pivot, inclusive, crack))
+        {
+            Ok(_) => return 0,
+            Err(index) => index,
+        };
+        let start = if index == 0 {
+            0
+        } else {
+            self.cracks[index - 1].position
+        };
+        let end = match self.cracks.get(index) {
+            Some(crack) => crack.position,
+            None => self.entries.len(),
+        };
+        end - start
+    }
+}
+
+type CrackerKey = (String, String);
+
+/// The cracker columns of a database.
+#[derive(Default)]
+pub struct CrackerColumns {
+    columns: Mutex<HashMap<CrackerKey, CrackerColumn>>,
+}
+
+impl CrackerColumns {
+    /// Runs a range selection on the cracker column of `table.column`,
+    /// creating the cracker column on first use. `version` is the version
+    /// of the table read before `rows` were.
+    pub fn select(
+        &self,
+        table: &str,
+        column: &str,
+        data_type: BasicType,
+        rows: &[Row],
+        version: u64,
+        range: &Range,
+    ) -> CrackerSelection {
+        let mut cracker_columns = self.columns.lock().unwrap();
+        let cracker = cracker_columns
+            .entry((table.to_string(), column.to_string()))
+            .or_insert_with(|| CrackerColumn::new(data_type));
+        cracker.refresh(column, rows, version);
+        cracker.select(range)
+    }
+
+    /// Estimated number of values a selection on `range` has to look at,
+    /// all `rows` of the table if it changed since its last selection.
+    pub fn estimate_values_touched(
+        &self,
+        table: &str,
+        column: &str,
+        range: &Range,
+        rows: usize,
+        version: u64,
+    ) -> usize {
+        let cracker_columns = self.columns.lock().unwrap();
+        let Some(cracker) = cracker_columns
+            .get(&(table.to_string(), column.to_string()))
+            .filter(|cracker| cracker.version == version)
+        else {
+            return rows;
+        };
+        let new_rows = rows.saturating_sub(cracker.rows_seen);
+        let lower = match &range.lower {
+            Some((value, included)) => cracker.piece_size(value, !included),
+            None => 0,
+        };
+        let upper = match &range.upper {
+            Some((value, included)) => cracker.piece_size(value, *included),
+            None => 0,
+        };
+        lower + upper + new_rows
+    }
+
+    /// Drops all crack state of a table. Called whenever its schema changes.
+    pub fn reset_table(&self, table: &str) {
+        let mut cracker_columns = self.columns.lock().unwrap();
+        cracker_columns.retain(|(cracked_table, _), _| cracked_table != table);
+    }
+
+    pub fn states(&self) -> Vec<CrackerState> {
+        let cracker_columns = self.columns.lock().unwrap();
+        let mut states: Vec<CrackerState> = cracker_columns
+            .iter()
+            .map(|((table, column), cracker)| CrackerState {
+                table: table.clone(),
+                column: column.clone(),
+                values: cracker.entries.len(),
+                pieces: cracker.cracks.len() + 1,
+                cracks: cracker.cracks.clone(),
+            })
+            .collect();
+        states.sort_by(|a, b| (&a.table, &a.column).cmp(&(&b.table, &b.column)));
+        states
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queryprocessing::dml::DMLOperator;
+
+    fn cracker_with(values: &[i64]) -> CrackerColumn {
+        let rows: Vec<Row> = values
+            .iter()
+            .map(|value| {
+                let mut row = Row::new();
+                row.insert("x".to_string(), serde_json::json!(value.to_string()));
+                row
+            })
+            .collect();
+        let mut cracker = CrackerColumn::new(BasicType::Int);
+        cracker.refresh("x", &rows, 0);
+        cracker
+    }
+
+    fn selected_values(cracker: &CrackerColumn, selection: &CrackerSelection) -> Vec<i64> {
+        let mut values: Vec<i64> = selection
+            .row_indices
+            .iter()
+            .map(|row| {
+                let (value, _) = cracker.entries.iter().find(|(_, r)| r == row).unwrap();
+                value.parse().unwrap()
+            })
+            .collect();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn test_cracking_range_queries() {
+        let mut cracker = cracker_with(&[13, 16, 4, 9, 2, 12, 7, 1, 19, 3, 14, 11, 8, 6]);
+
+        let mut range = Range::default();
+        range.restrict(DMLOperator::GreaterThan, "10");
+        range.restrict(DMLOperator::LessThan, "14");
+        let selection = cracker.select(&range);
+        assert_eq!(selected_values(&cracker, &selection), vec![11, 12, 13]);
+        assert_eq!(selection.values_touched, 14 + 6);
+        assert_eq!(cracker.cracks.len(), 2);
+
+        // A query inside an existing piece only touches that piece.
+        let mut range = Range::default();
+        range.restrict(DMLOperator::GreaterThanOrEquals, "12");
+        range.restrict(DMLOperator::LessThanOrEquals, "13");
+        let selection = cracker.select(&range);
+        assert_eq!(selected_values(&cracker, &selection), vec![12, 13]);
+        assert_eq!(selection.values_touched, 3 + 2);
+
+        // Repeating a query does not touch any values.
+        let selection = cracker.select(&range);
+        assert_eq!(selection.values_touched, 0);
+    }
+
+    #[test]
+    fn test_cracking_appended_rows() {
+        let mut cracker = cracker_with(&[5, 1, 9]);
+        let mut range = Range::default();
+        range.restrict(DMLOperator::LessThan, "6");
+        cracker.select(&range);
+
+        cracker.insert("3".to_string(), 3);
+        cracker.insert("7".to_string(), 4);
+        let selection = cracker.select(&range);
+        assert_eq!(selected_values(&cracker, &selection), vec![1, 3, 5]);
+        assert_eq!(selection.values_touched, 0);
+    }
+
+    #[test]
+    fn test_cracking_changed_rows() {
+        let rows = |values: &[i64]| -> Vec<Row> {
+            values
+                .iter()
+                .map(|value| {
+                    let mut row = Row::new();
+                    row.insert("x".to_string(), serde_json::json!(value.to_string()));
+                    row
+                })
+                .collect()
+        };
+        let columns = CrackerColumns::default();
+        let mut range = Range::default();
+        range.restrict(DMLOperator::LessThan, "6");
+        let select = |rows: &[Row], version| {
+            let mut row_indices = columns
+                .select("t", "x", BasicType::Int, rows, version, &range)
+                .row_indices;
+            row_indices.sort_unstable();
+            row_indices
+        };
+        assert_eq!(select(&rows(&[5, 1, 9]), 0), [0, 1]);
+
+        // A row changed in place, which leaves the number of rows as it was.
+        assert_eq!(select(&rows(&[5, 8, 9]), 1), [0]);
+        assert_eq!(columns.estimate_values_touched("t", "x", &range, 3, 2), 3);
+    }
+}
@@ -0,0 +1,162 @@
+//! Adaptive storage layout. Reads and writes are counted per table, and a
+//! background task moves read-mostly tables to the column layout and
+//! write-heavy tables back to the row layout. Tables whose model fixes the
+//! layout with `@@layout(...)` are never migrated.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tracing::info;
+
+use crate::catalogue::{Catalogue, Layout, Table};
+use crate::storage::Storage;
+use crate::Database;
+
+/// How often the workload is checked for tables to migrate.
+const MIGRATION_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Operations a table needs to see before its layout is reconsidered.
+const MIN_OPERATIONS: u64 = 100;
+
+/// Row tables whose reads make up at least this fraction move to columns.
+const COLUMN_READ_FRACTION: f64 = 0.9;
+
+/// Column tables whose reads make up at most this fraction move to rows.
+/// The gap to `COLUMN_READ_FRACTION` keeps tables from flapping.
+const ROW_READ_FRACTION: f64 = 0.5;
+
+#[derive(Default)]
+struct Operations {
+    reads: u64,
+    writes: u64,
+}
+
+/// Reads and writes per table since its layout was last judged.
+#[derive(Default)]
+pub struct Workload {
+    operations: Mutex<HashMap<String, Operations>>,
+}
+
+impl Workload {
+    pub fn record_read(&self, table: &str) {
+        let mut operations = self.operations.lock().unwrap();
+        operations.entry(table.to_string()).or_default().reads += 1;
+    }
+
+    pub fn record_write(&self, table: &str, rows: usize) {
+        let mut operations = self.operations.lock().unwrap();
+        operations.entry(table.to_string()).or_default().writes += rows as u64;
+    }
+}
+
+/// The layout the observed workload asks for, if it differs from `current`.
+fn target_layout(current: Layout, operations: &Operations) -> Option<Layout> {
+    let total = operations.reads + operations.writes;
+    if total < MIN_OPERATIONS {
+        return None;
+    }
+    let read_fraction = operations.reads as f64 / total as f64;
+    match current {
+        Layout::Row if read_fraction >= COLUMN_READ_FRACTION => Some(Layout::Column),
+        Layout::Column if read_fraction <= ROW_READ_FRACTION => Some(Layout::Row),
+        _ => None,
+    }
+}
+
+/// Rewrites the data of a table in `layout`.
+pub fn migrate(storage: &Storage, table: &mut Table, layout: Layout) -> Result<(), std::io::Error> {
+    let column_types = table
+        .columns()
+        .iter()
+        .map(|column| (column.name().to_string(), column.data_type()))
+        .collect();
+    storage.set_layout(table.name(), layout, column_types)?;
+    table.set_layout(layout);
+    Ok(())
+}
+
+/// Migrates the tables whose workload crossed a threshold. Tables with
+/// enough operations to be judged start counting afresh.
+pub fn run_migrations(db: &Database, catalogue: &mut Catalogue) -> Result<(), std::io::Error> {
+    let mut judged = Vec::new();
+    {
+        let mut operations = db.layout.operations.lock().unwrap();
+        operations.retain(|table_name, operations| {
+            if operations.reads + operations.writes < MIN_OPERATIONS {
+                return true;
+            }
+            judged.push((table_name.clone(), std::mem::take(operations)));
+            false
+        });
+    }
+
+    let mut migrated = false;
+    for (table_name, operations) in judged.iter() {
+        let Some(table) = catalogue.get_table_mut(table_name) else {
+            continue;
+        };
+        if table.is_layout_pinned() {
+            continue;
+        }
+        let Some(layout) = target_layout(table.layout(), operations) else {
+            continue;
+        };
+        info!(
+            "Migrating {} to {:?} layout after {} reads and {} writes",
+            table_name, layout, operations.reads, operations.writes
+        );
+        migrate(&db.storage, table, layout)?;
+        migrated = true;
+    }
+    if migrated {
+        catalogue.save(&db.storage)?;
+    }
+    Ok(())
+}
+
+/// Periodically migrates tables to the layout that suits their workload.
+pub async fn run_background_migrations(db: Arc<Database>) {
+    let mut interval = tokio::time::interval(MIGRATION_INTERVAL);
+    loop {
+        interval.tick().await;
+        let mut catalogue = db.catalogue.lock().await;
+        if let Err(error) = run_migrations(&db, &mut catalogue) {
+            tracing::error!("Migrating table layouts failed: {}", error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_layout() {
+        let scans = Operations {
+            reads: 95,
+            writes: 5,
+        };
+        assert_eq!(target_layout(Layout::Row, &scans), Some(Layout::Column));
+        assert_eq!(target_layout(Layout::Column, &scans), None);
+
+        let mixed = Operations {
+            reads: 70,
+            writes: 30,
+        };
+        assert_eq!(target_layout(Layout::Row, &mixed), None);
+        assert_eq!(target_layout(Layout::Column, &mixed), None);
+
+        let inserts = Operations {
+            reads: 10,
+            writes: 90,
+        };
+        assert_eq!(target_layout(Layout::Column, &inserts), Some(Layout::Row));
+
+        let few = Operations {
+            reads: 10,
+            writes: 0,
+        };
+        assert_eq!(target_layout(Layout::Row, &few), None);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod advisor;
+pub mod cracking;
+pub mod layout;
+pub mod statistics;
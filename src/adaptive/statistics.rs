@@ -0,0 +1,24 @@
+//! Cardinality feedback. The executor reports the selectivity it observed
+//! for every query shape it ran, and the planner prefers it over the default
+//! selectivities the next time it estimates the same shape.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Default)]
+pub struct Statistics {
+    selectivities: Mutex<HashMap<String, f64>>,
+}
+
+impl Statistics {
+    pub fn record(&self, shape: &str, selectivity: f64) {
+        let mut selectivities = self.selectivities.lock().unwrap();
+        selectivities.insert(shape.to_string(), selectivity);
+    }
+
+    /// The selectivity last observed for `shape`, if it ran before.
+    pub fn selectivity(&self, shape: &str) -> Option<f64> {
+        let selectivities = self.selectivities.lock().unwrap();
+        selectivities.get(shape).copied()
+    }
+}
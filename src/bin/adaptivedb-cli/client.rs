@@ -0,0 +1,63 @@
+//! A small HTTP client for the server's JSON API.
+
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::{Method, Request, StatusCode};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+
+pub struct Client {
+    base: String,
+    http: hyper_util::client::legacy::Client<HttpConnector, Full<Bytes>>,
+}
+
+pub struct Response {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl Client {
+    /// A client for the server at `base`, such as `http://127.0.0.1:3000`.
+    pub fn new(base: &str) -> Client {
+        Client {
+            base: base.trim_end_matches('/').to_string(),
+            http: hyper_util::client::legacy::Client::builder(TokioExecutor::new()).build_http(),
+        }
+    }
+
+    pub async fn get(&self, path: &str) -> Result<Response, String> {
+        self.send(Method::GET, path, Bytes::new()).await
+    }
+
+    pub async fn post(&self, path: &str, body: &serde_json::Value) -> Result<Response, String> {
+        self.send(Method::POST, path, Bytes::from(body.to_string()))
+            .await
+    }
+
+    async fn send(&self, method: Method, path: &str, body: Bytes) -> Result<Response, String> {
+        let uri = format!("{}{}", self.base, path);
+        let request = Request::builder()
+            .method(method)
+            .uri(&uri)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(Full::new(body))
+            .map_err(|error| format!("Invalid server address {}: {}", self.base, error))?;
+        let response = self.http.request(request).await.map_err(|error| {
+            match std::error::Error::source(&error) {
+                Some(cause) => format!("Cannot reach {}: {}: {}", self.base, error, cause),
+                None => format!("Cannot reach {}: {}", self.base, error),
+            }
+        })?;
+        let status = response.status();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|error| format!("Cannot read the response: {}", error))?
+            .to_bytes();
+        Ok(Response {
+            status,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        })
+    }
+}
@@ -0,0 +1,403 @@
+//! `adaptivedb-cli`, an interactive shell for an adaptivedb server. It sends
+//! SQL statements and model definitions to the server's HTTP API and prints
+//! the results as tables. Statements can also come from `-c` and `-f`, which
+//! run them and exit.
+
+mod client;
+mod output;
+mod script;
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use clap::Parser;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use client::Client;
+use script::{Buffer, Statement};
+
+const HELP: &str = "\
+\\d             list tables
+\\d TABLE       describe a table
+\\i FILE        run the statements in a file
+\\timing [on|off]
+               print how long each statement takes
+\\q             quit
+\\?             show this help
+
+SQL statements end with ;. Model definitions end with the } closing the model.";
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "adaptivedb-cli",
+    version,
+    about = "An interactive shell for adaptivedb"
+)]
+struct Cli {
+    /// Address of the server's HTTP API.
+    #[arg(long, env = "ADAPTIVEDB_URL", default_value = "http://127.0.0.1:3000")]
+    url: String,
+    /// Runs a statement or meta-command and exits. May be given more than once.
+    #[arg(short, long)]
+    command: Vec<String>,
+    /// Runs the statements in a file and exits. May be given more than once.
+    #[arg(short, long)]
+    file: Vec<PathBuf>,
+    /// Prints how long each statement takes.
+    #[arg(long)]
+    timing: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Ok,
+    Failed,
+    Quit,
+}
+
+struct Shell {
+    client: Client,
+    runtime: tokio::runtime::Runtime,
+    timing: bool,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to start the runtime");
+    let mut shell = Shell {
+        client: Client::new(&cli.url),
+        runtime,
+        timing: cli.timing,
+    };
+
+    if cli.command.is_empty() && cli.file.is_empty() {
+        if let Err(error) = shell.repl(&cli.url) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut failed = false;
+    for command in cli.command.iter() {
+        match shell.run_all(script::split_script(command)) {
+            Outcome::Ok => {}
+            Outcome::Failed => failed = true,
+            Outcome::Quit => break,
+        }
+    }
+    for file in cli.file.iter() {
+        match shell.run_file(file) {
+            Outcome::Ok => {}
+            Outcome::Failed => failed = true,
+            Outcome::Quit => break,
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+impl Shell {
+    fn repl(&mut self, url: &str) -> rustyline::Result<()> {
+        let mut editor = DefaultEditor::new()?;
+        let history =
+            std::env::var_os("HOME").map(|home| Path::new(&home).join(".adaptivedb_history"));
+        if let Some(history) = history.as_ref() {
+            // There is no history yet on the first run.
+            let _ = editor.load_history(history);
+        }
+        println!(
+            "adaptivedb-cli {} connected to {}. Type \\? for help.",
+            env!("CARGO_PKG_VERSION"),
+            url
+        );
+
+        let mut buffer = Buffer::default();
+        'repl: loop {
+            let prompt = if buffer.is_empty() {
+                "adaptivedb=> "
+            } else {
+                "adaptivedb-> "
+            };
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        editor.add_history_entry(line.as_str())?;
+                    }
+                    for statement in buffer.push_line(&line) {
+                        if self.run(statement) == Outcome::Quit {
+                            break 'repl;
+                        }
+                    }
+                }
+                // Ctrl-C discards the statement being typed.
+                Err(ReadlineError::Interrupted) => buffer.clear(),
+                Err(ReadlineError::Eof) => break,
+                Err(error) => return Err(error),
+            }
+        }
+
+        if let Some(history) = history.as_ref() {
+            editor.save_history(history)?;
+        }
+        Ok(())
+    }
+
+    fn run_file(&mut self, path: &Path) -> Outcome {
+        match std::fs::read_to_string(path) {
+            Ok(text) => self.run_all(script::split_script(&text)),
+            Err(error) => {
+                eprintln!("ERROR: Cannot read {}: {}", path.display(), error);
+                Outcome::Failed
+            }
+        }
+    }
+
+    /// Runs statements one after the other, carrying on after errors. Model
+    /// definitions next to each other are sent together, so they may refer
+    /// to each other.
+    fn run_all(&mut self, statements: Vec<Statement>) -> Outcome {
+        let mut outcome = Outcome::Ok;
+        let mut models: Vec<String> = Vec::new();
+        let mut statements = statements.into_iter().peekable();
+        while let Some(statement) = statements.next() {
+            let statement = match statement {
+                Statement::Model(model) => {
+                    models.push(model);
+                    if let Some(Statement::Model(_)) = statements.peek() {
+                        continue;
+                    }
+                    Statement::Model(std::mem::take(&mut models).join("\n\n"))
+                }
+                statement => statement,
+            };
+            match self.run(statement) {
+                Outcome::Ok => {}
+                Outcome::Failed => outcome = Outcome::Failed,
+                Outcome::Quit => return Outcome::Quit,
+            }
+        }
+        outcome
+    }
+
+    fn run(&mut self, statement: Statement) -> Outcome {
+        let result = match statement {
+            Statement::Meta(command) => return self.meta(&command),
+            Statement::Sql(sql) => self.timed(|shell| shell.sql(&sql)),
+            Statement::Model(model) => self.timed(|shell| shell.model(&model)),
+        };
+        match result {
+            Ok(output) => {
+                print!("{}", output);
+                Outcome::Ok
+            }
+            Err(error) => {
+                eprintln!("ERROR: {}", error);
+                Outcome::Failed
+            }
+        }
+    }
+
+    fn timed(
+        &mut self,
+        run: impl FnOnce(&mut Shell) -> Result<String, String>,
+    ) -> Result<String, String> {
+        let start = Instant::now();
+        let result = run(self);
+        if self.timing {
+            let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+            match result {
+                Ok(output) => Ok(format!("{}Time: {:.3} ms\n", output, elapsed)),
+                Err(error) => Err(format!("{}\nTime: {:.3} ms", error, elapsed)),
+            }
+        } else {
+            result
+        }
+    }
+
+    fn meta(&mut self, command: &str) -> Outcome {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let argument = words.next();
+        let result = match (name, argument) {
+            ("\\q", _) => return Outcome::Quit,
+            ("\\?", _) => Ok(format!("{}\n", HELP)),
+            ("\\d", None) => self.catalogue().map(|catalogue| output::tables(&catalogue)),
+            ("\\d", Some(table)) => self.catalogue().and_then(|catalogue| {
+                catalogue
+                    .tables
+                    .iter()
+                    .find(|t| t.name == table)
+                    .or_else(|| {
+                        catalogue
+                            .tables
+                            .iter()
+                            .find(|t| t.name.eq_ignore_ascii_case(table))
+                    })
+                    .map(output::describe)
+                    .ok_or_else(|| format!("Did not find table {}", table))
+            }),
+            ("\\i", Some(file)) => return self.run_file(Path::new(file)),
+            ("\\i", None) => Err("\\i needs a file name".to_string()),
+            ("\\timing", setting) => {
+                self.timing = match setting {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => !self.timing,
+                };
+                Ok(format!(
+                    "Timing is {}.\n",
+                    if self.timing { "on" } else { "off" }
+                ))
+            }
+            _ => Err(format!("Unknown command {}. Try \\? for help.", name)),
+        };
+        match result {
+            Ok(output) => {
+                print!("{}", output);
+                Outcome::Ok
+            }
+            Err(error) => {
+                eprintln!("ERROR: {}", error);
+                Outcome::Failed
+            }
+        }
+    }
+
+    fn catalogue(&self) -> Result<output::Catalogue, String> {
+        let response = self.runtime.block_on(self.client.get("/catalogue"))?;
+        if !response.status.is_success() {
+            return Err(response.body);
+        }
+        serde_json::from_str(&response.body)
+            .map_err(|error| format!("Unexpected catalogue from the server: {}", error))
+    }
+
+    fn model(&self, model: &str) -> Result<String, String> {
+        let response = self
+            .runtime
+            .block_on(self.client.post("/catalogue", &json!({ "schema": model })))?;
+        let body: Value = match serde_json::from_str(&response.body) {
+            Ok(body) => body,
+            Err(_) => return Err(response.body.trim_end().to_string()),
+        };
+        if let Some(errors) = server_errors(&body, model) {
+            return Err(errors);
+        }
+        match body.pointer("/result/tables").and_then(Value::as_array) {
+            Some(tables) => {
+                let tables: Vec<String> = tables.iter().map(text).collect();
+                if tables.is_empty() {
+                    Ok("OK\n".to_string())
+                } else {
+                    Ok(format!("Created tables {}\n", tables.join(", ")))
+                }
+            }
+            None => Err(format!("Unexpected response from the server: {}", body)),
+        }
+    }
+
+    fn sql(&self, sql: &str) -> Result<String, String> {
+        let response = self
+            .runtime
+            .block_on(self.client.post("/data", &json!({ "query": sql })))?;
+        let body: Value = match serde_json::from_str(&response.body) {
+            Ok(body) => body,
+            Err(_) => return Err(response.body.trim_end().to_string()),
+        };
+        if let Some(errors) = server_errors(&body, sql) {
+            return Err(errors);
+        }
+
+        match body.get("result") {
+            Some(Value::Array(rows)) => {
+                let rows: Vec<serde_json::Map<String, Value>> = rows
+                    .iter()
+                    .filter_map(|row| row.as_object().cloned())
+                    .collect();
+                let preferred = match select_list(sql) {
+                    Some(columns) => columns,
+                    None if rows.is_empty() => Vec::new(),
+                    None => self
+                        .catalogue()
+                        .map(|catalogue| {
+                            catalogue
+                                .tables
+                                .iter()
+                                .flat_map(|table| {
+                                    table.columns.iter().map(move |column| {
+                                        format!("{}.{}", table.name, column.name)
+                                    })
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                };
+                Ok(output::rows(&rows, &preferred))
+            }
+            Some(Value::Object(result)) => {
+                if let Some(plan) = result.get("plan") {
+                    let mut output = serde_json::to_string_pretty(plan).unwrap_or_default();
+                    output.push('\n');
+                    Ok(output)
+                } else {
+                    status(result)
+                }
+            }
+            // Declaring and closing cursors answer without a result.
+            _ => match body.as_object() {
+                Some(body) if body.contains_key("success") => status(body),
+                _ => Err(format!("Unexpected response from the server: {}", body)),
+            },
+        }
+    }
+}
+
+/// The errors of a response, rendered against the statement they are about.
+fn server_errors(body: &Value, source: &str) -> Option<String> {
+    let errors = body.get("errors")?;
+    match Vec::<output::Error>::deserialize(errors) {
+        Ok(errors) => Some(output::errors(&errors, source)),
+        Err(_) => Some(errors.to_string()),
+    }
+}
+
+fn status(result: &serde_json::Map<String, Value>) -> Result<String, String> {
+    match result.get("affected_rows") {
+        Some(rows) if rows == 1 => Ok("OK, 1 row affected\n".to_string()),
+        Some(rows) => Ok(format!("OK, {} rows affected\n", rows)),
+        None => Ok("OK\n".to_string()),
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// The columns a select names, or `None` if it selects `*` or is not a
+/// select.
+fn select_list(sql: &str) -> Option<Vec<String>> {
+    let sql = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+    let lower = sql.to_ascii_lowercase();
+    let start = lower.find("select")? + "select".len();
+    let end = start + lower[start..].find(" from ")?;
+    let columns: Vec<String> = sql[start..end]
+        .split(',')
+        .map(|column| column.trim().to_string())
+        .collect();
+    if columns.iter().any(|column| column == "*") {
+        None
+    } else {
+        Some(columns)
+    }
+}
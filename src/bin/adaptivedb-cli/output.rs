@@ -0,0 +1,340 @@
+//! Formats results as aligned text tables, and errors with the part of the
+//! statement they are about, like `psql` does.
+
+use serde::Deserialize;
+
+/// The parts of the catalogue the shell shows.
+#[derive(Debug, Deserialize)]
+pub struct Catalogue {
+    pub tables: Vec<Table>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Table {
+    pub name: String,
+    pub columns: Vec<Column>,
+    #[serde(deserialize_with = "primary_key")]
+    pub primary_key: Vec<usize>,
+    #[serde(default)]
+    pub indexes: Vec<Index>,
+    #[serde(default)]
+    pub layout: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Column {
+    pub name: String,
+    #[serde(deserialize_with = "data_type")]
+    pub data_type: String,
+    pub nullable: bool,
+    #[serde(default)]
+    pub adaptive: bool,
+}
+
+/// Reads the positions of the primary key columns. Servers from before
+/// composite keys send a single position.
+fn primary_key<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<usize>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PrimaryKey {
+        Column(usize),
+        Columns(Vec<usize>),
+    }
+    Ok(match PrimaryKey::deserialize(deserializer)? {
+        PrimaryKey::Column(position) => vec![position],
+        PrimaryKey::Columns(positions) => positions,
+    })
+}
+
+/// Reads a column type the way the model DDL writes it, as in
+/// `Decimal(10, 2)` or `String[]`. Enums are shown by name.
+fn data_type<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(type_name(&value))
+}
+
+fn type_name(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(name) if name == "TimestampTz" => "Timestamptz".to_string(),
+        serde_json::Value::String(name) => name.clone(),
+        serde_json::Value::Object(object) => match object.iter().next() {
+            Some((kind, details)) if kind == "Decimal" => {
+                format!("Decimal({}, {})", details["precision"], details["scale"])
+            }
+            Some((kind, details)) if kind == "Enum" => {
+                details["name"].as_str().unwrap_or(kind).to_string()
+            }
+            Some((kind, element)) if kind == "Array" => format!("{}[]", type_name(element)),
+            Some((kind, _)) => kind.clone(),
+            None => value.to_string(),
+        },
+        _ => value.to_string(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Index {
+    pub name: String,
+    pub column: String,
+    /// `btree` or `inverted`.
+    #[serde(default)]
+    pub kind: Option<String>,
+}
+
+/// An error reported by the server.
+#[derive(Debug, Deserialize)]
+pub struct Error {
+    pub message: String,
+    #[serde(default)]
+    pub span: Option<Span>,
+    #[serde(default)]
+    pub hint: Option<String>,
+}
+
+/// Characters of the statement an error is about.
+#[derive(Debug, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Renders errors about `source`. The line an error is about is shown with
+/// its span underlined.
+pub fn errors(errors: &[Error], source: &str) -> String {
+    let mut output = Vec::new();
+    for error in errors {
+        let mut lines = vec![error.message.clone()];
+        if let Some(span) = &error.span {
+            let mut line_start = 0;
+            for (number, line) in source.split('\n').enumerate() {
+                let length = line.chars().count();
+                if span.start <= line_start + length {
+                    let prefix = format!("LINE {}: ", number + 1);
+                    let column = span.start - line_start;
+                    let width = (span.end.min(line_start + length) - span.start).max(1);
+                    lines.push(format!("{}{}", prefix, line));
+                    lines.push(format!(
+                        "{}{}",
+                        " ".repeat(prefix.len() + column),
+                        "^".repeat(width)
+                    ));
+                    break;
+                }
+                line_start += length + 1;
+            }
+        }
+        if let Some(hint) = &error.hint {
+            lines.push(format!("HINT: {}", hint));
+        }
+        output.push(lines.join("\n"));
+    }
+    output.join("\nERROR: ")
+}
+
+/// Renders `rows` under `headers`, followed by a row count.
+pub fn table(headers: &[String], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let line = |values: &[String]| {
+        let cells: Vec<String> = values
+            .iter()
+            .zip(widths.iter())
+            .map(|(value, width)| format!(" {:<width$} ", value, width = width))
+            .collect();
+        cells.join("|").trim_end().to_string()
+    };
+
+    let mut output = String::new();
+    output.push_str(&line(headers));
+    output.push('\n');
+    let rule: Vec<String> = widths.iter().map(|width| "-".repeat(width + 2)).collect();
+    output.push_str(&rule.join("+"));
+    output.push('\n');
+    for row in rows {
+        output.push_str(&line(row));
+        output.push('\n');
+    }
+    let noun = if rows.len() == 1 { "row" } else { "rows" };
+    output.push_str(&format!("({} {})\n", rows.len(), noun));
+    output
+}
+
+/// Renders result rows. Columns are ordered like `preferred`, which may hold
+/// qualified or unqualified names; columns missing from it come last.
+pub fn rows(rows: &[serde_json::Map<String, serde_json::Value>], preferred: &[String]) -> String {
+    let mut headers: Vec<String> = Vec::new();
+    for row in rows {
+        for key in row.keys() {
+            if !headers.contains(key) {
+                headers.push(key.clone());
+            }
+        }
+    }
+    let rank = |header: &String| {
+        preferred
+            .iter()
+            .position(|name| {
+                name == header
+                    || name.ends_with(&format!(".{}", header))
+                    || header.ends_with(&format!(".{}", name))
+            })
+            .unwrap_or(preferred.len())
+    };
+    // Sorting is stable, so unknown columns keep their order.
+    headers.sort_by_key(rank);
+
+    let values: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            headers
+                .iter()
+                .map(|header| match row.get(header) {
+                    None | Some(serde_json::Value::Null) => String::new(),
+                    Some(serde_json::Value::String(value)) => value.clone(),
+                    Some(value) => value.to_string(),
+                })
+                .collect()
+        })
+        .collect();
+    table(&headers, &values)
+}
+
+/// Lists the tables of the catalogue.
+pub fn tables(catalogue: &Catalogue) -> String {
+    let headers = ["Name", "Columns", "Layout", "Indexes"].map(String::from);
+    let rows: Vec<Vec<String>> = catalogue
+        .tables
+        .iter()
+        .map(|table| {
+            vec![
+                table.name.clone(),
+                table.columns.len().to_string(),
+                table.layout.clone().unwrap_or_else(|| "row".to_string()),
+                table.indexes.len().to_string(),
+            ]
+        })
+        .collect();
+    table(&headers, &rows)
+}
+
+/// Describes the columns and indexes of a table.
+pub fn describe(table: &Table) -> String {
+    let headers = ["Column", "Type", "Nullable", "Key"].map(String::from);
+    let rows: Vec<Vec<String>> = table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(position, column)| {
+            let mut key = Vec::new();
+            if table.primary_key.contains(&position) {
+                key.push("primary key");
+            }
+            if column.adaptive {
+                key.push("adaptive");
+            }
+            vec![
+                column.name.clone(),
+                column.data_type.clone(),
+                if column.nullable { "yes" } else { "no" }.to_string(),
+                key.join(", "),
+            ]
+        })
+        .collect();
+    let mut output = format!("Table {}\n", table.name);
+    output.push_str(&self::table(&headers, &rows));
+    for index in table.indexes.iter() {
+        match index.kind.as_deref() {
+            Some("inverted") => output.push_str(&format!(
+                "Index {} on {} (inverted)\n",
+                index.name, index.column
+            )),
+            _ => output.push_str(&format!("Index {} on {}\n", index.name, index.column)),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_rows_table() {
+        let rows: Vec<serde_json::Map<String, serde_json::Value>> = vec![
+            serde_json::from_str(r#"{"FirstName": "Pat", "EmployeeID": "1"}"#).unwrap(),
+            serde_json::from_str(
+                r#"{"FirstName": "Alexandra", "EmployeeID": "2", "JobTitle": null}"#,
+            )
+            .unwrap(),
+        ];
+        let preferred = vec!["EmployeeID".to_string(), "Employee.FirstName".to_string()];
+        assert_eq!(
+            super::rows(&rows, &preferred),
+            " EmployeeID | FirstName | JobTitle\n\
+             ------------+-----------+----------\n \
+             1          | Pat       |\n \
+             2          | Alexandra |\n\
+             (2 rows)\n"
+        );
+    }
+
+    #[test]
+    fn test_column_types() {
+        let columns: Vec<super::Column> = serde_json::from_str(
+            r#"[
+                {"name": "Amount", "data_type": {"Decimal": {"precision": 10, "scale": 2}}, "nullable": false},
+                {"name": "Role", "data_type": {"Enum": {"name": "Role", "labels": ["admin"]}}, "nullable": false},
+                {"name": "Opened", "data_type": "TimestampTz", "nullable": true},
+                {"name": "Tags", "data_type": {"Array": "String"}, "nullable": false}
+            ]"#,
+        )
+        .unwrap();
+        let types: Vec<&str> = columns.iter().map(|c| c.data_type.as_str()).collect();
+        assert_eq!(types, ["Decimal(10, 2)", "Role", "Timestamptz", "String[]"]);
+    }
+
+    #[test]
+    fn test_describe_composite_key() {
+        let table: super::Table = serde_json::from_str(
+            r#"{"name": "SalaryHistory", "primary_key": [0, 1], "columns": [
+                {"name": "EmployeeID", "data_type": "Int", "nullable": false},
+                {"name": "FromDate", "data_type": "Date", "nullable": false},
+                {"name": "Amount", "data_type": "Int", "nullable": false}
+            ]}"#,
+        )
+        .unwrap();
+        let description = super::describe(&table);
+        let keys: Vec<&str> = description
+            .lines()
+            .filter(|line| line.ends_with("primary key"))
+            .map(|line| line.split('|').next().unwrap().trim())
+            .collect();
+        assert_eq!(keys, ["EmployeeID", "FromDate"]);
+
+        let legacy: super::Table =
+            serde_json::from_str(r#"{"name": "T", "primary_key": 0, "columns": []}"#).unwrap();
+        assert_eq!(legacy.primary_key, [0]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let errors: Vec<super::Error> = serde_json::from_str(
+            r#"[
+                {"message": "Syntax error at or near \"fro\"", "span": {"start": 16, "end": 19}},
+                {"message": "Table Nope does not exist", "hint": "Check the name"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            super::errors(&errors, "select *\n  from fro Employee"),
+            "Syntax error at or near \"fro\"\n\
+             LINE 2:   from fro Employee\n\
+             \x20              ^^^\n\
+             ERROR: Table Nope does not exist\n\
+             HINT: Check the name"
+        );
+    }
+}
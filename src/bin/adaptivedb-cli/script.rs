@@ -0,0 +1,183 @@
+//! Splits input into statements. SQL statements end at a `;` outside string
+//! literals and trigger bodies, model and enum definitions end at the `}`
+//! closing them, and lines starting with `\` are meta-commands. `--` starts a
+//! comment running to the end of the line.
+
+#[derive(Debug, PartialEq)]
+pub enum Statement {
+    Sql(String),
+    /// A model or enum definition.
+    Model(String),
+    Meta(String),
+}
+
+/// Collects lines typed into the shell until they complete a statement.
+#[derive(Default)]
+pub struct Buffer {
+    pending: String,
+}
+
+impl Buffer {
+    /// Adds a line and returns the statements it completes.
+    pub fn push_line(&mut self, line: &str) -> Vec<Statement> {
+        let text = format!("{}{}\n", self.pending, line);
+        let (statements, pending) = split(&text);
+        self.pending = pending;
+        statements
+    }
+
+    /// Whether no statement has been started.
+    pub fn is_empty(&self) -> bool {
+        self.pending.trim().is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+/// Splits a whole script. A statement missing its `;` at the end of the
+/// script is still returned.
+pub fn split_script(text: &str) -> Vec<Statement> {
+    let (mut statements, pending) = split(text);
+    if !pending.trim().is_empty() {
+        statements.push(complete(&pending));
+    }
+    statements
+}
+
+/// Returns the complete statements in `text` and the text of the statement
+/// that is still incomplete.
+fn split(text: &str) -> (Vec<Statement>, String) {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    let mut depth = 0usize;
+
+    for line in text.split_inclusive('\n') {
+        if current.trim().is_empty() && line.trim_start().starts_with('\\') {
+            statements.push(Statement::Meta(line.trim().to_string()));
+            current.clear();
+            continue;
+        }
+
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if !in_string && c == '-' && chars.peek() == Some(&'-') {
+                // Drop the comment but keep the line break.
+                if line.ends_with('\n') {
+                    current.push('\n');
+                }
+                break;
+            }
+            current.push(c);
+            match c {
+                '\'' => in_string = !in_string,
+                '{' if !in_string => depth += 1,
+                '}' if !in_string && depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 && is_model(&current) {
+                        statements.push(complete(&current));
+                        current.clear();
+                    }
+                }
+                ';' if !in_string && !is_model(&current) && !in_trigger_body(&current) => {
+                    statements.push(complete(&current));
+                    current.clear();
+                }
+                _ => {}
+            }
+        }
+        // A `;` directly after a statement leaves blank text behind.
+        if current.trim().is_empty() {
+            current.clear();
+        }
+    }
+    (statements, current)
+}
+
+/// Whether `text` starts a model or enum definition of the schema.
+fn is_model(text: &str) -> bool {
+    text.split_whitespace()
+        .next()
+        .is_some_and(|word| word.eq_ignore_ascii_case("model") || word.eq_ignore_ascii_case("enum"))
+}
+
+/// Whether `text`, which ends with a `;`, is a `create trigger` statement
+/// whose body is still open.
+fn in_trigger_body(text: &str) -> bool {
+    let mut words = text.split_whitespace();
+    let is_trigger = words
+        .next()
+        .is_some_and(|word| word.eq_ignore_ascii_case("create"))
+        && words
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case("trigger"));
+    let last = text[..text.len() - 1].split_whitespace().next_back();
+    is_trigger && !last.is_some_and(|word| word.eq_ignore_ascii_case("end"))
+}
+
+fn complete(text: &str) -> Statement {
+    let text = text.trim().to_string();
+    if is_model(&text) {
+        Statement::Model(text)
+    } else {
+        Statement::Sql(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_script() {
+        let script = "-- Employees\n\
+            insert into Employee (EmployeeID, LastName) values ('1', 'Doe; Jr');\n\
+            commit;\n\
+            \\d Employee\n\
+            enum Role {\n    Lead\n    Member\n}\n\
+            model Team {\n    TeamID Int @id\n}\n\
+            create trigger t after delete on Team begin\n    delete from Employee;\nend;\n\
+            select * from Employee";
+        assert_eq!(
+            split_script(script),
+            vec![
+                Statement::Sql(
+                    "insert into Employee (EmployeeID, LastName) values ('1', 'Doe; Jr');"
+                        .to_string()
+                ),
+                Statement::Sql("commit;".to_string()),
+                Statement::Meta("\\d Employee".to_string()),
+                Statement::Model("enum Role {\n    Lead\n    Member\n}".to_string()),
+                Statement::Model("model Team {\n    TeamID Int @id\n}".to_string()),
+                Statement::Sql(
+                    "create trigger t after delete on Team begin\n    delete from Employee;\nend;"
+                        .to_string()
+                ),
+                Statement::Sql("select * from Employee".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_buffer_multi_line() {
+        let mut buffer = Buffer::default();
+        assert!(buffer.push_line("select FirstName").is_empty());
+        assert!(!buffer.is_empty());
+        let statements = buffer.push_line("from Employee; select 1");
+        assert_eq!(
+            statements,
+            vec![Statement::Sql(
+                "select FirstName\nfrom Employee;".to_string()
+            )]
+        );
+        assert!(!buffer.is_empty());
+        buffer.clear();
+        assert_eq!(
+            buffer.push_line("\\timing"),
+            vec![Statement::Meta("\\timing".to_string())]
+        );
+        assert!(buffer.is_empty());
+    }
+}
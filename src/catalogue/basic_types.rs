@@ -1,17 +1,84 @@
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
 
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Decimals are scaled to `i128`, which holds 38 digits.
+pub const MAX_DECIMAL_PRECISION: u8 = 38;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BasicType {
     Int,
     Float,
     Bool,
     String,
     Date,
-    Blob
+    Blob,
+    /// Exact number of at most `precision` digits, `scale` of them after the
+    /// point. Stored with exactly `scale` digits after the point.
+    Decimal {
+        precision: u8,
+        scale: u8,
+    },
+    /// Date and time of day, without a time zone.
+    Timestamp,
+    /// Point in time. Stored in UTC, as `2024-01-31 12:00:00+00`.
+    TimestampTz,
+    /// Time of day.
+    Time,
+    /// Stored in lowercase, with hyphens.
+    Uuid,
+    /// JSON document. Stored as compact text, with the keys of objects
+    /// sorted, and parsed again by every operator that reads into it.
+    Json,
+    /// One of the labels of a user-defined enum, ordered as they are
+    /// declared.
+    Enum(EnumType),
+    /// List of values of a type. Stored as a PostgreSQL array literal, as
+    /// `{a,b,"c d"}`, whose elements may be NULL.
+    Array(Box<BasicType>),
+}
+
+/// An enum declared by an `enum Name { ... }` block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnumType {
+    name: String,
+    labels: Vec<String>,
+}
+
+impl EnumType {
+    pub fn new(name: String, labels: Vec<String>) -> EnumType {
+        EnumType { name, labels }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    fn position(&self, label: &str) -> Option<usize> {
+        self.labels.iter().position(|l| l == label)
+    }
+}
+
+/// Why a text is not a value of a type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueError {
+    /// It is not written as a value of the type.
+    Invalid,
+    /// It is a number with more digits than the type holds.
+    OutOfRange,
 }
 
 impl BasicType {
+    /// The type of a name in the model DDL. Decimals and enums take more
+    /// than a name and are made by the DDL parser.
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<BasicType> {
         match s {
             "Int" => Some(BasicType::Int),
@@ -20,7 +87,613 @@ impl BasicType {
             "String" => Some(BasicType::String),
             "Date" => Some(BasicType::Date),
             "Blob" => Some(BasicType::Blob),
-            _ => None
+            "Timestamp" => Some(BasicType::Timestamp),
+            "Timestamptz" => Some(BasicType::TimestampTz),
+            "Time" => Some(BasicType::Time),
+            "Uuid" => Some(BasicType::Uuid),
+            "Json" => Some(BasicType::Json),
+            _ => None,
         }
     }
-}
\ No newline at end of file
+
+    /// Parses a literal of this type into its stored form.
+    pub fn parse_value(&self, text: &str) -> Result<String, ValueError> {
+        let invalid = ValueError::Invalid;
+        match self {
+            BasicType::Int => text
+                .trim()
+                .parse::<i64>()
+                .map(|value| value.to_string())
+                .map_err(|_| invalid),
+            BasicType::Float => match text.trim().parse::<f64>() {
+                Ok(_) => Ok(text.trim().to_string()),
+                Err(_) => Err(invalid),
+            },
+            BasicType::Bool => parse_bool(text)
+                .map(|value| value.to_string())
+                .ok_or(invalid),
+            BasicType::String | BasicType::Blob => Ok(text.to_string()),
+            BasicType::Date => parse_date(text.trim()).map(days_to_date).ok_or(invalid),
+            BasicType::Decimal { precision, scale } => {
+                let value = parse_decimal(text, *scale)?;
+                if value.unsigned_abs() >= 10u128.pow(*precision as u32) {
+                    return Err(ValueError::OutOfRange);
+                }
+                Ok(format_decimal(value, *scale))
+            }
+            BasicType::Timestamp => parse_timestamp(text)
+                .map(|(micros, _)| format_timestamp(micros))
+                .ok_or(invalid),
+            BasicType::TimestampTz => parse_timestamp(text)
+                .map(|(micros, offset)| format!("{}+00", format_timestamp(micros - offset)))
+                .ok_or(invalid),
+            BasicType::Time => parse_time(text.trim()).map(format_time).ok_or(invalid),
+            BasicType::Uuid => parse_uuid(text).map(format_uuid).ok_or(invalid),
+            BasicType::Json => serde_json::from_str::<serde_json::Value>(text)
+                .map(|value| value.to_string())
+                .map_err(|_| invalid),
+            BasicType::Enum(enum_type) => match enum_type.position(text) {
+                Some(_) => Ok(text.to_string()),
+                None => Err(invalid),
+            },
+            BasicType::Array(element_type) => {
+                let elements = parse_array(text).ok_or(invalid)?;
+                let elements = elements
+                    .into_iter()
+                    .map(|element| match element {
+                        Some(element) => element_type.parse_value(&element).map(Some),
+                        None => Ok(None),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(format_array(&elements))
+            }
+        }
+    }
+
+    /// The type of the elements of an array type.
+    pub fn element_type(&self) -> Option<&BasicType> {
+        match self {
+            BasicType::Array(element_type) => Some(element_type),
+            _ => None,
+        }
+    }
+
+    /// Compares two stored values of this type. Returns `None` if either
+    /// value cannot be interpreted as this type.
+    pub fn compare(&self, left: &str, right: &str) -> Option<Ordering> {
+        match self {
+            BasicType::Int | BasicType::Float => {
+                let left = left.parse::<f64>().ok()?;
+                let right = right.parse::<f64>().ok()?;
+                left.partial_cmp(&right)
+            }
+            BasicType::Bool => Some(parse_bool(left)?.cmp(&parse_bool(right)?)),
+            BasicType::String | BasicType::Blob => Some(left.cmp(right)),
+            BasicType::Date => Some(parse_date(left)?.cmp(&parse_date(right)?)),
+            BasicType::Decimal { scale, .. } => {
+                // Literals may have more digits after the point than stored
+                // values, so compare at the larger scale.
+                let scale = (*scale)
+                    .max(fraction_digits(left))
+                    .max(fraction_digits(right))
+                    .min(MAX_DECIMAL_PRECISION);
+                let left = parse_decimal(left, scale).ok()?;
+                let right = parse_decimal(right, scale).ok()?;
+                Some(left.cmp(&right))
+            }
+            BasicType::Timestamp => {
+                let (left, _) = parse_timestamp(left)?;
+                let (right, _) = parse_timestamp(right)?;
+                Some(left.cmp(&right))
+            }
+            BasicType::TimestampTz => {
+                let (left, left_offset) = parse_timestamp(left)?;
+                let (right, right_offset) = parse_timestamp(right)?;
+                Some((left - left_offset).cmp(&(right - right_offset)))
+            }
+            BasicType::Time => Some(parse_time(left)?.cmp(&parse_time(right)?)),
+            BasicType::Uuid => Some(parse_uuid(left)?.cmp(&parse_uuid(right)?)),
+            BasicType::Json => {
+                let left: serde_json::Value = serde_json::from_str(left).ok()?;
+                let right: serde_json::Value = serde_json::from_str(right).ok()?;
+                Some(left.to_string().cmp(&right.to_string()))
+            }
+            BasicType::Enum(enum_type) => {
+                Some(enum_type.position(left)?.cmp(&enum_type.position(right)?))
+            }
+            BasicType::Array(element_type) => {
+                // Element by element, with NULL elements after all others,
+                // then the shorter array first.
+                let left = parse_array(left)?;
+                let right = parse_array(right)?;
+                for (left, right) in left.iter().zip(right.iter()) {
+                    let ordering = match (left, right) {
+                        (Some(left), Some(right)) => element_type.compare(left, right)?,
+                        (Some(_), None) => Ordering::Less,
+                        (None, Some(_)) => Ordering::Greater,
+                        (None, None) => Ordering::Equal,
+                    };
+                    if ordering != Ordering::Equal {
+                        return Some(ordering);
+                    }
+                }
+                Some(left.len().cmp(&right.len()))
+            }
+        }
+    }
+}
+
+/// The name of the type in the model DDL.
+impl fmt::Display for BasicType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BasicType::Decimal { precision, scale } => {
+                write!(f, "Decimal({}, {})", precision, scale)
+            }
+            BasicType::TimestampTz => write!(f, "Timestamptz"),
+            BasicType::Enum(enum_type) => write!(f, "{}", enum_type.name),
+            BasicType::Array(element_type) => write!(f, "{}[]", element_type),
+            data_type => write!(f, "{:?}", data_type),
+        }
+    }
+}
+
+/// The elements of a one-dimensional PostgreSQL array literal, as
+/// `{a,"b c",NULL}`. NULL elements are `None`.
+pub fn parse_array(text: &str) -> Option<Vec<Option<String>>> {
+    let inner = text.trim().strip_prefix('{')?.strip_suffix('}')?;
+    let mut elements = Vec::new();
+    if inner.trim().is_empty() {
+        return Some(elements);
+    }
+    let mut chars = inner.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.next_if_eq(&'"').is_some() {
+            let mut element = String::new();
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => element.push(chars.next()?),
+                    c => element.push(c),
+                }
+            }
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            elements.push(Some(element));
+        } else {
+            let mut element = String::new();
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                if matches!(c, '{' | '}' | '"' | '\\') {
+                    return None;
+                }
+                element.push(c);
+            }
+            let element = element.trim();
+            if element.is_empty() {
+                return None;
+            }
+            elements.push((!element.eq_ignore_ascii_case("null")).then(|| element.to_string()));
+        }
+        match chars.next() {
+            Some(',') => continue,
+            None => return Some(elements),
+            Some(_) => return None,
+        }
+    }
+}
+
+/// Writes elements as a PostgreSQL array literal, quoting those that would
+/// not read back as themselves.
+pub fn format_array(elements: &[Option<String>]) -> String {
+    let elements: Vec<String> = elements
+        .iter()
+        .map(|element| match element {
+            None => "NULL".to_string(),
+            Some(element)
+                if element.is_empty()
+                    || element.eq_ignore_ascii_case("null")
+                    || element.chars().any(|c| {
+                        c.is_whitespace() || matches!(c, '{' | '}' | ',' | '"' | '\\')
+                    }) =>
+            {
+                format!("\"{}\"", element.replace('\\', "\\\\").replace('"', "\\\""))
+            }
+            Some(element) => element.clone(),
+        })
+        .collect();
+    format!("{{{}}}", elements.join(","))
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    match text.trim().to_lowercase().as_str() {
+        "t" | "true" | "y" | "yes" | "on" | "1" => Some(true),
+        "f" | "false" | "n" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses digits that must all be present, as the `12` of `12:30`.
+fn parse_digits(text: &str, count: usize) -> Option<i64> {
+    if text.len() != count || !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+/// Days since 1970-01-01 of an ISO 8601 date, as `2024-01-31`.
+fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.splitn(3, '-');
+    let year = parse_digits(parts.next()?, 4)?;
+    let month = parse_digits(parts.next()?, 2)?;
+    let day = parse_digits(parts.next()?, 2)?;
+    let days = date_to_days(date)?;
+    // Rejects days past the end of their month, as 2023-02-29.
+    (days_to_date(days) == format!("{:04}-{:02}-{:02}", year, month, day)).then_some(days)
+}
+
+/// Days since 1970-01-01 of an ISO 8601 date.
+pub fn date_to_days(date: &str) -> Option<i64> {
+    let mut parts = date.get(..10)?.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Howard Hinnant's days_from_civil.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146097 + day_of_era - 719468)
+}
+
+/// The ISO 8601 date `days` after 1970-01-01.
+pub fn days_to_date(days: i64) -> String {
+    // Howard Hinnant's civil_from_days.
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Microseconds since midnight of a time of day, as `12:30`, `12:30:15` or
+/// `12:30:15.25`.
+pub fn parse_time(time: &str) -> Option<i64> {
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (time, None),
+    };
+    let mut parts = time.split(':');
+    let hours = parse_digits(parts.next()?, 2)?;
+    let minutes = parse_digits(parts.next()?, 2)?;
+    let seconds = match parts.next() {
+        Some(seconds) => parse_digits(seconds, 2)?,
+        None if fraction.is_none() => 0,
+        None => return None,
+    };
+    if parts.next().is_some() || hours > 23 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+    // Digits past microseconds are dropped.
+    let micros = match fraction {
+        Some(fraction) => {
+            if fraction.is_empty() || !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+                return None;
+            }
+            format!("{:0<6}", &fraction[..fraction.len().min(6)])
+                .parse::<i64>()
+                .ok()?
+        }
+        None => 0,
+    };
+    Some(((hours * 60 + minutes) * 60 + seconds) * MICROS_PER_SECOND + micros)
+}
+
+pub fn format_time(micros: i64) -> String {
+    let seconds = micros / MICROS_PER_SECOND;
+    let text = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    match micros % MICROS_PER_SECOND {
+        0 => text,
+        fraction => {
+            let fraction = format!("{:06}", fraction);
+            format!("{}.{}", text, fraction.trim_end_matches('0'))
+        }
+    }
+}
+
+/// Microseconds since 1970-01-01 00:00 of a timestamp, as `2024-01-31`,
+/// `2024-01-31 12:30:00` or `2024-01-31T12:30:00.5+02:00`, and the offset of
+/// its time zone in microseconds. Timestamps without a time zone are in UTC.
+pub fn parse_timestamp(timestamp: &str) -> Option<(i64, i64)> {
+    let timestamp = timestamp.trim();
+    let date = timestamp.get(..10)?;
+    let days = parse_date(date)?;
+    let rest = &timestamp[10..];
+    if rest.is_empty() {
+        return Some((days * MICROS_PER_DAY, 0));
+    }
+    let rest = rest.strip_prefix(['T', ' '])?;
+
+    let (time, offset) = match rest.find(['Z', 'z', '+', '-']) {
+        Some(position) => (&rest[..position], parse_offset(&rest[position..])?),
+        None => (rest, 0),
+    };
+    let micros = parse_time(time.trim_end())?;
+    Some((days * MICROS_PER_DAY + micros, offset))
+}
+
+/// Microseconds of a time zone offset, as `Z`, `+02`, `+02:00` or `-0530`.
+fn parse_offset(offset: &str) -> Option<i64> {
+    if offset.eq_ignore_ascii_case("z") {
+        return Some(0);
+    }
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let offset = offset[1..].replace(':', "");
+    let (hours, minutes) = match offset.len() {
+        2 => (parse_digits(&offset, 2)?, 0),
+        4 => (
+            parse_digits(&offset[..2], 2)?,
+            parse_digits(&offset[2..], 2)?,
+        ),
+        _ => return None,
+    };
+    if hours > 15 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes) * 60 * MICROS_PER_SECOND)
+}
+
+pub fn format_timestamp(micros: i64) -> String {
+    format!(
+        "{} {}",
+        days_to_date(micros.div_euclid(MICROS_PER_DAY)),
+        format_time(micros.rem_euclid(MICROS_PER_DAY))
+    )
+}
+
+/// Number of digits after the point of a decimal literal.
+fn fraction_digits(text: &str) -> u8 {
+    match text.trim().split_once('.') {
+        Some((_, fraction)) => fraction.len().min(u8::MAX as usize) as u8,
+        None => 0,
+    }
+}
+
+/// The value of a decimal literal times `10^scale`, rounded half away from
+/// zero.
+pub fn parse_decimal(text: &str, scale: u8) -> Result<i128, ValueError> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let all_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if (whole.is_empty() && fraction.is_empty()) || !all_digits(whole) || !all_digits(fraction) {
+        return Err(ValueError::Invalid);
+    }
+
+    let scale = scale as usize;
+    let whole = whole.trim_start_matches('0');
+    if whole.len() + scale > MAX_DECIMAL_PRECISION as usize {
+        return Err(ValueError::OutOfRange);
+    }
+    let kept = &fraction[..fraction.len().min(scale)];
+    let mut value: i128 = format!("{}{:0<scale$}", whole, kept, scale = scale)
+        .parse()
+        .unwrap_or(0);
+    if fraction
+        .as_bytes()
+        .get(scale)
+        .is_some_and(|digit| *digit >= b'5')
+    {
+        value += 1;
+    }
+    Ok(if negative { -value } else { value })
+}
+
+pub fn format_decimal(value: i128, scale: u8) -> String {
+    let scale = scale as usize;
+    let digits = format!("{:0>width$}", value.unsigned_abs(), width = scale + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - scale);
+    let sign = if value < 0 { "-" } else { "" };
+    if scale == 0 {
+        format!("{}{}", sign, whole)
+    } else {
+        format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+
+/// The bytes of a UUID, written with or without hyphens.
+pub fn parse_uuid(text: &str) -> Option<[u8; 16]> {
+    let hex: String = text.trim().chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 {
+        return None;
+    }
+    let mut bytes = [0; 16];
+    for (position, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(position * 2..position * 2 + 2)?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+pub fn format_uuid(bytes: [u8; 16]) -> String {
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// The current time as a value of a date or time type, in UTC.
+pub fn now(data_type: &BasicType) -> Option<String> {
+    let micros = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_micros() as i64);
+    match data_type {
+        BasicType::Timestamp => Some(format_timestamp(micros)),
+        BasicType::TimestampTz => Some(format!("{}+00", format_timestamp(micros))),
+        BasicType::Date => Some(days_to_date(micros.div_euclid(MICROS_PER_DAY))),
+        BasicType::Time => Some(format_time(micros.rem_euclid(MICROS_PER_DAY))),
+        _ => None,
+    }
+}
+
+/// A random version 4 UUID.
+pub fn random_uuid() -> String {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    use std::sync::atomic::{self, AtomicU64};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut bytes = [0; 16];
+    for half in bytes.chunks_mut(8) {
+        // Every RandomState is seeded differently, the counter and the time
+        // tell apart the UUIDs of one seed.
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, atomic::Ordering::Relaxed));
+        hasher.write_u128(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos()),
+        );
+        half.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    format_uuid(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data_type: &BasicType, text: &str) -> Result<String, ValueError> {
+        data_type.parse_value(text)
+    }
+
+    #[test]
+    fn test_parse_values() {
+        let decimal = BasicType::Decimal {
+            precision: 5,
+            scale: 2,
+        };
+        assert_eq!(parse(&decimal, "12.5"), Ok("12.50".to_string()));
+        assert_eq!(parse(&decimal, "-0.005"), Ok("-0.01".to_string()));
+        assert_eq!(parse(&decimal, "999.999"), Err(ValueError::OutOfRange));
+        assert_eq!(parse(&decimal, "1e3"), Err(ValueError::Invalid));
+
+        let timestamp = "2024-02-29T23:30:00.25+02:00";
+        assert_eq!(
+            parse(&BasicType::Timestamp, timestamp),
+            Ok("2024-02-29 23:30:00.25".to_string())
+        );
+        assert_eq!(
+            parse(&BasicType::TimestampTz, timestamp),
+            Ok("2024-02-29 21:30:00.25+00".to_string())
+        );
+        assert_eq!(
+            parse(&BasicType::TimestampTz, "2024-03-01 01:00-0530"),
+            Ok("2024-03-01 06:30:00+00".to_string())
+        );
+        assert_eq!(
+            parse(&BasicType::Timestamp, "2023-02-29 10:00"),
+            Err(ValueError::Invalid)
+        );
+        assert_eq!(parse(&BasicType::Time, "07:05"), Ok("07:05:00".to_string()));
+        assert_eq!(parse(&BasicType::Time, "24:00"), Err(ValueError::Invalid));
+
+        assert_eq!(
+            parse(&BasicType::Uuid, "A0EEBC999C0B4EF8BB6D6BB9BD380A11"),
+            Ok("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11".to_string())
+        );
+        assert_eq!(
+            parse(&BasicType::Json, r#"{"b": [1, 2], "a": null}"#),
+            Ok(r#"{"a":null,"b":[1,2]}"#.to_string())
+        );
+        assert_eq!(parse(&BasicType::Bool, "Yes"), Ok("true".to_string()));
+
+        let role = BasicType::Enum(EnumType::new(
+            "Role".to_string(),
+            vec!["admin".to_string(), "user".to_string()],
+        ));
+        assert_eq!(parse(&role, "user"), Ok("user".to_string()));
+        assert_eq!(parse(&role, "root"), Err(ValueError::Invalid));
+    }
+
+    #[test]
+    fn test_compare_values() {
+        let decimal = BasicType::Decimal {
+            precision: 10,
+            scale: 2,
+        };
+        assert_eq!(decimal.compare("2.50", "10.00"), Some(Ordering::Less));
+        assert_eq!(decimal.compare("1.00", "1.001"), Some(Ordering::Less));
+        assert_eq!(decimal.compare("1.10", "1.1"), Some(Ordering::Equal));
+
+        let tz = BasicType::TimestampTz;
+        assert_eq!(
+            tz.compare("2024-01-01 10:00:00+00", "2024-01-01T12:00:00+02:00"),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            BasicType::Time.compare("09:00:00", "10:00"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(BasicType::Date.compare("2024-13-01", "2024-01-01"), None);
+
+        let role = BasicType::Enum(EnumType::new(
+            "Role".to_string(),
+            vec!["user".to_string(), "admin".to_string()],
+        ));
+        assert_eq!(role.compare("admin", "user"), Some(Ordering::Greater));
+        assert_eq!(
+            BasicType::Json.compare(r#"{"a":1,"b":2}"#, r#"{"b": 2, "a": 1}"#),
+            Some(Ordering::Equal)
+        );
+
+        let scores = BasicType::Array(Box::new(BasicType::Int));
+        assert_eq!(scores.compare("{2,10}", "{2,9}"), Some(Ordering::Greater));
+        assert_eq!(scores.compare("{2}", "{2,NULL}"), Some(Ordering::Less));
+    }
+
+    #[test]
+    fn test_arrays() {
+        let tags = BasicType::Array(Box::new(BasicType::String));
+        assert_eq!(
+            parse(&tags, r#"{ rust , "a b", NULL, "NULL", "q\"uote" }"#),
+            Ok(r#"{rust,"a b",NULL,"NULL","q\"uote"}"#.to_string())
+        );
+        assert_eq!(parse(&tags, "{}"), Ok("{}".to_string()));
+        assert_eq!(parse(&tags, "{a,,b}"), Err(ValueError::Invalid));
+        assert_eq!(parse(&tags, "{{a}}"), Err(ValueError::Invalid));
+        assert_eq!(parse(&tags, "a,b"), Err(ValueError::Invalid));
+
+        let scores = BasicType::Array(Box::new(BasicType::Int));
+        assert_eq!(parse(&scores, "{1, 02}"), Ok("{1,2}".to_string()));
+        assert_eq!(parse(&scores, "{1,x}"), Err(ValueError::Invalid));
+        assert_eq!(tags.to_string(), "String[]");
+    }
+}
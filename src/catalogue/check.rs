@@ -0,0 +1,474 @@
+//! CHECK constraint expressions, as in `@check("Salary > 0")` and
+//! `@@check("FromDate <= ToDate or ToDate is null")`. Operands are columns
+//! and literals, compared as values of the column they are compared with.
+//! Like in SQL, a comparison with NULL is unknown and a constraint only
+//! fails when its expression is false.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use super::basic_types::BasicType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckExpression {
+    expression: Expression,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    Compare(Operand, Comparison, Operand),
+    IsNull(Operand, bool),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Column(String),
+    Literal(String),
+    Null,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl CheckExpression {
+    pub fn parse(text: &str) -> Result<CheckExpression, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            position: 0,
+        };
+        let expression = parser.parse_or()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("unexpected text"));
+        }
+        Ok(CheckExpression { expression })
+    }
+
+    /// The columns the expression reads, in order of appearance.
+    pub fn columns(&self) -> Vec<&str> {
+        let mut columns = Vec::new();
+        collect_columns(&self.expression, &mut columns);
+        columns
+    }
+
+    /// Checks that every column exists and every literal is a value of the
+    /// type of what it is compared with. `column_type` looks up a column.
+    pub fn resolve(&self, column_type: &impl Fn(&str) -> Option<BasicType>) -> Result<(), String> {
+        for column in self.columns() {
+            if column_type(column).is_none() {
+                return Err(format!("column {} does not exist", column));
+            }
+        }
+        resolve_literals(&self.expression, column_type)
+    }
+
+    /// Whether the expression holds for a row, or `None` if it is unknown.
+    /// `value` reads a column of the row, None for NULL.
+    pub fn holds<'a>(
+        &self,
+        value: &impl Fn(&str) -> Option<&'a str>,
+        column_type: &impl Fn(&str) -> Option<BasicType>,
+    ) -> Option<bool> {
+        test(&self.expression, value, column_type)
+    }
+}
+
+fn collect_columns<'a>(expression: &'a Expression, columns: &mut Vec<&'a str>) {
+    let mut operand = |operand: &'a Operand| {
+        if let Operand::Column(column) = operand {
+            if !columns.contains(&column.as_str()) {
+                columns.push(column);
+            }
+        }
+    };
+    match expression {
+        Expression::Compare(left, _, right) => {
+            operand(left);
+            operand(right);
+        }
+        Expression::IsNull(tested, _) => operand(tested),
+        Expression::And(left, right) | Expression::Or(left, right) => {
+            collect_columns(left, columns);
+            collect_columns(right, columns);
+        }
+        Expression::Not(operand) => collect_columns(operand, columns),
+    }
+}
+
+/// The type values of a comparison are compared as: that of its first
+/// column. Comparisons of literals compare text.
+fn comparison_type(
+    left: &Operand,
+    right: &Operand,
+    column_type: &impl Fn(&str) -> Option<BasicType>,
+) -> Option<BasicType> {
+    match (left, right) {
+        (Operand::Column(column), _) | (_, Operand::Column(column)) => column_type(column),
+        _ => Some(BasicType::String),
+    }
+}
+
+fn resolve_literals(
+    expression: &Expression,
+    column_type: &impl Fn(&str) -> Option<BasicType>,
+) -> Result<(), String> {
+    match expression {
+        Expression::Compare(left, _, right) => {
+            let Some(data_type) = comparison_type(left, right, column_type) else {
+                return Ok(());
+            };
+            for operand in [left, right] {
+                if let Operand::Literal(literal) = operand {
+                    if data_type.parse_value(literal).is_err() {
+                        return Err(format!(
+                            "'{}' is not a value of type {}",
+                            literal, data_type
+                        ));
+                    }
+                }
+            }
+            Ok(())
+        }
+        Expression::IsNull(..) => Ok(()),
+        Expression::And(left, right) | Expression::Or(left, right) => {
+            resolve_literals(left, column_type)?;
+            resolve_literals(right, column_type)
+        }
+        Expression::Not(operand) => resolve_literals(operand, column_type),
+    }
+}
+
+fn test<'a>(
+    expression: &Expression,
+    value: &impl Fn(&str) -> Option<&'a str>,
+    column_type: &impl Fn(&str) -> Option<BasicType>,
+) -> Option<bool> {
+    match expression {
+        Expression::Compare(left, comparison, right) => {
+            let data_type = comparison_type(left, right, column_type)?;
+            let operand = |operand: &Operand| match operand {
+                Operand::Column(column) => value(column).map(str::to_string),
+                Operand::Literal(literal) => data_type.parse_value(literal).ok(),
+                Operand::Null => None,
+            };
+            let ordering = data_type.compare(&operand(left)?, &operand(right)?)?;
+            Some(match comparison {
+                Comparison::Equal => ordering == Ordering::Equal,
+                Comparison::NotEqual => ordering != Ordering::Equal,
+                Comparison::Less => ordering == Ordering::Less,
+                Comparison::LessOrEqual => ordering != Ordering::Greater,
+                Comparison::Greater => ordering == Ordering::Greater,
+                Comparison::GreaterOrEqual => ordering != Ordering::Less,
+            })
+        }
+        Expression::IsNull(operand, negated) => {
+            let is_null = match operand {
+                Operand::Column(column) => value(column).is_none(),
+                Operand::Literal(_) => false,
+                Operand::Null => true,
+            };
+            Some(is_null != *negated)
+        }
+        Expression::And(left, right) => match test(left, value, column_type) {
+            Some(false) => Some(false),
+            left => match (left, test(right, value, column_type)) {
+                (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+        },
+        Expression::Or(left, right) => match test(left, value, column_type) {
+            Some(true) => Some(true),
+            left => match (left, test(right, value, column_type)) {
+                (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+        },
+        Expression::Not(operand) => test(operand, value, column_type).map(|holds| !holds),
+    }
+}
+
+impl fmt::Display for CheckExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expression::Compare(left, comparison, right) => {
+                let symbol = match comparison {
+                    Comparison::Equal => "=",
+                    Comparison::NotEqual => "<>",
+                    Comparison::Less => "<",
+                    Comparison::LessOrEqual => "<=",
+                    Comparison::Greater => ">",
+                    Comparison::GreaterOrEqual => ">=",
+                };
+                write!(f, "{} {} {}", left, symbol, right)
+            }
+            Expression::IsNull(operand, false) => write!(f, "{} is null", operand),
+            Expression::IsNull(operand, true) => write!(f, "{} is not null", operand),
+            Expression::And(left, right) => write!(f, "({} and {})", left, right),
+            Expression::Or(left, right) => write!(f, "({} or {})", left, right),
+            Expression::Not(operand) => write!(f, "not {}", operand),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Column(column) => write!(f, "{}", column),
+            Operand::Literal(literal) => write!(f, "'{}'", literal.replace('\'', "''")),
+            Operand::Null => write!(f, "null"),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.position)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        while self
+            .chars
+            .get(self.position)
+            .is_some_and(|c| c.is_whitespace())
+        {
+            self.position += 1;
+        }
+        self.chars.get(self.position).copied()
+    }
+
+    /// Consumes `symbol` if the text continues with it.
+    fn eat(&mut self, symbol: &str) -> bool {
+        self.peek();
+        let matches = symbol
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| self.chars.get(self.position + offset) == Some(&c));
+        if matches {
+            self.position += symbol.chars().count();
+        }
+        matches
+    }
+
+    /// Consumes the keyword `word`, in any case, if the text continues with
+    /// it.
+    fn eat_word(&mut self, word: &str) -> bool {
+        self.peek();
+        let start = self.position;
+        let end = start + word.len();
+        let matches = self.chars.get(start..end).is_some_and(|chars| {
+            chars
+                .iter()
+                .zip(word.chars())
+                .all(|(c, expected)| c.eq_ignore_ascii_case(&expected))
+        }) && !self
+            .chars
+            .get(end)
+            .is_some_and(|c| c.is_alphanumeric() || *c == '_');
+        if matches {
+            self.position = end;
+        }
+        matches
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_and()?;
+        while self.eat_word("or") {
+            let right = self.parse_and()?;
+            expression = Expression::Or(Box::new(expression), Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_unary()?;
+        while self.eat_word("and") {
+            let right = self.parse_unary()?;
+            expression = Expression::And(Box::new(expression), Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, String> {
+        if self.eat_word("not") {
+            return Ok(Expression::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.eat("(") {
+            let expression = self.parse_or()?;
+            if !self.eat(")") {
+                return Err(self.error("expected )"));
+            }
+            return Ok(expression);
+        }
+
+        let left = self.parse_operand()?;
+        if self.eat_word("is") {
+            let negated = self.eat_word("not");
+            if !self.eat_word("null") {
+                return Err(self.error("expected null after is"));
+            }
+            return Ok(Expression::IsNull(left, negated));
+        }
+        let comparison = if self.eat("=") {
+            Comparison::Equal
+        } else if self.eat("!=") || self.eat("<>") {
+            Comparison::NotEqual
+        } else if self.eat("<=") {
+            Comparison::LessOrEqual
+        } else if self.eat(">=") {
+            Comparison::GreaterOrEqual
+        } else if self.eat("<") {
+            Comparison::Less
+        } else if self.eat(">") {
+            Comparison::Greater
+        } else {
+            return Err(self.error("expected a comparison"));
+        };
+        let right = self.parse_operand()?;
+        Ok(Expression::Compare(left, comparison, right))
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, String> {
+        match self.peek() {
+            Some('\'') => {
+                // Quotes are escaped by doubling them.
+                let mut literal = String::new();
+                self.position += 1;
+                loop {
+                    match self.chars.get(self.position) {
+                        Some('\'') if self.chars.get(self.position + 1) == Some(&'\'') => {
+                            literal.push('\'');
+                            self.position += 2;
+                        }
+                        Some('\'') => {
+                            self.position += 1;
+                            return Ok(Operand::Literal(literal));
+                        }
+                        Some(c) => {
+                            literal.push(*c);
+                            self.position += 1;
+                        }
+                        None => return Err(self.error("unterminated string")),
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let start = self.position;
+                self.position += 1;
+                while self
+                    .chars
+                    .get(self.position)
+                    .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+                {
+                    self.position += 1;
+                }
+                Ok(Operand::Literal(
+                    self.chars[start..self.position].iter().collect(),
+                ))
+            }
+            Some(c) if c.is_alphabetic() => {
+                if self.eat_word("null") {
+                    return Ok(Operand::Null);
+                }
+                for word in ["true", "false"] {
+                    if self.eat_word(word) {
+                        return Ok(Operand::Literal(word.to_string()));
+                    }
+                }
+                let start = self.position;
+                while self
+                    .chars
+                    .get(self.position)
+                    .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+                {
+                    self.position += 1;
+                }
+                Ok(Operand::Column(
+                    self.chars[start..self.position].iter().collect(),
+                ))
+            }
+            _ => Err(self.error("expected a column or a value")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_expression() {
+        let column_type = |column: &str| match column {
+            "Salary" => Some(BasicType::Int),
+            "FromDate" | "ToDate" => Some(BasicType::Date),
+            "Name" => Some(BasicType::String),
+            _ => None,
+        };
+        let row = |salary: Option<&'static str>, to_date: Option<&'static str>| {
+            move |column: &str| match column {
+                "Salary" => salary,
+                "FromDate" => Some("2024-01-01"),
+                "ToDate" => to_date,
+                "Name" => Some("O'Brien"),
+                _ => None,
+            }
+        };
+
+        let check = CheckExpression::parse("Salary > 0").unwrap();
+        assert_eq!(
+            check.holds(&row(Some("10"), None), &column_type),
+            Some(true)
+        );
+        assert_eq!(
+            check.holds(&row(Some("-5"), None), &column_type),
+            Some(false)
+        );
+        // Compared as numbers, not text.
+        let check_ten = CheckExpression::parse("Salary > 9").unwrap();
+        assert_eq!(
+            check_ten.holds(&row(Some("10"), None), &column_type),
+            Some(true)
+        );
+        assert_eq!(check.holds(&row(None, None), &column_type), None);
+
+        let check = CheckExpression::parse("FromDate <= ToDate OR ToDate IS NULL").unwrap();
+        assert_eq!(check.columns(), ["FromDate", "ToDate"]);
+        assert_eq!(check.holds(&row(None, None), &column_type), Some(true));
+        let earlier = row(None, Some("2023-12-31"));
+        assert_eq!(check.holds(&earlier, &column_type), Some(false));
+        assert_eq!(check.to_string(), "(FromDate <= ToDate or ToDate is null)");
+
+        let check = CheckExpression::parse("not (Name = 'O''Brien')").unwrap();
+        assert_eq!(check.holds(&row(None, None), &column_type), Some(false));
+        assert!(check.resolve(&column_type).is_ok());
+
+        let check = CheckExpression::parse("Bonus > 0").unwrap();
+        assert!(check.resolve(&column_type).is_err());
+        let check = CheckExpression::parse("Salary > 'lots'").unwrap();
+        assert!(check.resolve(&column_type).is_err());
+        assert!(CheckExpression::parse("Salary >").is_err());
+        assert!(CheckExpression::parse("Salary").is_err());
+        assert!(CheckExpression::parse("Salary > 0 0").is_err());
+    }
+}
@@ -0,0 +1,362 @@
+//! Reading into JSON values with `->` and `->>`, and validating them against
+//! a JSON Schema.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::basic_types::BasicType;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JsonStep {
+    /// A member of an object.
+    Key(String),
+    /// An element of an array. Negative positions count from the end.
+    Index(i64),
+}
+
+/// A chain of `->` and `->>` operators, as in `Doc->'items'->0->>'sku'`.
+/// `->` reads a JSON value and `->>` reads it as text, so only the last step
+/// can be `->>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonAccess {
+    pub steps: Vec<JsonStep>,
+    pub as_text: bool,
+}
+
+impl JsonAccess {
+    /// The type of what the access reads.
+    pub fn value_type(&self) -> BasicType {
+        if self.as_text {
+            BasicType::String
+        } else {
+            BasicType::Json
+        }
+    }
+
+    /// The name of what the access reads in a result: its last key, or
+    /// `?column?` after an array position, as PostgreSQL calls it.
+    pub fn output_name(&self) -> &str {
+        match self.steps.last() {
+            Some(JsonStep::Key(key)) => key,
+            _ => "?column?",
+        }
+    }
+
+    /// Reads the value at the end of the steps out of a stored JSON value.
+    /// Returns `None` if there is no such value, or `->>` reads a JSON null.
+    pub fn extract(&self, document: &str) -> Option<String> {
+        let document: Value = serde_json::from_str(document).ok()?;
+        let mut value = &document;
+        for step in self.steps.iter() {
+            value = match (step, value) {
+                (JsonStep::Key(key), Value::Object(object)) => object.get(key)?,
+                (JsonStep::Index(position), Value::Array(elements)) => {
+                    let position = if *position < 0 {
+                        elements.len() as i64 + position
+                    } else {
+                        *position
+                    };
+                    elements.get(usize::try_from(position).ok()?)?
+                }
+                _ => return None,
+            };
+        }
+        match value {
+            Value::Null if self.as_text => None,
+            Value::String(text) if self.as_text => Some(text.clone()),
+            value => Some(value.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for JsonAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (position, step) in self.steps.iter().enumerate() {
+            let arrow = if self.as_text && position + 1 == self.steps.len() {
+                "->>"
+            } else {
+                "->"
+            };
+            match step {
+                JsonStep::Key(key) => write!(f, "{}'{}'", arrow, key)?,
+                JsonStep::Index(position) => write!(f, "{}{}", arrow, position)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+const SCHEMA_TYPES: [&str; 7] = [
+    "string", "number", "integer", "boolean", "object", "array", "null",
+];
+
+/// Checks that a JSON Schema only uses the keywords `validate` understands
+/// in the way it understands them. Other keywords are ignored.
+pub fn check_schema(schema: &Value) -> Result<(), String> {
+    let Value::Object(keywords) = schema else {
+        return match schema {
+            Value::Bool(_) => Ok(()),
+            _ => Err("a schema is an object".to_string()),
+        };
+    };
+    for (keyword, value) in keywords.iter() {
+        let valid = match keyword.as_str() {
+            "type" => match value {
+                Value::String(name) => SCHEMA_TYPES.contains(&name.as_str()),
+                Value::Array(names) => names.iter().all(|name| {
+                    name.as_str()
+                        .is_some_and(|name| SCHEMA_TYPES.contains(&name))
+                }),
+                _ => false,
+            },
+            "properties" => match value {
+                Value::Object(properties) => {
+                    for property in properties.values() {
+                        check_schema(property)?;
+                    }
+                    true
+                }
+                _ => false,
+            },
+            "items" | "additionalProperties" => {
+                check_schema(value)?;
+                true
+            }
+            "required" => value
+                .as_array()
+                .is_some_and(|names| names.iter().all(Value::is_string)),
+            "enum" => value.is_array(),
+            "minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum" => value.is_number(),
+            "minLength" | "maxLength" | "minItems" | "maxItems" => value.is_u64(),
+            _ => true,
+        };
+        if !valid {
+            return Err(format!("invalid value for {}: {}", keyword, value));
+        }
+    }
+    Ok(())
+}
+
+/// Validates a value against a JSON Schema. Understands `type`, `enum`,
+/// `const`, `properties`, `required`, `additionalProperties`, `items`, the
+/// bounds on numbers and the lengths of strings and arrays. Returns the first
+/// violation found, naming where in the value it is.
+pub fn validate(schema: &Value, value: &Value) -> Result<(), String> {
+    validate_at(schema, value, "$")
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    let keywords = match schema {
+        Value::Object(keywords) => keywords,
+        Value::Bool(false) => return Err(format!("{} is not allowed", path)),
+        _ => return Ok(()),
+    };
+
+    if let Some(types) = keywords.get("type") {
+        let types: Vec<&str> = match types {
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            name => name.as_str().into_iter().collect(),
+        };
+        if !types.iter().any(|name| is_of_type(value, name)) {
+            return Err(format!("{} must be of type {}", path, types.join(" or ")));
+        }
+    }
+    if let Some(Value::Array(allowed)) = keywords.get("enum") {
+        if !allowed.contains(value) {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            return Err(format!("{} must be one of {}", path, allowed.join(", ")));
+        }
+    }
+    if let Some(constant) = keywords.get("const") {
+        if constant != value {
+            return Err(format!("{} must be {}", path, constant));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Array(required)) = keywords.get("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(name) {
+                        return Err(format!("{}.{} is required", path, name));
+                    }
+                }
+            }
+            let properties = keywords.get("properties").and_then(Value::as_object);
+            for (name, member) in object.iter() {
+                let member_path = format!("{}.{}", path, name);
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(property) => validate_at(property, member, &member_path)?,
+                    None => {
+                        if let Some(additional) = keywords.get("additionalProperties") {
+                            validate_at(additional, member, &member_path)?;
+                        }
+                    }
+                }
+            }
+        }
+        Value::Array(elements) => {
+            check_length(
+                keywords,
+                "minItems",
+                "maxItems",
+                elements.len(),
+                path,
+                "items",
+            )?;
+            if let Some(items) = keywords.get("items") {
+                for (position, element) in elements.iter().enumerate() {
+                    validate_at(items, element, &format!("{}[{}]", path, position))?;
+                }
+            }
+        }
+        Value::String(text) => {
+            let length = text.chars().count();
+            check_length(
+                keywords,
+                "minLength",
+                "maxLength",
+                length,
+                path,
+                "characters",
+            )?;
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or(f64::NAN);
+            let bound = |keyword: &str| keywords.get(keyword).and_then(Value::as_f64);
+            if let Some(minimum) = bound("minimum").filter(|minimum| number < *minimum) {
+                return Err(format!("{} must be at least {}", path, minimum));
+            }
+            if let Some(maximum) = bound("maximum").filter(|maximum| number > *maximum) {
+                return Err(format!("{} must be at most {}", path, maximum));
+            }
+            if let Some(minimum) = bound("exclusiveMinimum").filter(|minimum| number <= *minimum) {
+                return Err(format!("{} must be greater than {}", path, minimum));
+            }
+            if let Some(maximum) = bound("exclusiveMaximum").filter(|maximum| number >= *maximum) {
+                return Err(format!("{} must be less than {}", path, maximum));
+            }
+        }
+        Value::Bool(_) | Value::Null => {}
+    }
+    Ok(())
+}
+
+fn is_of_type(value: &Value, name: &str) -> bool {
+    match name {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|v| v.fract() == 0.0)
+        }
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "null" => value.is_null(),
+        _ => false,
+    }
+}
+
+fn check_length(
+    keywords: &serde_json::Map<String, Value>,
+    minimum: &str,
+    maximum: &str,
+    length: usize,
+    path: &str,
+    unit: &str,
+) -> Result<(), String> {
+    if let Some(minimum) = keywords.get(minimum).and_then(Value::as_u64) {
+        if (length as u64) < minimum {
+            return Err(format!("{} must have at least {} {}", path, minimum, unit));
+        }
+    }
+    if let Some(maximum) = keywords.get(maximum).and_then(Value::as_u64) {
+        if length as u64 > maximum {
+            return Err(format!("{} must have at most {} {}", path, maximum, unit));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_access() {
+        let document = r#"{"a":{"b":"x","n":null},"items":[{"sku":"s1"},{"sku":"s2"}]}"#;
+        let access = |steps: Vec<JsonStep>, as_text| JsonAccess { steps, as_text };
+        let key = |key: &str| JsonStep::Key(key.to_string());
+
+        let b = access(vec![key("a"), key("b")], true);
+        assert_eq!(b.extract(document).as_deref(), Some("x"));
+        assert_eq!(b.to_string(), "->'a'->>'b'");
+        assert_eq!(b.output_name(), "b");
+        let a = access(vec![key("a")], false);
+        assert_eq!(
+            a.extract(document).as_deref(),
+            Some(r#"{"b":"x","n":null}"#)
+        );
+        let last = access(vec![key("items"), JsonStep::Index(-1), key("sku")], true);
+        assert_eq!(last.extract(document).as_deref(), Some("s2"));
+
+        assert_eq!(
+            access(vec![key("a"), key("n")], true).extract(document),
+            None
+        );
+        assert_eq!(
+            access(vec![key("a"), key("n")], false)
+                .extract(document)
+                .as_deref(),
+            Some("null")
+        );
+        assert_eq!(access(vec![key("missing")], false).extract(document), None);
+        assert_eq!(
+            access(vec![JsonStep::Index(0)], false).extract(document),
+            None
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "age": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"enum": ["a", "b"]}}
+            },
+            "additionalProperties": false
+        });
+        assert!(check_schema(&schema).is_ok());
+        assert!(validate(&schema, &json!({"name": "Ada", "age": 36, "tags": ["a"]})).is_ok());
+        assert_eq!(
+            validate(&schema, &json!({"age": 36})),
+            Err("$.name is required".to_string())
+        );
+        assert_eq!(
+            validate(&schema, &json!({"name": "Ada", "age": -1})),
+            Err("$.age must be at least 0".to_string())
+        );
+        assert_eq!(
+            validate(&schema, &json!({"name": "Ada", "tags": ["c"]})),
+            Err(r#"$.tags[0] must be one of "a", "b""#.to_string())
+        );
+        assert_eq!(
+            validate(&schema, &json!({"name": "Ada", "extra": 1})),
+            Err("$.extra is not allowed".to_string())
+        );
+        assert_eq!(
+            validate(&schema, &json!([])),
+            Err("$ must be of type object".to_string())
+        );
+
+        assert!(check_schema(&json!({"type": "text"})).is_err());
+        assert!(check_schema(&json!({"properties": {"a": 1}})).is_err());
+        assert!(check_schema(&json!([])).is_err());
+    }
+}
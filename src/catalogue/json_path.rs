@@ -0,0 +1,550 @@
+//! SQL/JSON path expressions, as in `Doc @? '$.items[*] ? (@.qty > 2)'` and
+//! `Doc @@ '$.status == "paid"'`. Paths are evaluated in lax mode: arrays
+//! are unwrapped where an object member or a filter is expected, and paths
+//! that lead nowhere find nothing rather than failing.
+
+use std::cmp::Ordering;
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    expression: Expression,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    /// `$` followed by steps, or `@`, the item a filter is testing.
+    Path {
+        current: bool,
+        steps: Vec<Step>,
+    },
+    Literal(Value),
+    Compare(Box<Expression>, Comparison, Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Exists(Box<Expression>),
+}
+
+impl Expression {
+    fn is_predicate(&self) -> bool {
+        !matches!(self, Expression::Path { .. } | Expression::Literal(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Key(String),
+    AnyKey,
+    Index(usize),
+    Last,
+    AnyIndex,
+    Filter(Box<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    StartsWith,
+}
+
+impl JsonPath {
+    pub fn parse(text: &str) -> Result<JsonPath, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            position: 0,
+        };
+        if parser.eat_word("strict") {
+            return Err("strict mode is not supported".to_string());
+        }
+        parser.eat_word("lax");
+        let expression = parser.parse_or()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(parser.error("unexpected text"));
+        }
+        Ok(JsonPath { expression })
+    }
+
+    /// Whether the path finds anything in `document`, as `@?` tests. A
+    /// predicate always finds its result.
+    pub fn exists(&self, document: &Value) -> bool {
+        if self.expression.is_predicate() {
+            return true;
+        }
+        !items(&self.expression, document, document).is_empty()
+    }
+
+    /// Whether the predicate holds for `document`, as `@@` tests. A path
+    /// holds if it finds just the boolean `true`.
+    pub fn matches(&self, document: &Value) -> bool {
+        if self.expression.is_predicate() {
+            return test(&self.expression, document, document) == Some(true);
+        }
+        items(&self.expression, document, document) == [&Value::Bool(true)]
+    }
+}
+
+/// The items an operand finds.
+fn items<'a>(
+    expression: &'a Expression,
+    document: &'a Value,
+    current: &'a Value,
+) -> Vec<&'a Value> {
+    match expression {
+        Expression::Path {
+            current: is_current,
+            steps,
+        } => {
+            let start = if *is_current { current } else { document };
+            walk(steps, start, document)
+        }
+        Expression::Literal(value) => vec![value],
+        _ => Vec::new(),
+    }
+}
+
+fn walk<'a>(steps: &'a [Step], start: &'a Value, document: &'a Value) -> Vec<&'a Value> {
+    let mut items = vec![start];
+    for step in steps {
+        let mut next = Vec::new();
+        for item in items {
+            // Lax mode looks into arrays for object members and filters.
+            let unwrapped: Vec<&Value> = match item {
+                Value::Array(elements) => elements.iter().collect(),
+                item => vec![item],
+            };
+            match step {
+                Step::Key(key) => next.extend(
+                    unwrapped
+                        .into_iter()
+                        .filter_map(|item| item.as_object()?.get(key)),
+                ),
+                Step::AnyKey => {
+                    for item in unwrapped {
+                        if let Value::Object(object) = item {
+                            next.extend(object.values());
+                        }
+                    }
+                }
+                Step::AnyIndex => next.extend(unwrapped),
+                Step::Index(position) => next.extend(unwrapped.get(*position)),
+                Step::Last => next.extend(unwrapped.last()),
+                Step::Filter(predicate) => next.extend(
+                    unwrapped
+                        .into_iter()
+                        .filter(|item| test(predicate, document, item) == Some(true)),
+                ),
+            }
+        }
+        items = next;
+    }
+    items
+}
+
+/// Evaluates a predicate. `None` stands for unknown, as when comparing
+/// values of different types.
+fn test(expression: &Expression, document: &Value, current: &Value) -> Option<bool> {
+    match expression {
+        Expression::Compare(left, comparison, right) => {
+            let unwrap = |items: Vec<&Value>| -> Vec<Value> {
+                items
+                    .into_iter()
+                    .flat_map(|item| match item {
+                        Value::Array(elements) => elements.clone(),
+                        item => vec![item.clone()],
+                    })
+                    .collect()
+            };
+            let left = unwrap(items(left, document, current));
+            let right = unwrap(items(right, document, current));
+            let mut unknown = false;
+            for left in left.iter() {
+                for right in right.iter() {
+                    match compare(left, *comparison, right) {
+                        Some(true) => return Some(true),
+                        Some(false) => {}
+                        None => unknown = true,
+                    }
+                }
+            }
+            if unknown {
+                None
+            } else {
+                Some(false)
+            }
+        }
+        Expression::And(left, right) => match test(left, document, current) {
+            Some(false) => Some(false),
+            left => match (left, test(right, document, current)) {
+                (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+        },
+        Expression::Or(left, right) => match test(left, document, current) {
+            Some(true) => Some(true),
+            left => match (left, test(right, document, current)) {
+                (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+        },
+        Expression::Not(operand) => test(operand, document, current).map(|holds| !holds),
+        Expression::Exists(operand) => Some(!items(operand, document, current).is_empty()),
+        Expression::Path { .. } | Expression::Literal(_) => {
+            match items(expression, document, current).as_slice() {
+                [Value::Bool(holds)] => Some(*holds),
+                _ => None,
+            }
+        }
+    }
+}
+
+fn compare(left: &Value, comparison: Comparison, right: &Value) -> Option<bool> {
+    let ordering = match (left, right) {
+        (Value::Number(left), Value::Number(right)) => {
+            left.as_f64()?.partial_cmp(&right.as_f64()?)?
+        }
+        (Value::String(left), Value::String(right)) => {
+            if comparison == Comparison::StartsWith {
+                return Some(left.starts_with(right.as_str()));
+            }
+            left.cmp(right)
+        }
+        (Value::Bool(left), Value::Bool(right)) => left.cmp(right),
+        (Value::Null, Value::Null) => Ordering::Equal,
+        // Null is only ever equal to null.
+        (Value::Null, _) | (_, Value::Null) => {
+            return match comparison {
+                Comparison::Equal => Some(false),
+                Comparison::NotEqual => Some(true),
+                _ => None,
+            };
+        }
+        _ => return None,
+    };
+    match comparison {
+        Comparison::Equal => Some(ordering == Ordering::Equal),
+        Comparison::NotEqual => Some(ordering != Ordering::Equal),
+        Comparison::Less => Some(ordering == Ordering::Less),
+        Comparison::LessOrEqual => Some(ordering != Ordering::Greater),
+        Comparison::Greater => Some(ordering == Ordering::Greater),
+        Comparison::GreaterOrEqual => Some(ordering != Ordering::Less),
+        Comparison::StartsWith => None,
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.position)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .get(self.position)
+            .is_some_and(|c| c.is_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.position).copied()
+    }
+
+    /// Consumes `symbol` if the text continues with it.
+    fn eat(&mut self, symbol: &str) -> bool {
+        self.skip_whitespace();
+        let matches = symbol
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| self.chars.get(self.position + offset) == Some(&c));
+        if matches {
+            self.position += symbol.chars().count();
+        }
+        matches
+    }
+
+    /// Consumes the word `word` if the text continues with it.
+    fn eat_word(&mut self, word: &str) -> bool {
+        let start = self.position;
+        if self.eat(word)
+            && !self
+                .chars
+                .get(self.position)
+                .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+        {
+            return true;
+        }
+        self.position = start;
+        false
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_and()?;
+        while self.eat("||") {
+            let right = self.parse_and()?;
+            expression = Expression::Or(Box::new(expression), Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_unary()?;
+        while self.eat("&&") {
+            let right = self.parse_unary()?;
+            expression = Expression::And(Box::new(expression), Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, String> {
+        if self.eat("!") {
+            return Ok(Expression::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.eat("(") {
+            let expression = self.parse_or()?;
+            if !self.eat(")") {
+                return Err(self.error("expected )"));
+            }
+            return Ok(expression);
+        }
+        if self.eat_word("exists") {
+            if !self.eat("(") {
+                return Err(self.error("expected ( after exists"));
+            }
+            let operand = self.parse_operand()?;
+            if !self.eat(")") {
+                return Err(self.error("expected )"));
+            }
+            return Ok(Expression::Exists(Box::new(operand)));
+        }
+
+        let left = self.parse_operand()?;
+        let comparison = if self.eat("==") {
+            Comparison::Equal
+        } else if self.eat("!=") || self.eat("<>") {
+            Comparison::NotEqual
+        } else if self.eat("<=") {
+            Comparison::LessOrEqual
+        } else if self.eat(">=") {
+            Comparison::GreaterOrEqual
+        } else if self.eat("<") {
+            Comparison::Less
+        } else if self.eat(">") {
+            Comparison::Greater
+        } else if self.eat_word("starts") {
+            if !self.eat_word("with") {
+                return Err(self.error("expected with after starts"));
+            }
+            Comparison::StartsWith
+        } else {
+            return Ok(left);
+        };
+        let right = self.parse_operand()?;
+        Ok(Expression::Compare(
+            Box::new(left),
+            comparison,
+            Box::new(right),
+        ))
+    }
+
+    fn parse_operand(&mut self) -> Result<Expression, String> {
+        match self.peek() {
+            Some('$') | Some('@') => {
+                let current = self.chars[self.position] == '@';
+                self.position += 1;
+                let steps = self.parse_steps()?;
+                Ok(Expression::Path { current, steps })
+            }
+            Some('"') => Ok(Expression::Literal(Value::String(self.parse_string()?))),
+            Some(c) if c.is_ascii_digit() || c == '-' => self.parse_number(),
+            _ => {
+                for (word, value) in [
+                    ("true", Value::Bool(true)),
+                    ("false", Value::Bool(false)),
+                    ("null", Value::Null),
+                ] {
+                    if self.eat_word(word) {
+                        return Ok(Expression::Literal(value));
+                    }
+                }
+                Err(self.error("expected a path or a value"))
+            }
+        }
+    }
+
+    fn parse_steps(&mut self) -> Result<Vec<Step>, String> {
+        let mut steps = Vec::new();
+        loop {
+            // Accessors follow their path directly, filters may be spaced.
+            match self.chars.get(self.position) {
+                Some('.') => {
+                    self.position += 1;
+                    match self.chars.get(self.position) {
+                        Some('*') => {
+                            self.position += 1;
+                            steps.push(Step::AnyKey);
+                        }
+                        Some('"') => steps.push(Step::Key(self.parse_string()?)),
+                        _ => {
+                            let start = self.position;
+                            while self
+                                .chars
+                                .get(self.position)
+                                .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+                            {
+                                self.position += 1;
+                            }
+                            if start == self.position {
+                                return Err(self.error("expected a key after ."));
+                            }
+                            let key = self.chars[start..self.position].iter().collect();
+                            steps.push(Step::Key(key));
+                        }
+                    }
+                }
+                Some('[') => {
+                    self.position += 1;
+                    let step = if self.eat("*") {
+                        Step::AnyIndex
+                    } else if self.eat_word("last") {
+                        Step::Last
+                    } else {
+                        self.skip_whitespace();
+                        let start = self.position;
+                        while self
+                            .chars
+                            .get(self.position)
+                            .is_some_and(char::is_ascii_digit)
+                        {
+                            self.position += 1;
+                        }
+                        let digits: String = self.chars[start..self.position].iter().collect();
+                        Step::Index(
+                            digits
+                                .parse()
+                                .map_err(|_| self.error("expected an array position"))?,
+                        )
+                    };
+                    if !self.eat("]") {
+                        return Err(self.error("expected ]"));
+                    }
+                    steps.push(step);
+                }
+                _ => {
+                    let start = self.position;
+                    if self.eat("?") {
+                        if !self.eat("(") {
+                            return Err(self.error("expected ( after ?"));
+                        }
+                        let predicate = self.parse_or()?;
+                        if !self.eat(")") {
+                            return Err(self.error("expected )"));
+                        }
+                        steps.push(Step::Filter(Box::new(predicate)));
+                    } else {
+                        self.position = start;
+                        return Ok(steps);
+                    }
+                }
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        let start = self.position;
+        // The opening quote.
+        self.position += 1;
+        let mut escaped = false;
+        while let Some(c) = self.chars.get(self.position) {
+            self.position += 1;
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => {
+                    let literal: String = self.chars[start..self.position].iter().collect();
+                    return serde_json::from_str(&literal)
+                        .map_err(|_| self.error("invalid string"));
+                }
+                _ => escaped = false,
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    fn parse_number(&mut self) -> Result<Expression, String> {
+        let start = self.position;
+        while self
+            .chars
+            .get(self.position)
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            self.position += 1;
+        }
+        let literal: String = self.chars[start..self.position].iter().collect();
+        match serde_json::from_str::<Value>(&literal) {
+            Ok(number @ Value::Number(_)) => Ok(Expression::Literal(number)),
+            _ => Err(self.error("invalid number")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_path() {
+        let document = json!({
+            "status": "paid",
+            "customer": {"name": "Ada Lovelace", "vip": true},
+            "items": [{"sku": "a", "qty": 1}, {"sku": "b", "qty": 3}],
+            "note": null
+        });
+        let exists = |path: &str| JsonPath::parse(path).unwrap().exists(&document);
+        let matches = |path: &str| JsonPath::parse(path).unwrap().matches(&document);
+
+        assert!(exists("$.customer.name"));
+        assert!(exists("$.note"));
+        assert!(!exists("$.missing"));
+        assert!(!exists("$.status.missing"));
+        assert!(exists("$.items[*] ? (@.qty > 2)"));
+        assert!(!exists("$.items[*] ? (@.qty > 3)"));
+        assert!(exists("$.items.sku ? (@ == \"b\")"));
+        assert!(exists("lax $.items[last] ? (@.sku == \"b\" && @.qty >= 3)"));
+        assert!(!exists("$.items[0] ? (@.sku == \"b\")"));
+        assert!(exists("$.customer.* ? (@ starts with \"Ada\")"));
+        assert!(!exists("$.* ? (@ starts with \"Ada\")"));
+
+        assert!(matches("$.status == \"paid\""));
+        assert!(matches("$.items[*].qty > 2"));
+        assert!(!matches("$.items[*].qty > 5"));
+        assert!(matches("$.customer.vip"));
+        assert!(matches("!($.status == \"open\") || $.note != null"));
+        assert!(matches("exists($.customer ? (@.name starts with \"Ada\"))"));
+        assert!(matches("$.note == null"));
+        // Comparing a string with a number is unknown, so neither holds.
+        assert!(!matches("$.status > 1"));
+        assert!(!matches("!($.status > 1)"));
+
+        assert!(JsonPath::parse("strict $.a").is_err());
+        assert!(JsonPath::parse("$.items[").is_err());
+        assert!(JsonPath::parse("$.a ==").is_err());
+        assert!(JsonPath::parse("status").is_err());
+    }
+}
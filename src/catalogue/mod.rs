@@ -1,13 +1,42 @@
 pub mod basic_types;
+pub mod check;
+pub mod json;
+pub mod json_path;
 
-use serde::{Deserialize, Serialize};
-use std::io::Write;
+use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Serialize, Deserialize)]
+use crate::storage::Storage;
+
+const CATALOGUE_FILE: &str = "catalogue.json";
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Column {
     name: String,
     data_type: basic_types::BasicType,
     nullable: bool,
+    /// Range queries on the column crack it into an adaptive index.
+    #[serde(default)]
+    adaptive: bool,
+    /// JSON Schema the values of a Json column must satisfy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    json_schema: Option<serde_json::Value>,
+    /// What an insert that omits the column stores.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default: Option<ColumnDefault>,
+}
+
+/// The default of a column.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnDefault {
+    /// A constant, in the canonical text of the column type.
+    Value(String),
+    /// The time of the insert.
+    Now,
+    /// A random v4 UUID.
+    Uuid,
+    /// The next value of the column's sequence.
+    Autoincrement,
 }
 
 impl Column {
@@ -16,26 +45,354 @@ impl Column {
             name,
             data_type,
             nullable,
+            adaptive: false,
+            json_schema: None,
+            default: None,
+        }
+    }
+
+    pub fn with_adaptive(mut self, adaptive: bool) -> Column {
+        self.adaptive = adaptive;
+        self
+    }
+
+    pub fn with_json_schema(mut self, json_schema: Option<serde_json::Value>) -> Column {
+        self.json_schema = json_schema;
+        self
+    }
+
+    pub fn with_default(mut self, default: Option<ColumnDefault>) -> Column {
+        self.default = default;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn data_type(&self) -> basic_types::BasicType {
+        self.data_type.clone()
+    }
+
+    pub fn is_nullable(&self) -> bool {
+        self.nullable
+    }
+
+    pub fn is_adaptive(&self) -> bool {
+        self.adaptive
+    }
+
+    pub fn json_schema(&self) -> Option<&serde_json::Value> {
+        self.json_schema.as_ref()
+    }
+
+    pub fn default(&self) -> Option<&ColumnDefault> {
+        self.default.as_ref()
+    }
+}
+
+/// The name of a constraint of a table on `columns`, as in
+/// `employee_firstname_lastname_key`.
+pub fn constraint_name(table_name: &str, columns: &[&str], suffix: &str) -> String {
+    let mut parts = vec![table_name];
+    parts.extend(columns);
+    parts.push(suffix);
+    parts.join("_").to_lowercase()
+}
+
+/// The name of the sequence behind an autoincrement column.
+pub fn sequence_name(table_name: &str, column_name: &str) -> String {
+    format!("{}_{}_seq", table_name, column_name).to_lowercase()
+}
+
+/// The system columns of a system-versioned table: when a version of a row
+/// became current, and when it stopped being, NULL while it still is.
+pub const VALID_FROM: &str = "valid_from";
+pub const VALID_TO: &str = "valid_to";
+
+pub fn is_system_column(column_name: &str) -> bool {
+    column_name == VALID_FROM || column_name == VALID_TO
+}
+
+/// How the rows of a table are stored.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// Whole rows stored one after another. Cheap to write.
+    #[default]
+    Row,
+    /// Rows grouped into segments that store each column separately, with
+    /// min/max zone maps. Cheap to scan a few columns of.
+    Column,
+}
+
+impl Layout {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Layout> {
+        match s {
+            "row" => Some(Layout::Row),
+            "column" => Some(Layout::Column),
+            _ => None,
+        }
+    }
+}
+
+/// How an index organizes the values of its column.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexKind {
+    /// Values in order, for range selections.
+    #[default]
+    BTree,
+    /// The rows holding each element of an array column, for containment.
+    Inverted,
+}
+
+/// A secondary index on a single column, or on a path into a Json column.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Index {
+    name: String,
+    column: String,
+    #[serde(default)]
+    kind: IndexKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<json::JsonAccess>,
+    /// Created by the index advisor rather than declared in the schema.
+    #[serde(default)]
+    advised: bool,
+}
+
+impl Index {
+    pub fn new(name: String, column: String) -> Index {
+        Index {
+            name,
+            column,
+            kind: IndexKind::BTree,
+            path: None,
+            advised: false,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: IndexKind) -> Index {
+        self.kind = kind;
+        self
+    }
+
+    /// Indexes what `path` reads out of the column instead of whole values.
+    pub fn with_path(mut self, path: json::JsonAccess) -> Index {
+        self.path = Some(path);
+        self
+    }
+
+    /// Marks the index as created by the index advisor, which may drop it
+    /// again.
+    pub fn advised(mut self) -> Index {
+        self.advised = true;
+        self
+    }
+
+    pub fn kind(&self) -> IndexKind {
+        self.kind
+    }
+
+    pub fn is_advised(&self) -> bool {
+        self.advised
+    }
+
+    pub fn path(&self) -> Option<&json::JsonAccess> {
+        self.path.as_ref()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+}
+
+/// A CHECK constraint: an expression every row must not make false.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Check {
+    name: String,
+    expression: String,
+}
+
+impl Check {
+    pub fn new(name: String, expression: String) -> Check {
+        Check { name, expression }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+}
+
+/// A UNIQUE constraint: no two rows have the same values in `columns`,
+/// unless one of them is NULL.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Unique {
+    name: String,
+    columns: Vec<String>,
+}
+
+impl Unique {
+    pub fn new(name: String, columns: Vec<String>) -> Unique {
+        Unique { name, columns }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+}
+
+/// A FOREIGN KEY constraint: the values of `columns` in every row, unless
+/// one of them is NULL, are the primary key of a row of `table`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ForeignKey {
+    name: String,
+    columns: Vec<String>,
+    table: String,
+    referenced_columns: Vec<String>,
+}
+
+impl ForeignKey {
+    pub fn new(
+        name: String,
+        columns: Vec<String>,
+        table: String,
+        referenced_columns: Vec<String>,
+    ) -> ForeignKey {
+        ForeignKey {
+            name,
+            columns,
+            table,
+            referenced_columns,
         }
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// The table whose primary key the columns hold.
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn referenced_columns(&self) -> &[String] {
+        &self.referenced_columns
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+/// Reads the positions of the primary key columns, also from catalogues
+/// written before keys could have more than one column, which stored a
+/// single position.
+fn primary_key_positions<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u32>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PrimaryKey {
+        Column(u32),
+        Columns(Vec<u32>),
+    }
+    Ok(match PrimaryKey::deserialize(deserializer)? {
+        PrimaryKey::Column(position) => vec![position],
+        PrimaryKey::Columns(positions) => positions,
+    })
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Table {
     name: String,
     columns: Vec<Column>,
-    primary_key: u32,
+    /// Positions of the primary key columns, in key order.
+    #[serde(deserialize_with = "primary_key_positions")]
+    primary_key: Vec<u32>,
+    #[serde(default)]
+    indexes: Vec<Index>,
+    #[serde(default)]
+    layout: Layout,
+    /// Set when the model fixes the layout, which turns off migrations.
+    #[serde(default)]
+    layout_pinned: bool,
+    #[serde(default)]
+    checks: Vec<Check>,
+    #[serde(default)]
+    uniques: Vec<Unique>,
+    #[serde(default)]
+    foreign_keys: Vec<ForeignKey>,
+    /// Seconds of history kept for time travel. None keeps all of it.
+    #[serde(default)]
+    retention: Option<u64>,
+    /// The table the past versions of the rows of a system-versioned
+    /// table are kept in.
+    #[serde(default)]
+    history_table: Option<String>,
 }
 
 impl Table {
-    pub fn new(name: String, columns: Vec<Column>, primary_key: u32) -> Table {
+    pub fn new(name: String, columns: Vec<Column>, primary_key: Vec<u32>) -> Table {
         Table {
             name,
             columns,
             primary_key,
+            indexes: Vec::new(),
+            layout: Layout::default(),
+            layout_pinned: false,
+            checks: Vec::new(),
+            uniques: Vec::new(),
+            foreign_keys: Vec::new(),
+            retention: None,
+            history_table: None,
         }
     }
 
+    pub fn with_pinned_layout(mut self, layout: Layout) -> Table {
+        self.layout = layout;
+        self.layout_pinned = true;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// The columns of the primary key, in key order.
+    pub fn primary_key(&self) -> Vec<&Column> {
+        self.primary_key
+            .iter()
+            .map(|&position| &self.columns[position as usize])
+            .collect()
+    }
+
+    /// The primary key as the unique constraint that keeps it unique, as
+    /// in `salary_pkey`. None for the tables of materialized views, which
+    /// have no key.
+    pub fn primary_key_constraint(&self) -> Option<Unique> {
+        if self.primary_key.is_empty() {
+            return None;
+        }
+        let columns = self.primary_key().iter().map(|column| column.name.clone()).collect();
+        Some(Unique::new(constraint_name(&self.name, &[], "pkey"), columns))
+    }
+
     pub fn get_column(&self, column_name: &str) -> Option<&Column> {
         self.columns.iter().find(|column| column.name == column_name)
     }
@@ -43,40 +400,276 @@ impl Table {
     pub fn column_exists(&self, column_name: &str) -> bool {
         self.columns.iter().any(|column| column.name == column_name)
     }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn is_layout_pinned(&self) -> bool {
+        self.layout_pinned
+    }
+
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    pub fn indexes(&self) -> &[Index] {
+        &self.indexes
+    }
+
+    pub fn index_on(&self, column_name: &str) -> Option<&Index> {
+        self.indexes
+            .iter()
+            .find(|index| index.column == column_name && index.path.is_none())
+    }
+
+    pub fn add_index(&mut self, index: Index) {
+        self.indexes.push(index);
+    }
+
+    pub fn drop_index(&mut self, index_name: &str) -> Option<Index> {
+        let position = self.indexes.iter().position(|index| index.name == index_name)?;
+        Some(self.indexes.remove(position))
+    }
+
+    pub fn checks(&self) -> &[Check] {
+        &self.checks
+    }
+
+    pub fn add_check(&mut self, check: Check) {
+        self.checks.push(check);
+    }
+
+    pub fn uniques(&self) -> &[Unique] {
+        &self.uniques
+    }
+
+    pub fn add_unique(&mut self, unique: Unique) {
+        self.uniques.push(unique);
+    }
+
+    pub fn foreign_keys(&self) -> &[ForeignKey] {
+        &self.foreign_keys
+    }
+
+    pub fn add_foreign_key(&mut self, foreign_key: ForeignKey) {
+        self.foreign_keys.push(foreign_key);
+    }
+
+    pub fn retention(&self) -> Option<u64> {
+        self.retention
+    }
+
+    pub fn set_retention(&mut self, retention: Option<u64>) {
+        self.retention = retention;
+    }
+
+    /// The table of past row versions, if the table is system-versioned.
+    pub fn history_table(&self) -> Option<&str> {
+        self.history_table.as_deref()
+    }
+
+    pub fn set_history_table(&mut self, history_table: Option<String>) {
+        self.history_table = history_table;
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Catalogue {
     tables: Vec<Table>,
+    #[serde(default)]
+    enums: Vec<basic_types::EnumType>,
+    #[serde(default)]
+    sequences: Vec<Sequence>,
+    #[serde(default)]
+    views: Vec<View>,
+    #[serde(default)]
+    triggers: Vec<Trigger>,
 }
 
-impl Catalogue {
-    pub fn new(tables: Vec<Table>) -> Catalogue {
-        Catalogue { tables }
+/// Statements run for every row an insert, update or delete on `table`
+/// changes, before or after the row is written. They read the row through
+/// `old.<column>` and `new.<column>`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Trigger {
+    name: String,
+    table: String,
+    timing: TriggerTiming,
+    event: TriggerEvent,
+    statements: Vec<String>,
+}
+
+/// Whether a trigger runs before or after the row it fires for is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerTiming {
+    Before,
+    After,
+}
+
+/// The kind of write a trigger fires for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerEvent {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Trigger {
+    pub fn new(
+        name: String,
+        table: String,
+        timing: TriggerTiming,
+        event: TriggerEvent,
+        statements: Vec<String>,
+    ) -> Trigger {
+        Trigger { name, table, timing, event, statements }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
-    pub fn save(&self) -> Result<(), std::io::Error> {
-        if !Self::catalogue_file().exists() {
-            std::fs::create_dir_all(Self::catalogue_file().parent().unwrap())?;
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn timing(&self) -> TriggerTiming {
+        self.timing
+    }
+
+    pub fn event(&self) -> TriggerEvent {
+        self.event
+    }
+
+    /// The text of every statement, in the order they run.
+    pub fn statements(&self) -> &[String] {
+        &self.statements
+    }
+}
+
+/// A select stored under a name. A plain view is expanded into its select
+/// wherever it is read; a materialized view keeps the rows of its select in a
+/// table of the same name.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct View {
+    name: String,
+    query: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    materialized: Option<Refresh>,
+}
+
+/// How the table of a materialized view is kept up to date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Refresh {
+    /// Only by `refresh materialized view`.
+    Manual,
+    /// After every write to the tables the view reads.
+    Incremental,
+}
+
+impl View {
+    pub fn new(name: String, query: String, materialized: Option<Refresh>) -> View {
+        View { name, query, materialized }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The text of the select of the view.
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn materialized(&self) -> Option<Refresh> {
+        self.materialized
+    }
+}
+
+/// A counter handing out increasing values, for `nextval` and autoincrement
+/// columns.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Sequence {
+    name: String,
+    start: i64,
+    increment: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_value: Option<i64>,
+}
+
+impl Sequence {
+    pub fn new(name: String, start: i64, increment: i64) -> Sequence {
+        Sequence {
+            name,
+            start,
+            increment,
+            last_value: None,
         }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Advances the sequence. None once the next value would overflow.
+    pub fn next_value(&mut self) -> Option<i64> {
+        let value = match self.last_value {
+            Some(last_value) => last_value.checked_add(self.increment)?,
+            None => self.start,
+        };
+        self.last_value = Some(value);
+        Some(value)
+    }
 
-        let mut file = std::fs::File::create(Self::catalogue_file())?;
+    /// Makes sure the sequence hands out no value up to `value` anymore, as
+    /// after rows with that value were restored.
+    pub fn advance_past(&mut self, value: i64) {
+        let next = match self.last_value {
+            Some(last_value) => last_value.saturating_add(self.increment),
+            None => self.start,
+        };
+        let handed_out = if self.increment > 0 {
+            next <= value
+        } else {
+            next >= value
+        };
+        if handed_out {
+            self.last_value = Some(value);
+        }
+    }
+}
+
+impl Catalogue {
+    pub fn new(tables: Vec<Table>) -> Catalogue {
+        Catalogue {
+            tables,
+            enums: Vec::new(),
+            sequences: Vec::new(),
+            views: Vec::new(),
+            triggers: Vec::new(),
+        }
+    }
+
+    pub fn save(&self, storage: &Storage) -> Result<(), std::io::Error> {
         let json = serde_json::to_string(self)?;
-        file.write_all(json.as_bytes())?;
-        Ok(())
+        storage.write_file(CATALOGUE_FILE, json.as_bytes())
     }
 
-    pub fn load() -> Result<Catalogue, std::io::Error> {
-        let file = std::fs::File::open(Self::catalogue_file())?;
+    /// Loads the catalogue of `storage`. A new database has no tables.
+    pub fn load(storage: &Storage) -> Result<Catalogue, std::io::Error> {
+        let path = storage.path(CATALOGUE_FILE);
+        if !path.exists() {
+            return Ok(Catalogue::new(Vec::new()));
+        }
+        let file = std::fs::File::open(path)?;
         let reader = std::io::BufReader::new(file);
         let catalogue = serde_json::from_reader(reader)?;
         Ok(catalogue)
     }
 
-    fn catalogue_file() -> std::path::PathBuf {
-        ".adaptivedb/catalogue.json".into()
-    }
-
     pub fn table_exists(&self, table_name: &str) -> bool {
         self.tables.iter().any(|table| table.name == table_name)
     }
@@ -88,4 +681,77 @@ impl Catalogue {
     pub fn get_table(&self, table_name: &str) -> Option<&Table> {
         self.tables.iter().find(|table| table.name == table_name)
     }
+
+    pub fn get_table_mut(&mut self, table_name: &str) -> Option<&mut Table> {
+        self.tables.iter_mut().find(|table| table.name == table_name)
+    }
+
+    /// The system-versioned table whose history table is `table_name`.
+    pub fn history_of(&self, table_name: &str) -> Option<&Table> {
+        self.tables
+            .iter()
+            .find(|table| table.history_table() == Some(table_name))
+    }
+
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+
+    pub fn add_enum(&mut self, enum_type: basic_types::EnumType) {
+        self.enums.push(enum_type);
+    }
+
+    pub fn get_enum(&self, enum_name: &str) -> Option<&basic_types::EnumType> {
+        self.enums
+            .iter()
+            .find(|enum_type| enum_type.name() == enum_name)
+    }
+
+    pub fn enums(&self) -> &[basic_types::EnumType] {
+        &self.enums
+    }
+
+    pub fn sequence_exists(&self, sequence_name: &str) -> bool {
+        self.sequences
+            .iter()
+            .any(|sequence| sequence.name == sequence_name)
+    }
+
+    pub fn add_sequence(&mut self, sequence: Sequence) {
+        self.sequences.push(sequence);
+    }
+
+    pub fn get_sequence_mut(&mut self, sequence_name: &str) -> Option<&mut Sequence> {
+        self.sequences
+            .iter_mut()
+            .find(|sequence| sequence.name == sequence_name)
+    }
+
+    pub fn get_view(&self, view_name: &str) -> Option<&View> {
+        self.views.iter().find(|view| view.name == view_name)
+    }
+
+    pub fn add_view(&mut self, view: View) {
+        self.views.push(view);
+    }
+
+    pub fn views(&self) -> &[View] {
+        &self.views
+    }
+
+    pub fn trigger_exists(&self, trigger_name: &str) -> bool {
+        self.triggers.iter().any(|trigger| trigger.name == trigger_name)
+    }
+
+    pub fn add_trigger(&mut self, trigger: Trigger) {
+        self.triggers.push(trigger);
+    }
+
+    /// The triggers of `table_name` that fire for `event`, in the order
+    /// they were created.
+    pub fn triggers_of<'a>(&'a self, table_name: &'a str, event: TriggerEvent) -> impl Iterator<Item = &'a Trigger> + 'a {
+        self.triggers
+            .iter()
+            .filter(move |trigger| trigger.table == table_name && trigger.event == event)
+    }
 }
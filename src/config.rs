@@ -0,0 +1,421 @@
+//! Server configuration. Settings come from defaults, then an optional TOML
+//! file, then `ADAPTIVEDB_*` environment variables and finally command-line
+//! options, each overriding the ones before. The result is validated once at
+//! startup and stays fixed while the database is open.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use tracing::Level;
+
+#[derive(Parser, Debug)]
+#[command(name = "adaptivedb", version, about = "An adaptive database server")]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Options of `serve`, which also runs without a subcommand.
+    #[command(flatten)]
+    serve: ServeArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Runs the server.
+    Serve(ServeArgs),
+    /// Backs up the database of a running server and archives its changes.
+    Backup(BackupArgs),
+    /// Rebuilds a data directory from a backup.
+    Restore(RestoreArgs),
+}
+
+#[derive(Args, Debug)]
+struct BackupArgs {
+    /// Name of the backup, a directory the server creates in its backup
+    /// directory.
+    #[arg(long)]
+    name: String,
+    /// Address of the server's HTTP API.
+    #[arg(long, env = "ADAPTIVEDB_URL", default_value = "http://127.0.0.1:3000")]
+    url: String,
+}
+
+#[derive(Args, Debug)]
+struct RestoreArgs {
+    /// Directory of the backup.
+    #[arg(long)]
+    from: PathBuf,
+    /// Empty directory to rebuild the database in.
+    #[arg(long, env = "ADAPTIVEDB_DATA_DIR")]
+    data_dir: Option<PathBuf>,
+    /// LSN or timestamp to replay the archived changes up to, instead of
+    /// all of them.
+    #[arg(long)]
+    until: Option<String>,
+}
+
+#[derive(Args, Debug, Default)]
+struct ServeArgs {
+    /// TOML file to read settings from.
+    #[arg(long, env = "ADAPTIVEDB_CONFIG")]
+    config: Option<PathBuf>,
+    /// Directory holding the catalogue and the data.
+    #[arg(long, env = "ADAPTIVEDB_DATA_DIR")]
+    data_dir: Option<PathBuf>,
+    /// Address of the HTTP listener.
+    #[arg(long, env = "ADAPTIVEDB_LISTEN")]
+    listen: Option<SocketAddr>,
+    /// Accept PostgreSQL wire protocol connections.
+    #[arg(long, env = "ADAPTIVEDB_PGWIRE")]
+    pgwire: bool,
+    /// Address of the PostgreSQL wire protocol listener.
+    #[arg(long, env = "ADAPTIVEDB_PGWIRE_LISTEN")]
+    pgwire_listen: Option<SocketAddr>,
+    /// One of error, warn, info, debug and trace.
+    #[arg(long, env = "ADAPTIVEDB_LOG_LEVEL")]
+    log_level: Option<String>,
+    /// Memory open cursors may hold, such as 512MiB.
+    #[arg(long, env = "ADAPTIVEDB_MEMORY_BUDGET")]
+    memory_budget: Option<ByteSize>,
+    /// When writes are flushed to disk.
+    #[arg(long, env = "ADAPTIVEDB_FSYNC")]
+    fsync: Option<FsyncPolicy>,
+    /// Let the index advisor create and drop indexes itself.
+    #[arg(long, env = "ADAPTIVEDB_AUTONOMOUS_INDEXING")]
+    autonomous_indexing: bool,
+    /// Directory `adaptivedb backup` may create backups in. Backups over
+    /// HTTP are disabled without it.
+    #[arg(long, env = "ADAPTIVEDB_BACKUP_DIR")]
+    backup_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub data_dir: PathBuf,
+    pub log_level: String,
+    pub memory_budget: ByteSize,
+    pub fsync: FsyncPolicy,
+    pub autonomous_indexing: bool,
+    /// Directory backups requested over HTTP are created in, if any.
+    pub backup_dir: Option<PathBuf>,
+    pub http: HttpConfig,
+    pub pgwire: PgwireConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    pub listen: SocketAddr,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PgwireConfig {
+    pub enabled: bool,
+    pub listen: SocketAddr,
+}
+
+/// When the catalogue and data files are flushed to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncPolicy {
+    /// After every write, so committed changes survive a power failure.
+    Always,
+    /// Whenever the operating system decides to.
+    #[default]
+    Never,
+}
+
+/// An amount of memory, written as a number of bytes with an optional unit
+/// such as `KiB`, `MiB` or `GiB`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ByteSize(pub usize);
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ByteSize, String> {
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let multiplier: usize = match unit.trim() {
+            "" | "B" => 1,
+            "KB" => 1000,
+            "KiB" => 1 << 10,
+            "MB" => 1000 * 1000,
+            "MiB" => 1 << 20,
+            "GB" => 1000 * 1000 * 1000,
+            "GiB" => 1 << 30,
+            unit => return Err(format!("Unknown unit {} in size {}", unit, s)),
+        };
+        number
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| number.checked_mul(multiplier))
+            .map(ByteSize)
+            .ok_or_else(|| format!("Invalid size {}", s))
+    }
+}
+
+impl TryFrom<String> for ByteSize {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<ByteSize, String> {
+        s.parse()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            data_dir: ".adaptivedb".into(),
+            log_level: "info".to_string(),
+            memory_budget: ByteSize(256 << 20),
+            fsync: FsyncPolicy::default(),
+            autonomous_indexing: false,
+            backup_dir: None,
+            http: HttpConfig::default(),
+            pgwire: PgwireConfig::default(),
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> HttpConfig {
+        HttpConfig {
+            listen: SocketAddr::from(([127, 0, 0, 1], 3000)),
+        }
+    }
+}
+
+impl Default for PgwireConfig {
+    fn default() -> PgwireConfig {
+        PgwireConfig {
+            enabled: false,
+            listen: SocketAddr::from(([127, 0, 0, 1], 5432)),
+        }
+    }
+}
+
+impl Config {
+    /// Parses a configuration file. Settings it leaves out keep their
+    /// defaults.
+    pub fn from_toml(text: &str) -> Result<Config, String> {
+        toml::from_str(text).map_err(|error| error.to_string())
+    }
+
+    fn apply(&mut self, args: ServeArgs) {
+        if let Some(data_dir) = args.data_dir {
+            self.data_dir = data_dir;
+        }
+        if let Some(listen) = args.listen {
+            self.http.listen = listen;
+        }
+        if args.pgwire {
+            self.pgwire.enabled = true;
+        }
+        if let Some(listen) = args.pgwire_listen {
+            self.pgwire.enabled = true;
+            self.pgwire.listen = listen;
+        }
+        if let Some(log_level) = args.log_level {
+            self.log_level = log_level;
+        }
+        if let Some(memory_budget) = args.memory_budget {
+            self.memory_budget = memory_budget;
+        }
+        if let Some(fsync) = args.fsync {
+            self.fsync = fsync;
+        }
+        if args.autonomous_indexing {
+            self.autonomous_indexing = true;
+        }
+        if let Some(backup_dir) = args.backup_dir {
+            self.backup_dir = Some(backup_dir);
+        }
+    }
+
+    /// Checks that the settings can be used. Returns every problem found.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if Level::from_str(&self.log_level).is_err() {
+            errors.push(format!(
+                "Log level {} is not one of error, warn, info, debug and trace",
+                self.log_level
+            ));
+        }
+        if self.memory_budget.0 == 0 {
+            errors.push("Memory budget must be larger than zero".to_string());
+        }
+        if self.data_dir.as_os_str().is_empty() {
+            errors.push("Data directory must not be empty".to_string());
+        } else if self.data_dir.exists() && !self.data_dir.is_dir() {
+            errors.push(format!(
+                "Data directory {} is not a directory",
+                self.data_dir.display()
+            ));
+        }
+        if let Some(backup_dir) = &self.backup_dir {
+            if backup_dir.exists() && !backup_dir.is_dir() {
+                errors.push(format!(
+                    "Backup directory {} is not a directory",
+                    backup_dir.display()
+                ));
+            }
+        }
+        if self.pgwire.enabled && self.pgwire.listen == self.http.listen {
+            errors.push(format!(
+                "HTTP and PostgreSQL wire protocol listeners both use {}",
+                self.http.listen
+            ));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn log_level(&self) -> Level {
+        Level::from_str(&self.log_level).expect("Validating should have caught this error")
+    }
+}
+
+/// What the command line asks for.
+#[derive(Debug)]
+pub enum Task {
+    /// Run the server.
+    Serve(Config),
+    /// Ask the server at `url` to back up its database as `name`.
+    Backup { url: String, name: String },
+    /// Rebuild `data_dir` from the backup in `from`, replaying its archived
+    /// changes up to `until`.
+    Restore {
+        from: PathBuf,
+        data_dir: PathBuf,
+        until: Option<String>,
+    },
+}
+
+/// Reads the command line. For the server, reads the configuration from the
+/// command line, the environment and the configuration file, and validates
+/// it.
+pub fn load() -> Result<Task, Vec<String>> {
+    let cli = Cli::parse();
+    let args = match cli.command {
+        Some(Command::Serve(args)) => args,
+        Some(Command::Backup(args)) => {
+            return Ok(Task::Backup {
+                url: args.url,
+                name: args.name,
+            })
+        }
+        Some(Command::Restore(args)) => {
+            return Ok(Task::Restore {
+                from: args.from,
+                data_dir: args.data_dir.unwrap_or_else(|| Config::default().data_dir),
+                until: args.until,
+            })
+        }
+        None => cli.serve,
+    };
+
+    let mut config = match args.config.as_ref() {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|error| vec![format!("Cannot read {}: {}", path.display(), error)])?;
+            Config::from_toml(&text).map_err(|error| vec![format!("{}: {}", path.display(), error)])?
+        }
+        None => Config::default(),
+    };
+    config.apply(args);
+    config.validate()?;
+    Ok(Task::Serve(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_file_and_overrides() {
+        let mut config = Config::from_toml(
+            r#"
+            data_dir = "/var/lib/adaptivedb"
+            memory_budget = "1GiB"
+            fsync = "always"
+
+            [pgwire]
+            enabled = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.memory_budget, ByteSize(1 << 30));
+        assert_eq!(config.fsync, FsyncPolicy::Always);
+        assert_eq!(config.http, HttpConfig::default());
+        assert_eq!(config.pgwire.listen.port(), 5432);
+
+        let cli = Cli::try_parse_from([
+            "adaptivedb",
+            "serve",
+            "--listen",
+            "0.0.0.0:8080",
+            "--log-level",
+            "debug",
+            "--memory-budget",
+            "64MiB",
+        ])
+        .unwrap();
+        let Some(Command::Serve(args)) = cli.command else {
+            panic!("expected the serve command");
+        };
+        config.apply(args);
+        assert_eq!(config.http.listen.port(), 8080);
+        assert_eq!(config.log_level(), Level::DEBUG);
+        assert_eq!(config.memory_budget, ByteSize(64 << 20));
+        assert_eq!(config.data_dir, PathBuf::from("/var/lib/adaptivedb"));
+        assert!(config.validate().is_ok());
+
+        assert!(Cli::try_parse_from(["adaptivedb", "--pgwire"]).unwrap().serve.pgwire);
+        let cli = Cli::try_parse_from([
+            "adaptivedb",
+            "restore",
+            "--from",
+            "/backups/monday",
+            "--until",
+            "42",
+        ])
+        .unwrap();
+        let Some(Command::Restore(args)) = cli.command else {
+            panic!("expected the restore command");
+        };
+        assert_eq!(args.from, PathBuf::from("/backups/monday"));
+        assert_eq!(args.until.as_deref(), Some("42"));
+        assert!(Cli::try_parse_from(["adaptivedb", "backup"]).is_err());
+        assert!(Cli::try_parse_from(["adaptivedb", "backup", "--name", "monday"]).is_ok());
+        assert!(Config::from_toml("listen = \"127.0.0.1:1\"").is_err());
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let config = Config {
+            log_level: "loud".to_string(),
+            memory_budget: ByteSize(0),
+            pgwire: PgwireConfig {
+                enabled: true,
+                listen: HttpConfig::default().listen,
+            },
+            ..Default::default()
+        };
+        assert_eq!(config.validate().unwrap_err().len(), 3);
+
+        assert_eq!("512".parse(), Ok(ByteSize(512)));
+        assert_eq!("2 KB".parse(), Ok(ByteSize(2000)));
+        assert!("12 parsecs".parse::<ByteSize>().is_err());
+    }
+}
@@ -0,0 +1,392 @@
+//! Online backups and point-in-time restore. A backup is a directory with a
+//! base copy of the catalogue, the data and the change log, taken between
+//! statements while the database keeps serving, and the changes committed
+//! since, which the database archives there as they commit. Restoring
+//! copies the base and replays the archived changes up to a point.
+//!
+//! Indexes are rebuilt from their definitions in the catalogue, so the
+//! catalogue is all a backup needs of them.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::Database;
+use crate::catalogue::basic_types::{self, BasicType};
+use crate::catalogue::{self, ColumnDefault, Refresh};
+use crate::config::FsyncPolicy;
+use crate::queryprocessing::{history, view};
+use crate::storage::changelog::{Change, ChangeLog, Operation};
+use crate::storage::{Row, Storage};
+use crate::{Error, ErrorCode};
+
+/// Name of the file describing a backup, in its directory.
+const MANIFEST_FILE: &str = "backup.json";
+
+/// Directory of a backup holding its base copy.
+const BASE_DIR: &str = "base";
+
+/// Name of the file naming the backup a database archives its changes to,
+/// in its data directory.
+const ARCHIVE_FILE: &str = "archive.json";
+
+/// How often changes are archived if no commit wakes the archiver sooner.
+const ARCHIVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Where a backup starts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Backup {
+    /// The LSN of the last change the base copy holds, 0 if none.
+    pub lsn: u64,
+    /// When the base copy was taken, as a `Timestamptz` value.
+    pub taken_at: String,
+}
+
+/// What a restore replayed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Restored {
+    /// The LSN of the last change the restored database holds.
+    pub lsn: u64,
+    /// How many archived changes were replayed on the base copy.
+    pub replayed: usize,
+}
+
+/// The point a restore replays the archived changes up to: the transaction
+/// with the change of an LSN, or the last one committed by a time.
+#[derive(Debug, Clone, PartialEq)]
+enum Until {
+    Lsn(u64),
+    Timestamp(String),
+}
+
+impl Until {
+    /// Parses an LSN or a timestamp.
+    fn parse(text: &str) -> Result<Until, Error> {
+        if let Ok(lsn) = text.parse::<u64>() {
+            return Ok(Until::Lsn(lsn));
+        }
+        if history::micros(text).is_some() {
+            return Ok(Until::Timestamp(text.to_string()));
+        }
+        Err(Error::new(
+            ErrorCode::InvalidParameter,
+            format!("Invalid restore point \"{}\"", text),
+        )
+        .with_hint("Restore up to an LSN, such as 42, or a timestamp, such as 2026-10-01T12:00"))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArchiveTarget {
+    to: PathBuf,
+}
+
+/// The backup a database archives its committed changes to.
+pub(crate) struct Archive {
+    storage: Storage,
+    changes: ChangeLog,
+    /// The LSN of the last change archived.
+    lsn: u64,
+}
+
+impl Archive {
+    /// The backup the database in `storage` archives to, if there is one.
+    /// A backup that was removed is no longer archived to.
+    pub(crate) fn open(storage: &Storage) -> Result<Option<Archive>, Error> {
+        let path = storage.path(ARCHIVE_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let target: ArchiveTarget =
+            serde_json::from_slice(&std::fs::read(path)?).map_err(std::io::Error::from)?;
+        let storage = Storage::new(target.to, FsyncPolicy::Always);
+        let backup = match read_manifest(&storage) {
+            Ok(backup) => backup,
+            Err(error) => {
+                tracing::warn!("Not archiving changes: {}", error);
+                return Ok(None);
+            }
+        };
+        let changes = ChangeLog::open(&storage)?;
+        let lsn = changes.last_lsn().max(backup.lsn);
+        Ok(Some(Archive {
+            storage,
+            changes,
+            lsn,
+        }))
+    }
+}
+
+/// Copies the catalogue, data and change log to the empty directory `to`
+/// between statements, and archives the changes committed from then on
+/// there, in place of the backup changes were archived to before.
+pub(crate) async fn backup(db: &Database, to: &Path) -> Result<Backup, Error> {
+    check_empty(to).map_err(|error| error.with_hint("Back up to a new directory"))?;
+    let storage = Storage::new(to.to_path_buf(), FsyncPolicy::Always);
+
+    // No statement runs while the base copy is taken.
+    let _statements = db.statements.write().await;
+    let catalogue = db.catalogue.lock().await;
+    let base = Storage::new(to.join(BASE_DIR), FsyncPolicy::Always);
+    catalogue.save(&base)?;
+    db.storage.copy_data(&base)?;
+    db.changes.copy(&db.storage, &base)?;
+    let backup = Backup {
+        lsn: db.changes.last_lsn(),
+        taken_at: basic_types::now(&BasicType::TimestampTz).unwrap(),
+    };
+    storage.write_file(
+        MANIFEST_FILE,
+        serde_json::to_string(&backup).unwrap().as_bytes(),
+    )?;
+
+    let target = ArchiveTarget {
+        to: to.to_path_buf(),
+    };
+    db.storage.write_file(
+        ARCHIVE_FILE,
+        serde_json::to_string(&target).unwrap().as_bytes(),
+    )?;
+    let changes = ChangeLog::open(&storage)?;
+    *db.archive.lock().unwrap() = Some(Archive {
+        storage,
+        changes,
+        lsn: backup.lsn,
+    });
+    Ok(backup)
+}
+
+/// Archives the changes committed since the last archived one. Returns how
+/// many were archived.
+pub(crate) fn archive(db: &Database) -> Result<usize, Error> {
+    let mut archive = db.archive.lock().unwrap();
+    let Some(archive) = archive.as_mut() else {
+        return Ok(0);
+    };
+    let changes = db.changes(archive.lsn, usize::MAX)?;
+    let Some(first) = changes.first() else {
+        return Ok(0);
+    };
+    if first.lsn != archive.lsn + 1 {
+        return Err(Error::new(
+            ErrorCode::IoError,
+            format!(
+                "Changes {} to {} were pruned before they were archived",
+                archive.lsn + 1,
+                first.lsn - 1
+            ),
+        )
+        .with_hint("Take a new backup"));
+    }
+    archive.changes.append(&archive.storage, &changes)?;
+    archive.lsn = changes.last().map_or(archive.lsn, |change| change.lsn);
+    Ok(changes.len())
+}
+
+/// Archives changes as they commit.
+pub async fn run_archiving(db: Arc<Database>) {
+    loop {
+        if let Err(error) = archive(&db) {
+            tracing::error!("Archiving the change log failed: {}", error);
+        }
+        let since = match db.archive.lock().unwrap().as_ref() {
+            Some(archive) => archive.lsn,
+            None => db.changes.last_lsn(),
+        };
+        tokio::select! {
+            _ = db.changes.wait_for(since) => {}
+            _ = tokio::time::sleep(ARCHIVE_INTERVAL) => {}
+        }
+    }
+}
+
+/// Rebuilds the data directory `data_dir`, which must be empty, from the
+/// backup in `from`: its base copy with the archived changes replayed up
+/// to `until`, an LSN or a timestamp, or all of them.
+pub(crate) async fn restore(
+    from: &Path,
+    data_dir: &Path,
+    until: Option<&str>,
+) -> Result<Restored, Error> {
+    let until = until.map(Until::parse).transpose()?;
+    let storage = Storage::new(from.to_path_buf(), FsyncPolicy::default());
+    let backup = read_manifest(&storage)?;
+    let archived = ChangeLog::open(&storage)?.since(&storage, backup.lsn, usize::MAX)?;
+    let changes = changes_until(&backup, archived, until.as_ref())?;
+    check_empty(data_dir).map_err(|error| error.with_hint("Restore to a new data directory"))?;
+
+    let restored = replay(from, data_dir, &backup, changes).await;
+    if restored.is_err() {
+        // The directory was empty, so it only holds what the restore wrote.
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+    restored
+}
+
+/// Copies the base of the backup in `from` to `data_dir` and replays
+/// `changes` on it.
+async fn replay(
+    from: &Path,
+    data_dir: &Path,
+    backup: &Backup,
+    changes: Vec<Change>,
+) -> Result<Restored, Error> {
+    let base = from.join(BASE_DIR);
+    std::fs::create_dir_all(data_dir)?;
+    for entry in std::fs::read_dir(&base)? {
+        let entry = entry?;
+        std::fs::copy(entry.path(), data_dir.join(entry.file_name()))?;
+    }
+
+    let db = Database::open(data_dir)?;
+    let mut catalogue = db.catalogue.lock().await;
+    let mut tables: Vec<&str> = Vec::new();
+    for change in changes.iter() {
+        if !tables.contains(&change.table.as_str()) {
+            tables.push(&change.table);
+        }
+    }
+    for table_name in tables.iter() {
+        if catalogue.get_table(table_name).is_none() && catalogue.get_view(table_name).is_none() {
+            return Err(Error::new(
+                ErrorCode::UndefinedTable,
+                format!("Table {} was created after the backup", table_name),
+            )
+            .with_hint("Take a new backup after changing the schema"));
+        }
+        db.storage.try_update_rows(table_name, |rows| {
+            changes
+                .iter()
+                .filter(|change| change.table == *table_name)
+                .try_for_each(|change| redo(rows, change))
+        })??;
+    }
+    db.changes.append(&db.storage, &changes)?;
+
+    // Sequences hand out values after those of the replayed rows.
+    let mut last_values = Vec::new();
+    for table in catalogue.tables() {
+        let rows = db.storage.scan(table.name())?.rows;
+        for column in table.columns() {
+            if column.default() != Some(&ColumnDefault::Autoincrement) {
+                continue;
+            }
+            let last_value = rows
+                .iter()
+                .filter_map(|row| row.get(column.name())?.as_str()?.parse::<i64>().ok())
+                .max();
+            if let Some(last_value) = last_value {
+                let sequence = catalogue::sequence_name(table.name(), column.name());
+                last_values.push((sequence, last_value));
+            }
+        }
+    }
+    for (sequence, last_value) in last_values {
+        if let Some(sequence) = catalogue.get_sequence_mut(&sequence) {
+            sequence.advance_past(last_value);
+        }
+    }
+    catalogue.save(&db.storage)?;
+
+    // Views kept up to date with every write follow the replayed changes.
+    let views: Vec<String> = catalogue
+        .views()
+        .iter()
+        .filter(|view| view.materialized() == Some(Refresh::Incremental))
+        .map(|view| view.name().to_string())
+        .collect();
+    drop(catalogue);
+    for name in views {
+        view::refresh(&db, &name).await?;
+    }
+
+    Ok(Restored {
+        lsn: changes.last().map_or(backup.lsn, |change| change.lsn),
+        replayed: changes.len(),
+    })
+}
+
+/// The archived changes a restore up to `until` replays. Transactions are
+/// replayed whole.
+fn changes_until(
+    backup: &Backup,
+    mut changes: Vec<Change>,
+    until: Option<&Until>,
+) -> Result<Vec<Change>, Error> {
+    let Some(until) = until else {
+        return Ok(changes);
+    };
+    let before_backup = match until {
+        Until::Lsn(lsn) => *lsn < backup.lsn,
+        Until::Timestamp(timestamp) => {
+            history::micros(timestamp) < history::micros(&backup.taken_at)
+        }
+    };
+    if before_backup {
+        return Err(Error::new(
+            ErrorCode::InvalidParameter,
+            format!(
+                "The backup starts at LSN {}, taken at {}, after the restore point",
+                backup.lsn, backup.taken_at
+            ),
+        )
+        .with_hint("Restore from an older backup"));
+    }
+    let last = changes
+        .iter()
+        .rev()
+        .find(|change| match until {
+            Until::Lsn(lsn) => change.lsn <= *lsn,
+            Until::Timestamp(timestamp) => {
+                history::micros(&change.committed_at) <= history::micros(timestamp)
+            }
+        })
+        .map(|change| change.transaction);
+    changes.retain(|change| last.is_some_and(|last| change.transaction <= last));
+    Ok(changes)
+}
+
+/// Makes the row change `change` made again. Fails if the row it changed
+/// is not among `rows`, as then the log no longer describes the table.
+fn redo(rows: &mut Vec<Row>, change: &Change) -> Result<(), Error> {
+    let position = change
+        .before
+        .as_ref()
+        .map(|before| rows.iter().position(|row| row == before));
+    match (change.op, position, &change.after) {
+        (Operation::Insert, None, Some(after)) => rows.push(after.clone()),
+        (Operation::Update, Some(Some(position)), Some(after)) => rows[position] = after.clone(),
+        (Operation::Delete, Some(Some(position)), _) => {
+            rows.remove(position);
+        }
+        _ => return Err(history::diverged(change)),
+    }
+    Ok(())
+}
+
+fn read_manifest(storage: &Storage) -> Result<Backup, Error> {
+    let path = storage.path(MANIFEST_FILE);
+    if !path.exists() {
+        return Err(Error::new(
+            ErrorCode::InvalidParameter,
+            format!(
+                "{} holds no backup",
+                path.parent().unwrap_or(&path).display()
+            ),
+        ));
+    }
+    Ok(serde_json::from_slice(&std::fs::read(path)?).map_err(std::io::Error::from)?)
+}
+
+/// Checks that `dir` does not exist or is empty.
+fn check_empty(dir: &Path) -> Result<(), Error> {
+    if dir.exists() && std::fs::read_dir(dir)?.next().is_some() {
+        return Err(Error::new(
+            ErrorCode::InvalidParameter,
+            format!("Directory {} is not empty", dir.display()),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,320 @@
+//! Errors of the database. Every error has a stable code that clients can
+//! match on, a message for people, and optionally the part of the statement
+//! it is about and a hint on how to fix it.
+
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+
+/// What went wrong, in broad strokes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The text of a statement, schema or request could not be parsed.
+    Parse,
+    /// The statement parsed, but refers to things that do not exist or uses
+    /// them wrongly.
+    Analysis,
+    /// A statement that analyzed fine could not run to completion: a value
+    /// it computes is out of range, or it ran into a limit.
+    Execution,
+    /// A row breaks a constraint of its table.
+    Constraint,
+    /// A cursor or prepared statement is not in the state the statement
+    /// needs.
+    Transaction,
+    /// Reading or writing the data directory failed.
+    Storage,
+}
+
+/// Stable error codes. They are named after the PostgreSQL condition of
+/// their SQLSTATE where there is one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    SyntaxError,
+    InvalidRequest,
+    UndefinedTable,
+    UndefinedColumn,
+    AmbiguousColumn,
+    DuplicateColumn,
+    InvalidJoin,
+    GroupingError,
+    DuplicateTable,
+    InvalidTableDefinition,
+    InvalidForeignKey,
+    WrongObjectType,
+    FeatureNotSupported,
+    WrongParameterCount,
+    InvalidParameter,
+    AmbiguousParameter,
+    UnusedParameter,
+    NullParameter,
+    GeneratedAlways,
+    DatatypeMismatch,
+    InvalidTextRepresentation,
+    NumericValueOutOfRange,
+    UndefinedType,
+    DuplicateType,
+    DuplicateObject,
+    ProgramLimitExceeded,
+    NoData,
+    NotNullViolation,
+    CheckViolation,
+    UniqueViolation,
+    ForeignKeyViolation,
+    UndefinedCursor,
+    DuplicateCursor,
+    CursorMemoryExceeded,
+    UndefinedPreparedStatement,
+    SnapshotTooOld,
+    IoError,
+    DataCorrupted,
+}
+
+impl ErrorCode {
+    pub fn kind(self) -> ErrorKind {
+        match self {
+            ErrorCode::SyntaxError | ErrorCode::InvalidRequest => ErrorKind::Parse,
+            ErrorCode::UndefinedTable
+            | ErrorCode::UndefinedColumn
+            | ErrorCode::AmbiguousColumn
+            | ErrorCode::DuplicateColumn
+            | ErrorCode::InvalidJoin
+            | ErrorCode::GroupingError
+            | ErrorCode::DuplicateTable
+            | ErrorCode::InvalidTableDefinition
+            | ErrorCode::InvalidForeignKey
+            | ErrorCode::WrongObjectType
+            | ErrorCode::FeatureNotSupported
+            | ErrorCode::WrongParameterCount
+            | ErrorCode::InvalidParameter
+            | ErrorCode::AmbiguousParameter
+            | ErrorCode::UnusedParameter
+            | ErrorCode::NullParameter
+            | ErrorCode::GeneratedAlways
+            | ErrorCode::DatatypeMismatch
+            | ErrorCode::InvalidTextRepresentation
+            | ErrorCode::UndefinedType
+            | ErrorCode::DuplicateType
+            | ErrorCode::DuplicateObject
+            | ErrorCode::NoData => ErrorKind::Analysis,
+            ErrorCode::NumericValueOutOfRange | ErrorCode::ProgramLimitExceeded => {
+                ErrorKind::Execution
+            }
+            ErrorCode::NotNullViolation
+            | ErrorCode::CheckViolation
+            | ErrorCode::UniqueViolation
+            | ErrorCode::ForeignKeyViolation => ErrorKind::Constraint,
+            ErrorCode::UndefinedCursor
+            | ErrorCode::DuplicateCursor
+            | ErrorCode::CursorMemoryExceeded
+            | ErrorCode::UndefinedPreparedStatement
+            | ErrorCode::SnapshotTooOld => ErrorKind::Transaction,
+            ErrorCode::IoError | ErrorCode::DataCorrupted => ErrorKind::Storage,
+        }
+    }
+
+    /// The SQLSTATE reported to PostgreSQL clients.
+    pub fn sqlstate(self) -> &'static str {
+        match self {
+            ErrorCode::SyntaxError => "42601",
+            ErrorCode::InvalidRequest => "08P01",
+            ErrorCode::UndefinedTable => "42P01",
+            ErrorCode::UndefinedColumn => "42703",
+            ErrorCode::AmbiguousColumn => "42702",
+            ErrorCode::DuplicateColumn => "42701",
+            ErrorCode::InvalidJoin => "42P10",
+            ErrorCode::GroupingError => "42803",
+            ErrorCode::DuplicateTable => "42P07",
+            ErrorCode::InvalidTableDefinition => "42P16",
+            ErrorCode::InvalidForeignKey => "42830",
+            ErrorCode::WrongObjectType => "42809",
+            ErrorCode::FeatureNotSupported => "0A000",
+            ErrorCode::WrongParameterCount => "08P01",
+            ErrorCode::InvalidParameter => "22P02",
+            ErrorCode::AmbiguousParameter => "42P08",
+            ErrorCode::UnusedParameter => "42P18",
+            ErrorCode::NullParameter => "22004",
+            ErrorCode::GeneratedAlways => "428C9",
+            ErrorCode::DatatypeMismatch => "42804",
+            ErrorCode::InvalidTextRepresentation => "22P02",
+            ErrorCode::NumericValueOutOfRange => "22003",
+            ErrorCode::UndefinedType => "42704",
+            ErrorCode::DuplicateType => "42710",
+            ErrorCode::DuplicateObject => "42710",
+            ErrorCode::ProgramLimitExceeded => "54000",
+            ErrorCode::NoData => "02000",
+            ErrorCode::NotNullViolation => "23502",
+            ErrorCode::CheckViolation => "23514",
+            ErrorCode::UniqueViolation => "23505",
+            ErrorCode::ForeignKeyViolation => "23503",
+            ErrorCode::UndefinedCursor => "34000",
+            ErrorCode::DuplicateCursor => "42P03",
+            ErrorCode::CursorMemoryExceeded => "53200",
+            ErrorCode::UndefinedPreparedStatement => "26000",
+            ErrorCode::SnapshotTooOld => "72000",
+            ErrorCode::IoError => "58030",
+            ErrorCode::DataCorrupted => "XX001",
+        }
+    }
+}
+
+/// A part of a statement, in characters from its start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    code: ErrorCode,
+    message: String,
+    span: Option<Span>,
+    hint: Option<String>,
+    source: Option<std::io::Error>,
+    /// Further problems found together with this one, as by analysis.
+    related: Vec<Error>,
+}
+
+impl Error {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Error {
+        Error {
+            code,
+            message: message.into(),
+            span: None,
+            hint: None,
+            source: None,
+            related: Vec::new(),
+        }
+    }
+
+    /// The first of `errors`, carrying the others. `errors` must not be
+    /// empty.
+    pub fn many(errors: Vec<Error>) -> Error {
+        let mut errors = errors.into_iter();
+        let mut error = errors.next().expect("There should be at least one error");
+        error.related.extend(errors);
+        error
+    }
+
+    pub fn with_span(mut self, span: Span) -> Error {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Error {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.code.kind()
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub fn hint(&self) -> Option<&str> {
+        self.hint.as_deref()
+    }
+
+    /// This error followed by the others found together with it.
+    pub fn all(&self) -> impl Iterator<Item = &Error> {
+        std::iter::once(self).chain(self.related.iter())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<&str> = self.all().map(Error::message).collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|error| error as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Error {
+        let mut wrapped = Error::new(ErrorCode::IoError, error.to_string());
+        wrapped.source = Some(error);
+        wrapped
+    }
+}
+
+/// Serializes a single error, without the related ones.
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Fields<'a> {
+            kind: ErrorKind,
+            code: ErrorCode,
+            message: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            span: Option<Span>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            hint: Option<&'a str>,
+        }
+        Fields {
+            kind: self.kind(),
+            code: self.code,
+            message: &self.message,
+            span: self.span,
+            hint: self.hint(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_errors() {
+        let error = Error::many(vec![
+            Error::new(ErrorCode::SyntaxError, "Syntax error at or near \"fro\"")
+                .with_span(Span { start: 8, end: 11 })
+                .with_hint("Did you mean from?"),
+            Error::new(ErrorCode::UndefinedTable, "Table Nope does not exist"),
+        ]);
+        let errors: Vec<&Error> = error.all().collect();
+        assert_eq!(
+            serde_json::to_value(errors).unwrap(),
+            serde_json::json!([
+                {
+                    "kind": "parse",
+                    "code": "syntax_error",
+                    "message": "Syntax error at or near \"fro\"",
+                    "span": { "start": 8, "end": 11 },
+                    "hint": "Did you mean from?"
+                },
+                {
+                    "kind": "analysis",
+                    "code": "undefined_table",
+                    "message": "Table Nope does not exist"
+                }
+            ])
+        );
+        assert_eq!(
+            error.to_string(),
+            "Syntax error at or near \"fro\"; Table Nope does not exist"
+        );
+    }
+}
@@ -0,0 +1,644 @@
+//! A database in a data directory. Everything a database knows, from its
+//! catalogue to the workload its adaptive components observed, lives in its
+//! [`Database`], so several databases can be open in one process.
+
+pub mod backup;
+pub mod error;
+pub mod result;
+
+use std::path::PathBuf;
+
+use tokio::sync::{Mutex, MutexGuard, RwLock};
+
+use crate::adaptive::advisor::{self, Advice, Advisor};
+use crate::adaptive::cracking::{CrackerColumns, CrackerState};
+use crate::adaptive::layout;
+use crate::adaptive::statistics::Statistics;
+use crate::catalogue::{self, check::CheckExpression, json::JsonStep, Catalogue};
+use crate::config::Config;
+use crate::queryprocessing::cursor::Cursors;
+use crate::queryprocessing::prepared::{self, Prepared, PreparedStatements};
+use crate::queryprocessing::{self, ddl, dml, history, trigger, view};
+use crate::storage::changelog::{Change, ChangeLog};
+use crate::storage::index::Indexes;
+use crate::storage::Storage;
+use backup::{Archive, Backup, Restored};
+pub use error::{Error, ErrorCode, ErrorKind, Span};
+pub use result::{FromValue, QueryResult, Record, Rows};
+
+pub struct Database {
+    config: Config,
+    pub(crate) catalogue: Mutex<Catalogue>,
+    pub(crate) storage: Storage,
+    pub(crate) indexes: Indexes,
+    pub(crate) statistics: Statistics,
+    pub(crate) layout: layout::Workload,
+    pub(crate) advisor: Advisor,
+    pub(crate) cracking: CrackerColumns,
+    pub(crate) prepared: PreparedStatements,
+    pub(crate) cursors: Cursors,
+    pub(crate) triggers: trigger::Callbacks,
+    pub(crate) changes: ChangeLog,
+    /// Held shared by statements that only read, and exclusively by
+    /// statements that write and while a backup is taken, so neither sees
+    /// a write halfway done.
+    pub(crate) statements: RwLock<()>,
+    /// The backup committed changes are archived to, if any.
+    pub(crate) archive: std::sync::Mutex<Option<Archive>>,
+}
+
+impl Database {
+    /// Opens the database in the directory `path` with the default
+    /// settings. The directory is created on the first write.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Database, Error> {
+        Database::open_with_config(Config {
+            data_dir: path.into(),
+            ..Config::default()
+        })
+    }
+
+    /// Opens the database in the data directory of `config`.
+    pub fn open_with_config(config: Config) -> Result<Database, Error> {
+        let storage = Storage::new(config.data_dir.clone(), config.fsync);
+        let catalogue = Catalogue::load(&storage)?;
+        let changes = ChangeLog::open(&storage)?;
+        let archive = Archive::open(&storage)?;
+        Ok(Database {
+            catalogue: Mutex::new(catalogue),
+            storage,
+            indexes: Indexes::default(),
+            statistics: Statistics::default(),
+            layout: layout::Workload::default(),
+            advisor: Advisor::new(config.autonomous_indexing),
+            cracking: CrackerColumns::default(),
+            prepared: PreparedStatements::default(),
+            cursors: Cursors::new(config.memory_budget.0),
+            triggers: trigger::Callbacks::default(),
+            changes,
+            statements: RwLock::new(()),
+            archive: std::sync::Mutex::new(archive),
+            config,
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The catalogue, locked until the guard is dropped.
+    pub async fn catalogue(&self) -> MutexGuard<'_, Catalogue> {
+        self.catalogue.lock().await
+    }
+
+    /// Creates the enums and a table for every model of a DDL schema.
+    /// Returns the names of the tables. Nothing is created if any model is
+    /// invalid or names a table or enum that exists.
+    pub async fn apply_schema(&self, ddl: &str) -> Result<Vec<String>, Error> {
+        let mut schema = ddl::parse(ddl.to_string())?;
+        ddl::analyze(&schema.models)?;
+
+        let mut catalogue = self.catalogue.lock().await;
+        for (i, enum_type) in schema.enums.iter().enumerate() {
+            let declared_before = schema.enums[..i]
+                .iter()
+                .any(|other| other.name() == enum_type.name());
+            if declared_before || catalogue.get_enum(enum_type.name()).is_some() {
+                return Err(Error::new(
+                    ErrorCode::DuplicateType,
+                    format!("Enum {} already exists", enum_type.name()),
+                )
+                .with_hint("No table or enum of the schema was created"));
+            }
+        }
+        ddl::resolve_types(&mut schema, catalogue.enums())?;
+        for model in schema.models.iter() {
+            if catalogue.table_exists(&model.name) || catalogue.get_view(&model.name).is_some() {
+                let kind = if catalogue.get_view(&model.name).is_some() { "View" } else { "Table" };
+                return Err(Error::new(
+                    ErrorCode::DuplicateTable,
+                    format!("{} {} already exists", kind, model.name),
+                )
+                .with_hint("No table or enum of the schema was created"));
+            }
+            check_history_table(&catalogue, &schema.models, model)
+                .map_err(|error| error.with_hint("No table or enum of the schema was created"))?;
+        }
+        ddl::resolve_references(&mut schema, catalogue.tables())?;
+
+        // The catalogue changes once the storage has.
+        let mut next = catalogue.clone();
+        for enum_type in schema.enums {
+            next.add_enum(enum_type);
+        }
+        let models = schema.models;
+        for model in models.iter() {
+            self.create_table(&mut next, model)?;
+        }
+
+        next.save(&self.storage)?;
+        *catalogue = next;
+        Ok(models.into_iter().map(|model| model.name).collect())
+    }
+
+    /// Applies a DDL schema to a database that may have some of its tables
+    /// already. Models of new tables and new enums are created as by
+    /// `apply_schema`. Models of existing tables must declare the fields and
+    /// primary key the table has; the checks, unique keys, foreign keys and
+    /// indexes they add are added once the rows of the table satisfy them,
+    /// and their retention replaces the table's. Returns the names of the
+    /// tables created or changed. Nothing changes if any model fails.
+    pub async fn migrate_schema(&self, ddl: &str) -> Result<Vec<String>, Error> {
+        let mut schema = ddl::parse(ddl.to_string())?;
+        ddl::analyze(&schema.models)?;
+
+        let mut catalogue = self.catalogue.lock().await;
+        let mut new_enums = Vec::new();
+        for enum_type in schema.enums.iter() {
+            let declared_before = new_enums
+                .iter()
+                .any(|other: &catalogue::basic_types::EnumType| other.name() == enum_type.name());
+            match catalogue.get_enum(enum_type.name()) {
+                Some(existing) if existing == enum_type => {}
+                None if !declared_before => new_enums.push(enum_type.clone()),
+                _ => {
+                    return Err(Error::new(
+                        ErrorCode::DuplicateType,
+                        format!("Enum {} already exists", enum_type.name()),
+                    )
+                    .with_hint("Migrations cannot change the labels of an enum"))
+                }
+            }
+        }
+        schema.enums = new_enums;
+        ddl::resolve_types(&mut schema, catalogue.enums())?;
+        ddl::resolve_references(&mut schema, catalogue.tables())?;
+
+        let mut new_models = Vec::new();
+        let mut changes = Vec::new();
+        for model in schema.models.iter() {
+            if !catalogue.table_exists(&model.name) {
+                check_history_table(&catalogue, &schema.models, model)?;
+            }
+        }
+        for model in schema.models {
+            if catalogue.get_view(&model.name).is_some() {
+                return Err(Error::new(
+                    ErrorCode::DuplicateTable,
+                    format!("View {} already exists", model.name),
+                )
+                .with_hint("Migrations change tables, not views"));
+            }
+            let Some(table) = catalogue.get_table(&model.name) else {
+                new_models.push(model);
+                continue;
+            };
+            if model.temporal != table.history_table().is_some() {
+                return Err(Error::new(
+                    ErrorCode::InvalidTableDefinition,
+                    format!(
+                        "Migrations cannot turn system versioning of table {} on or off",
+                        model.name
+                    ),
+                )
+                .with_hint("Declare @@temporal as the table was created"));
+            }
+            // The system columns of a system-versioned table come from
+            // @@temporal, not from fields.
+            let columns: Vec<&catalogue::Column> = table
+                .columns()
+                .iter()
+                .filter(|column| !(model.temporal && catalogue::is_system_column(column.name())))
+                .collect();
+            let same_fields = columns.len() == model.fields.len()
+                && columns
+                    .iter()
+                    .zip(model.fields.iter())
+                    .all(|(column, field)| {
+                        column.name() == field.name
+                            && column.data_type() == field.field_type
+                            && column.is_nullable() == field.is_nullable
+                    })
+                && table
+                    .primary_key()
+                    .iter()
+                    .map(|column| column.name())
+                    .eq(model.primary_key());
+            if !same_fields {
+                return Err(Error::new(
+                    ErrorCode::InvalidTableDefinition,
+                    format!(
+                        "Migrations cannot change the fields of table {}",
+                        model.name
+                    ),
+                )
+                .with_hint(
+                    "Declare the fields the table has, and add checks, unique keys, foreign keys or indexes",
+                ));
+            }
+
+            let (checks, uniques) = model_constraints(&model);
+            let foreign_keys: Vec<_> = model_foreign_keys(&model)
+                .into_iter()
+                .filter(|foreign_key| {
+                    !table.foreign_keys().iter().any(|f| f.name() == foreign_key.name())
+                })
+                .collect();
+            let checks: Vec<_> = checks
+                .into_iter()
+                .filter(|check| !table.checks().iter().any(|c| c.name() == check.name()))
+                .collect();
+            let uniques: Vec<_> = uniques
+                .into_iter()
+                .filter(|unique| !table.uniques().iter().any(|u| u.name() == unique.name()))
+                .collect();
+            let rows = self.storage.scan(&model.name)?.rows;
+            let all: Vec<usize> = (0..rows.len()).collect();
+            let mut violations =
+                queryprocessing::constraint_violations(table, &checks, &uniques, &rows, &all);
+            violations.extend(queryprocessing::foreign_key_violations(
+                self,
+                table,
+                &foreign_keys,
+                &rows,
+                &all,
+            )?);
+            if !violations.is_empty() {
+                let violations = violations
+                    .into_iter()
+                    .map(|violation| {
+                        violation.with_hint(
+                            "Fix or delete the rows that violate it before adding the constraint",
+                        )
+                    })
+                    .collect();
+                return Err(Error::many(violations));
+            }
+            changes.push((model, checks, uniques, foreign_keys));
+        }
+        let mut names: Vec<String> = new_models.iter().map(|model| model.name.clone()).collect();
+        // The catalogue changes once the storage has.
+        let mut next = catalogue.clone();
+        for (model, checks, uniques, foreign_keys) in changes {
+            let table = next
+                .get_table_mut(&model.name)
+                .expect("The table was found above");
+            add_indexes(table, &model);
+            for check in checks {
+                table.add_check(check);
+            }
+            for unique in uniques {
+                table.add_unique(unique);
+            }
+            for foreign_key in foreign_keys {
+                table.add_foreign_key(foreign_key);
+            }
+            table.set_retention(model.retention);
+            names.push(model.name);
+        }
+        for enum_type in schema.enums {
+            next.add_enum(enum_type);
+        }
+        for model in new_models.iter() {
+            self.create_table(&mut next, model)?;
+        }
+        next.save(&self.storage)?;
+        *catalogue = next;
+        Ok(names)
+    }
+
+    /// Adds the table of a model to the catalogue, with its indexes,
+    /// constraints and the sequences of its autoincrement fields.
+    fn create_table(&self, catalogue: &mut Catalogue, model: &ddl::Model) -> Result<(), Error> {
+        let mut columns: Vec<catalogue::Column> = model
+            .fields
+            .iter()
+            .map(|field| {
+                catalogue::Column::new(
+                    field.name.clone(),
+                    field.field_type.clone(),
+                    field.is_nullable,
+                )
+                .with_adaptive(field.is_adaptive)
+                .with_json_schema(field.json_schema.clone())
+                .with_default(field.default.clone())
+            })
+            .collect();
+        if model.temporal {
+            let timestamp = catalogue::basic_types::BasicType::TimestampTz;
+            columns.push(
+                catalogue::Column::new(catalogue::VALID_FROM.to_string(), timestamp.clone(), false)
+                    .with_default(Some(catalogue::ColumnDefault::Now)),
+            );
+            columns.push(catalogue::Column::new(
+                catalogue::VALID_TO.to_string(),
+                timestamp,
+                true,
+            ));
+        }
+        for field in model.fields.iter() {
+            let name = catalogue::sequence_name(&model.name, &field.name);
+            if field.default == Some(catalogue::ColumnDefault::Autoincrement)
+                && !catalogue.sequence_exists(&name)
+            {
+                catalogue.add_sequence(catalogue::Sequence::new(name, 1, 1));
+            }
+        }
+        let primary_key = model
+            .primary_key()
+            .into_iter()
+            .map(|name| {
+                let position = model.fields.iter().position(|field| field.name == name);
+                position.expect("Analyzing should have caught this error") as u32
+            })
+            .collect();
+        let mut table = catalogue::Table::new(model.name.clone(), columns, primary_key);
+        if let Some(layout) = model.layout {
+            table = table.with_pinned_layout(layout);
+            layout::migrate(&self.storage, &mut table, layout)?;
+        }
+        add_indexes(&mut table, model);
+        let (checks, uniques) = model_constraints(model);
+        for check in checks {
+            table.add_check(check);
+        }
+        for unique in uniques {
+            table.add_unique(unique);
+        }
+        for foreign_key in model_foreign_keys(model) {
+            table.add_foreign_key(foreign_key);
+        }
+        table.set_retention(model.retention);
+        if let Some(history_table) = model.history_table() {
+            // Past versions keep the columns of the rows, without their
+            // defaults, key or constraints.
+            let columns = table
+                .columns()
+                .iter()
+                .map(|column| {
+                    catalogue::Column::new(
+                        column.name().to_string(),
+                        column.data_type(),
+                        column.is_nullable(),
+                    )
+                    .with_json_schema(column.json_schema().cloned())
+                })
+                .collect();
+            catalogue.add_table(catalogue::Table::new(
+                history_table.clone(),
+                columns,
+                Vec::new(),
+            ));
+            self.cracking.reset_table(&history_table);
+            table.set_history_table(Some(history_table));
+        }
+        catalogue.add_table(table);
+        self.cracking.reset_table(&model.name);
+        Ok(())
+    }
+
+    /// Parses, analyzes and runs a statement with the values of its `$n`
+    /// parameters.
+    pub async fn execute(
+        &self,
+        sql: &str,
+        params: &[serde_json::Value],
+    ) -> Result<QueryResult, Error> {
+        let mut statement = dml::parse(sql)?;
+        view::expand(self, &mut statement).await?;
+        dml::analyze(self, statement.clone()).await?;
+        prepared::bind(self, &mut statement, params).await?;
+        queryprocessing::execute(self, statement, None).await
+    }
+
+    /// Runs a statement that returns rows, such as a select.
+    pub async fn query(&self, sql: &str, params: &[serde_json::Value]) -> Result<Rows, Error> {
+        self.execute(sql, params)
+            .await?
+            .into_rows()
+            .ok_or_else(|| {
+                Error::new(ErrorCode::NoData, "The statement returns no rows")
+                    .with_hint("Use execute for statements other than select and fetch")
+            })
+    }
+
+    /// Parses, analyzes and plans a statement once, so it can run any number
+    /// of times with [`Database::execute_prepared`].
+    pub async fn prepare(&self, sql: &str) -> Result<Prepared, Error> {
+        prepared::prepare(self, sql).await
+    }
+
+    /// Runs a prepared statement with the values of its `$n` parameters.
+    pub async fn execute_prepared(
+        &self,
+        handle: &str,
+        params: &[serde_json::Value],
+    ) -> Result<QueryResult, Error> {
+        let (statement, plan) = prepared::bind_prepared(self, handle, params).await?;
+        queryprocessing::execute(self, statement, plan).await
+    }
+
+    /// Forgets a prepared statement. Returns whether it existed.
+    pub fn deallocate(&self, handle: &str) -> bool {
+        self.prepared.deallocate(handle)
+    }
+
+    /// Registers a Rust trigger, which runs for every row `event` changes in
+    /// a table, before or after the row is written. An error it returns
+    /// rejects the write, which then changes nothing.
+    pub fn register_trigger(
+        &self,
+        table_name: &str,
+        timing: catalogue::TriggerTiming,
+        event: catalogue::TriggerEvent,
+        callback: impl Fn(&trigger::RowChange) -> Result<(), Error> + Send + Sync + 'static,
+    ) {
+        self.triggers
+            .register(table_name, timing, event, std::sync::Arc::new(callback));
+    }
+
+    /// Up to `limit` committed row changes after the LSN `since`, oldest
+    /// first.
+    pub fn changes(&self, since: u64, limit: usize) -> Result<Vec<Change>, Error> {
+        Ok(self.changes.since(&self.storage, since, limit)?)
+    }
+
+    /// Waits until a row change after the LSN `since` has committed.
+    pub async fn wait_for_changes(&self, since: u64) {
+        self.changes.wait_for(since).await
+    }
+
+    /// Removes the row changes older than the retention of their table
+    /// from the change log. Returns how many were removed.
+    pub async fn prune_history(&self) -> Result<usize, Error> {
+        history::prune(self).await
+    }
+
+    /// Copies the catalogue, data and change log to the empty directory
+    /// `to` between statements, and archives the changes committed from then
+    /// on there. Changes are no longer archived to earlier backups.
+    pub async fn backup(&self, to: impl Into<PathBuf>) -> Result<Backup, Error> {
+        backup::backup(self, &to.into()).await
+    }
+
+    /// Archives the changes committed since the last archived one to the
+    /// latest backup. Returns how many were archived.
+    pub fn archive_changes(&self) -> Result<usize, Error> {
+        backup::archive(self)
+    }
+
+    /// Rebuilds the empty data directory `data_dir` from the backup in
+    /// `from`, replaying the archived changes up to `until`, an LSN or a
+    /// timestamp, or all of them.
+    pub async fn restore(
+        from: impl Into<PathBuf>,
+        data_dir: impl Into<PathBuf>,
+        until: Option<&str>,
+    ) -> Result<Restored, Error> {
+        backup::restore(&from.into(), &data_dir.into(), until).await
+    }
+
+    /// The indexes the index advisor recommends creating and dropping.
+    pub async fn index_advice(&self) -> Advice {
+        let catalogue = self.catalogue.lock().await;
+        advisor::advise(self, &catalogue)
+    }
+
+    /// The state of every cracker column.
+    pub fn cracker_states(&self) -> Vec<CrackerState> {
+        self.cracking.states()
+    }
+
+    /// Forgets the crack state and index contents of `table_name` after a
+    /// write other than an append, since they only follow appends.
+    pub(crate) fn invalidate_table(&self, table_name: &str) {
+        self.cracking.reset_table(table_name);
+        self.indexes.invalidate_table(table_name);
+    }
+
+    /// An empty database in a temporary directory, for tests.
+    #[cfg(test)]
+    pub(crate) fn temporary(name: &str) -> Database {
+        let path =
+            std::env::temp_dir().join(format!("adaptivedb-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        Database::open(path).unwrap()
+    }
+}
+
+/// Checks that the history table of a `@@temporal` model is free to
+/// create.
+fn check_history_table(
+    catalogue: &Catalogue,
+    models: &[ddl::Model],
+    model: &ddl::Model,
+) -> Result<(), Error> {
+    let Some(history_table) = model.history_table() else {
+        return Ok(());
+    };
+    if catalogue.table_exists(&history_table)
+        || catalogue.get_view(&history_table).is_some()
+        || models.iter().any(|other| other.name == history_table)
+    {
+        return Err(Error::new(
+            ErrorCode::DuplicateTable,
+            format!(
+                "Table {} already exists, so model {} cannot keep its history in it",
+                history_table, model.name
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Adds the indexes a model declares to its table, unless the table has
+/// them.
+fn add_indexes(table: &mut catalogue::Table, model: &ddl::Model) {
+    for index in model.indexes.iter() {
+        let column = &index.field;
+        let Some(path) = &index.path else {
+            if table.index_on(column).is_none() {
+                let name = advisor::index_name(&model.name, column);
+                table.add_index(catalogue::Index::new(name, column.clone()).with_kind(index.kind));
+            }
+            continue;
+        };
+        let exists = table
+            .indexes()
+            .iter()
+            .any(|index| index.column() == column && index.path() == Some(path));
+        if !exists {
+            // Named after the field and the steps of the path, as in
+            // idx_order_doc_customer_name.
+            let steps: Vec<String> = path
+                .steps
+                .iter()
+                .map(|step| match step {
+                    JsonStep::Key(key) => key.clone(),
+                    JsonStep::Index(position) => position.to_string(),
+                })
+                .collect();
+            let name = advisor::index_name(&model.name, &format!("{}_{}", column, steps.join("_")));
+            table.add_index(catalogue::Index::new(name, column.clone()).with_path(path.clone()));
+        }
+    }
+}
+
+/// The foreign keys of a model whose references `resolve_references` has
+/// filled in, named like `salary_employeeid_fkey`.
+fn model_foreign_keys(model: &ddl::Model) -> Vec<catalogue::ForeignKey> {
+    model
+        .references
+        .iter()
+        .map(|reference| {
+            let columns: Vec<&str> = reference.fields.iter().map(String::as_str).collect();
+            catalogue::ForeignKey::new(
+                catalogue::constraint_name(&model.name, &columns, "fkey"),
+                reference.fields.clone(),
+                reference.model.clone(),
+                reference.referenced_fields.clone(),
+            )
+        })
+        .collect()
+}
+
+/// The checks and unique keys a model declares, named after their table and
+/// fields as PostgreSQL names them.
+fn model_constraints(model: &ddl::Model) -> (Vec<catalogue::Check>, Vec<catalogue::Unique>) {
+    let mut checks: Vec<catalogue::Check> = Vec::new();
+    let field_checks = model.fields.iter().filter_map(|field| {
+        let expression = field.check.as_ref()?;
+        Some((vec![field.name.clone()], expression))
+    });
+    let model_checks = model.checks.iter().map(|expression| {
+        // Analyzing made sure the expression parses.
+        let columns = CheckExpression::parse(expression)
+            .map(|check| check.columns().into_iter().map(str::to_string).collect())
+            .unwrap_or_default();
+        (columns, expression)
+    });
+    for (columns, expression) in field_checks.chain(model_checks) {
+        let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+        let name = catalogue::constraint_name(&model.name, &columns, "check");
+        // Numbered like employee_salary_check1 when the name is taken.
+        let mut unique_name = name.clone();
+        let mut number = 0;
+        while checks.iter().any(|check| check.name() == unique_name) {
+            number += 1;
+            unique_name = format!("{}{}", name, number);
+        }
+        checks.push(catalogue::Check::new(unique_name, expression.clone()));
+    }
+
+    let uniques = model
+        .uniques
+        .iter()
+        .map(|fields| {
+            let columns: Vec<&str> = fields.iter().map(String::as_str).collect();
+            catalogue::Unique::new(
+                catalogue::constraint_name(&model.name, &columns, "key"),
+                fields.clone(),
+            )
+        })
+        .collect();
+    (checks, uniques)
+}
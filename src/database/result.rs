@@ -0,0 +1,185 @@
+//! What statements return. Rows are typed by the columns of their select, so
+//! values can be read as Rust types with [`Record::get`].
+
+use std::sync::Arc;
+
+use crate::catalogue::basic_types::BasicType;
+use crate::storage::Row;
+
+use super::{Error, ErrorCode};
+
+#[derive(Debug)]
+pub enum QueryResult {
+    Select(Rows),
+    Fetch(Rows),
+    /// The number of rows inserted.
+    Insert(usize),
+    /// The number of rows updated.
+    Update(usize),
+    /// The number of rows deleted.
+    Delete(usize),
+    /// The plan of an `explain`, with the execution time for `explain
+    /// analyze`.
+    Explain(serde_json::Value),
+    Declare,
+    Close,
+    Commit,
+    CreateSequence,
+    CreateView,
+    RefreshView,
+    CreateTrigger,
+}
+
+impl QueryResult {
+    /// The rows of a select or fetch.
+    pub fn into_rows(self) -> Option<Rows> {
+        match self {
+            QueryResult::Select(rows) | QueryResult::Fetch(rows) => Some(rows),
+            _ => None,
+        }
+    }
+
+    /// The number of rows an insert, update or delete changed.
+    pub fn affected_rows(&self) -> Option<usize> {
+        match self {
+            QueryResult::Insert(rows) | QueryResult::Update(rows) | QueryResult::Delete(rows) => {
+                Some(*rows)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The rows of a result, in order.
+#[derive(Debug)]
+pub struct Rows {
+    columns: Vec<(String, BasicType)>,
+    names: Arc<[String]>,
+    rows: std::vec::IntoIter<Row>,
+}
+
+impl Rows {
+    pub(crate) fn new(columns: Vec<(String, BasicType)>, rows: Vec<Row>) -> Rows {
+        Rows {
+            names: columns.iter().map(|(name, _)| name.clone()).collect(),
+            columns,
+            rows: rows.into_iter(),
+        }
+    }
+
+    /// The name and type of every column, in select order. Columns of a
+    /// join are qualified with their table.
+    pub fn columns(&self) -> &[(String, BasicType)] {
+        &self.columns
+    }
+}
+
+impl Iterator for Rows {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Record> {
+        self.rows.next().map(|values| Record {
+            columns: self.names.clone(),
+            values,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rows.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Rows {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    columns: Arc<[String]>,
+    values: Row,
+}
+
+impl Record {
+    pub(crate) fn new(columns: Arc<[String]>, values: Row) -> Record {
+        Record { columns, values }
+    }
+
+    /// The value of `column` as a `T`. NULL is only accepted by `Option`.
+    pub fn get<T: FromValue>(&self, column: &str) -> Result<T, Error> {
+        if !self.columns.iter().any(|name| name == column) {
+            return Err(Error::new(
+                ErrorCode::UndefinedColumn,
+                format!("Column {} is not part of the result", column),
+            )
+            .with_hint(format!("The result has columns {}", self.columns.join(", "))));
+        }
+        // Rows leave out the columns an insert did not set, which are NULL.
+        let text;
+        let value = match self.values.get(column) {
+            None | Some(serde_json::Value::Null) => None,
+            Some(serde_json::Value::String(value)) => Some(value.as_str()),
+            Some(value) => {
+                text = value.to_string();
+                Some(text.as_str())
+            }
+        };
+        let is_null = value.is_none();
+        T::from_value(value).map_err(|error| {
+            let error = Error::new(
+                ErrorCode::DatatypeMismatch,
+                format!("Column {}: {}", column, error),
+            );
+            if is_null {
+                error.with_hint("Read columns that can be NULL as an Option")
+            } else {
+                error
+            }
+        })
+    }
+
+    /// The values as stored, keyed by column name.
+    pub fn into_map(self) -> Row {
+        self.values
+    }
+}
+
+/// A Rust type that stored values can be read as.
+pub trait FromValue: Sized {
+    /// Converts a stored value, `None` for NULL.
+    fn from_value(value: Option<&str>) -> Result<Self, String>;
+}
+
+fn parse<T: std::str::FromStr>(value: Option<&str>, type_name: &str) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("NULL is not a {}", type_name))?;
+    value
+        .parse()
+        .map_err(|_| format!("'{}' is not a {}", value, type_name))
+}
+
+impl FromValue for i64 {
+    fn from_value(value: Option<&str>) -> Result<i64, String> {
+        parse(value, "Int")
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: Option<&str>) -> Result<f64, String> {
+        parse(value, "Float")
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Option<&str>) -> Result<bool, String> {
+        parse(value, "Bool")
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Option<&str>) -> Result<String, String> {
+        parse(value, "String")
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Option<&str>) -> Result<Option<T>, String> {
+        value.map(|value| T::from_value(Some(value))).transpose()
+    }
+}
@@ -0,0 +1,30 @@
+use http_body_util::combinators::BoxBody;
+use hyper::{body::Bytes, Method, Request, Response, StatusCode};
+
+use super::{empty, full};
+use crate::Database;
+
+pub async fn advisor_handler(
+    db: &Database,
+    req: Request<hyper::body::Incoming>,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/advisor/indexes") => get_indexes(db).await,
+        // Return 404 Not Found for other routes.
+        _ => {
+            let mut not_found = Response::new(empty());
+            *not_found.status_mut() = StatusCode::NOT_FOUND;
+            Ok(not_found)
+        }
+    }
+}
+
+async fn get_indexes(
+    db: &Database,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    let advice = db.index_advice().await;
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(full(serde_json::to_string(&advice).unwrap()))
+        .unwrap())
+}
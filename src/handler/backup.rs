@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use http_body_util::{combinators::BoxBody, BodyExt};
+use hyper::{body::Bytes, Method, Request, Response, StatusCode};
+
+use super::{empty, error_response, full, parse_body};
+use crate::{Database, Error, ErrorCode};
+
+pub async fn backup_handler(
+    db: &Database,
+    req: Request<hyper::body::Incoming>,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    match (req.method(), req.uri().path()) {
+        (&Method::POST, "/backup") => post_backup(db, req).await,
+        // Return 404 Not Found for other routes.
+        _ => {
+            let mut not_found = Response::new(empty());
+            *not_found.status_mut() = StatusCode::NOT_FOUND;
+            Ok(not_found)
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct PostBackup {
+    /// The name of the backup, a directory in the backup directory.
+    name: String,
+}
+
+async fn post_backup(
+    db: &Database,
+    req: Request<hyper::body::Incoming>,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    let body = req.into_body().collect().await?.to_bytes();
+    let body_json = match parse_body::<PostBackup>(&body) {
+        Ok(body_json) => body_json,
+        Err(error) => return Ok(error_response(&error)),
+    };
+    let to = match backup_path(db, &body_json.name) {
+        Ok(to) => to,
+        Err(error) => return Ok(error_response(&error)),
+    };
+
+    match db.backup(to).await {
+        Ok(backup) => Ok(Response::builder()
+            .status(StatusCode::CREATED)
+            .body(full(serde_json::to_string(&backup).unwrap()))
+            .unwrap()),
+        Err(error) => Ok(error_response(&error)),
+    }
+}
+
+/// The directory of the backup `name` in the configured backup directory.
+/// Names are single path components, so backups stay in that directory.
+fn backup_path(db: &Database, name: &str) -> Result<PathBuf, Error> {
+    let Some(backup_dir) = &db.config().backup_dir else {
+        return Err(Error::new(
+            ErrorCode::FeatureNotSupported,
+            "Backups over HTTP are disabled",
+        )
+        .with_hint("Start the server with --backup-dir to enable them"));
+    };
+    let is_valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !is_valid {
+        return Err(Error::new(
+            ErrorCode::InvalidRequest,
+            format!("Invalid backup name \"{}\"", name),
+        )
+        .with_hint("Backup names are made of letters, digits, -, _ and ., as in 2026-10-01"));
+    }
+    Ok(backup_dir.join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_backup_path() {
+        let db = Database::temporary("backup_path");
+        let error = backup_path(&db, "monday").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::FeatureNotSupported);
+
+        let db = Database::open_with_config(Config {
+            data_dir: std::env::temp_dir().join("adaptivedb-test-backup-path-data"),
+            backup_dir: Some(PathBuf::from("/var/backups/adaptivedb")),
+            ..Config::default()
+        })
+        .unwrap();
+        assert_eq!(
+            backup_path(&db, "2026-10-01.full").unwrap(),
+            PathBuf::from("/var/backups/adaptivedb/2026-10-01.full")
+        );
+        for name in ["", "..", "../etc", "/tmp/x", "a/b", ".hidden"] {
+            let error = backup_path(&db, name).unwrap_err();
+            assert_eq!(error.code(), ErrorCode::InvalidRequest, "{}", name);
+        }
+    }
+}
@@ -1,13 +1,18 @@
 use http_body_util::{combinators::BoxBody, BodyExt};
 use hyper::{body::Bytes, Method, Request, Response, StatusCode};
 
-use crate::{catalogue, empty, full, queryprocessing};
+use super::{empty, error_response, full, parse_body};
+use crate::Database;
 
 pub async fn catalogue_handler(
+    db: &Database,
     req: Request<hyper::body::Incoming>,
 ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
     match (req.method(), req.uri().path()) {
-        (&Method::POST, "/catalogue") => post_catalogue(req).await,
+        (&Method::GET, "/catalogue") => get_catalogue(db).await,
+        (&Method::POST, "/catalogue") => post_catalogue(db, req).await,
+        (&Method::PUT, "/catalogue") => put_catalogue(db, req).await,
+        (&Method::GET, "/catalogue/cracking") => get_cracking(db),
         // Return 404 Not Found for other routes.
         _ => {
             let mut not_found = Response::new(empty());
@@ -22,76 +27,66 @@ struct PostTable {
     schema: String,
 }
 
-#[derive(Debug, serde::Serialize)]
-struct PostTableErrorResponse {
-    errors: Vec<String>,
-}
-
 async fn post_catalogue(
+    db: &Database,
     req: Request<hyper::body::Incoming>,
 ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
     let body = req.into_body().collect().await?.to_bytes();
-    let body_json = serde_json::from_slice::<PostTable>(&body);
-    if body_json.is_err() {
-        return Ok(Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body(full(body_json.as_ref().unwrap_err().to_string()))
-            .unwrap());
-    }
+    let body_json = match parse_body::<PostTable>(&body) {
+        Ok(body_json) => body_json,
+        Err(error) => return Ok(error_response(&error)),
+    };
 
-    let body_json = body_json.unwrap();
-    let schema = body_json.schema;
-
-    let models = queryprocessing::ddl::parse(schema);
-    let errors = queryprocessing::ddl::analyze(&models);
-    if !errors.is_empty() {
-        return Ok(Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body(full(
-                serde_json::to_string(&PostTableErrorResponse { errors }).unwrap(),
-            ))
-            .unwrap());
-    }
-
-    let mut catalogue = crate::get_catalogue().lock().await;
-    for model in models.iter() {
-        if catalogue.table_exists(&model.name) {
-            return Ok(Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body(full(format!(
-                    "Aborted. Table {} already exists",
-                    model.name
-                )))
-                .unwrap());
+    match db.apply_schema(&body_json.schema).await {
+        Ok(table_names) => {
+            let result = serde_json::json!({ "result": { "tables": table_names } });
+            Ok(Response::builder()
+                .status(StatusCode::CREATED)
+                .body(full(serde_json::to_string(&result).unwrap()))
+                .unwrap())
         }
+        Err(error) => Ok(error_response(&error)),
     }
+}
 
-    for model in models.iter() {
-        let columns = model
-            .fields
-            .iter()
-            .map(|field| {
-                catalogue::Column::new(
-                    field.name.clone(),
-                    field.field_type.clone(),
-                    field.is_nullable,
-                )
-            })
-            .collect();
-        let primary_key_index = model
-            .fields
-            .iter()
-            .position(|field| field.is_primary_key)
-            .expect("Analyzing should have caught this error");
-        let table = catalogue::Table::new(model.name.clone(), columns, primary_key_index as u32);
-        catalogue.add_table(table);
+/// Migrates the catalogue to a schema, which may redeclare existing tables
+/// with more constraints and indexes.
+async fn put_catalogue(
+    db: &Database,
+    req: Request<hyper::body::Incoming>,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    let body = req.into_body().collect().await?.to_bytes();
+    let body_json = match parse_body::<PostTable>(&body) {
+        Ok(body_json) => body_json,
+        Err(error) => return Ok(error_response(&error)),
+    };
+
+    match db.migrate_schema(&body_json.schema).await {
+        Ok(table_names) => {
+            let result = serde_json::json!({ "result": { "tables": table_names } });
+            Ok(Response::builder()
+                .status(StatusCode::OK)
+                .body(full(serde_json::to_string(&result).unwrap()))
+                .unwrap())
+        }
+        Err(error) => Ok(error_response(&error)),
     }
+}
 
-    catalogue.save().unwrap();
+async fn get_catalogue(
+    db: &Database,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    let catalogue = db.catalogue().await;
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(full(serde_json::to_string(&*catalogue).unwrap()))
+        .unwrap())
+}
 
-    let model_names: Vec<String> = models.iter().map(|model| model.name.clone()).collect();
+fn get_cracking(db: &Database) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    let states = db.cracker_states();
     Ok(Response::builder()
-        .status(StatusCode::CREATED)
-        .body(full(format!("Created following tables: {:?}", model_names)))
+        .status(StatusCode::OK)
+        .body(full(serde_json::to_string(&states).unwrap()))
         .unwrap())
 }
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_body_util::combinators::BoxBody;
+use hyper::body::Bytes;
+use hyper::header::{ACCEPT, CACHE_CONTROL, CONTENT_TYPE};
+use hyper::{Method, Request, Response, StatusCode};
+use tokio::sync::mpsc;
+
+use super::{chunked, empty, error_response, full};
+use crate::{Database, Error, ErrorCode};
+
+/// Changes sent in one response, or read at once for an event stream.
+const DEFAULT_LIMIT: usize = 1000;
+
+/// Seconds a long poll waits for changes unless the request says otherwise.
+const DEFAULT_WAIT: u64 = 30;
+
+/// An event stream without changes sends a comment this often, to notice
+/// clients that went away.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+const EVENT_STREAM: &str = "text/event-stream";
+
+pub async fn changes_handler(
+    db: &Arc<Database>,
+    req: Request<hyper::body::Incoming>,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/changes") => get_changes(db, req).await,
+        // Return 404 Not Found for other routes.
+        _ => {
+            let mut not_found = Response::new(empty());
+            *not_found.status_mut() = StatusCode::NOT_FOUND;
+            Ok(not_found)
+        }
+    }
+}
+
+/// The parameters of `GET /changes`.
+#[derive(Debug)]
+struct Position {
+    /// The LSN of the last change the client has seen.
+    since: u64,
+    limit: usize,
+    /// Seconds to wait for changes if there are none yet.
+    wait: u64,
+}
+
+impl Position {
+    fn parse(query: Option<&str>) -> Result<Position, Error> {
+        let mut position = Position {
+            since: 0,
+            limit: DEFAULT_LIMIT,
+            wait: DEFAULT_WAIT,
+        };
+        for pair in query
+            .unwrap_or("")
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+        {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let number = || {
+                value.parse::<u64>().map_err(|_| {
+                    Error::new(
+                        ErrorCode::InvalidRequest,
+                        format!("Invalid value \"{}\" for {}", value, name),
+                    )
+                    .with_hint("Expected a non-negative integer")
+                })
+            };
+            match name {
+                "since" => position.since = number()?,
+                "limit" => position.limit = number()?.max(1) as usize,
+                "wait" => position.wait = number()?,
+                _ => {
+                    return Err(Error::new(
+                        ErrorCode::InvalidRequest,
+                        format!("Unknown parameter {}", name),
+                    )
+                    .with_hint("Changes are read with since, limit and wait"))
+                }
+            }
+        }
+        Ok(position)
+    }
+}
+
+/// Sends the changes after `since`. Clients that accept `text/event-stream`
+/// get a stream of them that follows new commits; others get the changes
+/// there are, waiting up to `wait` seconds for the first one.
+async fn get_changes(
+    db: &Arc<Database>,
+    req: Request<hyper::body::Incoming>,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    let mut position = match Position::parse(req.uri().query()) {
+        Ok(position) => position,
+        Err(error) => return Ok(error_response(&error)),
+    };
+    let event_stream = req
+        .headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(EVENT_STREAM));
+    if !event_stream {
+        return Ok(long_poll(db, &position).await);
+    }
+
+    // Reconnecting event sources resume after the last event they received.
+    if let Some(last_event_id) = req
+        .headers()
+        .get("last-event-id")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse().ok())
+    {
+        position.since = last_event_id;
+    }
+    let (sender, receiver) = mpsc::channel(1);
+    tokio::spawn(stream_changes(db.clone(), position, sender));
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, EVENT_STREAM)
+        .header(CACHE_CONTROL, "no-cache")
+        .body(chunked(receiver))
+        .unwrap())
+}
+
+/// Answers with `{"changes": [...], "next": lsn}`, where `next` is the
+/// `since` to ask for the following changes with.
+async fn long_poll(db: &Database, position: &Position) -> Response<BoxBody<Bytes, hyper::Error>> {
+    let wait = Duration::from_secs(position.wait);
+    let _ = tokio::time::timeout(wait, db.wait_for_changes(position.since)).await;
+    let changes = match db.changes(position.since, position.limit) {
+        Ok(changes) => changes,
+        Err(error) => return error_response(&error),
+    };
+    let next = changes.last().map_or(position.since, |change| change.lsn);
+    let body = serde_json::json!({ "changes": changes, "next": next });
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .body(full(serde_json::to_string(&body).unwrap()))
+        .unwrap()
+}
+
+/// Sends every change after `since` as a server-sent event whose id is its
+/// LSN, until the client goes away.
+async fn stream_changes(db: Arc<Database>, mut position: Position, sender: mpsc::Sender<Bytes>) {
+    loop {
+        let changes = match db.changes(position.since, position.limit) {
+            Ok(changes) => changes,
+            Err(error) => {
+                let data = serde_json::json!({ "errors": [error] });
+                let _ = sender
+                    .send(Bytes::from(format!("event: error\ndata: {}\n\n", data)))
+                    .await;
+                return;
+            }
+        };
+        for change in changes {
+            position.since = change.lsn;
+            let event = format!(
+                "id: {}\nevent: change\ndata: {}\n\n",
+                change.lsn,
+                serde_json::to_string(&change).unwrap()
+            );
+            if sender.send(Bytes::from(event)).await.is_err() {
+                return;
+            }
+        }
+        let waited = tokio::time::timeout(KEEP_ALIVE, db.wait_for_changes(position.since)).await;
+        if waited.is_err() && sender.send(Bytes::from(": keep-alive\n\n")).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_position() {
+        let position = Position::parse(Some("since=42&limit=10&wait=0")).unwrap();
+        assert_eq!((position.since, position.limit, position.wait), (42, 10, 0));
+        let position = Position::parse(None).unwrap();
+        assert_eq!((position.since, position.limit), (0, DEFAULT_LIMIT));
+
+        let error = Position::parse(Some("since=-1")).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::InvalidRequest);
+        assert!(Position::parse(Some("from=1")).is_err());
+    }
+}
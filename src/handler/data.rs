@@ -1,13 +1,28 @@
 use http_body_util::{combinators::BoxBody, BodyExt};
-use hyper::{body::Bytes, Method, Request, Response, StatusCode};
+use hyper::body::Bytes;
+use hyper::header::{ACCEPT, CONTENT_TYPE};
+use hyper::{Method, Request, Response, StatusCode};
+use tokio::sync::mpsc;
 
-use crate::{empty, full, queryprocessing};
+use super::{chunked, empty, error_response, full, parse_body};
+use crate::catalogue::basic_types::BasicType;
+use crate::{storage::Row, Database, Error, ErrorCode, QueryResult};
+
+/// Rows serialized into one chunk of a streamed result.
+const ROWS_PER_CHUNK: usize = 256;
+
+/// Chunks serialized ahead of the client. Once they are all waiting to be
+/// sent, serializing pauses until the client reads more.
+const BUFFERED_CHUNKS: usize = 4;
+
+const NDJSON: &str = "application/x-ndjson";
 
 pub async fn data_handler(
+    db: &Database,
     req: Request<hyper::body::Incoming>,
 ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
     match (req.method(), req.uri().path()) {
-        (&Method::POST, "/data") => post_data(req).await,
+        (&Method::POST, "/data") => post_data(db, req).await,
         // Return 404 Not Found for other routes.
         _ => {
             let mut not_found = Response::new(empty());
@@ -17,53 +32,178 @@ pub async fn data_handler(
     }
 }
 
+/// A query, or the handle of a prepared statement, with the values of its
+/// `$n` parameters.
 #[derive(Debug, serde::Deserialize)]
 struct PostData {
-    query: String,
-}
-
-#[derive(Debug, serde::Serialize)]
-struct PostDataErrorResponse {
-    errors: Vec<String>,
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(default)]
+    handle: Option<String>,
+    #[serde(default)]
+    params: Vec<serde_json::Value>,
 }
 
 async fn post_data(
+    db: &Database,
     req: Request<hyper::body::Incoming>,
 ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    // Clients that accept NDJSON get one row per line instead of an array.
+    let ndjson = req
+        .headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(NDJSON));
     let body = req.into_body().collect().await?.to_bytes();
-    let body_json = serde_json::from_slice::<PostData>(&body);
-    if body_json.is_err() {
-        return Ok(Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body(full(body_json.as_ref().unwrap_err().to_string()))
-            .unwrap());
-    }
+    let body_json = match parse_body::<PostData>(&body) {
+        Ok(body_json) => body_json,
+        Err(error) => return Ok(error_response(&error)),
+    };
+
+    let result = match (body_json.query, body_json.handle) {
+        (Some(query), None) => db.execute(&query, &body_json.params).await,
+        (None, Some(handle)) => db.execute_prepared(&handle, &body_json.params).await,
+        _ => {
+            return Ok(error_response(&Error::new(
+                ErrorCode::InvalidRequest,
+                "Expected either a query or the handle of a prepared statement",
+            )))
+        }
+    };
+
+    let (status, result) = match result {
+        Ok(QueryResult::Select(rows) | QueryResult::Fetch(rows)) => {
+            let json_columns: Vec<String> = rows
+                .columns()
+                .iter()
+                .filter(|(_, column_type)| *column_type == BasicType::Json)
+                .map(|(name, _)| name.clone())
+                .collect();
+            let rows = rows.map(move |record| json_values(record.into_map(), &json_columns));
+            return Ok(rows_response(rows, ndjson));
+        }
+        Ok(QueryResult::Insert(_)) => (StatusCode::CREATED, serde_json::json!({ "success": true })),
+        Ok(QueryResult::Update(affected_rows) | QueryResult::Delete(affected_rows)) => (
+            StatusCode::OK,
+            serde_json::json!({ "success": true, "affected_rows": affected_rows }),
+        ),
+        Ok(QueryResult::Explain(plan)) => (StatusCode::OK, plan),
+        Ok(
+            QueryResult::Declare
+            | QueryResult::Close
+            | QueryResult::Commit
+            | QueryResult::CreateSequence
+            | QueryResult::CreateView
+            | QueryResult::RefreshView
+            | QueryResult::CreateTrigger,
+        ) => (StatusCode::OK, serde_json::json!({ "success": true })),
+        Err(error) => return Ok(error_response(&error)),
+    };
+    let result = serde_json::json!({ "result": result });
+
+    Ok(Response::builder()
+        .status(status)
+        .body(full(serde_json::to_string(&result).unwrap()))
+        .unwrap())
+}
 
-    let body_json = body_json.unwrap();
-    let query = body_json.query;
-    
-    let statement = queryprocessing::dml::parse(&query);
-    if statement.is_none() {
-        return Ok(Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body(full("Invalid query"))
-            .unwrap());
+/// Replaces the stored text of `json_columns` with the documents it holds,
+/// so clients get them as JSON rather than as strings.
+fn json_values(mut row: Row, json_columns: &[String]) -> Row {
+    for column in json_columns {
+        if let Some(value) = row.get_mut(column) {
+            if let Some(document) = value
+                .as_str()
+                .and_then(|text| serde_json::from_str(text).ok())
+            {
+                *value = document;
+            }
+        }
     }
+    row
+}
+
+/// Sends the rows of a select or fetch as a streamed body, serializing them
+/// as the client reads.
+fn rows_response(
+    rows: impl Iterator<Item = Row> + Send + 'static,
+    ndjson: bool,
+) -> Response<BoxBody<Bytes, hyper::Error>> {
+    let (sender, receiver) = mpsc::channel(BUFFERED_CHUNKS);
+    tokio::spawn(async move {
+        if !ndjson && sender.send(Bytes::from(r#"{"result":["#)).await.is_err() {
+            return;
+        }
+        let mut rows = rows.enumerate().peekable();
+        while rows.peek().is_some() {
+            let mut bytes = Vec::new();
+            for (position, row) in rows.by_ref().take(ROWS_PER_CHUNK) {
+                if !ndjson && position > 0 {
+                    bytes.push(b',');
+                }
+                serde_json::to_writer(&mut bytes, &row).unwrap();
+                if ndjson {
+                    bytes.push(b'\n');
+                }
+            }
+            // The client went away.
+            if sender.send(Bytes::from(bytes)).await.is_err() {
+                return;
+            }
+        }
+        if !ndjson {
+            let _ = sender.send(Bytes::from("]}")).await;
+        }
+    });
+
+    let content_type = if ndjson { NDJSON } else { "application/json" };
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, content_type)
+        .body(chunked(receiver))
+        .unwrap()
+}
 
-    let statement = statement.unwrap();
-    let errors = queryprocessing::dml::analyze(statement.clone()).await;
-    if !errors.is_empty() {
-        return Ok(Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body(full(serde_json::to_string(&PostDataErrorResponse { errors }).unwrap()))
-            .unwrap());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(count: usize) -> Vec<Row> {
+        (0..count)
+            .map(|id| {
+                let mut row = Row::new();
+                row.insert("id".to_string(), serde_json::json!(id.to_string()));
+                row
+            })
+            .collect()
     }
 
-    let result = queryprocessing::execute(statement);
-    let result = serde_json::to_string(&result).unwrap();
+    #[tokio::test]
+    async fn test_streamed_rows() {
+        let response = rows_response(rows(1000).into_iter(), false);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let result: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(result, serde_json::json!({ "result": rows(1000) }));
 
-    Ok(Response::builder()
-        .status(StatusCode::CREATED)
-        .body(full(result))
-        .unwrap())
+        let response = rows_response(rows(300).into_iter(), true);
+        assert_eq!(response.headers()[CONTENT_TYPE], NDJSON);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let lines: Vec<&[u8]> = body.split(|byte| *byte == b'\n').collect();
+        assert_eq!(lines.len(), 301);
+        assert_eq!(lines[299], br#"{"id":"299"}"#);
+
+        let response = rows_response(std::iter::empty(), false);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], br#"{"result":[]}"#);
+    }
+
+    #[test]
+    fn test_json_values() {
+        let mut row = Row::new();
+        row.insert("id".to_string(), serde_json::json!("1"));
+        row.insert("doc".to_string(), serde_json::json!(r#"{"a":[1,"2"]}"#));
+        let row = json_values(row, &["doc".to_string(), "missing".to_string()]);
+        assert_eq!(row["id"], serde_json::json!("1"));
+        assert_eq!(row["doc"], serde_json::json!({ "a": [1, "2"] }));
+    }
 }
@@ -1,2 +1,179 @@
+pub mod advisor;
+pub mod backup;
 pub mod catalogue;
-pub mod data;
\ No newline at end of file
+pub mod changes;
+pub mod data;
+pub mod prepare;
+pub mod subscribe;
+mod websocket;
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http_body_util::Full;
+use http_body_util::{combinators::BoxBody, BodyExt};
+use hyper::body::{Body, Bytes, Frame};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::StatusCode;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+use crate::{Database, Error, ErrorCode, ErrorKind};
+
+/// Accepts HTTP connections until the listener fails.
+pub async fn serve(db: Arc<Database>, listener: TcpListener) -> Result<(), std::io::Error> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+
+        // Use an adapter to access something implementing `tokio::io` traits as if they implement
+        // `hyper::rt` IO traits.
+        let io = TokioIo::new(stream);
+        let db = db.clone();
+
+        // Spawn a tokio task to serve multiple connections concurrently
+        tokio::task::spawn(async move {
+            let service = service_fn(|req| {
+                let db = db.clone();
+                async move { handle(&db, req).await }
+            });
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(io, service)
+                .with_upgrades()
+                .await
+            {
+                println!("Error serving connection: {:?}", err);
+            }
+        });
+    }
+}
+
+/// Routes a request to the handler of its path.
+pub async fn handle(
+    db: &Arc<Database>,
+    req: Request<hyper::body::Incoming>,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    if req.uri().path().starts_with("/catalogue") {
+        return catalogue::catalogue_handler(db, req).await;
+    }
+    if req.uri().path().starts_with("/data") {
+        return data::data_handler(db, req).await;
+    }
+    if req.uri().path().starts_with("/prepare") {
+        return prepare::prepare_handler(db, req).await;
+    }
+    if req.uri().path().starts_with("/advisor") {
+        return advisor::advisor_handler(db, req).await;
+    }
+    if req.uri().path().starts_with("/changes") {
+        return changes::changes_handler(db, req).await;
+    }
+    if req.uri().path().starts_with("/subscribe") {
+        return subscribe::subscribe_handler(db, req).await;
+    }
+    if req.uri().path().starts_with("/backup") {
+        return backup::backup_handler(db, req).await;
+    }
+
+    let mut not_found = Response::new(empty());
+    *not_found.status_mut() = StatusCode::NOT_FOUND;
+    Ok(not_found)
+}
+
+/// Reports `error` as `{"errors": [...]}`, with a status that depends on its
+/// kind.
+fn error_response(error: &Error) -> Response<BoxBody<Bytes, hyper::Error>> {
+    let status = match (error.kind(), error.code()) {
+        (_, ErrorCode::UndefinedCursor | ErrorCode::UndefinedPreparedStatement) => {
+            StatusCode::NOT_FOUND
+        }
+        (ErrorKind::Parse | ErrorKind::Analysis, _) => StatusCode::BAD_REQUEST,
+        (ErrorKind::Execution, _) => StatusCode::UNPROCESSABLE_ENTITY,
+        (ErrorKind::Constraint | ErrorKind::Transaction, _) => StatusCode::CONFLICT,
+        (ErrorKind::Storage, _) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let errors: Vec<&Error> = error.all().collect();
+    Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(full(
+            serde_json::to_string(&serde_json::json!({ "errors": errors })).unwrap(),
+        ))
+        .unwrap()
+}
+
+/// Parses the JSON body of a request.
+fn parse_body<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+    serde_json::from_slice(body).map_err(|error| {
+        Error::new(ErrorCode::InvalidRequest, error.to_string())
+            .with_hint("The body should be a JSON object")
+    })
+}
+
+// We create some utility functions to make Empty and Full bodies
+// fit our broadened Response body type.
+fn empty() -> BoxBody<Bytes, hyper::Error> {
+    Full::new(Bytes::new())
+        .map_err(|never| match never {})
+        .boxed()
+}
+fn full<T: Into<Bytes>>(chunk: T) -> BoxBody<Bytes, hyper::Error> {
+    Full::new(chunk.into())
+        .map_err(|never| match never {})
+        .boxed()
+}
+
+/// A body made of the chunks sent to `receiver`. It ends once the sender is
+/// dropped.
+fn chunked(receiver: mpsc::Receiver<Bytes>) -> BoxBody<Bytes, hyper::Error> {
+    ChunkedBody { receiver }.boxed()
+}
+
+struct ChunkedBody {
+    receiver: mpsc::Receiver<Bytes>,
+}
+
+impl Body for ChunkedBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, hyper::Error>>> {
+        self.receiver
+            .poll_recv(cx)
+            .map(|chunk| chunk.map(|bytes| Ok(Frame::data(bytes))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_status() {
+        for (code, status) in [
+            (ErrorCode::SyntaxError, StatusCode::BAD_REQUEST),
+            (ErrorCode::UndefinedTable, StatusCode::BAD_REQUEST),
+            (
+                ErrorCode::NumericValueOutOfRange,
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                ErrorCode::ProgramLimitExceeded,
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (ErrorCode::UniqueViolation, StatusCode::CONFLICT),
+            (ErrorCode::SnapshotTooOld, StatusCode::CONFLICT),
+            (ErrorCode::UndefinedCursor, StatusCode::NOT_FOUND),
+            (ErrorCode::IoError, StatusCode::INTERNAL_SERVER_ERROR),
+        ] {
+            let response = error_response(&Error::new(code, "message"));
+            assert_eq!(response.status(), status, "{:?}", code);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use http_body_util::{combinators::BoxBody, BodyExt};
+use hyper::{body::Bytes, Method, Request, Response, StatusCode};
+
+use super::{empty, error_response, full, parse_body};
+use crate::{Database, Error, ErrorCode};
+
+pub async fn prepare_handler(
+    db: &Database,
+    req: Request<hyper::body::Incoming>,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    let path = req.uri().path().to_string();
+    match (req.method(), path.as_str()) {
+        (&Method::POST, "/prepare") => post_prepare(db, req).await,
+        (&Method::DELETE, path) if path.starts_with("/prepare/") => {
+            delete_prepare(db, &path["/prepare/".len()..])
+        }
+        // Return 404 Not Found for other routes.
+        _ => {
+            let mut not_found = Response::new(empty());
+            *not_found.status_mut() = StatusCode::NOT_FOUND;
+            Ok(not_found)
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct PostPrepare {
+    query: String,
+}
+
+async fn post_prepare(
+    db: &Database,
+    req: Request<hyper::body::Incoming>,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    let body = req.into_body().collect().await?.to_bytes();
+    let body_json = match parse_body::<PostPrepare>(&body) {
+        Ok(body_json) => body_json,
+        Err(error) => return Ok(error_response(&error)),
+    };
+
+    match db.prepare(&body_json.query).await {
+        Ok(prepared) => Ok(Response::builder()
+            .status(StatusCode::CREATED)
+            .body(full(serde_json::to_string(&prepared).unwrap()))
+            .unwrap()),
+        Err(error) => Ok(error_response(&error)),
+    }
+}
+
+fn delete_prepare(
+    db: &Database,
+    handle: &str,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    if !db.deallocate(handle) {
+        return Ok(error_response(&Error::new(
+            ErrorCode::UndefinedPreparedStatement,
+            format!("Prepared statement {} does not exist", handle),
+        )));
+    }
+    let mut response = Response::new(empty());
+    *response.status_mut() = StatusCode::NO_CONTENT;
+    Ok(response)
+}
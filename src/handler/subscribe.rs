@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use http_body_util::combinators::BoxBody;
+use hyper::body::Bytes;
+use hyper::header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE};
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio::io::AsyncWrite;
+use tokio::sync::mpsc;
+
+use super::websocket::{self, Message, MessageReader, Opcode};
+use super::{empty, error_response, parse_body};
+use crate::queryprocessing::subscription::Subscription;
+use crate::{Database, Error, ErrorCode};
+
+pub async fn subscribe_handler(
+    db: &Arc<Database>,
+    req: Request<hyper::body::Incoming>,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/subscribe") => Ok(upgrade(db, req)),
+        // Return 404 Not Found for other routes.
+        _ => {
+            let mut not_found = Response::new(empty());
+            *not_found.status_mut() = StatusCode::NOT_FOUND;
+            Ok(not_found)
+        }
+    }
+}
+
+/// A message of a client: a select to subscribe to under an id of the
+/// client's choosing, or the id of a subscription to end.
+#[derive(Debug, serde::Deserialize)]
+struct ClientMessage {
+    #[serde(default)]
+    subscribe: Option<String>,
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(default)]
+    params: Vec<serde_json::Value>,
+    #[serde(default)]
+    unsubscribe: Option<String>,
+}
+
+/// Switches the connection to the WebSocket protocol and serves the
+/// subscriptions of the client on it.
+fn upgrade(
+    db: &Arc<Database>,
+    mut req: Request<hyper::body::Incoming>,
+) -> Response<BoxBody<Bytes, hyper::Error>> {
+    let is_websocket = req
+        .headers()
+        .get(UPGRADE)
+        .and_then(|upgrade| upgrade.to_str().ok())
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+    let key = req
+        .headers()
+        .get(SEC_WEBSOCKET_KEY)
+        .and_then(|key| key.to_str().ok());
+    let Some(key) = key.filter(|_| is_websocket) else {
+        return error_response(
+            &Error::new(ErrorCode::InvalidRequest, "Expected a WebSocket upgrade")
+                .with_hint("Send Upgrade: websocket and a Sec-WebSocket-Key"),
+        );
+    };
+    let accept = websocket::accept_key(key);
+
+    let on_upgrade = hyper::upgrade::on(&mut req);
+    let db = db.clone();
+    tokio::spawn(async move {
+        match on_upgrade.await {
+            Ok(upgraded) => {
+                if let Err(error) = serve_subscriptions(db, TokioIo::new(upgraded)).await {
+                    tracing::debug!("Subscription connection failed: {}", error);
+                }
+            }
+            Err(error) => tracing::debug!("WebSocket upgrade failed: {}", error),
+        }
+    });
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(UPGRADE, "websocket")
+        .header(CONNECTION, "Upgrade")
+        .header(SEC_WEBSOCKET_ACCEPT, accept)
+        .body(empty())
+        .unwrap()
+}
+
+/// Answers the messages of a client, and sends the changes to the results
+/// of its subscriptions as the transactions making them commit.
+async fn serve_subscriptions(
+    db: Arc<Database>,
+    io: TokioIo<hyper::upgrade::Upgraded>,
+) -> Result<(), std::io::Error> {
+    let (reader, mut writer) = tokio::io::split(io);
+    // Reading a message cannot be cancelled halfway through, so it gets a
+    // task of its own.
+    let (sender, mut messages) = mpsc::channel(16);
+    let reading = tokio::spawn(async move {
+        let mut reader = MessageReader::new(reader);
+        loop {
+            let message = reader.next().await;
+            let last = !matches!(
+                message,
+                Ok(Message::Text(_) | Message::Binary(_) | Message::Ping(_) | Message::Pong)
+            );
+            if sender.send(message).await.is_err() || last {
+                return;
+            }
+        }
+    });
+
+    let mut subscriptions: BTreeMap<String, Subscription> = BTreeMap::new();
+    let result = loop {
+        let since = subscriptions.values().map(Subscription::lsn).min();
+        let changed = async {
+            match since {
+                Some(since) => db.wait_for_changes(since).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            message = messages.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    let reply = answer(&db, &mut subscriptions, &text).await;
+                    send(&mut writer, &reply).await?;
+                }
+                Some(Ok(Message::Binary(_))) => {
+                    let error = Error::new(ErrorCode::InvalidRequest, "Expected a text message");
+                    send(&mut writer, &serde_json::json!({ "errors": [error] })).await?;
+                }
+                Some(Ok(Message::Ping(payload))) => {
+                    websocket::write_frame(&mut writer, Opcode::Pong, &payload).await?;
+                }
+                Some(Ok(Message::Pong)) => {}
+                Some(Ok(Message::Close)) | None => {
+                    break websocket::write_frame(&mut writer, Opcode::Close, &[]).await;
+                }
+                Some(Err(error)) => break Err(error),
+            },
+            _ = changed => {
+                for (id, subscription) in subscriptions.iter_mut() {
+                    let transactions = match subscription.poll(&db).await {
+                        Ok(transactions) => transactions,
+                        Err(error) => {
+                            let reply = serde_json::json!({ "subscription": id, "errors": [error] });
+                            send(&mut writer, &reply).await?;
+                            continue;
+                        }
+                    };
+                    for transaction in transactions {
+                        let mut reply = serde_json::to_value(transaction).unwrap();
+                        reply["subscription"] = serde_json::json!(id);
+                        send(&mut writer, &reply).await?;
+                    }
+                }
+            }
+        }
+    };
+    reading.abort();
+    result
+}
+
+/// Subscribes to a select or ends a subscription, and returns the reply:
+/// the rows of the select, or that the subscription ended.
+async fn answer(
+    db: &Database,
+    subscriptions: &mut BTreeMap<String, Subscription>,
+    text: &str,
+) -> serde_json::Value {
+    let message = match parse_body::<ClientMessage>(text.as_bytes()) {
+        Ok(message) => message,
+        Err(error) => return serde_json::json!({ "errors": [error] }),
+    };
+    match message {
+        ClientMessage {
+            subscribe: Some(id),
+            query: Some(query),
+            unsubscribe: None,
+            params,
+        } => {
+            if subscriptions.contains_key(&id) {
+                let error = Error::new(
+                    ErrorCode::DuplicateObject,
+                    format!("Subscription {} already exists", id),
+                );
+                return serde_json::json!({ "subscription": id, "errors": [error] });
+            }
+            match Subscription::new(db, &query, &params).await {
+                Ok((subscription, rows)) => {
+                    let reply = serde_json::json!({
+                        "subscription": id,
+                        "lsn": subscription.lsn(),
+                        "rows": rows,
+                    });
+                    subscriptions.insert(id, subscription);
+                    reply
+                }
+                Err(error) => serde_json::json!({ "subscription": id, "errors": [error] }),
+            }
+        }
+        ClientMessage {
+            subscribe: None,
+            query: None,
+            unsubscribe: Some(id),
+            ..
+        } => {
+            if subscriptions.remove(&id).is_some() {
+                serde_json::json!({ "subscription": id, "unsubscribed": true })
+            } else {
+                let error = Error::new(
+                    ErrorCode::InvalidRequest,
+                    format!("Subscription {} does not exist", id),
+                );
+                serde_json::json!({ "subscription": id, "errors": [error] })
+            }
+        }
+        _ => {
+            let error = Error::new(
+                ErrorCode::InvalidRequest,
+                "Expected a subscription id with a query, or the id to unsubscribe",
+            )
+            .with_hint(
+                r#"Send {"subscribe": "s1", "query": "select ..."} or {"unsubscribe": "s1"}"#,
+            );
+            serde_json::json!({ "errors": [error] })
+        }
+    }
+}
+
+async fn send(
+    writer: &mut (impl AsyncWrite + Unpin),
+    message: &serde_json::Value,
+) -> Result<(), std::io::Error> {
+    let text = serde_json::to_string(message).unwrap();
+    websocket::write_frame(writer, Opcode::Text, text.as_bytes()).await
+}
@@ -0,0 +1,303 @@
+//! The parts of the WebSocket protocol (RFC 6455) the server needs: the
+//! opening handshake, and reading and writing frames.
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Appended to the key of a handshake before hashing it.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Messages larger than this close the connection.
+const MAX_MESSAGE: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn from_bits(bits: u8) -> Option<Opcode> {
+        match bits {
+            0x0 => Some(Opcode::Continuation),
+            0x1 => Some(Opcode::Text),
+            0x2 => Some(Opcode::Binary),
+            0x8 => Some(Opcode::Close),
+            0x9 => Some(Opcode::Ping),
+            0xA => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xA,
+        }
+    }
+
+    fn is_control(self) -> bool {
+        matches!(self, Opcode::Close | Opcode::Ping | Opcode::Pong)
+    }
+}
+
+/// A message, or a control frame, received from a client.
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong,
+    Close,
+}
+
+/// The `Sec-WebSocket-Accept` answering a `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key.trim(), GUID).as_bytes()))
+}
+
+/// Reads the messages a client sends.
+pub struct MessageReader<R> {
+    reader: R,
+    /// The opcode and data of a message whose last frame is still to come.
+    pending: Option<(Opcode, Vec<u8>)>,
+}
+
+impl<R: AsyncRead + Unpin> MessageReader<R> {
+    pub fn new(reader: R) -> MessageReader<R> {
+        MessageReader {
+            reader,
+            pending: None,
+        }
+    }
+
+    /// Reads the next message, joining the frames it was split into.
+    /// Control frames may arrive between those frames, and are returned
+    /// as soon as they do.
+    pub async fn next(&mut self) -> Result<Message, std::io::Error> {
+        loop {
+            let (fin, opcode, payload) = read_frame(&mut self.reader).await?;
+            let opcode = match (opcode, &mut self.pending) {
+                (Opcode::Close, _) => return Ok(Message::Close),
+                (Opcode::Ping, _) => return Ok(Message::Ping(payload)),
+                (Opcode::Pong, _) => return Ok(Message::Pong),
+                (Opcode::Continuation, Some((opcode, data))) => {
+                    data.extend(payload);
+                    if data.len() > MAX_MESSAGE {
+                        return Err(invalid("Message too large"));
+                    }
+                    *opcode
+                }
+                (Opcode::Text | Opcode::Binary, None) => {
+                    self.pending = Some((opcode, payload));
+                    opcode
+                }
+                _ => return Err(invalid("Unexpected frame")),
+            };
+            if !fin {
+                continue;
+            }
+            let (_, data) = self.pending.take().unwrap();
+            return match opcode {
+                Opcode::Text => String::from_utf8(data)
+                    .map(Message::Text)
+                    .map_err(|_| invalid("Text message is not UTF-8")),
+                _ => Ok(Message::Binary(data)),
+            };
+        }
+    }
+}
+
+/// Reads a frame and returns whether it is the last one of its message,
+/// its opcode and its unmasked payload.
+async fn read_frame(
+    reader: &mut (impl AsyncRead + Unpin),
+) -> Result<(bool, Opcode, Vec<u8>), std::io::Error> {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header).await?;
+    let fin = header[0] & 0x80 != 0;
+    let opcode = Opcode::from_bits(header[0] & 0x0F).ok_or_else(|| invalid("Unknown opcode"))?;
+    let masked = header[1] & 0x80 != 0;
+    let length = match header[1] & 0x7F {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        length => length as u64,
+    };
+    if opcode.is_control() && (!fin || length > 125) {
+        return Err(invalid("Invalid control frame"));
+    }
+    if length > MAX_MESSAGE as u64 {
+        return Err(invalid("Message too large"));
+    }
+    // Clients must mask every frame they send.
+    if !masked {
+        return Err(invalid("Frame is not masked"));
+    }
+    let mut mask = [0; 4];
+    reader.read_exact(&mut mask).await?;
+    let mut payload = vec![0; length as usize];
+    reader.read_exact(&mut payload).await?;
+    for (position, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[position % 4];
+    }
+    Ok((fin, opcode, payload))
+}
+
+/// Writes `payload` as one unmasked frame.
+pub async fn write_frame(
+    writer: &mut (impl AsyncWrite + Unpin),
+    opcode: Opcode,
+    payload: &[u8],
+) -> Result<(), std::io::Error> {
+    let mut frame = vec![0x80 | opcode.bits()];
+    match payload.len() {
+        length @ 0..=125 => frame.push(length as u8),
+        length @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend((length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend((length as u64).to_be_bytes());
+        }
+    }
+    frame.extend(payload);
+    writer.write_all(&frame).await?;
+    writer.flush().await
+}
+
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend((data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (value, added) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(added);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, value) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for position in 0..4 {
+            if position <= chunk.len() {
+                text.push(ALPHABET[(bits >> (18 - 6 * position) & 0x3F) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key() {
+        // The example of RFC 6455.
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b"a"), "YQ==");
+    }
+
+    #[tokio::test]
+    async fn test_read_fragmented_message() {
+        let mask = [1, 2, 3, 4];
+        let frame = |first: u8, payload: &[u8]| {
+            let mut frame = vec![first, 0x80 | payload.len() as u8];
+            frame.extend(mask);
+            frame.extend(
+                payload
+                    .iter()
+                    .enumerate()
+                    .map(|(i, byte)| byte ^ mask[i % 4]),
+            );
+            frame
+        };
+        let mut input = frame(0x01, b"hel");
+        input.extend(frame(0x89, b"!"));
+        input.extend(frame(0x80, b"lo"));
+        let mut reader = MessageReader::new(&input[..]);
+        assert_eq!(reader.next().await.unwrap(), Message::Ping(b"!".to_vec()));
+        assert_eq!(
+            reader.next().await.unwrap(),
+            Message::Text("hello".to_string())
+        );
+
+        // Unmasked client frames are rejected.
+        let mut reader = MessageReader::new(&[0x81, 0x01, b'a'][..]);
+        assert!(reader.next().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_frame() {
+        let mut output = Vec::new();
+        write_frame(&mut output, Opcode::Text, &[b'x'; 200])
+            .await
+            .unwrap();
+        assert_eq!(&output[..4], &[0x81, 126, 0, 200]);
+        assert_eq!(output.len(), 204);
+    }
+}
@@ -0,0 +1,17 @@
+//! AdaptiveDB, a database that adapts its indexes, layouts and plans to the
+//! workload it sees. [`Database`] opens a database in a directory and runs
+//! statements on it in-process; the `adaptivedb` server makes the same
+//! database available over HTTP and the PostgreSQL wire protocol.
+
+pub mod adaptive;
+pub mod catalogue;
+pub mod config;
+mod database;
+pub mod handler;
+pub mod pgwire;
+pub mod queryprocessing;
+pub mod storage;
+
+pub use database::{
+    backup, Database, Error, ErrorCode, ErrorKind, FromValue, QueryResult, Record, Rows, Span,
+};
@@ -1,96 +1,137 @@
-use http_body_util::Full;
-use http_body_util::{combinators::BoxBody, BodyExt};
+use std::sync::Arc;
 
+use http_body_util::{BodyExt, Full};
 use hyper::body::Bytes;
-use hyper::server::conn::http1;
-use hyper::service::service_fn;
-use hyper::StatusCode;
-use hyper::{Request, Response};
-use hyper_util::rt::TokioIo;
-
-use once_cell::sync::Lazy;
-use std::net::SocketAddr;
-
+use hyper::{Method, Request};
+use hyper_util::rt::TokioExecutor;
 use tokio::net::TcpListener;
-use tokio::sync::Mutex;
 
-use tracing::{info, Level};
+use tracing::info;
 use tracing_subscriber::FmtSubscriber;
 
-mod catalogue;
-mod handler;
-mod queryprocessing;
-
-async fn handler(
-    req: Request<hyper::body::Incoming>,
-) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
-    if req.uri().path().starts_with("/catalogue") {
-        return handler::catalogue::catalogue_handler(req).await;
-    }
-    if req.uri().path().starts_with("/data") {
-        return handler::data::data_handler(req).await;
-    }
-
-    let mut not_found = Response::new(empty());
-    *not_found.status_mut() = StatusCode::NOT_FOUND;
-    Ok(not_found)
-}
-
-// We create some utility functions to make Empty and Full bodies
-// fit our broadened Response body type.
-fn empty() -> BoxBody<Bytes, hyper::Error> {
-    Full::new(Bytes::new())
-        .map_err(|never| match never {})
-        .boxed()
-}
-fn full<T: Into<Bytes>>(chunk: T) -> BoxBody<Bytes, hyper::Error> {
-    Full::new(chunk.into())
-        .map_err(|never| match never {})
-        .boxed()
-}
-
-static CATALOGUE: Lazy<Mutex<catalogue::Catalogue>> = Lazy::new(|| {
-    let catalogue =
-        catalogue::Catalogue::load().unwrap_or_else(|_| catalogue::Catalogue::new(Vec::new()));
-    Mutex::new(catalogue)
-});
-
-pub fn get_catalogue() -> &'static Mutex<catalogue::Catalogue> {
-    &CATALOGUE
-}
+use adaptivedb::config::{self, Config, Task};
+use adaptivedb::{adaptive, backup, handler, pgwire, queryprocessing, Database};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = match config::load() {
+        Ok(Task::Serve(config)) => config,
+        Ok(Task::Backup { url, name }) => {
+            if let Err(error) = request_backup(&url, &name).await {
+                eprintln!("Backup failed: {}", error);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Ok(Task::Restore {
+            from,
+            data_dir,
+            until,
+        }) => {
+            match Database::restore(from, &data_dir, until.as_deref()).await {
+                Ok(restored) => println!(
+                    "Restored {} to LSN {}, replaying {} archived changes",
+                    data_dir.display(),
+                    restored.lsn,
+                    restored.replayed
+                ),
+                Err(error) => {
+                    eprintln!("Restore failed: {}", error);
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        Err(errors) => {
+            for error in errors {
+                eprintln!("Invalid configuration: {}", error);
+            }
+            std::process::exit(2);
+        }
+    };
+    serve(config).await
+}
+
+async fn serve(config: Config) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::TRACE)
+        .with_max_level(config.log_level())
         .finish();
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
+    let db = Arc::new(Database::open_with_config(config.clone())?);
 
-    // We create a TcpListener and bind it to 127.0.0.1:3000
-    let listener = TcpListener::bind(addr).await?;
+    // In autonomous mode the index advisor creates and drops indexes itself.
+    if config.autonomous_indexing {
+        tokio::spawn(adaptive::advisor::run_autonomous(db.clone()));
+        info!("Autonomous indexing enabled");
+    }
+    tokio::spawn(adaptive::layout::run_background_migrations(db.clone()));
+    tokio::spawn(queryprocessing::history::run_pruning(db.clone()));
+    tokio::spawn(backup::run_archiving(db.clone()));
+
+    // Drivers and BI tools can connect over the PostgreSQL wire protocol.
+    if config.pgwire.enabled {
+        let pgwire_listener = TcpListener::bind(config.pgwire.listen).await?;
+        info!(
+            "AdaptiveDB accepting PostgreSQL connections on {}",
+            config.pgwire.listen
+        );
+        let db = db.clone();
+        tokio::spawn(async move {
+            if let Err(err) = pgwire::serve(db, pgwire_listener).await {
+                tracing::error!("pgwire listener failed: {}", err);
+            }
+        });
+    }
 
-    info!("AdaptiveDB listening on http://{}", addr);
-    // We start a loop to continuously accept incoming connections
-    loop {
-        let (stream, _) = listener.accept().await?;
+    let addr = config.http.listen;
 
-        // Use an adapter to access something implementing `tokio::io` traits as if they implement
-        // `hyper::rt` IO traits.
-        let io = TokioIo::new(stream);
+    // We create a TcpListener and bind it to the configured address
+    let listener = TcpListener::bind(addr).await?;
 
-        // Spawn a tokio task to serve multiple connections concurrently
-        tokio::task::spawn(async move {
-            // Finally, we bind the incoming connection to our `hello` service
-            if let Err(err) = http1::Builder::new()
-                // `service_fn` converts our function in a `Service`
-                .serve_connection(io, service_fn(handler))
-                .await
-            {
-                println!("Error serving connection: {:?}", err);
-            }
-        });
+    info!(
+        "AdaptiveDB listening on http://{} with data in {}",
+        addr,
+        config.data_dir.display()
+    );
+    handler::serve(db, listener).await?;
+    Ok(())
+}
+
+/// Asks the server at `url` to back up its database as `name`, in its
+/// backup directory.
+async fn request_backup(url: &str, name: &str) -> Result<(), String> {
+    let body = serde_json::json!({ "name": name }).to_string();
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(format!("{}/backup", url.trim_end_matches('/')))
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body)))
+        .map_err(|error| format!("Invalid server address {}: {}", url, error))?;
+    let client = hyper_util::client::legacy::Client::builder(TokioExecutor::new()).build_http();
+    let response = client
+        .request(request)
+        .await
+        .map_err(|error| format!("Cannot reach {}: {}", url, error))?;
+    let status = response.status();
+    let body = response
+        .into_body()
+        .collect()
+        .await
+        .map_err(|error| format!("Cannot read the response: {}", error))?
+        .to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body)
+        .map_err(|_| format!("Unexpected response from the server: {}", status))?;
+    if !status.is_success() {
+        return Err(body["errors"][0]["message"]
+            .as_str()
+            .unwrap_or("unknown error")
+            .to_string());
     }
+    println!(
+        "Backed up as {} at LSN {}; the server archives its changes there from now on",
+        name, body["lsn"]
+    );
+    Ok(())
 }
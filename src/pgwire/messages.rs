@@ -0,0 +1,666 @@
+//! Framing and value encoding of the PostgreSQL v3 protocol.
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::catalogue::basic_types::{self, BasicType};
+
+pub const PROTOCOL_VERSION: i32 = 196608;
+pub const SSL_REQUEST: i32 = 80877103;
+pub const GSSENC_REQUEST: i32 = 80877104;
+pub const CANCEL_REQUEST: i32 = 80877102;
+
+/// Messages larger than this are rejected rather than buffered.
+const MAX_MESSAGE_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Days between 1970-01-01 and 2000-01-01, the epoch of binary dates.
+const POSTGRES_EPOCH_DAYS: i64 = 10957;
+
+/// Microseconds between 1970-01-01 and 2000-01-01, the epoch of binary
+/// timestamps.
+const POSTGRES_EPOCH_MICROS: i64 = POSTGRES_EPOCH_DAYS * 86_400_000_000;
+
+/// Version of the binary jsonb format, the byte before the JSON text.
+const JSONB_VERSION: u8 = 1;
+
+pub const TEXT_FORMAT: i16 = 0;
+pub const BINARY_FORMAT: i16 = 1;
+
+/// A message sent by the client, without its length.
+pub struct Message {
+    pub tag: u8,
+    pub body: Vec<u8>,
+}
+
+/// Reads the first message of a connection, which has no tag. Returns its
+/// code and the rest of its body.
+pub async fn read_startup(
+    reader: &mut (impl AsyncRead + Unpin),
+) -> Result<(i32, Vec<u8>), std::io::Error> {
+    let length = read_length(reader, 8).await?;
+    let code = reader.read_i32().await?;
+    let mut body = vec![0; length - 8];
+    reader.read_exact(&mut body).await?;
+    Ok((code, body))
+}
+
+/// Reads the next message, or `None` once the client closed the connection.
+pub async fn read_message(
+    reader: &mut (impl AsyncRead + Unpin),
+) -> Result<Option<Message>, std::io::Error> {
+    let tag = match reader.read_u8().await {
+        Ok(tag) => tag,
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let length = read_length(reader, 4).await?;
+    let mut body = vec![0; length - 4];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(Message { tag, body }))
+}
+
+async fn read_length(
+    reader: &mut (impl AsyncRead + Unpin),
+    minimum: usize,
+) -> Result<usize, std::io::Error> {
+    let length = reader.read_i32().await?;
+    match usize::try_from(length) {
+        Ok(length) if (minimum..=MAX_MESSAGE_LENGTH).contains(&length) => Ok(length),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Invalid message length {}", length),
+        )),
+    }
+}
+
+/// Reads the fields of a message body in order.
+pub struct Body<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Body<'a> {
+    pub fn new(bytes: &'a [u8]) -> Body<'a> {
+        Body { bytes }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < length {
+            return Err("Message is shorter than its fields".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn i16(&mut self) -> Result<i16, String> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// A null-terminated string.
+    pub fn string(&mut self) -> Result<String, String> {
+        let Some(end) = self.bytes.iter().position(|byte| *byte == 0) else {
+            return Err("String is not null-terminated".to_string());
+        };
+        let string = self.take(end)?;
+        self.take(1)?;
+        String::from_utf8(string.to_vec()).map_err(|_| "String is not valid UTF-8".to_string())
+    }
+
+    /// A value prefixed by its length, where -1 stands for null.
+    pub fn value(&mut self) -> Result<Option<&'a [u8]>, String> {
+        let length = self.i32()?;
+        if length < 0 {
+            return Ok(None);
+        }
+        self.take(length as usize).map(Some)
+    }
+
+    /// A list of 16-bit integers prefixed by its length.
+    pub fn i16_list(&mut self) -> Result<Vec<i16>, String> {
+        let count = self.i16()?;
+        (0..count).map(|_| self.i16()).collect()
+    }
+}
+
+/// Builds the messages sent to the client.
+#[derive(Default)]
+pub struct Writer {
+    pub buffer: Vec<u8>,
+}
+
+impl Writer {
+    fn message(&mut self, tag: u8, body: impl FnOnce(&mut Vec<u8>)) {
+        self.buffer.push(tag);
+        let start = self.buffer.len();
+        self.buffer.extend_from_slice(&[0; 4]);
+        body(&mut self.buffer);
+        let length = (self.buffer.len() - start) as i32;
+        self.buffer[start..start + 4].copy_from_slice(&length.to_be_bytes());
+    }
+
+    pub fn authentication_ok(&mut self) {
+        self.message(b'R', |body| body.extend_from_slice(&0i32.to_be_bytes()));
+    }
+
+    pub fn parameter_status(&mut self, name: &str, value: &str) {
+        self.message(b'S', |body| {
+            put_string(body, name);
+            put_string(body, value);
+        });
+    }
+
+    pub fn backend_key_data(&mut self, process_id: i32, secret_key: i32) {
+        self.message(b'K', |body| {
+            body.extend_from_slice(&process_id.to_be_bytes());
+            body.extend_from_slice(&secret_key.to_be_bytes());
+        });
+    }
+
+    /// Tells the client the server is idle and outside a transaction block.
+    pub fn ready_for_query(&mut self) {
+        self.message(b'Z', |body| body.push(b'I'));
+    }
+
+    pub fn row_description(&mut self, columns: &[(String, BasicType)], formats: &[i16]) {
+        self.message(b'T', |body| {
+            body.extend_from_slice(&(columns.len() as i16).to_be_bytes());
+            for (position, (name, data_type)) in columns.iter().enumerate() {
+                put_string(body, name);
+                // Not a column of a catalogue table Postgres knows about.
+                body.extend_from_slice(&0i32.to_be_bytes());
+                body.extend_from_slice(&0i16.to_be_bytes());
+                body.extend_from_slice(&type_oid(data_type).to_be_bytes());
+                body.extend_from_slice(&type_length(data_type).to_be_bytes());
+                body.extend_from_slice(&type_modifier(data_type).to_be_bytes());
+                let format = formats.get(position).copied().unwrap_or(TEXT_FORMAT);
+                body.extend_from_slice(&format.to_be_bytes());
+            }
+        });
+    }
+
+    pub fn data_row(&mut self, values: &[Option<Vec<u8>>]) {
+        self.message(b'D', |body| {
+            body.extend_from_slice(&(values.len() as i16).to_be_bytes());
+            for value in values {
+                match value {
+                    Some(value) => {
+                        body.extend_from_slice(&(value.len() as i32).to_be_bytes());
+                        body.extend_from_slice(value);
+                    }
+                    None => body.extend_from_slice(&(-1i32).to_be_bytes()),
+                }
+            }
+        });
+    }
+
+    pub fn command_complete(&mut self, command_tag: &str) {
+        self.message(b'C', |body| put_string(body, command_tag));
+    }
+
+    pub fn empty_query_response(&mut self) {
+        self.message(b'I', |_| {});
+    }
+
+    /// Reports an error, with a hint and the 1-based character position in
+    /// the query it is about if there are any.
+    pub fn error_response(
+        &mut self,
+        sqlstate: &str,
+        message: &str,
+        hint: Option<&str>,
+        position: Option<usize>,
+    ) {
+        let position = position.map(|position| position.to_string());
+        self.message(b'E', |body| {
+            let fields = [
+                (b'S', Some("ERROR")),
+                (b'V', Some("ERROR")),
+                (b'C', Some(sqlstate)),
+                (b'M', Some(message)),
+                (b'H', hint),
+                (b'P', position.as_deref()),
+            ];
+            for (field, value) in fields {
+                if let Some(value) = value {
+                    body.push(field);
+                    put_string(body, value);
+                }
+            }
+            body.push(0);
+        });
+    }
+
+    pub fn parse_complete(&mut self) {
+        self.message(b'1', |_| {});
+    }
+
+    pub fn bind_complete(&mut self) {
+        self.message(b'2', |_| {});
+    }
+
+    pub fn close_complete(&mut self) {
+        self.message(b'3', |_| {});
+    }
+
+    pub fn no_data(&mut self) {
+        self.message(b'n', |_| {});
+    }
+
+    pub fn portal_suspended(&mut self) {
+        self.message(b's', |_| {});
+    }
+
+    pub fn parameter_description(&mut self, parameters: &[BasicType]) {
+        self.message(b't', |body| {
+            body.extend_from_slice(&(parameters.len() as i16).to_be_bytes());
+            for data_type in parameters {
+                body.extend_from_slice(&type_oid(data_type).to_be_bytes());
+            }
+        });
+    }
+}
+
+fn put_string(body: &mut Vec<u8>, string: &str) {
+    body.extend_from_slice(string.as_bytes());
+    body.push(0);
+}
+
+/// The Postgres type a column of `data_type` is presented as. Ints are
+/// 64-bit, as that is what they are validated against. Enums are presented
+/// as text, as clients cannot look their types up. Json is presented as
+/// jsonb, as values are stored normalized.
+pub fn type_oid(data_type: &BasicType) -> i32 {
+    match data_type {
+        BasicType::Int => 20,
+        BasicType::Float => 701,
+        BasicType::Bool => 16,
+        BasicType::String | BasicType::Enum(_) => 25,
+        BasicType::Date => 1082,
+        BasicType::Blob => 17,
+        BasicType::Decimal { .. } => 1700,
+        BasicType::Timestamp => 1114,
+        BasicType::TimestampTz => 1184,
+        BasicType::Time => 1083,
+        BasicType::Uuid => 2950,
+        BasicType::Json => 3802,
+        BasicType::Array(element_type) => match type_oid(element_type) {
+            20 => 1016,
+            701 => 1022,
+            16 => 1000,
+            1082 => 1182,
+            17 => 1001,
+            1700 => 1231,
+            1114 => 1115,
+            1184 => 1185,
+            1083 => 1183,
+            2950 => 2951,
+            3802 => 3807,
+            _ => 1009,
+        },
+    }
+}
+
+fn type_length(data_type: &BasicType) -> i16 {
+    match data_type {
+        BasicType::Int | BasicType::Float => 8,
+        BasicType::Bool => 1,
+        BasicType::Date => 4,
+        BasicType::Timestamp | BasicType::TimestampTz | BasicType::Time => 8,
+        BasicType::Uuid => 16,
+        BasicType::String
+        | BasicType::Blob
+        | BasicType::Decimal { .. }
+        | BasicType::Json
+        | BasicType::Enum(_)
+        | BasicType::Array(_) => -1,
+    }
+}
+
+/// The precision and scale of decimals, as Postgres encodes them.
+fn type_modifier(data_type: &BasicType) -> i32 {
+    match data_type {
+        BasicType::Decimal { precision, scale } => {
+            (((*precision as i32) << 16) | *scale as i32) + 4
+        }
+        _ => -1,
+    }
+}
+
+/// Encodes a stored value in `format`. Values that do not fit their type
+/// are sent as they are stored.
+pub fn encode_value(value: &serde_json::Value, data_type: &BasicType, format: i16) -> Option<Vec<u8>> {
+    let text = match value {
+        serde_json::Value::Null => return None,
+        serde_json::Value::String(text) => text.clone(),
+        value => value.to_string(),
+    };
+    if let BasicType::Array(element_type) = data_type {
+        if let Some(elements) = basic_types::parse_array(&text) {
+            return Some(encode_array(&elements, element_type, format));
+        }
+    }
+    if format == TEXT_FORMAT {
+        let text = match (data_type, text.as_str()) {
+            (BasicType::Bool, "true") => "t".to_string(),
+            (BasicType::Bool, "false") => "f".to_string(),
+            _ => text,
+        };
+        return Some(text.into_bytes());
+    }
+
+    let binary = match data_type {
+        BasicType::Int => text.parse::<i64>().ok().map(|v| v.to_be_bytes().to_vec()),
+        BasicType::Float => text.parse::<f64>().ok().map(|v| v.to_be_bytes().to_vec()),
+        BasicType::Bool => text.parse::<bool>().ok().map(|v| vec![v as u8]),
+        BasicType::Date => basic_types::date_to_days(&text)
+            .map(|days| ((days - POSTGRES_EPOCH_DAYS) as i32).to_be_bytes().to_vec()),
+        BasicType::Decimal { scale, .. } => basic_types::parse_decimal(&text, *scale)
+            .ok()
+            .map(|value| encode_numeric(value, *scale)),
+        BasicType::Timestamp | BasicType::TimestampTz => {
+            basic_types::parse_timestamp(&text).map(|(micros, offset)| {
+                (micros - offset - POSTGRES_EPOCH_MICROS)
+                    .to_be_bytes()
+                    .to_vec()
+            })
+        }
+        BasicType::Time => {
+            basic_types::parse_time(&text).map(|micros| micros.to_be_bytes().to_vec())
+        }
+        BasicType::Uuid => basic_types::parse_uuid(&text).map(|bytes| bytes.to_vec()),
+        // Binary jsonb is the text after a version number.
+        BasicType::Json => Some([&[JSONB_VERSION], text.as_bytes()].concat()),
+        BasicType::String | BasicType::Blob | BasicType::Enum(_) | BasicType::Array(_) => None,
+    };
+    Some(binary.unwrap_or_else(|| text.into_bytes()))
+}
+
+/// Encodes the elements of an array. The binary format is a header with a
+/// single dimension, followed by the length and encoding of every element.
+fn encode_array(elements: &[Option<String>], element_type: &BasicType, format: i16) -> Vec<u8> {
+    let encoded: Vec<Option<Vec<u8>>> = elements
+        .iter()
+        .map(|element| {
+            let element = element.clone().map(serde_json::Value::String);
+            encode_value(element.as_ref()?, element_type, format)
+        })
+        .collect();
+    if format == TEXT_FORMAT {
+        let elements: Vec<Option<String>> = encoded
+            .into_iter()
+            .map(|element| element.map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
+            .collect();
+        return basic_types::format_array(&elements).into_bytes();
+    }
+
+    let dimensions = if encoded.is_empty() { 0i32 } else { 1 };
+    let has_nulls = encoded.iter().any(Option::is_none) as i32;
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&dimensions.to_be_bytes());
+    bytes.extend_from_slice(&has_nulls.to_be_bytes());
+    bytes.extend_from_slice(&type_oid(element_type).to_be_bytes());
+    if dimensions == 1 {
+        bytes.extend_from_slice(&(encoded.len() as i32).to_be_bytes());
+        bytes.extend_from_slice(&1i32.to_be_bytes());
+    }
+    for element in encoded {
+        match element {
+            Some(element) => {
+                bytes.extend_from_slice(&(element.len() as i32).to_be_bytes());
+                bytes.extend_from_slice(&element);
+            }
+            None => bytes.extend_from_slice(&(-1i32).to_be_bytes()),
+        }
+    }
+    bytes
+}
+
+/// Decodes a binary array of at most one dimension into an array literal.
+fn decode_array(number: usize, bytes: &[u8], element_type: &BasicType) -> Option<String> {
+    let field = |position: usize| -> Option<i32> {
+        Some(i32::from_be_bytes(
+            bytes.get(position..position + 4)?.try_into().ok()?,
+        ))
+    };
+    let (count, mut position) = match field(0)? {
+        0 => (0, 12),
+        1 => (field(12)? as usize, 20),
+        _ => return None,
+    };
+    let mut elements = Vec::new();
+    for _ in 0..count {
+        let length = field(position)?;
+        position += 4;
+        if length < 0 {
+            elements.push(None);
+            continue;
+        }
+        let element = bytes.get(position..position + length as usize)?;
+        position += length as usize;
+        let element = decode_parameter(number, Some(element), element_type, BINARY_FORMAT).ok()?;
+        elements.push(element.as_str().map(str::to_string));
+    }
+    Some(basic_types::format_array(&elements))
+}
+
+/// Decodes a parameter sent in `format` into its stored form.
+pub fn decode_parameter(
+    number: usize,
+    bytes: Option<&[u8]>,
+    data_type: &BasicType,
+    format: i16,
+) -> Result<serde_json::Value, String> {
+    let Some(bytes) = bytes else {
+        return Ok(serde_json::Value::Null);
+    };
+    let invalid = || format!("Parameter ${} is not a valid binary {}", number, data_type);
+    let text = match (format, data_type) {
+        (TEXT_FORMAT, _)
+        | (_, BasicType::String)
+        | (_, BasicType::Blob)
+        | (_, BasicType::Enum(_)) => String::from_utf8(bytes.to_vec()).map_err(|_| invalid())?,
+        (_, BasicType::Json) => match bytes.split_first() {
+            Some((&JSONB_VERSION, json)) => {
+                String::from_utf8(json.to_vec()).map_err(|_| invalid())?
+            }
+            _ => return Err(invalid()),
+        },
+        (_, BasicType::Int) => match bytes.len() {
+            2 => i16::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+            4 => i32::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+            8 => i64::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+            _ => return Err(invalid()),
+        },
+        (_, BasicType::Float) => match bytes.len() {
+            4 => f32::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+            8 => f64::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+            _ => return Err(invalid()),
+        },
+        (_, BasicType::Bool) => match bytes {
+            [value] => (*value != 0).to_string(),
+            _ => return Err(invalid()),
+        },
+        (_, BasicType::Date) => {
+            let days: [u8; 4] = bytes.try_into().map_err(|_| invalid())?;
+            basic_types::days_to_date(i32::from_be_bytes(days) as i64 + POSTGRES_EPOCH_DAYS)
+        }
+        (_, BasicType::Decimal { .. }) => decode_numeric(bytes).ok_or_else(invalid)?,
+        (_, BasicType::Timestamp | BasicType::TimestampTz) => {
+            let micros: [u8; 8] = bytes.try_into().map_err(|_| invalid())?;
+            let micros = i64::from_be_bytes(micros) + POSTGRES_EPOCH_MICROS;
+            basic_types::format_timestamp(micros)
+        }
+        (_, BasicType::Time) => {
+            let micros: [u8; 8] = bytes.try_into().map_err(|_| invalid())?;
+            basic_types::format_time(i64::from_be_bytes(micros))
+        }
+        (_, BasicType::Uuid) => {
+            let uuid: [u8; 16] = bytes.try_into().map_err(|_| invalid())?;
+            basic_types::format_uuid(uuid)
+        }
+        (_, BasicType::Array(element_type)) => {
+            decode_array(number, bytes, element_type).ok_or_else(invalid)?
+        }
+    };
+    if format == TEXT_FORMAT && *data_type == BasicType::Bool {
+        // Postgres spells booleans in several ways; they are stored as
+        // `true` and `false`.
+        return match text.to_lowercase().as_str() {
+            "t" | "true" | "y" | "yes" | "on" | "1" => Ok(serde_json::json!("true")),
+            "f" | "false" | "n" | "no" | "off" | "0" => Ok(serde_json::json!("false")),
+            _ => Ok(serde_json::Value::String(text)),
+        };
+    }
+    Ok(serde_json::Value::String(text))
+}
+
+/// Encodes a decimal scaled by `10^scale` as a binary numeric: base 10000
+/// digits with the weight of the first, a sign and the scale.
+fn encode_numeric(value: i128, scale: u8) -> Vec<u8> {
+    let text = basic_types::format_decimal(value.abs(), scale);
+    let (whole, fraction) = text.split_once('.').unwrap_or((&text, ""));
+    let whole = whole.trim_start_matches('0');
+    let whole = format!("{:0>width$}", whole, width = whole.len().div_ceil(4) * 4);
+    let fraction = format!(
+        "{:0<width$}",
+        fraction,
+        width = fraction.len().div_ceil(4) * 4
+    );
+    let mut weight = (whole.len() / 4) as i16 - 1;
+    let mut digits: Vec<i16> = format!("{}{}", whole, fraction)
+        .as_bytes()
+        .chunks(4)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap().parse().unwrap())
+        .collect();
+    while digits.first() == Some(&0) {
+        digits.remove(0);
+        weight -= 1;
+    }
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    if digits.is_empty() {
+        weight = 0;
+    }
+
+    let sign: u16 = if value < 0 { 0x4000 } else { 0 };
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(digits.len() as i16).to_be_bytes());
+    bytes.extend_from_slice(&weight.to_be_bytes());
+    bytes.extend_from_slice(&sign.to_be_bytes());
+    bytes.extend_from_slice(&(scale as i16).to_be_bytes());
+    for digit in digits {
+        bytes.extend_from_slice(&digit.to_be_bytes());
+    }
+    bytes
+}
+
+/// Decodes a binary numeric into a decimal literal.
+fn decode_numeric(bytes: &[u8]) -> Option<String> {
+    let field = |position: usize| -> Option<i16> {
+        Some(i16::from_be_bytes(
+            bytes.get(position..position + 2)?.try_into().ok()?,
+        ))
+    };
+    let count = field(0)? as usize;
+    let weight = field(2)? as i64;
+    let negative = field(4)? as u16 == 0x4000;
+    let scale = field(6)?.max(0) as usize;
+    let digits = (0..count)
+        .map(|position| field(8 + position * 2))
+        .collect::<Option<Vec<i16>>>()?;
+    let digit = |position: i64| {
+        usize::try_from(position)
+            .ok()
+            .and_then(|position| digits.get(position))
+            .copied()
+            .unwrap_or(0)
+    };
+
+    let whole: String = (0..=weight)
+        .map(|position| format!("{:04}", digit(position)))
+        .collect();
+    let whole = whole.trim_start_matches('0');
+    let fraction: String = (0..scale.div_ceil(4) as i64)
+        .map(|position| format!("{:04}", digit(weight + 1 + position)))
+        .collect();
+    let mut text = String::new();
+    if negative {
+        text.push('-');
+    }
+    text.push_str(if whole.is_empty() { "0" } else { whole });
+    if scale > 0 {
+        text.push('.');
+        text.push_str(&fraction[..scale]);
+    }
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_value_encoding() {
+        let encoded = encode_value(&json!("42"), &BasicType::Int, BINARY_FORMAT);
+        assert_eq!(encoded, Some(42i64.to_be_bytes().to_vec()));
+        let encoded = encode_value(&json!("true"), &BasicType::Bool, TEXT_FORMAT);
+        assert_eq!(encoded, Some(b"t".to_vec()));
+        assert_eq!(encode_value(&json!(null), &BasicType::Int, TEXT_FORMAT), None);
+
+        let days = 2i32.to_be_bytes();
+        let decoded = decode_parameter(1, Some(&days), &BasicType::Date, BINARY_FORMAT);
+        assert_eq!(decoded, Ok(json!("2000-01-03")));
+        let encoded = encode_value(&json!("2000-01-03"), &BasicType::Date, BINARY_FORMAT);
+        assert_eq!(encoded, Some(days.to_vec()));
+        assert_eq!(
+            basic_types::date_to_days("2024-02-29").map(basic_types::days_to_date),
+            Some("2024-02-29".to_string())
+        );
+
+        let decoded = decode_parameter(2, Some(b"no"), &BasicType::Bool, TEXT_FORMAT);
+        assert_eq!(decoded, Ok(json!("false")));
+        let decoded = decode_parameter(3, Some(&[1, 2, 3]), &BasicType::Int, BINARY_FORMAT);
+        assert_eq!(decoded, Err("Parameter $3 is not a valid binary Int".to_string()));
+
+        let decimal = BasicType::Decimal {
+            precision: 10,
+            scale: 2,
+        };
+        let encoded = encode_value(&json!("-1234.50"), &decimal, BINARY_FORMAT).unwrap();
+        let decoded = decode_parameter(4, Some(&encoded), &decimal, BINARY_FORMAT);
+        assert_eq!(decoded, Ok(json!("-1234.50")));
+        let encoded = encode_value(
+            &json!("2000-01-01 00:00:01+00"),
+            &BasicType::TimestampTz,
+            BINARY_FORMAT,
+        );
+        assert_eq!(encoded, Some(1_000_000i64.to_be_bytes().to_vec()));
+
+        let flags = BasicType::Array(Box::new(BasicType::Bool));
+        let encoded = encode_value(&json!("{true,NULL}"), &flags, TEXT_FORMAT);
+        assert_eq!(encoded, Some(b"{t,NULL}".to_vec()));
+        let scores = BasicType::Array(Box::new(BasicType::Int));
+        let encoded = encode_value(&json!("{1,NULL,3}"), &scores, BINARY_FORMAT).unwrap();
+        let decoded = decode_parameter(5, Some(&encoded), &scores, BINARY_FORMAT);
+        assert_eq!(decoded, Ok(json!("{1,NULL,3}")));
+        let encoded = encode_value(&json!("{}"), &scores, BINARY_FORMAT).unwrap();
+        let decoded = decode_parameter(6, Some(&encoded), &scores, BINARY_FORMAT);
+        assert_eq!(decoded, Ok(json!("{}")));
+
+        let encoded = encode_value(&json!(r#"{"a":1}"#), &BasicType::Json, BINARY_FORMAT);
+        assert_eq!(encoded, Some(b"\x01{\"a\":1}".to_vec()));
+        let decoded = decode_parameter(7, Some(b"\x01[1]"), &BasicType::Json, BINARY_FORMAT);
+        assert_eq!(decoded, Ok(json!("[1]")));
+        let decoded = decode_parameter(8, Some(b"[1]"), &BasicType::Json, BINARY_FORMAT);
+        assert_eq!(decoded, Err("Parameter $8 is not a valid binary Json".to_string()));
+    }
+}
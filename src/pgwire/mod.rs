@@ -0,0 +1,618 @@
+//! PostgreSQL wire protocol frontend. Speaks enough of protocol version 3 for
+//! drivers and BI tools to connect: startup without authentication, simple
+//! queries and extended queries (Parse/Bind/Describe/Execute). Statements go
+//! through the same parser, planner and executor as `POST /data`, and
+//! extended queries reuse the prepared statements of `POST /prepare`.
+
+pub mod messages;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{error, info};
+
+use crate::catalogue::basic_types::BasicType;
+use crate::queryprocessing::{
+    self,
+    dml::{self, DMLExplainStatement, DMLStatement},
+    plan::PlanNode,
+    prepared, view,
+};
+use crate::{Database, Error, QueryResult};
+use messages::{Body, Message, Writer, BINARY_FORMAT, TEXT_FORMAT};
+
+/// Accepts pgwire connections until the listener fails.
+pub async fn serve(db: Arc<Database>, listener: TcpListener) -> Result<(), std::io::Error> {
+    // Process ids handed out in BackendKeyData. Cancel requests are not
+    // supported, so they only need to tell connections apart.
+    let mut next_process_id: i32 = 1;
+    loop {
+        let (stream, address) = listener.accept().await?;
+        let db = db.clone();
+        let process_id = next_process_id;
+        next_process_id = next_process_id.wrapping_add(1);
+        tokio::task::spawn(async move {
+            if let Err(err) = serve_connection(db, stream, process_id).await {
+                error!("Error serving pgwire connection from {}: {}", address, err);
+            }
+        });
+    }
+}
+
+/// An error reported to the client in an ErrorResponse.
+struct PgError {
+    sqlstate: &'static str,
+    message: String,
+    hint: Option<String>,
+    /// Character of the query the error is about, counted from 1.
+    position: Option<usize>,
+}
+
+impl PgError {
+    fn new(sqlstate: &'static str, message: impl Into<String>) -> PgError {
+        PgError {
+            sqlstate,
+            message: message.into(),
+            hint: None,
+            position: None,
+        }
+    }
+
+    fn protocol_violation(message: String) -> PgError {
+        PgError::new("08P01", message)
+    }
+
+    fn from_error(error: Error) -> PgError {
+        PgError {
+            sqlstate: error.code().sqlstate(),
+            message: error.to_string(),
+            hint: error.hint().map(str::to_string),
+            position: error.span().map(|span| span.start + 1),
+        }
+    }
+
+    fn write(&self, out: &mut Writer) {
+        out.error_response(
+            self.sqlstate,
+            &self.message,
+            self.hint.as_deref(),
+            self.position,
+        );
+    }
+}
+
+/// A statement created by Parse.
+struct Statement {
+    handle: String,
+    parameters: Vec<BasicType>,
+    /// Columns it returns, or `None` if it returns no rows.
+    columns: Option<Vec<(String, BasicType)>>,
+}
+
+/// A statement with bound parameters, created by Bind.
+struct Portal {
+    state: PortalState,
+    columns: Option<Vec<(String, BasicType)>>,
+    /// Format of every returned column.
+    formats: Vec<i16>,
+}
+
+enum PortalState {
+    Bound(Box<(DMLStatement, Option<PlanNode>)>),
+    /// Executed, with the rows not yet sent.
+    Running {
+        rows: VecDeque<Vec<serde_json::Value>>,
+        command_tag: String,
+    },
+    Done,
+}
+
+struct Session {
+    db: Arc<Database>,
+    statements: HashMap<String, Statement>,
+    portals: HashMap<String, Portal>,
+}
+
+async fn serve_connection(
+    db: Arc<Database>,
+    stream: TcpStream,
+    process_id: i32,
+) -> Result<(), std::io::Error> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    loop {
+        let (code, _parameters) = messages::read_startup(&mut reader).await?;
+        match code {
+            messages::SSL_REQUEST | messages::GSSENC_REQUEST => {
+                // Encryption is not supported; the client continues in plain text.
+                writer.write_all(b"N").await?;
+            }
+            messages::PROTOCOL_VERSION => break,
+            messages::CANCEL_REQUEST => return Ok(()),
+            _ => {
+                let mut out = Writer::default();
+                PgError::new("0A000", format!("Unsupported frontend protocol {}", code))
+                    .write(&mut out);
+                writer.write_all(&out.buffer).await?;
+                return Ok(());
+            }
+        }
+    }
+
+    let mut out = Writer::default();
+    out.authentication_ok();
+    for (name, value) in [
+        ("server_version", "14.0"),
+        ("server_encoding", "UTF8"),
+        ("client_encoding", "UTF8"),
+        ("DateStyle", "ISO, MDY"),
+        ("integer_datetimes", "on"),
+        ("standard_conforming_strings", "on"),
+    ] {
+        out.parameter_status(name, value);
+    }
+    out.backend_key_data(process_id, 0);
+    out.ready_for_query();
+    writer.write_all(&out.buffer).await?;
+    info!("pgwire connection {} started", process_id);
+
+    let mut session = Session {
+        db,
+        statements: HashMap::new(),
+        portals: HashMap::new(),
+    };
+    // After an error in an extended query, messages are skipped until Sync.
+    let mut skipping = false;
+    while let Some(message) = messages::read_message(&mut reader).await? {
+        let mut out = Writer::default();
+        match message.tag {
+            b'X' => break,
+            b'S' => {
+                skipping = false;
+                session.portals.clear();
+                out.ready_for_query();
+            }
+            b'H' => {}
+            b'Q' => {
+                if let Err(error) = session.simple_query(&message, &mut out).await {
+                    error.write(&mut out);
+                }
+                out.ready_for_query();
+            }
+            _ if skipping => {}
+            _ => {
+                if let Err(error) = session.extended_query(&message, &mut out).await {
+                    error.write(&mut out);
+                    skipping = true;
+                }
+            }
+        }
+        writer.write_all(&out.buffer).await?;
+    }
+
+    session.close();
+    info!("pgwire connection {} closed", process_id);
+    Ok(())
+}
+
+impl Session {
+    async fn simple_query(&mut self, message: &Message, out: &mut Writer) -> Result<(), PgError> {
+        let query = Body::new(&message.body)
+            .string()
+            .map_err(PgError::protocol_violation)?;
+        if query.trim().trim_end_matches(';').trim().is_empty() {
+            out.empty_query_response();
+            return Ok(());
+        }
+
+        let mut statement = dml::parse(&query).map_err(PgError::from_error)?;
+        view::expand(&self.db, &mut statement)
+            .await
+            .map_err(PgError::from_error)?;
+        dml::analyze(&self.db, statement.clone())
+            .await
+            .map_err(PgError::from_error)?;
+        prepared::bind(&self.db, &mut statement, &[])
+            .await
+            .map_err(PgError::from_error)?;
+
+        let columns = describe(&self.db, &statement).await;
+        if let Some(columns) = columns.as_ref() {
+            out.row_description(columns, &[]);
+        }
+        let (rows, command_tag) = execute(&self.db, statement, None, columns.as_deref()).await?;
+        for row in rows {
+            send_row(out, &row, columns.as_deref().unwrap_or_default(), &[]);
+        }
+        out.command_complete(&command_tag);
+        Ok(())
+    }
+
+    async fn extended_query(&mut self, message: &Message, out: &mut Writer) -> Result<(), PgError> {
+        let mut body = Body::new(&message.body);
+        match message.tag {
+            b'P' => self.parse(&mut body, out).await,
+            b'B' => self.bind(&mut body, out).await,
+            b'D' => self.describe(&mut body, out),
+            b'E' => self.execute(&mut body, out).await,
+            b'C' => self.close_message(&mut body, out),
+            tag => Err(PgError::protocol_violation(format!(
+                "Unsupported message type {}",
+                tag as char
+            ))),
+        }
+    }
+
+    async fn parse(&mut self, body: &mut Body<'_>, out: &mut Writer) -> Result<(), PgError> {
+        let name = body.string().map_err(PgError::protocol_violation)?;
+        let query = body.string().map_err(PgError::protocol_violation)?;
+        // Parameter types given by the client are ignored; they are inferred
+        // from the columns the parameters are compared with instead.
+        if !name.is_empty() && self.statements.contains_key(&name) {
+            return Err(PgError::new(
+                "42P05",
+                format!("Prepared statement {} already exists", name),
+            ));
+        }
+
+        let prepared = prepared::prepare(&self.db, &query)
+            .await
+            .map_err(PgError::from_error)?;
+        let columns = describe(&self.db, &prepared.statement).await;
+        let statement = Statement {
+            handle: prepared.handle,
+            parameters: prepared.parameters,
+            columns,
+        };
+        if let Some(replaced) = self.statements.insert(name, statement) {
+            self.db.deallocate(&replaced.handle);
+        }
+        out.parse_complete();
+        Ok(())
+    }
+
+    async fn bind(&mut self, body: &mut Body<'_>, out: &mut Writer) -> Result<(), PgError> {
+        let portal_name = body.string().map_err(PgError::protocol_violation)?;
+        let statement_name = body.string().map_err(PgError::protocol_violation)?;
+        let Some(statement) = self.statements.get(&statement_name) else {
+            return Err(PgError::new(
+                "26000",
+                format!("Prepared statement {} does not exist", statement_name),
+            ));
+        };
+
+        let parameter_formats = body.i16_list().map_err(PgError::protocol_violation)?;
+        let count = body.i16().map_err(PgError::protocol_violation)? as usize;
+        if count != statement.parameters.len() {
+            return Err(PgError::protocol_violation(format!(
+                "Expected {} parameters, got {}",
+                statement.parameters.len(),
+                count
+            )));
+        }
+        let mut parameters = Vec::with_capacity(count);
+        for (position, data_type) in statement.parameters.iter().enumerate() {
+            let format = format_of(&parameter_formats, position)?;
+            let value = body.value().map_err(PgError::protocol_violation)?;
+            let parameter = messages::decode_parameter(position + 1, value, data_type, format)
+                .map_err(|message| PgError::new("22P03", message))?;
+            parameters.push(parameter);
+        }
+
+        let result_formats = body.i16_list().map_err(PgError::protocol_violation)?;
+        let column_count = statement.columns.as_ref().map_or(0, Vec::len);
+        let formats = (0..column_count)
+            .map(|position| format_of(&result_formats, position))
+            .collect::<Result<Vec<i16>, PgError>>()?;
+
+        let (bound, plan) = prepared::bind_prepared(&self.db, &statement.handle, &parameters)
+            .await
+            .map_err(PgError::from_error)?;
+        let portal = Portal {
+            state: PortalState::Bound(Box::new((bound, plan))),
+            columns: statement.columns.clone(),
+            formats,
+        };
+        self.portals.insert(portal_name, portal);
+        out.bind_complete();
+        Ok(())
+    }
+
+    fn describe(&mut self, body: &mut Body<'_>, out: &mut Writer) -> Result<(), PgError> {
+        let kind = body.u8().map_err(PgError::protocol_violation)?;
+        let name = body.string().map_err(PgError::protocol_violation)?;
+        let (columns, formats) = match kind {
+            b'S' => {
+                let Some(statement) = self.statements.get(&name) else {
+                    return Err(PgError::new(
+                        "26000",
+                        format!("Prepared statement {} does not exist", name),
+                    ));
+                };
+                out.parameter_description(&statement.parameters);
+                (statement.columns.as_ref(), &[][..])
+            }
+            b'P' => {
+                let Some(portal) = self.portals.get(&name) else {
+                    return Err(PgError::new("34000", format!("Portal {} does not exist", name)));
+                };
+                (portal.columns.as_ref(), &portal.formats[..])
+            }
+            kind => {
+                return Err(PgError::protocol_violation(format!(
+                    "Invalid Describe kind {}",
+                    kind as char
+                )))
+            }
+        };
+        match columns {
+            Some(columns) => out.row_description(columns, formats),
+            None => out.no_data(),
+        }
+        Ok(())
+    }
+
+    async fn execute(&mut self, body: &mut Body<'_>, out: &mut Writer) -> Result<(), PgError> {
+        let name = body.string().map_err(PgError::protocol_violation)?;
+        let max_rows = body.i32().map_err(PgError::protocol_violation)?;
+        let Some(portal) = self.portals.get_mut(&name) else {
+            return Err(PgError::new("34000", format!("Portal {} does not exist", name)));
+        };
+
+        let state = std::mem::replace(&mut portal.state, PortalState::Done);
+        let (mut rows, command_tag) = match state {
+            PortalState::Bound(bound) => {
+                let (statement, plan) = *bound;
+                let (rows, command_tag) =
+                    execute(&self.db, statement, plan, portal.columns.as_deref()).await?;
+                (VecDeque::from(rows), command_tag)
+            }
+            PortalState::Running { rows, command_tag } => (rows, command_tag),
+            PortalState::Done => {
+                return Err(PgError::new(
+                    "55000",
+                    format!("Portal {} has already run to completion", name),
+                ))
+            }
+        };
+
+        let columns = portal.columns.as_deref().unwrap_or_default();
+        let limit = if max_rows > 0 { max_rows as usize } else { usize::MAX };
+        for row in rows.drain(..limit.min(rows.len())) {
+            send_row(out, &row, columns, &portal.formats);
+        }
+        if rows.is_empty() {
+            out.command_complete(&command_tag);
+        } else {
+            portal.state = PortalState::Running { rows, command_tag };
+            out.portal_suspended();
+        }
+        Ok(())
+    }
+
+    fn close_message(&mut self, body: &mut Body<'_>, out: &mut Writer) -> Result<(), PgError> {
+        let kind = body.u8().map_err(PgError::protocol_violation)?;
+        let name = body.string().map_err(PgError::protocol_violation)?;
+        match kind {
+            b'S' => {
+                if let Some(statement) = self.statements.remove(&name) {
+                    self.db.deallocate(&statement.handle);
+                }
+            }
+            b'P' => {
+                self.portals.remove(&name);
+            }
+            kind => {
+                return Err(PgError::protocol_violation(format!(
+                    "Invalid Close kind {}",
+                    kind as char
+                )))
+            }
+        }
+        // Closing something that does not exist is not an error.
+        out.close_complete();
+        Ok(())
+    }
+
+    /// Forgets the prepared statements of the session.
+    fn close(&mut self) {
+        for statement in self.statements.values() {
+            self.db.deallocate(&statement.handle);
+        }
+        self.statements.clear();
+    }
+}
+
+/// The format of the value at `position`. A single format applies to all
+/// values, and no formats mean text.
+fn format_of(formats: &[i16], position: usize) -> Result<i16, PgError> {
+    let format = match formats {
+        [] => TEXT_FORMAT,
+        [format] => *format,
+        formats => formats.get(position).copied().unwrap_or(TEXT_FORMAT),
+    };
+    if format != TEXT_FORMAT && format != BINARY_FORMAT {
+        return Err(PgError::protocol_violation(format!(
+            "Invalid format code {}",
+            format
+        )));
+    }
+    Ok(format)
+}
+
+/// The columns a statement returns, or `None` if it returns no rows.
+async fn describe(db: &Database, statement: &DMLStatement) -> Option<Vec<(String, BasicType)>> {
+    let select = match statement {
+        DMLStatement::Select(select) => select.clone(),
+        DMLStatement::Explain(DMLExplainStatement { .. }) => {
+            return Some(vec![("QUERY PLAN".to_string(), BasicType::String)]);
+        }
+        DMLStatement::NextValue(_) => {
+            return Some(vec![("nextval".to_string(), BasicType::Int)]);
+        }
+        // The rows of a cursor are those of its select. Fetching from a
+        // cursor that does not exist fails, so it has no rows to describe.
+        DMLStatement::Fetch(fetch) => db.cursors.statement(&fetch.name)?,
+        _ => return None,
+    };
+    queryprocessing::output_columns(db, &select).await
+}
+
+/// Executes a statement. Returns the values of the rows it returns, ordered
+/// like `columns`, and its command tag.
+async fn execute(
+    db: &Database,
+    statement: DMLStatement,
+    plan: Option<PlanNode>,
+    columns: Option<&[(String, BasicType)]>,
+) -> Result<(Vec<Vec<serde_json::Value>>, String), PgError> {
+    let result = queryprocessing::execute(db, statement, plan)
+        .await
+        .map_err(PgError::from_error)?;
+    let (command, rows) = match result {
+        QueryResult::Select(rows) => ("SELECT", rows),
+        QueryResult::Fetch(rows) => ("FETCH", rows),
+        QueryResult::Insert(inserted) => return Ok((Vec::new(), format!("INSERT 0 {}", inserted))),
+        QueryResult::Update(updated) => return Ok((Vec::new(), format!("UPDATE {}", updated))),
+        QueryResult::Delete(deleted) => return Ok((Vec::new(), format!("DELETE {}", deleted))),
+        QueryResult::Explain(plan) => {
+            let plan = serde_json::to_string_pretty(&plan).unwrap();
+            return Ok((
+                vec![vec![serde_json::Value::String(plan)]],
+                "EXPLAIN".to_string(),
+            ));
+        }
+        QueryResult::Declare => return Ok((Vec::new(), "DECLARE CURSOR".to_string())),
+        QueryResult::Close => return Ok((Vec::new(), "CLOSE CURSOR".to_string())),
+        QueryResult::Commit => return Ok((Vec::new(), "COMMIT".to_string())),
+        QueryResult::CreateSequence => return Ok((Vec::new(), "CREATE SEQUENCE".to_string())),
+        QueryResult::CreateView => return Ok((Vec::new(), "CREATE VIEW".to_string())),
+        QueryResult::CreateTrigger => return Ok((Vec::new(), "CREATE TRIGGER".to_string())),
+        QueryResult::RefreshView => {
+            return Ok((Vec::new(), "REFRESH MATERIALIZED VIEW".to_string()))
+        }
+    };
+
+    let columns = columns.unwrap_or_default();
+    let rows: Vec<Vec<serde_json::Value>> = rows
+        .map(|record| {
+            let row = record.into_map();
+            columns
+                .iter()
+                .map(|(name, _)| row.get(name).cloned().unwrap_or_default())
+                .collect()
+        })
+        .collect();
+    let command_tag = format!("{} {}", command, rows.len());
+    Ok((rows, command_tag))
+}
+
+fn send_row(
+    out: &mut Writer,
+    row: &[serde_json::Value],
+    columns: &[(String, BasicType)],
+    formats: &[i16],
+) {
+    let values: Vec<Option<Vec<u8>>> = row
+        .iter()
+        .zip(columns)
+        .enumerate()
+        .map(|(position, (value, (_, data_type)))| {
+            let format = formats.get(position).copied().unwrap_or(TEXT_FORMAT);
+            messages::encode_value(value, data_type, format)
+        })
+        .collect();
+    out.data_row(&values);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalogue::{Column, Table};
+    use tokio_postgres::{error::SqlState, types::Type, NoTls, SimpleQueryMessage};
+
+    async fn connect(name: &str) -> tokio_postgres::Client {
+        let table = Table::new(
+            "PgwireTestTable".to_string(),
+            vec![
+                Column::new("id".to_string(), BasicType::Int, false),
+                Column::new("name".to_string(), BasicType::String, false),
+                Column::new("hired".to_string(), BasicType::Date, true),
+            ],
+            vec![0],
+        );
+        let db = Database::temporary(name);
+        db.catalogue().await.add_table(table);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(serve(Arc::new(db), listener));
+        let config = format!("host=127.0.0.1 port={} user=test", port);
+        let (client, connection) = tokio_postgres::connect(&config, NoTls).await.unwrap();
+        tokio::spawn(connection);
+        client
+    }
+
+    #[tokio::test]
+    async fn test_simple_and_extended_queries() {
+        let client = connect("pgwire_queries").await;
+
+        let messages = client
+            .simple_query("select * from PgwireTestTable")
+            .await
+            .unwrap();
+        assert!(matches!(
+            messages.last(),
+            Some(SimpleQueryMessage::CommandComplete(0))
+        ));
+
+        let statement = client
+            .prepare("select name, hired from PgwireTestTable where id = $1")
+            .await
+            .unwrap();
+        assert_eq!(statement.params(), &[Type::INT8]);
+        let columns: Vec<(&str, &Type)> = statement
+            .columns()
+            .iter()
+            .map(|column| (column.name(), column.type_()))
+            .collect();
+        assert_eq!(columns, vec![("name", &Type::TEXT), ("hired", &Type::DATE)]);
+        let rows = client.query(&statement, &[&7i64]).await.unwrap();
+        assert!(rows.is_empty());
+
+        let rows = client
+            .query("explain select * from PgwireTestTable where id > $1", &[&1i64])
+            .await
+            .unwrap();
+        let plan: String = rows[0].get("QUERY PLAN");
+        assert!(plan.contains("SeqScan"));
+    }
+
+    #[tokio::test]
+    async fn test_error_responses() {
+        let client = connect("pgwire_errors").await;
+
+        let error = client
+            .simple_query("select * from MissingTable")
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), Some(&SqlState::UNDEFINED_TABLE));
+        let error = client.prepare("selec * fro").await.unwrap_err();
+        assert_eq!(error.code(), Some(&SqlState::SYNTAX_ERROR));
+        let error = client
+            .prepare("select missing from PgwireTestTable")
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), Some(&SqlState::UNDEFINED_COLUMN));
+
+        // The connection is usable after errors.
+        let rows = client
+            .query("select id from PgwireTestTable", &[])
+            .await
+            .unwrap();
+        assert!(rows.is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+//! Server-side cursors. `declare` runs a select and keeps its rows under the
+//! name of the cursor, and every `fetch` hands out the next rows, so clients
+//! can page through a large result across requests. Cursors that are not
+//! used for a while are closed. Together, open cursors hold no more rows
+//! than fit in the memory budget of the database.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::dml::DMLSelectStatement;
+use crate::storage::Row;
+use crate::{Error, ErrorCode};
+
+/// How long a cursor stays open without being fetched from.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+struct Cursor {
+    statement: DMLSelectStatement,
+    /// Rows not fetched yet.
+    rows: VecDeque<Row>,
+    /// Approximate memory taken up by the rows not fetched yet.
+    bytes: usize,
+    last_used: Instant,
+}
+
+/// The open cursors of a database.
+pub struct Cursors {
+    cursors: Mutex<HashMap<String, Cursor>>,
+    /// Bytes all open cursors may hold together.
+    memory_budget: usize,
+}
+
+impl Cursors {
+    pub fn new(memory_budget: usize) -> Cursors {
+        Cursors {
+            cursors: Mutex::new(HashMap::new()),
+            memory_budget,
+        }
+    }
+
+    /// Opens a cursor over the result `rows` of `statement`.
+    pub fn declare(
+        &self,
+        name: &str,
+        statement: DMLSelectStatement,
+        rows: Vec<Row>,
+    ) -> Result<(), Error> {
+        let mut cursors = self.cursors.lock().unwrap();
+        cursors.retain(|_, cursor| cursor.last_used.elapsed() < IDLE_TIMEOUT);
+        if cursors.contains_key(name) {
+            return Err(Error::new(
+                ErrorCode::DuplicateCursor,
+                format!("Cursor {} already exists", name),
+            )
+            .with_hint(format!("Close it first with close {}", name)));
+        }
+
+        let bytes: usize = rows.iter().map(row_bytes).sum();
+        let held: usize = cursors.values().map(|cursor| cursor.bytes).sum();
+        let budget = self.memory_budget;
+        if held + bytes > budget {
+            return Err(Error::new(
+                ErrorCode::CursorMemoryExceeded,
+                format!(
+                    "Cursor {} needs {} bytes, but only {} of the {} byte memory budget are left",
+                    name,
+                    bytes,
+                    budget.saturating_sub(held),
+                    budget
+                ),
+            )
+            .with_hint("Close other cursors, narrow the select or raise memory_budget"));
+        }
+
+        cursors.insert(
+            name.to_string(),
+            Cursor {
+                statement,
+                rows: rows.into(),
+                bytes,
+                last_used: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Takes the next `count` rows of a cursor, or all remaining rows if
+    /// `count` is `None`. An exhausted cursor returns no rows.
+    pub fn fetch(&self, name: &str, count: Option<usize>) -> Result<Vec<Row>, Error> {
+        let mut cursors = self.cursors.lock().unwrap();
+        let cursor = match cursors.get_mut(name) {
+            Some(cursor) if cursor.last_used.elapsed() < IDLE_TIMEOUT => cursor,
+            _ => return Err(undefined_cursor(name)),
+        };
+        cursor.last_used = Instant::now();
+        let count = count.unwrap_or(usize::MAX).min(cursor.rows.len());
+        let rows: Vec<Row> = cursor.rows.drain(..count).collect();
+        // Fetched rows no longer count against the memory budget.
+        let fetched: usize = rows.iter().map(row_bytes).sum();
+        cursor.bytes = cursor.bytes.saturating_sub(fetched);
+        Ok(rows)
+    }
+
+    /// Closes a cursor. Returns whether it was open.
+    pub fn close(&self, name: &str) -> bool {
+        self.cursors.lock().unwrap().remove(name).is_some()
+    }
+
+    /// The select a cursor was declared for.
+    pub fn statement(&self, name: &str) -> Option<DMLSelectStatement> {
+        let cursors = self.cursors.lock().unwrap();
+        cursors.get(name).map(|cursor| cursor.statement.clone())
+    }
+}
+
+/// Approximate memory taken up by `row`.
+fn row_bytes(row: &Row) -> usize {
+    serde_json::to_vec(row).map(|v| v.len()).unwrap_or(0)
+}
+
+/// The error for a cursor that is not open, or was closed for being idle.
+pub fn undefined_cursor(name: &str) -> Error {
+    Error::new(
+        ErrorCode::UndefinedCursor,
+        format!("Cursor {} does not exist", name),
+    )
+    .with_hint(format!(
+        "Cursors close after {} minutes without a fetch",
+        IDLE_TIMEOUT.as_secs() / 60
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows_of(count: usize) -> Vec<Row> {
+        (0..count)
+            .map(|id| {
+                let mut row = Row::new();
+                row.insert("id".to_string(), serde_json::json!(id.to_string()));
+                row
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fetch_pages() {
+        let statement = match super::super::dml::parse("select * from CursorTestTable") {
+            Ok(super::super::dml::DMLStatement::Select(select)) => select,
+            _ => panic!("expected a select statement"),
+        };
+        let rows = rows_of(5);
+        let cursors = Cursors::new(1 << 20);
+        cursors.declare("pages", statement.clone(), rows).unwrap();
+        assert!(cursors
+            .declare("pages", statement.clone(), Vec::new())
+            .is_err());
+
+        assert_eq!(cursors.fetch("pages", Some(2)).unwrap().len(), 2);
+        let rest = cursors.fetch("pages", None).unwrap();
+        assert_eq!(rest[0]["id"], "2");
+        assert_eq!(rest.len(), 3);
+        assert!(cursors.fetch("pages", Some(2)).unwrap().is_empty());
+
+        assert!(cursors.close("pages"));
+        assert!(cursors.fetch("pages", Some(1)).is_err());
+
+        let small = Cursors::new(16);
+        let error = small
+            .declare("big", statement.clone(), rows_of(5))
+            .unwrap_err();
+        assert_eq!(error.code(), ErrorCode::CursorMemoryExceeded);
+
+        // Fetching frees the memory of the fetched rows.
+        let budget = Cursors::new(row_bytes(&rows_of(1)[0]) * 2);
+        budget
+            .declare("first", statement.clone(), rows_of(2))
+            .unwrap();
+        assert!(budget
+            .declare("second", statement.clone(), rows_of(1))
+            .is_err());
+        budget.fetch("first", Some(1)).unwrap();
+        budget.declare("second", statement, rows_of(1)).unwrap();
+    }
+}
@@ -1,28 +1,47 @@
-use crate::catalogue::basic_types::BasicType;
+use crate::catalogue::{
+    self,
+    basic_types::{BasicType, EnumType, MAX_DECIMAL_PRECISION},
+    check::CheckExpression,
+    json::{self, JsonAccess, JsonStep},
+    ColumnDefault, IndexKind, Layout, Table,
+};
+use crate::{Error, ErrorCode, Span};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 enum DDLTokenType {
     Model,
+    Enum,
     QuestionMark,
     AtSign,
     Type(String),
     Identifier(String),
+    Number(u32),
+    /// A number that is negative or has a fraction, as written.
+    Literal(String),
+    /// A quoted literal, without its quotes and escapes.
+    String(String),
+    /// `->`, which reads a member or element of a JSON value.
+    Arrow,
+    /// `->>`, which reads it as text.
+    TextArrow,
     OpenBrace,
     CloseBrace,
     Comma,
     OpenParen,
     CloseParen,
+    OpenBracket,
+    CloseBracket,
 }
 
 struct DDLToken {
     token_type: DDLTokenType,
-    position: usize,
+    span: Span,
 }
 
 struct DDLLexer {
     input: String,
     position: usize,
-    errors: Vec<String>,
+    errors: Vec<Error>,
 }
 
 impl DDLLexer {
@@ -38,7 +57,8 @@ impl DDLLexer {
         if self.position >= self.input.len() {
             return None;
         }
-        let mut token = None;
+        let start = self.position;
+        let token_type;
         let mut current_char = self.input.chars().nth(self.position).unwrap();
         if current_char.is_whitespace() {
             self.position += 1;
@@ -47,74 +67,141 @@ impl DDLLexer {
 
         if current_char.is_alphabetic() {
             let mut value = String::new();
-            while current_char.is_alphabetic() {
+            while current_char.is_alphanumeric() || current_char == '_' {
                 value.push(current_char);
                 self.position += 1;
-                current_char = self.input.chars().nth(self.position).unwrap();
+                match self.input.chars().nth(self.position) {
+                    Some(next_char) => current_char = next_char,
+                    None => break,
+                }
             }
 
-            let types = vec!["Int", "Float", "String", "Date"];
-            let token_type = if types.contains(&&value[..]) {
+            let value_type = if BasicType::from_str(&value).is_some() || value == "Decimal" {
                 DDLTokenType::Type(value.clone())
             } else if value == "model" {
                 DDLTokenType::Model
+            } else if value == "enum" {
+                DDLTokenType::Enum
             } else {
                 DDLTokenType::Identifier(value.clone())
             };
 
-            token = Some(DDLToken {
-                token_type,
-                position: self.position,
-            });
+            token_type = value_type;
+        } else if current_char.is_ascii_digit()
+            || (current_char == '-'
+                && self
+                    .input
+                    .chars()
+                    .nth(self.position + 1)
+                    .is_some_and(|c| c.is_ascii_digit()))
+        {
+            let mut value = String::new();
+            while current_char.is_ascii_digit()
+                || (current_char == '.' && !value.contains('.'))
+                || (current_char == '-' && value.is_empty())
+            {
+                value.push(current_char);
+                self.position += 1;
+                match self.input.chars().nth(self.position) {
+                    Some(next_char) => current_char = next_char,
+                    None => break,
+                }
+            }
+            token_type = if value.bytes().all(|byte| byte.is_ascii_digit()) {
+                DDLTokenType::Number(value.parse().unwrap_or(u32::MAX))
+            } else {
+                DDLTokenType::Literal(value)
+            };
+        } else if current_char == '"' || current_char == '\'' {
+            // Quoted literals end at an unescaped closing quote.
+            let quote = current_char;
+            let mut value = String::new();
+            let mut escaped = false;
+            let mut closed = false;
+            self.position += 1;
+            while let Some(c) = self.input.chars().nth(self.position) {
+                self.position += 1;
+                match c {
+                    '\\' if !escaped => escaped = true,
+                    c if c == quote && !escaped => {
+                        closed = true;
+                        break;
+                    }
+                    c => {
+                        value.push(c);
+                        escaped = false;
+                    }
+                }
+            }
+            if !closed {
+                self.errors.push(
+                    Error::new(ErrorCode::SyntaxError, "Unterminated string")
+                        .with_span(Span {
+                            start,
+                            end: self.position,
+                        })
+                        .with_hint(format!("Strings end with {}", quote)),
+                );
+            }
+            token_type = DDLTokenType::String(value);
+        } else if current_char == '-' && self.input.chars().nth(self.position + 1) == Some('>') {
+            if self.input.chars().nth(self.position + 2) == Some('>') {
+                token_type = DDLTokenType::TextArrow;
+                self.position += 3;
+            } else {
+                token_type = DDLTokenType::Arrow;
+                self.position += 2;
+            }
         } else if current_char == '@' {
-            token = Some(DDLToken {
-                token_type: DDLTokenType::AtSign,
-                position: self.position,
-            });
+            token_type = DDLTokenType::AtSign;
             self.position += 1;
         } else if current_char == '?' {
-            token = Some(DDLToken {
-                token_type: DDLTokenType::QuestionMark,
-                position: self.position,
-            });
+            token_type = DDLTokenType::QuestionMark;
             self.position += 1;
         } else if current_char == ',' {
-            token = Some(DDLToken {
-                token_type: DDLTokenType::Comma,
-                position: self.position,
-            });
+            token_type = DDLTokenType::Comma;
             self.position += 1;
         } else if current_char == '(' {
-            token = Some(DDLToken {
-                token_type: DDLTokenType::OpenParen,
-                position: self.position,
-            });
+            token_type = DDLTokenType::OpenParen;
             self.position += 1;
         } else if current_char == ')' {
-            token = Some(DDLToken {
-                token_type: DDLTokenType::CloseParen,
-                position: self.position,
-            });
+            token_type = DDLTokenType::CloseParen;
+            self.position += 1;
+        } else if current_char == '[' {
+            token_type = DDLTokenType::OpenBracket;
+            self.position += 1;
+        } else if current_char == ']' {
+            token_type = DDLTokenType::CloseBracket;
             self.position += 1;
         } else if current_char == '{' {
-            token = Some(DDLToken {
-                token_type: DDLTokenType::OpenBrace,
-                position: self.position,
-            });
+            token_type = DDLTokenType::OpenBrace;
             self.position += 1;
         } else if current_char == '}' {
-            token = Some(DDLToken {
-                token_type: DDLTokenType::CloseBrace,
-                position: self.position,
-            });
+            token_type = DDLTokenType::CloseBrace;
             self.position += 1;
         } else {
-            self.errors
-                .push(format!("Invalid character: {}", current_char));
             self.position += 1;
+            self.errors.push(
+                Error::new(
+                    ErrorCode::SyntaxError,
+                    format!("Invalid character: {}", current_char),
+                )
+                .with_span(Span {
+                    start,
+                    end: self.position,
+                })
+                .with_hint("Names start with a letter and consist of letters, digits and _"),
+            );
+            return self.next_token();
         }
 
-        token
+        Some(DDLToken {
+            token_type,
+            span: Span {
+                start,
+                end: self.position,
+            },
+        })
     }
 }
 
@@ -122,6 +209,65 @@ impl DDLLexer {
 pub struct Model {
     pub name: String,
     pub fields: Vec<Field>,
+    /// Storage layout fixed by a `@@layout(...)` attribute.
+    pub layout: Option<Layout>,
+    /// Fields indexed by `@@index(...)` attributes, and how.
+    pub indexes: Vec<ModelIndex>,
+    /// Field lists of `@@unique([...])` attributes.
+    pub uniques: Vec<Vec<String>>,
+    /// Expressions of `@@check("...")` attributes.
+    pub checks: Vec<String>,
+    /// Fields of an `@@id([...])` attribute, for keys of several fields.
+    pub primary_key: Vec<String>,
+    /// Foreign keys of `@@references([...], Model)` attributes and of
+    /// fields with `@references(Model, Field)`.
+    pub references: Vec<ModelReference>,
+    /// Seconds of history a `@@retention("...")` attribute keeps for time
+    /// travel. None keeps all of it.
+    pub retention: Option<u64>,
+    /// Set by `@@temporal`, which makes the table system-versioned.
+    pub temporal: bool,
+}
+
+impl Model {
+    /// The table a `@@temporal` model keeps the past versions of its rows
+    /// in, as `SalaryHistory`.
+    pub fn history_table(&self) -> Option<String> {
+        self.temporal.then(|| format!("{}History", self.name))
+    }
+
+    /// The fields of the primary key: those of `@@id([...])`, or the field
+    /// marked `@id`.
+    pub fn primary_key(&self) -> Vec<&str> {
+        if !self.primary_key.is_empty() {
+            return self.primary_key.iter().map(String::as_str).collect();
+        }
+        self.fields
+            .iter()
+            .filter(|field| field.is_primary_key)
+            .map(|field| field.name.as_str())
+            .collect()
+    }
+}
+
+/// A foreign key of a model, from `@@references([...], Model)` or the
+/// `@references(Model, Field)` of a field: `fields` hold the primary key of
+/// `model`. `referenced_fields` are the fields of that key, empty until
+/// `resolve_references` looks them up if the attribute leaves them out.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ModelReference {
+    pub fields: Vec<String>,
+    pub model: String,
+    pub referenced_fields: Vec<String>,
+}
+
+/// An `@@index(Field)` attribute, or `@@index(Field->'a'->>'b')` on what
+/// a path reads out of a Json field.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ModelIndex {
+    pub field: String,
+    pub path: Option<JsonAccess>,
+    pub kind: IndexKind,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -132,13 +278,28 @@ pub struct Field {
     pub is_primary_key: bool,
     pub is_foreign_key: bool,
     pub references: Option<(String, String)>,
+    pub is_adaptive: bool,
+    /// JSON Schema declared by `@schema("...")` on a Json field.
+    pub json_schema: Option<serde_json::Value>,
+    /// Declared by `@default(...)`.
+    pub default: Option<ColumnDefault>,
+    /// Expression of a `@check("...")` attribute.
+    pub check: Option<String>,
+}
+
+/// The models and enums of a schema. Fields of an enum type only name it
+/// until `resolve_types` looks it up.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Schema {
+    pub models: Vec<Model>,
+    pub enums: Vec<EnumType>,
 }
 
 struct DDLParser {
-    _lexer: DDLLexer,
+    lexer: DDLLexer,
     tokens: Vec<DDLToken>,
     position: usize,
-    errors: Vec<String>,
+    errors: Vec<Error>,
 }
 
 impl DDLParser {
@@ -150,7 +311,7 @@ impl DDLParser {
         }
         let lexer = lexer;
         Self {
-            _lexer: lexer,
+            lexer,
             tokens,
             position: 0,
             errors: Vec::new(),
@@ -164,15 +325,23 @@ impl DDLParser {
         let mut model = Model {
             name: String::new(),
             fields: Vec::new(),
+            layout: None,
+            indexes: Vec::new(),
+            uniques: Vec::new(),
+            checks: Vec::new(),
+            primary_key: Vec::new(),
+            references: Vec::new(),
+            retention: None,
+            temporal: false,
         };
 
-        let mut token = &self.tokens[self.position];
+        let mut token = self.tokens.get(self.position)?;
         if token.token_type != DDLTokenType::Model {
             return None;
         }
         self.position += 1;
 
-        token = &self.tokens[self.position];
+        token = self.tokens.get(self.position)?;
         match &token.token_type {
             DDLTokenType::Identifier(name) => {
                 model.name = name.clone();
@@ -181,7 +350,7 @@ impl DDLParser {
         }
         self.position += 1;
 
-        token = &self.tokens[self.position];
+        token = self.tokens.get(self.position)?;
         if token.token_type != DDLTokenType::OpenBrace {
             return None;
         }
@@ -195,10 +364,372 @@ impl DDLParser {
                 break;
             }
         }
+        for field in model.fields.iter() {
+            if let Some((referenced, referenced_field)) = &field.references {
+                model.references.push(ModelReference {
+                    fields: vec![field.name.clone()],
+                    model: referenced.clone(),
+                    referenced_fields: vec![referenced_field.clone()],
+                });
+            }
+        }
+
+        while self.token_type_at(self.position) == Some(&DDLTokenType::AtSign)
+            && self.token_type_at(self.position + 1) == Some(&DDLTokenType::AtSign)
+        {
+            self.position += 2;
+            self.parse_model_attribute(&mut model)?;
+        }
+
+        if self.token_type_at(self.position) != Some(&DDLTokenType::CloseBrace) {
+            return None;
+        }
+        self.position += 1;
 
         Some(model)
     }
 
+    fn token_type_at(&self, position: usize) -> Option<&DDLTokenType> {
+        self.tokens.get(position).map(|token| &token.token_type)
+    }
+
+    /// The error for a schema whose parsing stopped at the current token.
+    fn syntax_error(&self) -> Error {
+        let Some(token) = self.tokens.get(self.position) else {
+            return Error::new(ErrorCode::SyntaxError, "Unexpected end of schema")
+                .with_hint("Models end with }");
+        };
+        let text: String = self
+            .lexer
+            .input
+            .chars()
+            .skip(token.span.start)
+            .take(token.span.end - token.span.start)
+            .collect();
+        let error = Error::new(
+            ErrorCode::SyntaxError,
+            format!("Syntax error at or near \"{}\"", text),
+        )
+        .with_span(token.span);
+        if self.position == 0 {
+            error.with_hint("Schemas consist of model and enum blocks")
+        } else {
+            error
+        }
+    }
+
+    /// Parses the `->` and `->>` steps following a field, as in
+    /// `Doc->'items'->0->>'sku'`, if any. Only the last step may read text.
+    fn parse_path(&mut self) -> Option<Option<JsonAccess>> {
+        let mut access = JsonAccess {
+            steps: Vec::new(),
+            as_text: false,
+        };
+        while let Some(arrow @ (DDLTokenType::Arrow | DDLTokenType::TextArrow)) =
+            self.token_type_at(self.position).cloned()
+        {
+            let step = match self.token_type_at(self.position + 1)? {
+                DDLTokenType::String(key) => JsonStep::Key(key.clone()),
+                DDLTokenType::Number(position) => JsonStep::Index(*position as i64),
+                _ => return None,
+            };
+            self.position += 2;
+            access.steps.push(step);
+            if arrow == DDLTokenType::TextArrow {
+                access.as_text = true;
+                break;
+            }
+        }
+        if access.steps.is_empty() {
+            Some(None)
+        } else {
+            Some(Some(access))
+        }
+    }
+
+    /// Parses a model attribute such as `layout(column)`, `index(Tags, gin)`,
+    /// `index(Doc->>'type')`, `id([EmployeeID, FromDate])` or `temporal`,
+    /// after its `@@`.
+    fn parse_model_attribute(&mut self, model: &mut Model) -> Option<()> {
+        let name = match self.token_type_at(self.position)? {
+            DDLTokenType::Identifier(name) => name.clone(),
+            _ => return None,
+        };
+        if name == "temporal" {
+            model.temporal = true;
+            self.position += 1;
+            return Some(());
+        }
+        if self.token_type_at(self.position + 1)? != &DDLTokenType::OpenParen {
+            return None;
+        }
+        if name == "check" {
+            let expression = self.parse_string_argument()?;
+            model.checks.push(expression);
+            return Some(());
+        }
+        if name == "retention" {
+            let retention = self.parse_string_argument()?;
+            match parse_retention(&retention) {
+                Some(seconds) => model.retention = Some(seconds),
+                None => self.errors.push(
+                    Error::new(
+                        ErrorCode::InvalidTableDefinition,
+                        format!("Invalid retention: {}", retention),
+                    )
+                    .with_hint(
+                        "Write a number and a unit of seconds, minutes, hours, days or weeks, as in @@retention(\"30 days\")",
+                    ),
+                ),
+            }
+            return Some(());
+        }
+        self.position += 2;
+        if name == "unique" || name == "id" {
+            let fields = self.parse_field_list()?;
+            if name == "id" {
+                model.primary_key = fields;
+            } else {
+                model.uniques.push(fields);
+            }
+            if self.token_type_at(self.position)? != &DDLTokenType::CloseParen {
+                return None;
+            }
+            self.position += 1;
+            return Some(());
+        }
+        if name == "references" {
+            let fields = self.parse_field_list()?;
+            let (
+                Some(DDLTokenType::Comma),
+                Some(DDLTokenType::Identifier(referenced)),
+                Some(DDLTokenType::CloseParen),
+            ) = (
+                self.token_type_at(self.position),
+                self.token_type_at(self.position + 1),
+                self.token_type_at(self.position + 2),
+            )
+            else {
+                return None;
+            };
+            model.references.push(ModelReference {
+                fields,
+                model: referenced.clone(),
+                referenced_fields: Vec::new(),
+            });
+            self.position += 3;
+            return Some(());
+        }
+        let mut arguments = Vec::new();
+        let mut path = None;
+        loop {
+            match self.token_type_at(self.position)? {
+                DDLTokenType::Identifier(argument) => arguments.push(argument.clone()),
+                _ => return None,
+            }
+            self.position += 1;
+            if arguments.len() == 1 && name == "index" {
+                path = self.parse_path()?;
+            }
+            match self.token_type_at(self.position)? {
+                DDLTokenType::Comma => self.position += 1,
+                DDLTokenType::CloseParen => break,
+                _ => return None,
+            }
+        }
+        self.position += 1;
+
+        let argument = arguments[0].clone();
+        if name == "index" && arguments.len() <= 2 {
+            let kind = match arguments.get(1).map(String::as_str) {
+                None | Some("btree") => IndexKind::BTree,
+                Some("gin") => IndexKind::Inverted,
+                Some(method) => {
+                    self.errors.push(
+                        Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!("Invalid index method: {}", method),
+                        )
+                        .with_hint("Index methods are btree and gin"),
+                    );
+                    return None;
+                }
+            };
+            model.indexes.push(ModelIndex {
+                field: argument,
+                path,
+                kind,
+            });
+        } else if arguments.len() > 1 {
+            self.errors.push(Error::new(
+                ErrorCode::InvalidTableDefinition,
+                format!("Too many arguments to model attribute: {}", name),
+            ));
+            return None;
+        } else if name == "layout" {
+            match Layout::from_str(&argument) {
+                Some(layout) => model.layout = Some(layout),
+                None => {
+                    self.errors.push(
+                        Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!("Invalid layout: {}", argument),
+                        )
+                        .with_hint("Layouts are row and column"),
+                    );
+                    return None;
+                }
+            }
+        } else {
+            self.errors.push(
+                Error::new(
+                    ErrorCode::InvalidTableDefinition,
+                    format!("Unknown model attribute: {}", name),
+                )
+                .with_hint(
+                    "The model attributes are @@id, @@layout, @@index, @@unique, @@check, @@references, @@retention and @@temporal",
+                ),
+            );
+        }
+        Some(())
+    }
+
+    /// Parses the `("...")` of an attribute that takes a string, as `check`
+    /// and `retention`, after its name.
+    fn parse_string_argument(&mut self) -> Option<String> {
+        let (
+            Some(DDLTokenType::OpenParen),
+            Some(DDLTokenType::String(expression)),
+            Some(DDLTokenType::CloseParen),
+        ) = (
+            self.token_type_at(self.position + 1),
+            self.token_type_at(self.position + 2),
+            self.token_type_at(self.position + 3),
+        )
+        else {
+            return None;
+        };
+        let expression = expression.clone();
+        self.position += 4;
+        Some(expression)
+    }
+
+    /// Parses a bracketed list of fields, as the `[FirstName, LastName]` of
+    /// `@@unique([FirstName, LastName])`.
+    fn parse_field_list(&mut self) -> Option<Vec<String>> {
+        if self.token_type_at(self.position)? != &DDLTokenType::OpenBracket {
+            return None;
+        }
+        self.position += 1;
+        let mut fields = Vec::new();
+        loop {
+            match self.token_type_at(self.position)? {
+                DDLTokenType::Identifier(field) => fields.push(field.clone()),
+                _ => return None,
+            }
+            self.position += 1;
+            match self.token_type_at(self.position)? {
+                DDLTokenType::Comma => self.position += 1,
+                DDLTokenType::CloseBracket => break,
+                _ => return None,
+            }
+        }
+        self.position += 1;
+        Some(fields)
+    }
+
+    /// Parses the `(precision, scale)` of a decimal, after `Decimal`. The
+    /// scale defaults to 0.
+    fn parse_decimal(&mut self) -> Option<BasicType> {
+        if self.token_type_at(self.position)? != &DDLTokenType::OpenParen {
+            return None;
+        }
+        let DDLTokenType::Number(precision) = *self.token_type_at(self.position + 1)? else {
+            return None;
+        };
+        self.position += 2;
+        let mut scale = 0;
+        if self.token_type_at(self.position)? == &DDLTokenType::Comma {
+            let DDLTokenType::Number(number) = *self.token_type_at(self.position + 1)? else {
+                return None;
+            };
+            scale = number;
+            self.position += 2;
+        }
+        if self.token_type_at(self.position)? != &DDLTokenType::CloseParen {
+            return None;
+        }
+        self.position += 1;
+
+        if precision == 0 || precision > MAX_DECIMAL_PRECISION as u32 || scale > precision {
+            self.errors.push(
+                Error::new(
+                    ErrorCode::InvalidTableDefinition,
+                    format!(
+                        "Invalid decimal precision and scale: {}, {}",
+                        precision, scale
+                    ),
+                )
+                .with_hint(format!(
+                    "The precision is between 1 and {}, and the scale at most the precision",
+                    MAX_DECIMAL_PRECISION
+                )),
+            );
+            return None;
+        }
+        Some(BasicType::Decimal {
+            precision: precision as u8,
+            scale: scale as u8,
+        })
+    }
+
+    /// Parses an `enum Name { label label ... }` block. Labels may be
+    /// separated by commas.
+    fn parse_enum(&mut self) -> Option<EnumType> {
+        if self.token_type_at(self.position)? != &DDLTokenType::Enum {
+            return None;
+        }
+        let DDLTokenType::Identifier(name) = self.token_type_at(self.position + 1)? else {
+            return None;
+        };
+        let name = name.clone();
+        if self.token_type_at(self.position + 2)? != &DDLTokenType::OpenBrace {
+            return None;
+        }
+        self.position += 3;
+
+        let mut labels: Vec<String> = Vec::new();
+        loop {
+            match self.token_type_at(self.position)?.clone() {
+                DDLTokenType::Identifier(label) => {
+                    if labels.contains(&label) {
+                        self.errors.push(Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!("Enum {} has label {} more than once", name, label),
+                        ));
+                    }
+                    labels.push(label);
+                }
+                DDLTokenType::Comma => {}
+                DDLTokenType::CloseBrace => break,
+                _ => return None,
+            }
+            self.position += 1;
+        }
+        self.position += 1;
+
+        if labels.is_empty() {
+            self.errors.push(
+                Error::new(
+                    ErrorCode::InvalidTableDefinition,
+                    format!("Enum {} has no labels", name),
+                )
+                .with_hint(format!("List its labels, as in enum {} {{ a b }}", name)),
+            );
+        }
+        Some(EnumType::new(name, labels))
+    }
+
     fn parse_field(&mut self) -> Option<Field> {
         if self.position >= self.tokens.len() {
             return None;
@@ -210,9 +741,13 @@ impl DDLParser {
             is_primary_key: false,
             is_foreign_key: false,
             references: None,
+            is_adaptive: false,
+            json_schema: None,
+            default: None,
+            check: None,
         };
 
-        let mut token = &self.tokens[self.position];
+        let mut token = self.tokens.get(self.position)?;
         match &token.token_type {
             DDLTokenType::Identifier(name) => {
                 field.name = name.clone();
@@ -221,68 +756,182 @@ impl DDLParser {
         }
         self.position += 1;
 
-        token = &self.tokens[self.position];
+        token = self.tokens.get(self.position)?;
         match &token.token_type {
+            DDLTokenType::Type(t) if t == "Decimal" => {
+                self.position += 1;
+                field.field_type = self.parse_decimal()?;
+            }
             DDLTokenType::Type(t) => {
-                let field_type = match BasicType::from_str(&t) {
+                let field_type = match BasicType::from_str(t) {
                     Some(t) => t,
                     None => {
-                        self.errors.push(format!("Invalid field type: {}", t));
+                        self.errors.push(Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!("Invalid field type: {}", t),
+                        ));
                         return None;
                     }
                 };
                 field.field_type = field_type;
+                self.position += 1;
+            }
+            DDLTokenType::Identifier(name) => {
+                field.field_type = BasicType::Enum(EnumType::new(name.clone(), Vec::new()));
+                self.position += 1;
             }
             _ => return None,
         }
-        self.position += 1;
+        if self.token_type_at(self.position) == Some(&DDLTokenType::OpenBracket) {
+            if self.token_type_at(self.position + 1)? != &DDLTokenType::CloseBracket {
+                return None;
+            }
+            field.field_type = BasicType::Array(Box::new(field.field_type));
+            self.position += 2;
+        }
 
-        token = &self.tokens[self.position];
+        token = self.tokens.get(self.position)?;
         if token.token_type == DDLTokenType::QuestionMark {
             field.is_nullable = true;
             self.position += 1;
         }
 
-        token = &self.tokens[self.position];
-        while token.token_type == DDLTokenType::AtSign {
+        token = self.tokens.get(self.position)?;
+        // `@@` starts a model attribute rather than one of this field.
+        while token.token_type == DDLTokenType::AtSign
+            && self.token_type_at(self.position + 1) != Some(&DDLTokenType::AtSign)
+        {
             self.position += 1;
-            token = &self.tokens[self.position];
+            token = self.tokens.get(self.position)?;
             if token.token_type == DDLTokenType::Identifier("id".to_string()) {
                 field.is_primary_key = true;
                 self.position += 1;
+            } else if token.token_type == DDLTokenType::Identifier("adaptive".to_string()) {
+                field.is_adaptive = true;
+                self.position += 1;
+            } else if token.token_type == DDLTokenType::Identifier("schema".to_string()) {
+                let (
+                    Some(DDLTokenType::OpenParen),
+                    Some(DDLTokenType::String(schema)),
+                    Some(DDLTokenType::CloseParen),
+                ) = (
+                    self.token_type_at(self.position + 1),
+                    self.token_type_at(self.position + 2),
+                    self.token_type_at(self.position + 3),
+                )
+                else {
+                    return None;
+                };
+                let schema = serde_json::from_str(schema)
+                    .map_err(|error| error.to_string())
+                    .and_then(|schema| json::check_schema(&schema).map(|_| schema));
+                match schema {
+                    Ok(schema) => field.json_schema = Some(schema),
+                    Err(error) => self.errors.push(
+                        Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!("Invalid JSON Schema for field {}: {}", field.name, error),
+                        )
+                        .with_hint(r#"Write schemas as @schema("{\"type\": \"object\"}")"#),
+                    ),
+                }
+                self.position += 4;
+            } else if token.token_type == DDLTokenType::Identifier("check".to_string()) {
+                field.check = Some(self.parse_string_argument()?);
+            } else if token.token_type == DDLTokenType::Identifier("default".to_string()) {
+                if self.token_type_at(self.position + 1)? != &DDLTokenType::OpenParen {
+                    return None;
+                }
+                self.position += 2;
+                field.default = Some(self.parse_default()?);
+                if self.token_type_at(self.position)? != &DDLTokenType::CloseParen {
+                    return None;
+                }
+                self.position += 1;
             } else if token.token_type == DDLTokenType::Identifier("references".to_string()) {
                 self.position += 1;
-                token = &self.tokens[self.position];
+                token = self.tokens.get(self.position)?;
 
                 if !matches!(token.token_type, DDLTokenType::OpenParen) {
                     return None;
                 }
 
-                let model = match &self.tokens[self.position + 1].token_type {
+                let model = match self.token_type_at(self.position + 1)? {
                     DDLTokenType::Identifier(id) => id.clone(),
                     _ => return None,
                 };
 
-                let model_field = match &self.tokens[self.position + 2].token_type {
+                if self.token_type_at(self.position + 2)? != &DDLTokenType::Comma {
+                    return None;
+                }
+
+                let model_field = match self.token_type_at(self.position + 3)? {
                     DDLTokenType::Identifier(id) => id.clone(),
                     _ => return None,
                 };
 
                 field.references = Some((model, model_field));
-                self.position += 3;
+                self.position += 4;
 
-                token = &self.tokens[self.position];
+                token = self.tokens.get(self.position)?;
                 if !matches!(token.token_type, DDLTokenType::CloseParen) {
                     return None;
                 }
 
                 self.position += 1;
             }
-            token = &self.tokens[self.position];
+            token = self.tokens.get(self.position)?;
         }
 
         Some(field)
     }
+
+    /// Parses what `@default(...)` holds: a literal, or `now()`, `uuid()` or
+    /// `autoincrement()`.
+    fn parse_default(&mut self) -> Option<ColumnDefault> {
+        let token = self.tokens.get(self.position)?;
+        let value = match &token.token_type {
+            DDLTokenType::String(value) | DDLTokenType::Literal(value) => value.clone(),
+            // Read as written, as numbers too large for a u32 saturate.
+            DDLTokenType::Number(_) => self
+                .lexer
+                .input
+                .chars()
+                .skip(token.span.start)
+                .take(token.span.end - token.span.start)
+                .collect(),
+            DDLTokenType::Identifier(name)
+                if self.token_type_at(self.position + 1) == Some(&DDLTokenType::OpenParen) =>
+            {
+                if self.token_type_at(self.position + 2)? != &DDLTokenType::CloseParen {
+                    return None;
+                }
+                let default = match name.as_str() {
+                    "now" => ColumnDefault::Now,
+                    "uuid" => ColumnDefault::Uuid,
+                    "autoincrement" => ColumnDefault::Autoincrement,
+                    _ => {
+                        self.errors.push(
+                            Error::new(
+                                ErrorCode::InvalidTableDefinition,
+                                format!("Unknown default function {}()", name),
+                            )
+                            .with_span(token.span)
+                            .with_hint("Defaults are a value, now(), uuid() or autoincrement()"),
+                        );
+                        ColumnDefault::Now
+                    }
+                };
+                self.position += 3;
+                return Some(default);
+            }
+            // Booleans and enum labels.
+            DDLTokenType::Identifier(value) => value.clone(),
+            _ => return None,
+        };
+        self.position += 1;
+        Some(ColumnDefault::Value(value))
+    }
 }
 
 /*
@@ -307,20 +956,221 @@ impl DDLAnalyzer {
         Self { models }
     }
 
-    fn analyze(&self) -> Vec<String> {
+    fn analyze(&self) -> Vec<Error> {
         let mut errors = Vec::new();
         for check in self.checks() {
-            let check_errors = check(&self);
+            let check_errors = check(self);
             errors.extend(check_errors);
         }
         errors
     }
 
-    fn checks(&self) -> Vec<fn(&Self) -> Vec<String>> {
-        vec![Self::every_model_has_primary_key]
+    fn checks(&self) -> Vec<fn(&Self) -> Vec<Error>> {
+        vec![
+            Self::every_model_has_primary_key,
+            Self::every_index_is_on_a_field,
+            Self::every_schema_is_on_a_json_field,
+            Self::every_default_fits_its_field,
+            Self::every_constraint_is_valid,
+            Self::every_foreign_key_is_on_fields,
+            Self::no_temporal_model_has_system_fields,
+        ]
     }
 
-    fn every_model_has_primary_key(&self) -> Vec<String> {
+    /// Checks that `@@temporal` models leave the names of the system
+    /// columns to them.
+    fn no_temporal_model_has_system_fields(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for model in self.models.iter().filter(|model| model.temporal) {
+            for field in model.fields.iter() {
+                if catalogue::is_system_column(&field.name) {
+                    errors.push(
+                        Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!(
+                                "Field {} of model {} has the name of a system column",
+                                field.name, model.name
+                            ),
+                        )
+                        .with_hint(format!(
+                            "@@temporal adds the system columns {} and {} itself",
+                            catalogue::VALID_FROM,
+                            catalogue::VALID_TO
+                        )),
+                    );
+                }
+            }
+        }
+        errors
+    }
+
+    /// Checks that unique keys name distinct fields of their model, and
+    /// that check expressions parse and compare fields with values of their
+    /// types. Enum labels are checked once `resolve_types` knows them.
+    fn every_constraint_is_valid(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for model in self.models.iter() {
+            for fields in model.uniques.iter() {
+                for (position, field) in fields.iter().enumerate() {
+                    if !model.fields.iter().any(|f| &f.name == field) {
+                        errors.push(Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!("Model {} has no field {} to make unique", model.name, field),
+                        ));
+                    } else if fields[..position].contains(field) {
+                        errors.push(Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!(
+                                "Field {}.{} is more than once in a unique key",
+                                model.name, field
+                            ),
+                        ));
+                    }
+                }
+            }
+
+            let column_type = |column: &str| {
+                let field = model.fields.iter().find(|field| field.name == column)?;
+                if is_enum(&field.field_type) {
+                    return Some(BasicType::String);
+                }
+                Some(field.field_type.clone())
+            };
+            let checks = model
+                .fields
+                .iter()
+                .filter_map(|field| field.check.as_ref())
+                .chain(model.checks.iter());
+            for expression in checks {
+                let checked = CheckExpression::parse(expression)
+                    .and_then(|check| check.resolve(&column_type));
+                if let Err(error) = checked {
+                    errors.push(
+                        Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!(
+                                "Invalid check \"{}\" on model {}: {}",
+                                expression, model.name, error
+                            ),
+                        )
+                        .with_hint(
+                            r#"Checks compare fields with values, as in @check("Salary > 0")"#,
+                        ),
+                    );
+                }
+            }
+        }
+        errors
+    }
+
+    /// Literals of an enum type are checked once `resolve_types` knows its
+    /// labels.
+    fn every_default_fits_its_field(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for model in self.models.iter() {
+            for field in model.fields.iter() {
+                let Some(default) = &field.default else {
+                    continue;
+                };
+                let fits = match default {
+                    ColumnDefault::Value(value) => {
+                        is_enum(&field.field_type) || field.field_type.parse_value(value).is_ok()
+                    }
+                    ColumnDefault::Now => matches!(
+                        field.field_type,
+                        BasicType::Timestamp
+                            | BasicType::TimestampTz
+                            | BasicType::Date
+                            | BasicType::Time
+                    ),
+                    ColumnDefault::Uuid => {
+                        matches!(field.field_type, BasicType::Uuid | BasicType::String)
+                    }
+                    ColumnDefault::Autoincrement => field.field_type == BasicType::Int,
+                };
+                if !fits {
+                    errors.push(default_error(&model.name, field, default));
+                }
+            }
+        }
+        errors
+    }
+
+    fn every_schema_is_on_a_json_field(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for model in self.models.iter() {
+            for field in model.fields.iter() {
+                if field.json_schema.is_some() && field.field_type != BasicType::Json {
+                    errors.push(Error::new(
+                        ErrorCode::InvalidTableDefinition,
+                        format!(
+                            "Field {}.{} is not Json and cannot have a @schema",
+                            model.name, field.name
+                        ),
+                    ));
+                }
+            }
+        }
+        errors
+    }
+
+    fn every_index_is_on_a_field(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for model in self.models.iter() {
+            for index in model.indexes.iter() {
+                let column = &index.field;
+                let Some(field) = model.fields.iter().find(|field| &field.name == column) else {
+                    errors.push(Error::new(
+                        ErrorCode::InvalidTableDefinition,
+                        format!("Model {} has no field {} to index", model.name, column),
+                    ));
+                    continue;
+                };
+                if index.path.is_some() {
+                    if field.field_type != BasicType::Json {
+                        errors.push(
+                            Error::new(
+                                ErrorCode::InvalidTableDefinition,
+                                format!(
+                                    "Field {}.{} is not Json and cannot have a path index",
+                                    model.name, column
+                                ),
+                            )
+                            .with_hint(
+                                "Path indexes are for Json fields, as in @@index(Doc->>'type')",
+                            ),
+                        );
+                    } else if index.kind != IndexKind::BTree {
+                        errors.push(Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!(
+                                "Path index on field {}.{} must be a btree index",
+                                model.name, column
+                            ),
+                        ));
+                    }
+                } else if index.kind == IndexKind::Inverted
+                    && field.field_type.element_type().is_none()
+                {
+                    errors.push(
+                        Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!(
+                                "Field {}.{} is not an array and cannot have a gin index",
+                                model.name, column
+                            ),
+                        )
+                        .with_hint("gin indexes are for array fields, such as Tags String[]"),
+                    );
+                }
+            }
+        }
+        errors
+    }
+
+    /// Checks that every model has one primary key, a field marked `@id` or
+    /// the distinct fields of an `@@id([...])`, none of them optional.
+    fn every_model_has_primary_key(&self) -> Vec<Error> {
         let mut errors = Vec::new();
         for model in self.models.iter() {
             let mut primary_key_count = 0;
@@ -329,36 +1179,336 @@ impl DDLAnalyzer {
                     primary_key_count += 1;
                 }
             }
+            if !model.primary_key.is_empty() {
+                primary_key_count += 1;
+            }
             if primary_key_count != 1 {
-                errors.push(format!(
-                    "Model {} has {} primary keys, expected 1",
-                    model.name, primary_key_count
-                ));
+                errors.push(
+                    Error::new(
+                        ErrorCode::InvalidTableDefinition,
+                        format!(
+                            "Model {} has {} primary keys, expected 1",
+                            model.name, primary_key_count
+                        ),
+                    )
+                    .with_hint(
+                        "Mark exactly one field with @id, or list the fields of the key in @@id([...])",
+                    ),
+                );
+                continue;
+            }
+            let primary_key = model.primary_key();
+            for (position, name) in primary_key.iter().enumerate() {
+                let Some(field) = model.fields.iter().find(|field| &field.name == name) else {
+                    errors.push(Error::new(
+                        ErrorCode::InvalidTableDefinition,
+                        format!(
+                            "Model {} has no field {} for its primary key",
+                            model.name, name
+                        ),
+                    ));
+                    continue;
+                };
+                if primary_key[..position].contains(name) {
+                    errors.push(Error::new(
+                        ErrorCode::InvalidTableDefinition,
+                        format!(
+                            "Field {}.{} is more than once in the primary key",
+                            model.name, name
+                        ),
+                    ));
+                } else if field.is_nullable {
+                    errors.push(
+                        Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!(
+                                "Field {}.{} is optional and cannot be in the primary key",
+                                model.name, name
+                            ),
+                        )
+                        .with_hint("Remove the ? from the type of the field"),
+                    );
+                }
+            }
+        }
+        errors
+    }
+
+    /// Checks that foreign keys name distinct fields of their model. What
+    /// they reference is checked by `resolve_references`, which also knows
+    /// the tables of the catalogue.
+    fn every_foreign_key_is_on_fields(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for model in self.models.iter() {
+            for reference in model.references.iter() {
+                for (position, field) in reference.fields.iter().enumerate() {
+                    if !model.fields.iter().any(|f| &f.name == field) {
+                        errors.push(Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!(
+                                "Model {} has no field {} for its foreign key to {}",
+                                model.name, field, reference.model
+                            ),
+                        ));
+                    } else if reference.fields[..position].contains(field) {
+                        errors.push(Error::new(
+                            ErrorCode::InvalidTableDefinition,
+                            format!(
+                                "Field {}.{} is more than once in a foreign key",
+                                model.name, field
+                            ),
+                        ));
+                    }
+                }
             }
         }
         errors
     }
 }
 
-pub fn parse(ddl: String) -> Vec<Model> {
+pub fn parse(ddl: String) -> Result<Schema, Error> {
     let lexer = DDLLexer::new(ddl);
     let mut parser = DDLParser::new(lexer);
-    let mut models = Vec::new();
-    while let Some(model) = parser.parse_model() {
-        models.push(model);
+    let mut schema = Schema::default();
+    let mut complete = true;
+    while parser.position < parser.tokens.len() {
+        let parsed = if parser.token_type_at(parser.position) == Some(&DDLTokenType::Enum) {
+            parser
+                .parse_enum()
+                .map(|enum_type| schema.enums.push(enum_type))
+        } else {
+            parser.parse_model().map(|model| schema.models.push(model))
+        };
+        if parsed.is_none() {
+            complete = false;
+            break;
+        }
+    }
+
+    let mut errors = std::mem::take(&mut parser.lexer.errors);
+    errors.append(&mut parser.errors);
+    if errors.is_empty() && !complete {
+        errors.push(parser.syntax_error());
+    }
+    if !errors.is_empty() {
+        return Err(Error::many(errors));
     }
-    models
+    Ok(schema)
 }
 
-pub fn analyze(models: &Vec<Model>) -> Vec<String> {
-    let analyzer = DDLAnalyzer::new(models.clone());
-    analyzer.analyze()
+/// Looks up the enum named by every field of an enum type, among the enums
+/// of the schema and `known` ones.
+pub fn resolve_types(schema: &mut Schema, known: &[EnumType]) -> Result<(), Error> {
+    let mut errors = Vec::new();
+    for model in schema.models.iter_mut() {
+        for field in model.fields.iter_mut() {
+            // Arrays of an enum name it in their element type.
+            let field_type = match &mut field.field_type {
+                BasicType::Array(element_type) => element_type.as_mut(),
+                field_type => field_type,
+            };
+            let BasicType::Enum(named) = field_type else {
+                continue;
+            };
+            let found = schema
+                .enums
+                .iter()
+                .chain(known)
+                .find(|enum_type| enum_type.name() == named.name());
+            match found {
+                Some(enum_type) => {
+                    *field_type = BasicType::Enum(enum_type.clone());
+                    if let Some(default @ ColumnDefault::Value(value)) = &field.default {
+                        if field.field_type.parse_value(value).is_err() {
+                            errors.push(default_error(&model.name, field, default));
+                        }
+                    }
+                }
+                None => errors.push(
+                    Error::new(
+                        ErrorCode::UndefinedType,
+                        format!(
+                            "Type {} of field {}.{} does not exist",
+                            named.name(),
+                            model.name,
+                            field.name
+                        ),
+                    )
+                    .with_hint(
+                        "Field types are Int, Float, Bool, String, Date, Blob, Decimal(p, s), \
+                         Timestamp, Timestamptz, Time, Uuid, Json, an enum or an array of one, as in String[]",
+                    ),
+                ),
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(Error::many(errors));
+    }
+    Ok(())
+}
+
+/// Checks that the foreign keys of a schema reference the primary key of a
+/// model of the schema or of one of the existing `tables`, with fields of
+/// the same types in the same order, and fills in the fields of the keys
+/// they reference. Runs once `resolve_types` has looked up enums, so enum
+/// fields only match fields of the same enum.
+pub fn resolve_references(schema: &mut Schema, tables: &[Table]) -> Result<(), Error> {
+    let mut errors = Vec::new();
+    let mut resolved = Vec::new();
+    for model in schema.models.iter() {
+        for reference in model.references.iter() {
+            let referenced_key: Option<Vec<(String, BasicType)>> = match schema
+                .models
+                .iter()
+                .find(|other| other.name == reference.model)
+            {
+                Some(referenced) => referenced
+                    .primary_key()
+                    .into_iter()
+                    .map(|name| {
+                        let field = referenced.fields.iter().find(|f| f.name == name)?;
+                        Some((name.to_string(), field.field_type.clone()))
+                    })
+                    .collect(),
+                None => tables
+                    .iter()
+                    .find(|table| table.name() == reference.model)
+                    .map(|table| {
+                        table
+                            .primary_key()
+                            .into_iter()
+                            .map(|column| (column.name().to_string(), column.data_type()))
+                            .collect()
+                    }),
+            };
+            let Some(referenced_key) = referenced_key else {
+                errors.push(Error::new(
+                    ErrorCode::UndefinedTable,
+                    format!(
+                        "Model {} references model {}, which does not exist",
+                        model.name, reference.model
+                    ),
+                ));
+                continue;
+            };
+            let key: Vec<String> = referenced_key
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect();
+            let names_key =
+                reference.referenced_fields.is_empty() || reference.referenced_fields == key;
+            if !names_key || reference.fields.len() != key.len() {
+                errors.push(
+                    Error::new(
+                        ErrorCode::InvalidForeignKey,
+                        format!(
+                            "Foreign key ({}) of model {} does not match the primary key ({}) of model {}",
+                            reference.fields.join(", "),
+                            model.name,
+                            key.join(", "),
+                            reference.model
+                        ),
+                    )
+                    .with_hint(
+                        "Foreign keys reference the whole primary key of a model, as in @@references([a, b], Model)",
+                    ),
+                );
+                continue;
+            }
+            for (name, (referenced_name, referenced_type)) in
+                reference.fields.iter().zip(referenced_key)
+            {
+                let Some(field) = model.fields.iter().find(|field| &field.name == name) else {
+                    continue;
+                };
+                if field.field_type != referenced_type {
+                    errors.push(Error::new(
+                        ErrorCode::DatatypeMismatch,
+                        format!(
+                            "Field {}.{} is {} but references {}.{}, which is {}",
+                            model.name,
+                            name,
+                            field.field_type,
+                            reference.model,
+                            referenced_name,
+                            referenced_type
+                        ),
+                    ));
+                }
+            }
+            resolved.push(key);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(Error::many(errors));
+    }
+    let references = schema
+        .models
+        .iter_mut()
+        .flat_map(|model| model.references.iter_mut());
+    for (reference, key) in references.zip(resolved) {
+        reference.referenced_fields = key;
+    }
+    Ok(())
+}
+
+/// Seconds of a retention as `30 days`, `1 week` or `90 minutes`.
+fn parse_retention(retention: &str) -> Option<u64> {
+    let (count, unit) = retention.trim().split_once(char::is_whitespace)?;
+    let count: u64 = count.parse().ok()?;
+    let unit = unit.trim().to_ascii_lowercase();
+    let seconds = match unit.strip_suffix('s').unwrap_or(&unit) {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => 24 * 60 * 60,
+        "week" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    count.checked_mul(seconds)
+}
+
+fn is_enum(field_type: &BasicType) -> bool {
+    match field_type {
+        BasicType::Array(element_type) => is_enum(element_type),
+        field_type => matches!(field_type, BasicType::Enum(_)),
+    }
+}
+
+fn default_error(model_name: &str, field: &Field, default: &ColumnDefault) -> Error {
+    let default = match default {
+        ColumnDefault::Value(value) => value.clone(),
+        ColumnDefault::Now => "now()".to_string(),
+        ColumnDefault::Uuid => "uuid()".to_string(),
+        ColumnDefault::Autoincrement => "autoincrement()".to_string(),
+    };
+    Error::new(
+        ErrorCode::InvalidTableDefinition,
+        format!(
+            "Default {} does not fit field {}.{} of type {}",
+            default, model_name, field.name, field.field_type
+        ),
+    )
+    .with_hint(
+        "now() is for date and time fields, uuid() for Uuid and String, autoincrement() for Int",
+    )
+}
+
+pub fn analyze(models: &[Model]) -> Result<(), Error> {
+    let analyzer = DDLAnalyzer::new(models.to_vec());
+    let errors = analyzer.analyze();
+    if !errors.is_empty() {
+        return Err(Error::many(errors));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[allow(dead_code)]
     const DDL_CORRECT: &str = "
         model Employee {
             EmployeeID String @id
@@ -382,12 +1532,14 @@ mod tests {
             ToDate Date? 
         }";
 
+    #[allow(dead_code)]
     const DDL_MISSING_PRIMARY_KEY: &str = "
         model Employee {
             EmployeeID String
         }
         ";
 
+    #[allow(dead_code)]
     const DDL_MISSING_FOREIGN_KEY_MODEL: &str = "
         model Salary {
             SalaryID Int @id
@@ -435,6 +1587,10 @@ mod tests {
                     is_primary_key: true,
                     is_foreign_key: false,
                     references: None,
+                    is_adaptive: false,
+                    json_schema: None,
+                    default: None,
+                    check: None,
                 },
                 Field {
                     name: "FirstName".to_string(),
@@ -443,6 +1599,10 @@ mod tests {
                     is_primary_key: false,
                     is_foreign_key: false,
                     references: None,
+                    is_adaptive: false,
+                    json_schema: None,
+                    default: None,
+                    check: None,
                 },
                 Field {
                     name: "LastName".to_string(),
@@ -451,6 +1611,10 @@ mod tests {
                     is_primary_key: false,
                     is_foreign_key: false,
                     references: None,
+                    is_adaptive: false,
+                    json_schema: None,
+                    default: None,
+                    check: None,
                 },
                 Field {
                     name: "DepartmentID".to_string(),
@@ -459,6 +1623,10 @@ mod tests {
                     is_primary_key: false,
                     is_foreign_key: false,
                     references: None,
+                    is_adaptive: false,
+                    json_schema: None,
+                    default: None,
+                    check: None,
                 },
                 Field {
                     name: "JobTitle".to_string(),
@@ -467,6 +1635,10 @@ mod tests {
                     is_primary_key: false,
                     is_foreign_key: false,
                     references: None,
+                    is_adaptive: false,
+                    json_schema: None,
+                    default: None,
+                    check: None,
                 },
                 Field {
                     name: "HireDate".to_string(),
@@ -475,8 +1647,20 @@ mod tests {
                     is_primary_key: false,
                     is_foreign_key: false,
                     references: None,
+                    is_adaptive: false,
+                    json_schema: None,
+                    default: None,
+                    check: None,
                 },
             ],
+            layout: None,
+            indexes: Vec::new(),
+            uniques: Vec::new(),
+            checks: Vec::new(),
+            primary_key: Vec::new(),
+            references: Vec::new(),
+            retention: None,
+            temporal: false,
         }];
         let lexer = DDLLexer::new(ddl.to_string());
         let mut parser = DDLParser::new(lexer);
@@ -499,7 +1683,319 @@ mod tests {
             models.push(model);
         }
         let analyzer = DDLAnalyzer::new(models);
-        let errors = analyzer.analyze();
+        let errors: Vec<String> = analyzer
+            .analyze()
+            .iter()
+            .map(|error| error.message().to_string())
+            .collect();
         assert_eq!(errors, correct_errors);
     }
+
+    #[test]
+    fn test_ddl_parser_adaptive_field() {
+        let ddl = "model Salary { SalaryID Int @id Salary Float @adaptive }";
+        let models = parse(ddl.to_string()).unwrap().models;
+        assert_eq!(models.len(), 1);
+        assert!(!models[0].fields[0].is_adaptive);
+        assert!(models[0].fields[0].is_primary_key);
+        assert!(models[0].fields[1].is_adaptive);
+    }
+
+    #[test]
+    fn test_ddl_parser_layout_attribute() {
+        let ddl = "
+            model Employee {
+                EmployeeID String @id
+            }
+
+            model Salary {
+                SalaryID Int @id
+                Salary Float
+                @@layout(column)
+            }";
+        let models = parse(ddl.to_string()).unwrap().models;
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].layout, None);
+        assert_eq!(models[1].name, "Salary");
+        assert_eq!(models[1].fields.len(), 2);
+        assert_eq!(models[1].layout, Some(Layout::Column));
+    }
+
+    #[test]
+    fn test_ddl_parser_retention_attribute() {
+        let ddl = r#"
+            model Salary {
+                SalaryID Int @id
+                @@retention("30 days")
+            }
+            model Employee {
+                EmployeeID Int @id
+            }"#;
+        let models = parse(ddl.to_string()).unwrap().models;
+        assert_eq!(models[0].retention, Some(30 * 24 * 60 * 60));
+        assert_eq!(models[1].retention, None);
+        assert_eq!(parse_retention("1 week"), Some(7 * 24 * 60 * 60));
+        assert_eq!(parse_retention("90 Minutes"), Some(90 * 60));
+
+        for retention in ["30", "days", "-1 days", "2 fortnights"] {
+            let ddl = format!(r#"model T {{ A Int @id @@retention("{}") }}"#, retention);
+            let error = parse(ddl).unwrap_err();
+            assert_eq!(error.code(), ErrorCode::InvalidTableDefinition);
+        }
+    }
+
+    #[test]
+    fn test_ddl_parser_temporal_attribute() {
+        let ddl = r#"
+            model Salary {
+                SalaryID Int @id
+                @@temporal
+            }
+            model Employee {
+                EmployeeID Int @id
+            }"#;
+        let models = parse(ddl.to_string()).unwrap().models;
+        assert!(models[0].temporal);
+        assert_eq!(models[0].history_table(), Some("SalaryHistory".to_string()));
+        assert!(!models[1].temporal);
+        assert_eq!(models[1].history_table(), None);
+
+        let ddl = "model Salary { SalaryID Int @id valid_from TimestampTz @@temporal }";
+        let error = analyze(&parse(ddl.to_string()).unwrap().models).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::InvalidTableDefinition);
+        assert_eq!(
+            error.message(),
+            "Field valid_from of model Salary has the name of a system column"
+        );
+    }
+
+    #[test]
+    fn test_ddl_parser_syntax_error() {
+        let error = parse("model Employee { EmployeeID 5 @id }".to_string()).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::SyntaxError);
+        assert_eq!(error.message(), "Syntax error at or near \"5\"");
+        assert_eq!(error.span(), Some(Span { start: 28, end: 29 }));
+
+        let error = parse("model Employee { EmployeeID Int @id".to_string()).unwrap_err();
+        assert_eq!(error.message(), "Unexpected end of schema");
+
+        let error = parse("model Employee { EmployeeID Int @id }".to_string());
+        assert_eq!(error.unwrap().models[0].name, "Employee");
+    }
+
+    #[test]
+    fn test_ddl_parser_types() {
+        let ddl = "
+            enum Role { admin, member guest }
+
+            model Account {
+                AccountID Uuid @id
+                Balance Decimal(12, 2)
+                Role Role
+                Opened Timestamptz
+                Settings Json?
+            }";
+        let mut schema = parse(ddl.to_string()).unwrap();
+        assert_eq!(schema.enums[0].labels(), ["admin", "member", "guest"]);
+        let fields = &schema.models[0].fields;
+        assert_eq!(fields[0].field_type, BasicType::Uuid);
+        assert_eq!(
+            fields[1].field_type,
+            BasicType::Decimal {
+                precision: 12,
+                scale: 2
+            }
+        );
+        assert_eq!(fields[3].field_type, BasicType::TimestampTz);
+        resolve_types(&mut schema, &[]).unwrap();
+        assert_eq!(
+            schema.models[0].fields[2].field_type,
+            BasicType::Enum(schema.enums[0].clone())
+        );
+
+        let mut schema =
+            parse("model Account { AccountID Int @id Kind Kind }".to_string()).unwrap();
+        let error = resolve_types(&mut schema, &[]).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::UndefinedType);
+        let known = [EnumType::new("Kind".to_string(), vec!["a".to_string()])];
+        assert!(resolve_types(&mut schema, &known).is_ok());
+
+        let error = parse("model Account { AccountID Int @id Balance Decimal(2, 4) }".to_string());
+        assert_eq!(error.unwrap_err().code(), ErrorCode::InvalidTableDefinition);
+        let error = parse("enum Role { admin admin }".to_string()).unwrap_err();
+        assert_eq!(error.message(), "Enum Role has label admin more than once");
+    }
+
+    #[test]
+    fn test_ddl_parser_defaults() {
+        let ddl = r#"
+            enum Status { open closed }
+
+            model Ticket {
+                TicketID Int @id @default(autoincrement())
+                Key Uuid @default(uuid())
+                Title String @default("untitled")
+                Priority Int @default(-1)
+                Weight Float @default(0.5)
+                Urgent Bool @default(false)
+                Status Status @default(open)
+                Opened Timestamptz @default(now())
+            }"#;
+        let mut schema = parse(ddl.to_string()).unwrap();
+        analyze(&schema.models).unwrap();
+        let defaults: Vec<Option<ColumnDefault>> = schema.models[0]
+            .fields
+            .iter()
+            .map(|field| field.default.clone())
+            .collect();
+        let value = |value: &str| Some(ColumnDefault::Value(value.to_string()));
+        assert_eq!(
+            defaults,
+            vec![
+                Some(ColumnDefault::Autoincrement),
+                Some(ColumnDefault::Uuid),
+                value("untitled"),
+                value("-1"),
+                value("0.5"),
+                value("false"),
+                value("open"),
+                Some(ColumnDefault::Now),
+            ]
+        );
+        resolve_types(&mut schema, &[]).unwrap();
+
+        let invalid = |ddl: &str| {
+            let schema = parse(ddl.to_string())?;
+            analyze(&schema.models)
+        };
+        for ddl in [
+            "model T { TID Int @id Name String @default(now()) }",
+            "model T { TID String @id @default(autoincrement()) }",
+            r#"model T { TID Int @id Count Int @default("many") }"#,
+            "model T { TID Int @id Count Int @default(random()) }",
+        ] {
+            let error = invalid(ddl).unwrap_err();
+            assert_eq!(error.code(), ErrorCode::InvalidTableDefinition, "{}", ddl);
+        }
+        let mut schema = parse(
+            "enum Status { open } model T { TID Int @id Status Status @default(shut) }".to_string(),
+        )
+        .unwrap();
+        let error = resolve_types(&mut schema, &[]).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::InvalidTableDefinition);
+    }
+
+    #[test]
+    fn test_ddl_parser_constraints() {
+        let ddl = r#"
+            model Employee {
+                EmployeeID Int @id
+                FirstName String
+                LastName String
+                Salary Int @check("Salary > 0")
+                FromDate Date
+                ToDate Date?
+                @@unique([FirstName, LastName])
+                @@check("FromDate <= ToDate or ToDate is null")
+            }"#;
+        let schema = parse(ddl.to_string()).unwrap();
+        analyze(&schema.models).unwrap();
+        let model = &schema.models[0];
+        assert_eq!(model.fields[3].check.as_deref(), Some("Salary > 0"));
+        assert_eq!(model.uniques, vec![vec!["FirstName", "LastName"]]);
+        assert_eq!(model.checks, vec!["FromDate <= ToDate or ToDate is null"]);
+
+        let invalid = |ddl: &str| {
+            let schema = parse(ddl.to_string())?;
+            analyze(&schema.models)
+        };
+        for ddl in [
+            r#"model T { TID Int @id Count Int @check("Count >") }"#,
+            r#"model T { TID Int @id Count Int @check("Total > 0") }"#,
+            r#"model T { TID Int @id Count Int @check("Count > 'many'") }"#,
+            "model T { TID Int @id Name String @@unique([Name, Missing]) }",
+            "model T { TID Int @id Name String @@unique([Name, Name]) }",
+        ] {
+            let error = invalid(ddl).unwrap_err();
+            assert_eq!(error.code(), ErrorCode::InvalidTableDefinition, "{}", ddl);
+        }
+        assert!(parse("model T { TID Int @id Name String @@unique(Name) }".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_ddl_parser_composite_keys() {
+        let ddl = r#"
+            model SalaryHistory {
+                EmployeeID Int
+                FromDate Date
+                Amount Int
+                @@id([EmployeeID, FromDate])
+            }
+            model Bonus {
+                BonusID Int @id
+                EmployeeID Int
+                FromDate Date
+                ManagerID Int @references(Manager, ManagerID)
+                @@references([EmployeeID, FromDate], SalaryHistory)
+            }
+            model Manager {
+                ManagerID Int @id
+            }"#;
+        let mut schema = parse(ddl.to_string()).unwrap();
+        analyze(&schema.models).unwrap();
+        assert_eq!(schema.models[0].primary_key(), ["EmployeeID", "FromDate"]);
+        assert_eq!(schema.models[1].primary_key(), ["BonusID"]);
+        resolve_references(&mut schema, &[]).unwrap();
+        let fields = |reference: &ModelReference| {
+            (
+                reference.fields.clone(),
+                reference.referenced_fields.clone(),
+            )
+        };
+        let references: Vec<_> = schema.models[1].references.iter().map(fields).collect();
+        assert_eq!(
+            references,
+            [
+                (vec!["ManagerID".to_string()], vec!["ManagerID".to_string()]),
+                (
+                    vec!["EmployeeID".to_string(), "FromDate".to_string()],
+                    vec!["EmployeeID".to_string(), "FromDate".to_string()]
+                ),
+            ]
+        );
+
+        for ddl in [
+            "model T { A Int @id B Int @@id([A, B]) }",
+            "model T { A Int B Int @@id([A, Missing]) }",
+            "model T { A Int B Int @@id([A, A]) }",
+            "model T { A Int B Int? @@id([A, B]) }",
+            "model T { A Int @id B Int @@references([B, Missing], T) }",
+        ] {
+            let error = analyze(&parse(ddl.to_string()).unwrap().models).unwrap_err();
+            assert_eq!(error.code(), ErrorCode::InvalidTableDefinition, "{}", ddl);
+        }
+        for (ddl, code) in [
+            (
+                "model T { A Int @id B Int @@references([B], Missing) }",
+                ErrorCode::UndefinedTable,
+            ),
+            (
+                "model T { A Int B Int C Int @@id([A, B]) @@references([C], T) }",
+                ErrorCode::InvalidForeignKey,
+            ),
+            (
+                "model T { A Int B Int C Int @references(T, B) @@id([A, B]) }",
+                ErrorCode::InvalidForeignKey,
+            ),
+            (
+                "model T { A Int @id B String @@references([B], T) }",
+                ErrorCode::DatatypeMismatch,
+            ),
+        ] {
+            let mut schema = parse(ddl.to_string()).unwrap();
+            analyze(&schema.models).unwrap();
+            let error = resolve_references(&mut schema, &[]).unwrap_err();
+            assert_eq!(error.code(), code, "{}", ddl);
+        }
+    }
 }
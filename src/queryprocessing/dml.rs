@@ -1,4 +1,11 @@
-use tracing::info;
+use std::fmt;
+
+use super::plan;
+use crate::catalogue::basic_types::{self, BasicType};
+use crate::catalogue::json::{JsonAccess, JsonStep};
+use crate::catalogue::json_path::JsonPath;
+use crate::catalogue::{self, Refresh, TriggerEvent, TriggerTiming};
+use crate::{Database, Error, ErrorCode, Span};
 
 #[derive(Debug, PartialEq, Clone)]
 enum DMLTokenType {
@@ -6,36 +13,111 @@ enum DMLTokenType {
     Into,
     Values,
     Commit,
+    Select,
+    From,
+    Join,
+    On,
+    Where,
+    And,
+    Explain,
+    Analyze,
+    Update,
+    Set,
+    Delete,
+    Declare,
+    Cursor,
+    For,
+    Fetch,
+    All,
+    Close,
+    Create,
     OpenParenthesis,
     CloseParenthesis,
     Comma,
+    Star,
+    /// `->`, which reads a member or element of a JSON value.
+    Arrow,
+    /// `->>`, which reads it as text.
+    TextArrow,
+    Operator(DMLOperator),
     Identifier(String),
     String(String),
     Number(f64),
+    Parameter(usize),
     SemiColon,
 }
 
-const DML_KEYWORDS: [(&str, DMLTokenType); 4] = [
+const DML_KEYWORDS: [(&str, DMLTokenType); 22] = [
     ("insert", DMLTokenType::Insert),
     ("into", DMLTokenType::Into),
     ("values", DMLTokenType::Values),
     ("commit", DMLTokenType::Commit),
+    ("select", DMLTokenType::Select),
+    ("from", DMLTokenType::From),
+    ("join", DMLTokenType::Join),
+    ("on", DMLTokenType::On),
+    ("where", DMLTokenType::Where),
+    ("and", DMLTokenType::And),
+    ("explain", DMLTokenType::Explain),
+    ("analyze", DMLTokenType::Analyze),
+    ("update", DMLTokenType::Update),
+    ("set", DMLTokenType::Set),
+    ("delete", DMLTokenType::Delete),
+    ("declare", DMLTokenType::Declare),
+    ("cursor", DMLTokenType::Cursor),
+    ("for", DMLTokenType::For),
+    ("fetch", DMLTokenType::Fetch),
+    ("all", DMLTokenType::All),
+    ("close", DMLTokenType::Close),
+    ("create", DMLTokenType::Create),
 ];
 
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | ',' | ';' | '=' | '<' | '>' | '!' | '@' | '&')
+}
+
 #[derive(Debug, PartialEq, Clone)]
 struct DMLToken {
     token_type: DMLTokenType,
-    position: usize,
+    /// Where the token starts and ends, in characters.
+    span: Span,
 }
 
 struct DMLLexer {
-    input: String,
+    input: Vec<char>,
     position: usize,
+    /// Why lexing stopped before the end of the input, if it did.
+    error: Option<Error>,
 }
 
 impl DMLLexer {
     fn new(input: String) -> DMLLexer {
-        DMLLexer { input, position: 0 }
+        DMLLexer {
+            input: input.chars().collect(),
+            position: 0,
+            error: None,
+        }
+    }
+
+    /// Stops lexing with a syntax error for the input from `start` up to
+    /// the current position.
+    fn fail(&mut self, start: usize, message: String, hint: &str) -> Option<DMLToken> {
+        self.error = Some(
+            Error::new(ErrorCode::SyntaxError, message)
+                .with_hint(hint)
+                .with_span(Span {
+                    start,
+                    end: self.position,
+                }),
+        );
+        self.position = self.input.len();
+        None
+    }
+
+    /// Whether the input continues with `->` at `position`, which ends an
+    /// identifier or number just like a delimiter does.
+    fn is_arrow(&self, position: usize) -> bool {
+        self.input.get(position) == Some(&'-') && self.input.get(position + 1) == Some(&'>')
     }
 
     fn next_token(&mut self) -> Option<DMLToken> {
@@ -43,129 +125,735 @@ impl DMLLexer {
             return None;
         }
 
-        let mut token = None;
+        let mut token_type = None;
+        let mut start = self.position;
 
         while self.position < self.input.len() {
-            let c = self.input.chars().nth(self.position).unwrap();
+            start = self.position;
+            let c = self.input[self.position];
             match c {
-                ' ' => {
+                c if c.is_whitespace() => {
                     self.position += 1;
                 }
                 '(' => {
-                    token = Some(DMLToken {
-                        token_type: DMLTokenType::OpenParenthesis,
-                        position: self.position,
-                    });
+                    token_type = Some(DMLTokenType::OpenParenthesis);
                     self.position += 1;
                     break;
                 }
                 ')' => {
-                    token = Some(DMLToken {
-                        token_type: DMLTokenType::CloseParenthesis,
-                        position: self.position,
-                    });
+                    token_type = Some(DMLTokenType::CloseParenthesis);
                     self.position += 1;
                     break;
                 }
                 ',' => {
-                    token = Some(DMLToken {
-                        token_type: DMLTokenType::Comma,
-                        position: self.position,
-                    });
+                    token_type = Some(DMLTokenType::Comma);
                     self.position += 1;
                     break;
                 }
                 ';' => {
-                    token = Some(DMLToken {
-                        token_type: DMLTokenType::SemiColon,
-                        position: self.position,
-                    });
+                    token_type = Some(DMLTokenType::SemiColon);
+                    self.position += 1;
+                    break;
+                }
+                '*' => {
+                    token_type = Some(DMLTokenType::Star);
                     self.position += 1;
                     break;
                 }
+                '-' if self.is_arrow(self.position) => {
+                    let text = self.input.get(self.position + 2) == Some(&'>');
+                    token_type = Some(if text {
+                        DMLTokenType::TextArrow
+                    } else {
+                        DMLTokenType::Arrow
+                    });
+                    self.position += if text { 3 } else { 2 };
+                    break;
+                }
+                '=' | '<' | '>' | '!' | '@' | '&' => {
+                    let next = self.input.get(self.position + 1).copied();
+                    let (operator, length) = match (c, next) {
+                        ('<', Some('=')) => (DMLOperator::LessThanOrEquals, 2),
+                        ('<', Some('>')) => (DMLOperator::NotEquals, 2),
+                        ('<', Some('@')) => (DMLOperator::ContainedBy, 2),
+                        ('@', Some('>')) => (DMLOperator::Contains, 2),
+                        ('@', Some('?')) => (DMLOperator::PathExists, 2),
+                        ('@', Some('@')) => (DMLOperator::PathMatches, 2),
+                        ('&', Some('&')) => (DMLOperator::Overlaps, 2),
+                        ('>', Some('=')) => (DMLOperator::GreaterThanOrEquals, 2),
+                        ('!', Some('=')) => (DMLOperator::NotEquals, 2),
+                        ('<', _) => (DMLOperator::LessThan, 1),
+                        ('>', _) => (DMLOperator::GreaterThan, 1),
+                        ('=', _) => (DMLOperator::Equals, 1),
+                        _ => {
+                            self.position += 1;
+                            continue;
+                        }
+                    };
+                    token_type = Some(DMLTokenType::Operator(operator));
+                    self.position += length;
+                    break;
+                }
                 '\'' => {
                     let mut value = String::new();
                     self.position += 1;
                     while self.position < self.input.len() {
-                        let c = self.input.chars().nth(self.position).unwrap();
+                        let c = self.input[self.position];
                         if c == '\'' {
-                            break;
+                            // A quote inside a string is written twice.
+                            if self.input.get(self.position + 1) != Some(&'\'') {
+                                break;
+                            }
+                            self.position += 1;
                         }
                         value.push(c);
                         self.position += 1;
                     }
-                    token = Some(DMLToken {
-                        token_type: DMLTokenType::String(value),
-                        position: self.position,
-                    });
+                    if self.position == self.input.len() {
+                        return self.fail(
+                            start,
+                            "Unterminated string".to_string(),
+                            "Strings end with '",
+                        );
+                    }
+                    token_type = Some(DMLTokenType::String(value));
                     self.position += 1;
                     break;
                 }
-                '0'..='9' => {
+                '$' => {
+                    let mut digits = String::new();
+                    self.position += 1;
+                    while self.position < self.input.len() {
+                        let c = self.input[self.position];
+                        if !c.is_ascii_digit() {
+                            break;
+                        }
+                        digits.push(c);
+                        self.position += 1;
+                    }
+                    // `$0` and a bare `$` name no parameter.
+                    match digits.parse() {
+                        Ok(number) if number > 0 => {
+                            token_type = Some(DMLTokenType::Parameter(number));
+                            break;
+                        }
+                        _ => continue,
+                    }
+                }
+                c if c.is_ascii_digit()
+                    || (c == '-'
+                        && self
+                            .input
+                            .get(self.position + 1)
+                            .is_some_and(|c| c.is_ascii_digit())) =>
+                {
                     let mut value = String::new();
                     while self.position < self.input.len() {
-                        let c = self.input.chars().nth(self.position).unwrap();
-                        if c == ' ' || c == '(' || c == ')' || c == ',' || c == ';' {
+                        let c = self.input[self.position];
+                        if is_delimiter(c) || self.is_arrow(self.position) {
                             break;
                         }
                         value.push(c);
                         self.position += 1;
                     }
-                    token = Some(DMLToken {
-                        token_type: DMLTokenType::Number(value.parse().unwrap()),
-                        position: self.position,
-                    });
+                    let Ok(number) = value.parse() else {
+                        return self.fail(
+                            start,
+                            format!("Invalid number \"{}\"", value),
+                            "Quote values that are not numbers",
+                        );
+                    };
+                    token_type = Some(DMLTokenType::Number(number));
                     break;
                 }
                 _ => {
                     let mut value = String::new();
                     while self.position < self.input.len() {
-                        let c = self.input.chars().nth(self.position).unwrap();
-                        if c == ' ' || c == '(' || c == ')' || c == ',' || c == ';' {
+                        let c = self.input[self.position];
+                        if is_delimiter(c) || self.is_arrow(self.position) {
                             break;
                         }
                         value.push(c);
                         self.position += 1;
                     }
-                    let token_type =
-                        match DML_KEYWORDS.iter().find(|(keyword, _)| *keyword == value) {
-                            Some((_, token_type)) => (*token_type).clone(),
-                            None => DMLTokenType::Identifier(value),
-                        };
-                    token = Some(DMLToken {
-                        token_type,
-                        position: self.position,
-                    });
+                    token_type = match DML_KEYWORDS
+                        .iter()
+                        .find(|(keyword, _)| keyword.eq_ignore_ascii_case(&value))
+                    {
+                        Some((_, token_type)) => Some((*token_type).clone()),
+                        None => Some(DMLTokenType::Identifier(value)),
+                    };
                     break;
                 }
             }
         }
 
-        token
+        token_type.map(|token_type| DMLToken {
+            token_type,
+            span: Span {
+                start,
+                end: self.position,
+            },
+        })
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum DMLStatement {
     Insert(DMLInsertStatement),
-    Update,
-    Delete,
-    Select,
+    Update(DMLUpdateStatement),
+    Delete(DMLDeleteStatement),
+    Select(DMLSelectStatement),
+    Explain(DMLExplainStatement),
+    Declare(DMLDeclareStatement),
+    Fetch(DMLFetchStatement),
+    /// Closes the named cursor.
+    Close(String),
     Commit,
+    CreateSequence(DMLCreateSequenceStatement),
+    /// `select nextval('name')`, which advances the named sequence.
+    NextValue(String),
+    CreateView(DMLCreateViewStatement),
+    /// `refresh materialized view <name>`, which recomputes the rows of the
+    /// named view.
+    RefreshView(String),
+    CreateTrigger(DMLCreateTriggerStatement),
+}
+
+/// `create trigger <name> before|after insert|update|delete on <table>
+/// [for each row] begin <statement>; ... end`.
+#[derive(Debug, Clone)]
+pub struct DMLCreateTriggerStatement {
+    pub name: String,
+    pub table_name: String,
+    pub timing: TriggerTiming,
+    pub event: TriggerEvent,
+    /// The text of every statement of the body, without its `;`.
+    pub statements: Vec<String>,
+}
+
+/// The row a `old.<column>` or `new.<column>` of a trigger statement reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerRow {
+    Old,
+    New,
+}
+
+/// `create [incremental] [materialized] view <name> as <select>`.
+#[derive(Debug, Clone)]
+pub struct DMLCreateViewStatement {
+    pub name: String,
+    /// The text of the select, as stored in the catalogue.
+    pub query: String,
+    pub materialized: Option<Refresh>,
+    pub statement: DMLSelectStatement,
+}
+
+/// `create sequence <name> [start [with] n] [increment [by] n]`.
+#[derive(Debug, Clone)]
+pub struct DMLCreateSequenceStatement {
+    pub name: String,
+    pub start: i64,
+    pub increment: i64,
 }
 
 #[derive(Debug, Clone)]
 pub struct DMLInsertStatement {
     pub table_name: String,
     pub columns: Vec<String>,
-    pub values: Vec<String>,
+    pub values: Vec<DMLValue>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DMLUpdateStatement {
+    pub table_name: String,
+    /// Column and new value of every assignment in the set clause.
+    pub assignments: Vec<(String, DMLValue)>,
+    pub predicates: Vec<DMLPredicate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DMLDeleteStatement {
+    pub table_name: String,
+    pub predicates: Vec<DMLPredicate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DMLSelectStatement {
+    /// Projected columns. Empty for `select *`.
+    pub columns: Vec<DMLColumn>,
+    pub table_name: String,
+    /// Tables joined to `table_name`, in query order.
+    pub joins: Vec<DMLJoin>,
+    /// Arrays unnested in the from clause, which add a column per element.
+    pub unnests: Vec<DMLUnnest>,
+    /// Conjunction of the predicates in the where clause.
+    pub predicates: Vec<DMLPredicate>,
+    /// The past point an `as of` clause reads the tables at.
+    pub as_of: Option<DMLAsOf>,
+    /// The period of a `for system_time` clause.
+    pub system_time: Option<DMLSystemTime>,
+    /// Columns of the group by clause.
+    pub group_by: Vec<String>,
+}
+
+/// The point of an `as of timestamp '<timestamp>'` or `as of transaction
+/// <id>` clause. The select reads every table as it was once the changes
+/// committed up to that point were made, and no others.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DMLAsOf {
+    /// The text of the timestamp, checked by analysis.
+    Timestamp(String),
+    Transaction(u64),
+}
+
+impl fmt::Display for DMLAsOf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DMLAsOf::Timestamp(timestamp) => write!(f, "as of timestamp '{}'", timestamp),
+            DMLAsOf::Transaction(transaction) => write!(f, "as of transaction {}", transaction),
+        }
+    }
+}
+
+/// The period of a `for system_time between '<timestamp>' and
+/// '<timestamp>'` clause. The select reads every version of the rows of a
+/// system-versioned table that was current at some time in it, from the
+/// table and its history table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DMLSystemTime {
+    pub from: String,
+    pub to: String,
+}
+
+impl fmt::Display for DMLSystemTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "for system_time between '{}' and '{}'",
+            self.from, self.to
+        )
+    }
+}
+
+/// An item of a select list: a column, the `array_length` of one, or an
+/// aggregate of one. `count(*)` counts the column `*`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DMLColumn {
+    pub name: String,
+    /// One of `Value`, `Length` and `Path`.
+    pub target: DMLTarget,
+    /// The name given with `as`, if any.
+    pub alias: Option<String>,
+    /// The aggregate function applied to the column, if any.
+    pub aggregate: Option<DMLAggregate>,
+}
+
+impl DMLColumn {
+    /// The name of the item in the result.
+    pub fn output_name(&self) -> &str {
+        if let Some(alias) = &self.alias {
+            return alias;
+        }
+        if let Some(aggregate) = self.aggregate {
+            return aggregate.name();
+        }
+        match &self.target {
+            DMLTarget::Length => "array_length",
+            DMLTarget::Path(access) => access.output_name(),
+            DMLTarget::Value | DMLTarget::AnyElement => &self.name,
+        }
+    }
+}
+
+impl fmt::Display for DMLColumn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.aggregate, &self.target) {
+            (Some(aggregate), _) => write!(f, "{}({})", aggregate.name(), self.name)?,
+            (None, DMLTarget::Length) => write!(f, "array_length({}, 1)", self.name)?,
+            (None, DMLTarget::Path(access)) => write!(f, "{}{}", self.name, access)?,
+            (None, DMLTarget::Value | DMLTarget::AnyElement) => write!(f, "{}", self.name)?,
+        }
+        match &self.alias {
+            Some(alias) => write!(f, " as {}", alias),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An aggregate function of a select list. Aggregates fold the values of a
+/// column over the rows of a group and skip NULLs, so that only `count` has
+/// a value for a group without any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DMLAggregate {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl DMLAggregate {
+    const ALL: [DMLAggregate; 5] = [
+        DMLAggregate::Count,
+        DMLAggregate::Sum,
+        DMLAggregate::Min,
+        DMLAggregate::Max,
+        DMLAggregate::Avg,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DMLAggregate::Count => "count",
+            DMLAggregate::Sum => "sum",
+            DMLAggregate::Min => "min",
+            DMLAggregate::Max => "max",
+            DMLAggregate::Avg => "avg",
+        }
+    }
+}
+
+/// An `unnest(<column>) [as] <alias>` item of a from clause. Every row is
+/// repeated for each element of its array, which is the `alias` column of
+/// the repeated row. Rows with empty or NULL arrays are left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DMLUnnest {
+    pub column: String,
+    pub alias: String,
+}
+
+/// A `join <table> on <column> = <column>` clause. Columns may be qualified
+/// with their table, as in `Employee.EmployeeID`.
+#[derive(Debug, Clone)]
+pub struct DMLJoin {
+    pub table_name: String,
+    pub left_column: String,
+    pub right_column: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DMLExplainStatement {
+    /// Execute the statement and report actual row counts and timings.
+    pub analyze: bool,
+    pub statement: DMLSelectStatement,
+}
+
+/// A `declare <name> cursor for <select>` statement.
+#[derive(Debug, Clone)]
+pub struct DMLDeclareStatement {
+    pub name: String,
+    pub statement: DMLSelectStatement,
+}
+
+/// A `fetch [<count> | all] [from] <name>` statement.
+#[derive(Debug, Clone)]
+pub struct DMLFetchStatement {
+    pub name: String,
+    /// Rows to fetch, or `None` for all remaining rows.
+    pub count: Option<usize>,
+}
+
+impl DMLSelectStatement {
+    /// The selected table followed by the joined ones.
+    pub fn table_names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.table_name.as_str())
+            .chain(self.joins.iter().map(|join| join.table_name.as_str()))
+    }
+
+    /// The unnest whose column `column` names, if it is one.
+    pub fn unnest_of(&self, column: &str) -> Option<&DMLUnnest> {
+        self.unnests.iter().find(|unnest| unnest.alias == column)
+    }
+
+    /// Whether the select returns a row per group of rows rather than one
+    /// per row, having aggregates or a group by clause.
+    pub fn is_grouped(&self) -> bool {
+        !self.group_by.is_empty() || self.columns.iter().any(|column| column.aggregate.is_some())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DMLOperator {
+    Equals,
+    NotEquals,
+    LessThan,
+    LessThanOrEquals,
+    GreaterThan,
+    GreaterThanOrEquals,
+    /// `@>`: an array holds every element of another.
+    Contains,
+    /// `<@`: every element of an array is in another.
+    ContainedBy,
+    /// `&&`: two arrays have an element in common.
+    Overlaps,
+    /// `@?`: a JSON path finds something in a JSON value.
+    PathExists,
+    /// `@@`: a JSON path predicate holds for a JSON value.
+    PathMatches,
+}
+
+impl DMLOperator {
+    /// Whether the operator holds for two values that compare as `ordering`.
+    /// Array and path operators do not compare values and never hold.
+    pub fn matches(&self, ordering: std::cmp::Ordering) -> bool {
+        use std::cmp::Ordering::*;
+        match self {
+            DMLOperator::Equals => ordering == Equal,
+            DMLOperator::NotEquals => ordering != Equal,
+            DMLOperator::LessThan => ordering == Less,
+            DMLOperator::LessThanOrEquals => ordering != Greater,
+            DMLOperator::GreaterThan => ordering == Greater,
+            DMLOperator::GreaterThanOrEquals => ordering != Less,
+            DMLOperator::Contains
+            | DMLOperator::ContainedBy
+            | DMLOperator::Overlaps
+            | DMLOperator::PathExists
+            | DMLOperator::PathMatches => false,
+        }
+    }
+
+    /// Whether the operator compares two values by their order.
+    pub fn is_comparison(&self) -> bool {
+        !self.is_array_operator() && !self.is_path_operator()
+    }
+
+    /// Whether the operator compares arrays as sets of elements.
+    pub fn is_array_operator(&self) -> bool {
+        matches!(
+            self,
+            DMLOperator::Contains | DMLOperator::ContainedBy | DMLOperator::Overlaps
+        )
+    }
+
+    /// Whether the operator applies a JSON path to a JSON value.
+    pub fn is_path_operator(&self) -> bool {
+        matches!(self, DMLOperator::PathExists | DMLOperator::PathMatches)
+    }
+}
+
+impl fmt::Display for DMLOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            DMLOperator::Equals => "=",
+            DMLOperator::NotEquals => "<>",
+            DMLOperator::LessThan => "<",
+            DMLOperator::LessThanOrEquals => "<=",
+            DMLOperator::GreaterThan => ">",
+            DMLOperator::GreaterThanOrEquals => ">=",
+            DMLOperator::Contains => "@>",
+            DMLOperator::ContainedBy => "<@",
+            DMLOperator::Overlaps => "&&",
+            DMLOperator::PathExists => "@?",
+            DMLOperator::PathMatches => "@@",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// A value in a statement: a literal, or a `$n` parameter that is bound to
+/// the n-th of the parameters sent along with the statement. Inserts and
+/// updates may also write the `nextval('name')` of a sequence.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DMLValue {
+    Literal(String),
+    Parameter(usize),
+    NextValue(String),
+}
+
+impl DMLValue {
+    /// The literal value. Statements are bound and their sequences advanced
+    /// before they run, so executing one never comes across anything else.
+    pub fn literal(&self) -> &str {
+        match self {
+            DMLValue::Literal(value) => value,
+            DMLValue::Parameter(number) => panic!("Parameter ${} is not bound", number),
+            DMLValue::NextValue(name) => panic!("nextval('{}') is not evaluated", name),
+        }
+    }
+
+    /// Replaces a parameter by its value in `parameters`.
+    pub fn bind(&mut self, parameters: &[String]) {
+        if let DMLValue::Parameter(number) = self {
+            *self = DMLValue::Literal(parameters[*number - 1].clone());
+        }
+    }
+}
+
+impl fmt::Display for DMLValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DMLValue::Literal(value) => write!(f, "'{}'", value.replace('\'', "''")),
+            DMLValue::Parameter(number) => write!(f, "${}", number),
+            DMLValue::NextValue(name) => write!(f, "nextval('{}')", name),
+        }
+    }
+}
+
+/// What a predicate or select list item reads of its column.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DMLTarget {
+    /// The value of the column.
+    #[default]
+    Value,
+    /// Any element of an array column, as in `'rust' = ANY(Tags)`.
+    AnyElement,
+    /// The length of an array column, as in `array_length(Tags, 1)`.
+    Length,
+    /// What `->` and `->>` read out of a Json column, as in `Doc->>'sku'`.
+    Path(JsonAccess),
+}
+
+/// A `column <operator> value` comparison in a where clause, or one of
+/// `value <operator> ANY(column)` and `array_length(column, 1) <operator>
+/// value` on an array column. The column of a comparison may be followed by
+/// `->` and `->>` to compare what they read out of a Json column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DMLPredicate {
+    pub column: String,
+    pub target: DMLTarget,
+    pub operator: DMLOperator,
+    pub value: DMLValue,
+}
+
+impl DMLPredicate {
+    /// The type of the value of the predicate, given that of its column.
+    pub fn value_type(&self, column_type: BasicType) -> BasicType {
+        if self.operator.is_path_operator() {
+            return BasicType::String;
+        }
+        match (&self.target, column_type) {
+            (DMLTarget::AnyElement, BasicType::Array(element_type)) => *element_type,
+            (DMLTarget::Length, _) => BasicType::Int,
+            (DMLTarget::Path(access), _) => access.value_type(),
+            (_, column_type) => column_type,
+        }
+    }
+}
+
+impl fmt::Display for DMLPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.target {
+            DMLTarget::Value => write!(f, "{} {} {}", self.column, self.operator, self.value),
+            DMLTarget::Path(access) => write!(
+                f,
+                "{}{} {} {}",
+                self.column, access, self.operator, self.value
+            ),
+            DMLTarget::AnyElement => {
+                write!(f, "{} {} ANY({})", self.value, self.operator, self.column)
+            }
+            DMLTarget::Length => write!(
+                f,
+                "array_length({}, 1) {} {}",
+                self.column, self.operator, self.value
+            ),
+        }
+    }
+}
+
+impl DMLStatement {
+    /// Whether the statement leaves the rows of every table as they are, so
+    /// it may run alongside other statements.
+    pub(crate) fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            DMLStatement::Select(_)
+                | DMLStatement::Explain(_)
+                | DMLStatement::Declare(_)
+                | DMLStatement::Fetch(_)
+                | DMLStatement::Close(_)
+                | DMLStatement::Commit
+        )
+    }
+
+    /// Every value in the statement.
+    pub(crate) fn values(&self) -> Vec<&DMLValue> {
+        match self {
+            DMLStatement::Insert(insert) => insert.values.iter().collect(),
+            DMLStatement::Update(update) => update
+                .assignments
+                .iter()
+                .map(|(_, value)| value)
+                .chain(update.predicates.iter().map(|p| &p.value))
+                .collect(),
+            DMLStatement::Delete(delete) => delete.predicates.iter().map(|p| &p.value).collect(),
+            DMLStatement::Select(select)
+            | DMLStatement::Explain(DMLExplainStatement {
+                statement: select, ..
+            })
+            | DMLStatement::Declare(DMLDeclareStatement {
+                statement: select, ..
+            })
+            | DMLStatement::CreateView(DMLCreateViewStatement {
+                statement: select, ..
+            }) => select.predicates.iter().map(|p| &p.value).collect(),
+            DMLStatement::Fetch(_)
+            | DMLStatement::Close(_)
+            | DMLStatement::Commit
+            | DMLStatement::CreateSequence(_)
+            | DMLStatement::NextValue(_)
+            | DMLStatement::RefreshView(_)
+            | DMLStatement::CreateTrigger(_) => Vec::new(),
+        }
+    }
+
+    fn values_mut(&mut self) -> Vec<&mut DMLValue> {
+        match self {
+            DMLStatement::Insert(insert) => insert.values.iter_mut().collect(),
+            DMLStatement::Update(update) => update
+                .assignments
+                .iter_mut()
+                .map(|(_, value)| value)
+                .chain(update.predicates.iter_mut().map(|p| &mut p.value))
+                .collect(),
+            DMLStatement::Delete(delete) => {
+                delete.predicates.iter_mut().map(|p| &mut p.value).collect()
+            }
+            DMLStatement::Select(select)
+            | DMLStatement::Explain(DMLExplainStatement {
+                statement: select, ..
+            })
+            | DMLStatement::Declare(DMLDeclareStatement {
+                statement: select, ..
+            })
+            | DMLStatement::CreateView(DMLCreateViewStatement {
+                statement: select, ..
+            }) => select.predicates.iter_mut().map(|p| &mut p.value).collect(),
+            DMLStatement::Fetch(_)
+            | DMLStatement::Close(_)
+            | DMLStatement::Commit
+            | DMLStatement::CreateSequence(_)
+            | DMLStatement::NextValue(_)
+            | DMLStatement::RefreshView(_)
+            | DMLStatement::CreateTrigger(_) => Vec::new(),
+        }
+    }
+
+    /// Number of parameters the statement takes, the highest `$n` in it.
+    pub fn parameter_count(&self) -> usize {
+        self.values()
+            .into_iter()
+            .filter_map(|value| match value {
+                DMLValue::Parameter(number) => Some(*number),
+                DMLValue::Literal(_) | DMLValue::NextValue(_) => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Replaces every parameter by its value in `parameters`, which must hold
+    /// at least `parameter_count` values.
+    pub fn bind(&mut self, parameters: &[String]) {
+        for value in self.values_mut() {
+            value.bind(parameters);
+        }
+    }
 }
 
 struct DMLParser {
     lexer: DMLLexer,
     current_token: Option<DMLToken>,
     peek_token: Option<DMLToken>,
-    errors: Vec<String>,
 }
 
 impl DMLParser {
@@ -177,7 +865,6 @@ impl DMLParser {
             lexer,
             current_token,
             peek_token,
-            errors: Vec::new(),
         }
     }
 
@@ -186,25 +873,123 @@ impl DMLParser {
         self.peek_token = self.lexer.next_token();
     }
 
+    fn current_token_type(&self) -> Option<DMLTokenType> {
+        self.current_token.clone().map(|token| token.token_type)
+    }
+
+    /// Reports a statement that does not parse at the token where parsing
+    /// stopped.
+    fn syntax_error(&self, query: &str) -> Error {
+        let Some(token) = &self.current_token else {
+            let end = query.chars().count();
+            let error = Error::new(ErrorCode::SyntaxError, "Unexpected end of statement")
+                .with_span(Span { start: end, end });
+            return if query.trim_end().ends_with(';') {
+                error
+            } else {
+                error.with_hint("Statements end with ;")
+            };
+        };
+        let text: String = query
+            .chars()
+            .skip(token.span.start)
+            .take(token.span.end - token.span.start)
+            .collect();
+        let error = Error::new(
+            ErrorCode::SyntaxError,
+            format!("Syntax error at or near \"{}\"", text),
+        )
+        .with_span(token.span);
+        let is_first = query.chars().take(token.span.start).all(char::is_whitespace);
+        if is_first {
+            error.with_hint(
+                "Statements start with select, insert, update, delete, explain, declare, fetch, close, commit, create or refresh",
+            )
+        } else {
+            error
+        }
+    }
+
     fn parse(&mut self) -> Option<DMLStatement> {
-        match self.current_token.clone() {
-            Some(token) => match token.token_type {
-                DMLTokenType::Insert => match self.parse_insert_statement() {
-                    Some(insert_statement) => {
-                        return Some(DMLStatement::Insert(insert_statement));
-                    }
-                    None => {}
-                },
-                DMLTokenType::Commit => {
+        match self.current_token_type()? {
+            DMLTokenType::Insert => {
+                if let Some(insert_statement) = self.parse_insert_statement() {
+                    return Some(DMLStatement::Insert(insert_statement));
+                }
+            }
+            DMLTokenType::Update => {
+                if let Some(update_statement) = self.parse_update_statement() {
+                    return Some(DMLStatement::Update(update_statement));
+                }
+            }
+            DMLTokenType::Delete => {
+                if let Some(delete_statement) = self.parse_delete_statement() {
+                    return Some(DMLStatement::Delete(delete_statement));
+                }
+            }
+            DMLTokenType::Select => {
+                if matches!(&self.peek_token, Some(token) if token.token_type == DMLTokenType::Identifier("nextval".to_string()))
+                {
+                    self.next_token();
+                    let DMLValue::NextValue(name) = self.parse_next_value()? else {
+                        return None;
+                    };
+                    self.parse_end_of_statement()?;
+                    return Some(DMLStatement::NextValue(name));
+                }
+                if let Some(select_statement) = self.parse_select_statement() {
+                    return Some(DMLStatement::Select(select_statement));
+                }
+            }
+            DMLTokenType::Explain => {
+                if let Some(explain_statement) = self.parse_explain_statement() {
+                    return Some(DMLStatement::Explain(explain_statement));
+                }
+            }
+            DMLTokenType::Declare => {
+                if let Some(declare_statement) = self.parse_declare_statement() {
+                    return Some(DMLStatement::Declare(declare_statement));
+                }
+            }
+            DMLTokenType::Fetch => {
+                if let Some(fetch_statement) = self.parse_fetch_statement() {
+                    return Some(DMLStatement::Fetch(fetch_statement));
+                }
+            }
+            DMLTokenType::Close => {
+                self.next_token();
+                if let Some(DMLTokenType::Identifier(name)) = self.current_token_type() {
+                    self.next_token();
+                    self.parse_end_of_statement()?;
+                    return Some(DMLStatement::Close(name));
+                }
+            }
+            DMLTokenType::Commit => {
+                self.next_token();
+                if self.current_token_type() == Some(DMLTokenType::SemiColon) {
                     self.next_token();
-                    if self.current_token.clone().unwrap().token_type == DMLTokenType::SemiColon {
-                        self.next_token();
-                        return Some(DMLStatement::Commit);
+                    return Some(DMLStatement::Commit);
+                }
+            }
+            DMLTokenType::Create => {
+                if self.is_word_ahead("sequence") {
+                    if let Some(create_statement) = self.parse_create_sequence_statement() {
+                        return Some(DMLStatement::CreateSequence(create_statement));
                     }
+                } else if self.is_word_ahead("trigger") {
+                    if let Some(create_statement) = self.parse_create_trigger_statement() {
+                        return Some(DMLStatement::CreateTrigger(create_statement));
+                    }
+                } else if let Some(create_statement) = self.parse_create_view_statement() {
+                    return Some(DMLStatement::CreateView(create_statement));
                 }
-                _ => {}
-            },
-            None => {}
+            }
+            DMLTokenType::Identifier(word) if word.eq_ignore_ascii_case("refresh") => {
+                if let Some(name) = self.parse_refresh_statement() {
+                    return Some(DMLStatement::RefreshView(name));
+                }
+            }
+            _ => {}
         }
 
         None
@@ -219,88 +1004,106 @@ impl DMLParser {
         self.next_token();
 
         // into
-        if self.current_token.clone().unwrap().token_type != DMLTokenType::Into {
+        if self.current_token_type()? != DMLTokenType::Into {
             return None;
         }
         self.next_token();
 
         // table name
-        match self.current_token.clone() {
-            Some(token) => match token.token_type {
-                DMLTokenType::Identifier(value) => {
-                    table_name = value;
-                }
-                _ => {}
-            },
-            None => {}
+        if let Some(DMLTokenType::Identifier(value)) = self.current_token_type() {
+            table_name = value;
         }
 
         // columns
         self.next_token();
 
-        match self.current_token.clone() {
-            Some(token) => match token.token_type {
-                DMLTokenType::OpenParenthesis => {
-                    self.next_token();
-                    while let Some(token) = self.current_token.clone() {
-                        match token.token_type {
-                            DMLTokenType::Identifier(value) => {
-                                columns.push(value);
-                            }
-                            DMLTokenType::Comma => {}
-                            DMLTokenType::CloseParenthesis => {
-                                break;
-                            }
-                            _ => {}
+        let mut column_spans = Vec::new();
+        if self.current_token_type() == Some(DMLTokenType::OpenParenthesis) {
+            self.next_token();
+            while let Some(token_type) = self.current_token_type() {
+                match token_type {
+                    DMLTokenType::Identifier(value) => {
+                        if columns.contains(&value) {
+                            return self.fail(
+                                ErrorCode::DuplicateColumn,
+                                format!("Column {} is specified more than once", value),
+                                "Name every column once",
+                            );
                         }
-                        self.next_token();
+                        columns.push(value);
+                        column_spans.push(self.current_token.as_ref()?.span);
+                    }
+                    DMLTokenType::Comma => {}
+                    DMLTokenType::CloseParenthesis => {
+                        break;
                     }
+                    _ => {}
                 }
-                _ => {}
-            },
-            None => {}
+                self.next_token();
+            }
         }
 
         // values
         self.next_token();
 
-        match self.current_token.clone() {
-            Some(token) => match token.token_type {
-                DMLTokenType::Values => {
-                    self.next_token();
-                    match self.current_token.clone() {
-                        Some(token) => match token.token_type {
-                            DMLTokenType::OpenParenthesis => {
-                                self.next_token();
-                                while let Some(token) = self.current_token.clone() {
-                                    match token.token_type {
-                                        DMLTokenType::String(value) => {
-                                            values.push(value);
-                                        }
-                                        DMLTokenType::Comma => {}
-                                        DMLTokenType::CloseParenthesis => {
-                                            break;
-                                        }
-                                        _ => {}
-                                    }
-                                    self.next_token();
-                                }
-                            }
-                            _ => {}
-                        },
-                        None => {}
+        let mut value_spans = Vec::new();
+        if self.current_token_type() == Some(DMLTokenType::Values) {
+            self.next_token();
+            if self.current_token_type() == Some(DMLTokenType::OpenParenthesis) {
+                self.next_token();
+                while let Some(token_type) = self.current_token_type() {
+                    let span = self.current_token.as_ref()?.span;
+                    match token_type {
+                        DMLTokenType::String(value) => {
+                            values.push(DMLValue::Literal(value));
+                        }
+                        DMLTokenType::Number(value) => {
+                            values.push(DMLValue::Literal(value.to_string()));
+                        }
+                        DMLTokenType::Parameter(number) => {
+                            values.push(DMLValue::Parameter(number));
+                        }
+                        DMLTokenType::Identifier(_) if self.is_function("nextval") => {
+                            values.push(self.parse_next_value()?);
+                            value_spans.push(span);
+                            continue;
+                        }
+                        DMLTokenType::Comma => {}
+                        DMLTokenType::CloseParenthesis => {
+                            break;
+                        }
+                        _ => {}
                     }
+                    if value_spans.len() < values.len() {
+                        value_spans.push(span);
+                    }
+                    self.next_token();
                 }
-                _ => {}
-            },
-            None => {}
+            }
+        }
+
+        if let Some(span) = value_spans.get(columns.len()) {
+            return self.fail_at(
+                *span,
+                ErrorCode::SyntaxError,
+                "INSERT has more values than columns".to_string(),
+                "Name a column for every value",
+            );
+        }
+        if let Some(span) = column_spans.get(values.len()) {
+            return self.fail_at(
+                *span,
+                ErrorCode::SyntaxError,
+                "INSERT has more columns than values".to_string(),
+                "Give a value for every column",
+            );
         }
 
         // )
         self.next_token();
 
         // ;
-        if self.current_token.clone().unwrap().token_type != DMLTokenType::SemiColon {
+        if self.current_token_type()? != DMLTokenType::SemiColon {
             return None;
         }
 
@@ -312,131 +1115,1658 @@ impl DMLParser {
             values,
         })
     }
-}
 
-pub fn parse(query: &str) -> Option<DMLStatement> {
-    let mut parser = DMLParser::new(query.to_string());
-    parser.parse()
-}
+    fn parse_select_statement(&mut self) -> Option<DMLSelectStatement> {
+        let mut columns = Vec::new();
 
-// Analyzer
-// - Check if the table exists
-// - Check if the columns exist
-// - Check if the values are of the correct type
-// - Check if the values are not null if the column is not nullable
-// - Check if the primary key is unique
-// - Check if the foreign key exists
-// - Check if the foreign key is not null
+        // select
+        self.next_token();
+
+        // * or column list
+        if self.current_token_type()? == DMLTokenType::Star {
+            self.next_token();
+        } else {
+            loop {
+                if let Some(aggregate) = DMLAggregate::ALL
+                    .into_iter()
+                    .find(|aggregate| self.is_function(aggregate.name()))
+                {
+                    columns.push(self.parse_aggregate(aggregate)?);
+                } else if self.is_function("array_length") {
+                    columns.push(DMLColumn {
+                        name: self.parse_array_length()?,
+                        target: DMLTarget::Length,
+                        alias: None,
+                        aggregate: None,
+                    });
+                } else {
+                    let name = match self.current_token_type()? {
+                        DMLTokenType::Identifier(value) => value,
+                        _ => return None,
+                    };
+                    self.next_token();
+                    let target = self.parse_path()?;
+                    columns.push(DMLColumn {
+                        name,
+                        target,
+                        alias: None,
+                        aggregate: None,
+                    });
+                }
+                if matches!(self.current_token_type()?, DMLTokenType::Identifier(word) if word.eq_ignore_ascii_case("as"))
+                {
+                    self.next_token();
+                    let alias = match self.current_token_type()? {
+                        DMLTokenType::Identifier(value) => value,
+                        _ => return None,
+                    };
+                    self.next_token();
+                    columns.last_mut()?.alias = Some(alias);
+                }
+                if self.current_token_type() != Some(DMLTokenType::Comma) {
+                    break;
+                }
+                self.next_token();
+            }
+        }
+
+        // from
+        if self.current_token_type()? != DMLTokenType::From {
+            return None;
+        }
+        self.next_token();
+
+        // table name
+        let table_name = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+        let as_of = self.parse_as_of()?;
+        let system_time = match as_of {
+            Some(_) => None,
+            None => self.parse_system_time()?,
+        };
+
+        let mut joins = Vec::new();
+        while self.current_token_type() == Some(DMLTokenType::Join) {
+            joins.push(self.parse_join()?);
+        }
+
+        let mut unnests = Vec::new();
+        while self.current_token_type() == Some(DMLTokenType::Comma) {
+            self.next_token();
+            unnests.push(self.parse_unnest()?);
+        }
+
+        let predicates = self.parse_where_clause()?;
+        let group_by = self.parse_group_by()?;
+        self.parse_end_of_statement()?;
+
+        Some(DMLSelectStatement {
+            columns,
+            table_name,
+            joins,
+            unnests,
+            predicates,
+            as_of,
+            system_time,
+            group_by,
+        })
+    }
+
+    /// Parses an `as of timestamp '<timestamp>'` or `as of transaction <id>`
+    /// clause, if there is one. Returns None on a syntax error.
+    fn parse_as_of(&mut self) -> Option<Option<DMLAsOf>> {
+        let is_word = |token_type: &DMLTokenType, word: &str| {
+            matches!(token_type, DMLTokenType::Identifier(value) if value.eq_ignore_ascii_case(word))
+        };
+        match self.current_token_type() {
+            Some(token_type) if is_word(&token_type, "as") && self.is_word_ahead("of") => {}
+            _ => return Some(None),
+        }
+        // as of
+        self.next_token();
+        self.next_token();
+
+        let kind = self.current_token_type()?;
+        self.next_token();
+        let as_of = match self.current_token_type()? {
+            DMLTokenType::String(timestamp) if is_word(&kind, "timestamp") => {
+                DMLAsOf::Timestamp(timestamp)
+            }
+            DMLTokenType::Number(transaction)
+                if is_word(&kind, "transaction")
+                    && transaction.fract() == 0.0
+                    && transaction >= 0.0 =>
+            {
+                DMLAsOf::Transaction(transaction as u64)
+            }
+            _ => return None,
+        };
+        self.next_token();
+        Some(Some(as_of))
+    }
+
+    /// Parses a `for system_time between '<timestamp>' and '<timestamp>'`
+    /// clause, if there is one. Returns None on a syntax error.
+    fn parse_system_time(&mut self) -> Option<Option<DMLSystemTime>> {
+        if self.current_token_type() != Some(DMLTokenType::For)
+            || !self.is_word_ahead("system_time")
+        {
+            return Some(None);
+        }
+        // for system_time between
+        self.next_token();
+        self.next_token();
+        if !matches!(self.current_token_type()?, DMLTokenType::Identifier(word) if word.eq_ignore_ascii_case("between"))
+        {
+            return None;
+        }
+        self.next_token();
+
+        let DMLTokenType::String(from) = self.current_token_type()? else {
+            return None;
+        };
+        self.next_token();
+        if self.current_token_type()? != DMLTokenType::And {
+            return None;
+        }
+        self.next_token();
+        let DMLTokenType::String(to) = self.current_token_type()? else {
+            return None;
+        };
+        self.next_token();
+        Some(Some(DMLSystemTime { from, to }))
+    }
+
+    /// Whether the current token calls the function `name`, as in
+    /// `unnest(`. Function names are not keywords, so columns may have them.
+    /// Stops parsing with an error at the current token, for input that
+    /// parses but cannot be run.
+    fn fail<T>(&mut self, code: ErrorCode, message: String, hint: &str) -> Option<T> {
+        let span = self.current_token.as_ref()?.span;
+        self.fail_at(span, code, message, hint)
+    }
+
+    /// Stops parsing with an error at `span`.
+    fn fail_at<T>(
+        &mut self,
+        span: Span,
+        code: ErrorCode,
+        message: String,
+        hint: &str,
+    ) -> Option<T> {
+        self.lexer.error = Some(Error::new(code, message).with_hint(hint).with_span(span));
+        None
+    }
+
+    fn is_function(&self, name: &str) -> bool {
+        let is_name = matches!(
+            self.current_token_type(),
+            Some(DMLTokenType::Identifier(value)) if value.eq_ignore_ascii_case(name)
+        );
+        let is_call = matches!(
+            &self.peek_token,
+            Some(token) if token.token_type == DMLTokenType::OpenParenthesis
+        );
+        is_name && is_call
+    }
+
+    /// Parses the `->` and `->>` steps following a column, if any. Steps are
+    /// keys or array positions, as in `Doc->'items'->0->>'sku'`, and only
+    /// the last one may read text.
+    fn parse_path(&mut self) -> Option<DMLTarget> {
+        let mut access = JsonAccess {
+            steps: Vec::new(),
+            as_text: false,
+        };
+        while let Some(arrow @ (DMLTokenType::Arrow | DMLTokenType::TextArrow)) =
+            self.current_token_type()
+        {
+            self.next_token();
+            let step = match self.current_token_type()? {
+                DMLTokenType::String(key) => JsonStep::Key(key),
+                DMLTokenType::Number(position) if position.fract() == 0.0 => {
+                    JsonStep::Index(position as i64)
+                }
+                _ => return None,
+            };
+            self.next_token();
+            access.steps.push(step);
+            if arrow == DMLTokenType::TextArrow {
+                access.as_text = true;
+                break;
+            }
+        }
+        if access.steps.is_empty() {
+            Some(DMLTarget::Value)
+        } else {
+            Some(DMLTarget::Path(access))
+        }
+    }
+
+    /// Parses a parenthesized column name, as the `(Tags)` of `ANY(Tags)`.
+    fn parse_column_argument(&mut self) -> Option<String> {
+        if self.current_token_type()? != DMLTokenType::OpenParenthesis {
+            return None;
+        }
+        self.next_token();
+        let column = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+        if self.current_token_type()? != DMLTokenType::CloseParenthesis {
+            return None;
+        }
+        self.next_token();
+        Some(column)
+    }
+
+    /// Parses `<aggregate>(<column>)`, or `count(*)`.
+    fn parse_aggregate(&mut self, aggregate: DMLAggregate) -> Option<DMLColumn> {
+        // <aggregate> (
+        self.next_token();
+        if self.current_token_type()? != DMLTokenType::OpenParenthesis {
+            return None;
+        }
+        self.next_token();
+        let name = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            DMLTokenType::Star if aggregate == DMLAggregate::Count => "*".to_string(),
+            _ => return None,
+        };
+        self.next_token();
+        if self.current_token_type()? != DMLTokenType::CloseParenthesis {
+            return None;
+        }
+        self.next_token();
+        Some(DMLColumn {
+            name,
+            target: DMLTarget::Value,
+            alias: None,
+            aggregate: Some(aggregate),
+        })
+    }
+
+    /// Parses `array_length(<column>, 1)` and returns the column. Arrays
+    /// have a single dimension.
+    fn parse_array_length(&mut self) -> Option<String> {
+        // array_length (
+        self.next_token();
+        if self.current_token_type()? != DMLTokenType::OpenParenthesis {
+            return None;
+        }
+        self.next_token();
+        let column = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+        if self.current_token_type()? != DMLTokenType::Comma {
+            return None;
+        }
+        self.next_token();
+        if self.current_token_type()? != DMLTokenType::Number(1.0) {
+            return None;
+        }
+        self.next_token();
+        if self.current_token_type()? != DMLTokenType::CloseParenthesis {
+            return None;
+        }
+        self.next_token();
+        Some(column)
+    }
+
+    /// Parses an `unnest(<column>) [as] <alias>` item of a from clause. The
+    /// alias defaults to `unnest`.
+    fn parse_unnest(&mut self) -> Option<DMLUnnest> {
+        if !self.is_function("unnest") {
+            return None;
+        }
+        self.next_token();
+        let column = self.parse_column_argument()?;
+
+        let mut alias = "unnest".to_string();
+        if let Some(DMLTokenType::Identifier(value)) = self.current_token_type() {
+            self.next_token();
+            alias = value;
+            if alias.eq_ignore_ascii_case("as") {
+                alias = match self.current_token_type()? {
+                    DMLTokenType::Identifier(value) => value,
+                    _ => return None,
+                };
+                self.next_token();
+            }
+        }
+        Some(DMLUnnest { column, alias })
+    }
+
+    fn parse_update_statement(&mut self) -> Option<DMLUpdateStatement> {
+        let mut assignments = Vec::new();
+
+        // update
+        self.next_token();
+
+        // table name
+        let table_name = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+
+        // set
+        if self.current_token_type()? != DMLTokenType::Set {
+            return None;
+        }
+        self.next_token();
+
+        loop {
+            let column = match self.current_token_type()? {
+                DMLTokenType::Identifier(value) => value,
+                _ => return None,
+            };
+            self.next_token();
+            if self.current_token_type()? != DMLTokenType::Operator(DMLOperator::Equals) {
+                return None;
+            }
+            self.next_token();
+            let value = if self.is_function("nextval") {
+                self.parse_next_value()?
+            } else {
+                self.parse_value()?
+            };
+            assignments.push((column, value));
+            if self.current_token_type() != Some(DMLTokenType::Comma) {
+                break;
+            }
+            self.next_token();
+        }
+
+        let predicates = self.parse_where_clause()?;
+        self.parse_end_of_statement()?;
+
+        Some(DMLUpdateStatement {
+            table_name,
+            assignments,
+            predicates,
+        })
+    }
+
+    fn parse_delete_statement(&mut self) -> Option<DMLDeleteStatement> {
+        // delete
+        self.next_token();
+
+        // from
+        if self.current_token_type()? != DMLTokenType::From {
+            return None;
+        }
+        self.next_token();
+
+        // table name
+        let table_name = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+
+        let predicates = self.parse_where_clause()?;
+        self.parse_end_of_statement()?;
+
+        Some(DMLDeleteStatement {
+            table_name,
+            predicates,
+        })
+    }
+
+    fn parse_join(&mut self) -> Option<DMLJoin> {
+        // join
+        self.next_token();
+
+        let table_name = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+
+        // on
+        if self.current_token_type()? != DMLTokenType::On {
+            return None;
+        }
+        self.next_token();
+
+        let left_column = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+
+        // Only equi-joins are supported.
+        if self.current_token_type()? != DMLTokenType::Operator(DMLOperator::Equals) {
+            return None;
+        }
+        self.next_token();
+
+        let right_column = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+
+        Some(DMLJoin {
+            table_name,
+            left_column,
+            right_column,
+        })
+    }
+
+    /// Parses an optional where clause into its conjunction of predicates.
+    fn parse_where_clause(&mut self) -> Option<Vec<DMLPredicate>> {
+        let mut predicates = Vec::new();
+        if self.current_token_type() == Some(DMLTokenType::Where) {
+            self.next_token();
+            loop {
+                predicates.push(self.parse_predicate()?);
+                if self.current_token_type() != Some(DMLTokenType::And) {
+                    break;
+                }
+                self.next_token();
+            }
+        }
+        Some(predicates)
+    }
+
+    /// Parses a `group by <column>, ...` clause, if there is one.
+    fn parse_group_by(&mut self) -> Option<Vec<String>> {
+        let mut columns = Vec::new();
+        let is_group = matches!(
+            self.current_token_type(),
+            Some(DMLTokenType::Identifier(word)) if word.eq_ignore_ascii_case("group")
+        );
+        if !is_group || !self.is_word_ahead("by") {
+            return Some(columns);
+        }
+        // group by
+        self.next_token();
+        self.next_token();
+        loop {
+            match self.current_token_type()? {
+                DMLTokenType::Identifier(column) => columns.push(column),
+                _ => return None,
+            }
+            self.next_token();
+            if self.current_token_type() != Some(DMLTokenType::Comma) {
+                break;
+            }
+            self.next_token();
+        }
+        Some(columns)
+    }
+
+    /// Consumes the `;` ending a statement, which is optional at the end of
+    /// the input.
+    fn parse_end_of_statement(&mut self) -> Option<()> {
+        match self.current_token_type() {
+            Some(DMLTokenType::SemiColon) => self.next_token(),
+            Some(_) => return None,
+            None => {}
+        }
+        Some(())
+    }
+
+    fn parse_predicate(&mut self) -> Option<DMLPredicate> {
+        // value <operator> ANY(column)
+        if let Some(value) = self.parse_value() {
+            let operator = match self.current_token_type()? {
+                DMLTokenType::Operator(operator) if operator.is_comparison() => operator,
+                _ => return None,
+            };
+            self.next_token();
+            if !self.is_function("any") {
+                return None;
+            }
+            self.next_token();
+            let column = self.parse_column_argument()?;
+            return Some(DMLPredicate {
+                column,
+                target: DMLTarget::AnyElement,
+                operator,
+                value,
+            });
+        }
+
+        let (column, target) = if self.is_function("array_length") {
+            (self.parse_array_length()?, DMLTarget::Length)
+        } else {
+            let column = match self.current_token_type()? {
+                DMLTokenType::Identifier(value) => value,
+                _ => return None,
+            };
+            self.next_token();
+            (column, self.parse_path()?)
+        };
+
+        let operator = match self.current_token_type()? {
+            DMLTokenType::Operator(operator) => operator,
+            _ => return None,
+        };
+        if target != DMLTarget::Value && !operator.is_comparison() {
+            return None;
+        }
+        self.next_token();
+
+        let value = self.parse_value()?;
+
+        Some(DMLPredicate {
+            column,
+            target,
+            operator,
+            value,
+        })
+    }
+
+    /// Parses a literal or parameter, if the current token is one.
+    fn parse_value(&mut self) -> Option<DMLValue> {
+        let value = match self.current_token_type()? {
+            DMLTokenType::String(value) => DMLValue::Literal(value),
+            DMLTokenType::Number(value) => DMLValue::Literal(value.to_string()),
+            DMLTokenType::Parameter(number) => DMLValue::Parameter(number),
+            _ => return None,
+        };
+        self.next_token();
+        Some(value)
+    }
+
+    /// Parses `nextval('<sequence>')`.
+    fn parse_next_value(&mut self) -> Option<DMLValue> {
+        // nextval (
+        self.next_token();
+        if self.current_token_type()? != DMLTokenType::OpenParenthesis {
+            return None;
+        }
+        self.next_token();
+        let name = match self.current_token_type()? {
+            DMLTokenType::String(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+        if self.current_token_type()? != DMLTokenType::CloseParenthesis {
+            return None;
+        }
+        self.next_token();
+        Some(DMLValue::NextValue(name))
+    }
+
+    fn parse_create_sequence_statement(&mut self) -> Option<DMLCreateSequenceStatement> {
+        let is_word = |token_type: &DMLTokenType, word: &str| {
+            matches!(token_type, DMLTokenType::Identifier(value) if value.eq_ignore_ascii_case(word))
+        };
+
+        // create sequence
+        self.next_token();
+        if !is_word(&self.current_token_type()?, "sequence") {
+            return None;
+        }
+        self.next_token();
+
+        let name = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+
+        let mut statement = DMLCreateSequenceStatement {
+            name,
+            start: 1,
+            increment: 1,
+        };
+        loop {
+            let token_type = self.current_token_type()?;
+            let (option, filler) = if is_word(&token_type, "start") {
+                (&mut statement.start, "with")
+            } else if is_word(&token_type, "increment") {
+                (&mut statement.increment, "by")
+            } else {
+                break;
+            };
+            self.next_token();
+            if is_word(&self.current_token_type()?, filler) {
+                self.next_token();
+            }
+            *option = match self.current_token_type()? {
+                DMLTokenType::Number(value) if value.fract() == 0.0 => value as i64,
+                _ => return None,
+            };
+            self.next_token();
+        }
+        self.parse_end_of_statement()?;
+
+        Some(statement)
+    }
+
+    /// Whether the token after the current one is the word `word`.
+    fn is_word_ahead(&self, word: &str) -> bool {
+        matches!(&self.peek_token, Some(DMLToken { token_type: DMLTokenType::Identifier(value), .. }) if value.eq_ignore_ascii_case(word))
+    }
+
+    fn parse_create_view_statement(&mut self) -> Option<DMLCreateViewStatement> {
+        let is_word = |token_type: &DMLTokenType, word: &str| {
+            matches!(token_type, DMLTokenType::Identifier(value) if value.eq_ignore_ascii_case(word))
+        };
+
+        // create [incremental] [materialized] view
+        self.next_token();
+        let mut materialized = None;
+        if is_word(&self.current_token_type()?, "incremental") {
+            materialized = Some(Refresh::Incremental);
+            self.next_token();
+            if !is_word(&self.current_token_type()?, "materialized") {
+                return None;
+            }
+        }
+        if is_word(&self.current_token_type()?, "materialized") {
+            materialized = materialized.or(Some(Refresh::Manual));
+            self.next_token();
+        }
+        if !is_word(&self.current_token_type()?, "view") {
+            return None;
+        }
+        self.next_token();
+
+        let name = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+
+        // as <select>
+        if !is_word(&self.current_token_type()?, "as") {
+            return None;
+        }
+        self.next_token();
+        let start = self.current_token.as_ref()?.span.start;
+        if self.current_token_type()? != DMLTokenType::Select {
+            return None;
+        }
+        let statement = self.parse_select_statement()?;
+        let query: String = self.lexer.input[start..].iter().collect();
+        let query = query.trim_end().trim_end_matches(';').trim_end().to_string();
+
+        Some(DMLCreateViewStatement {
+            name,
+            query,
+            materialized,
+            statement,
+        })
+    }
+
+    fn parse_create_trigger_statement(&mut self) -> Option<DMLCreateTriggerStatement> {
+        let is_word = |token_type: &DMLTokenType, word: &str| {
+            matches!(token_type, DMLTokenType::Identifier(value) if value.eq_ignore_ascii_case(word))
+        };
+
+        // create trigger
+        self.next_token();
+        self.next_token();
+        let name = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+
+        // before|after insert|update|delete on <table>
+        let token_type = self.current_token_type()?;
+        let timing = if is_word(&token_type, "before") {
+            TriggerTiming::Before
+        } else if is_word(&token_type, "after") {
+            TriggerTiming::After
+        } else {
+            return None;
+        };
+        self.next_token();
+        let event = match self.current_token_type()? {
+            DMLTokenType::Insert => TriggerEvent::Insert,
+            DMLTokenType::Update => TriggerEvent::Update,
+            DMLTokenType::Delete => TriggerEvent::Delete,
+            _ => return None,
+        };
+        self.next_token();
+        if self.current_token_type()? != DMLTokenType::On {
+            return None;
+        }
+        self.next_token();
+        let table_name = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+
+        // [for each row]
+        if self.current_token_type()? == DMLTokenType::For {
+            self.next_token();
+            for word in ["each", "row"] {
+                if !is_word(&self.current_token_type()?, word) {
+                    return None;
+                }
+                self.next_token();
+            }
+        }
+
+        // begin <statement>; ... end
+        if !is_word(&self.current_token_type()?, "begin") {
+            return None;
+        }
+        self.next_token();
+        let mut statements = Vec::new();
+        while !is_word(&self.current_token_type()?, "end") {
+            let start = self.current_token.as_ref()?.span.start;
+            while self.current_token_type()? != DMLTokenType::SemiColon {
+                self.next_token();
+            }
+            let end = self.current_token.as_ref()?.span.start;
+            let statement: String = self.lexer.input[start..end].iter().collect();
+            statements.push(statement.trim().to_string());
+            self.next_token();
+        }
+        self.next_token();
+        self.parse_end_of_statement()?;
+        if statements.is_empty() {
+            return None;
+        }
+
+        Some(DMLCreateTriggerStatement {
+            name,
+            table_name,
+            timing,
+            event,
+            statements,
+        })
+    }
+
+    fn parse_refresh_statement(&mut self) -> Option<String> {
+        let is_word = |token_type: &DMLTokenType, word: &str| {
+            matches!(token_type, DMLTokenType::Identifier(value) if value.eq_ignore_ascii_case(word))
+        };
+
+        // refresh materialized view
+        for word in ["materialized", "view"] {
+            self.next_token();
+            if !is_word(&self.current_token_type()?, word) {
+                return None;
+            }
+        }
+        self.next_token();
+
+        let name = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+        self.parse_end_of_statement()?;
+
+        Some(name)
+    }
+
+    fn parse_explain_statement(&mut self) -> Option<DMLExplainStatement> {
+        // explain
+        self.next_token();
+
+        // analyze
+        let analyze = self.current_token_type()? == DMLTokenType::Analyze;
+        if analyze {
+            self.next_token();
+        }
+
+        if self.current_token_type()? != DMLTokenType::Select {
+            return None;
+        }
+        let statement = self.parse_select_statement()?;
+
+        Some(DMLExplainStatement { analyze, statement })
+    }
+
+    fn parse_declare_statement(&mut self) -> Option<DMLDeclareStatement> {
+        // declare
+        self.next_token();
+
+        let name = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+
+        // cursor for
+        if self.current_token_type()? != DMLTokenType::Cursor {
+            return None;
+        }
+        self.next_token();
+        if self.current_token_type()? != DMLTokenType::For {
+            return None;
+        }
+        self.next_token();
+
+        if self.current_token_type()? != DMLTokenType::Select {
+            return None;
+        }
+        let statement = self.parse_select_statement()?;
+
+        Some(DMLDeclareStatement { name, statement })
+    }
+
+    fn parse_fetch_statement(&mut self) -> Option<DMLFetchStatement> {
+        // fetch
+        self.next_token();
+
+        // count, one row if left out
+        let count = match self.current_token_type()? {
+            DMLTokenType::Number(value) if value >= 0.0 && value.fract() == 0.0 => {
+                self.next_token();
+                Some(value as usize)
+            }
+            DMLTokenType::All => {
+                self.next_token();
+                None
+            }
+            _ => Some(1),
+        };
+
+        // from
+        if self.current_token_type()? == DMLTokenType::From {
+            self.next_token();
+        }
+
+        let name = match self.current_token_type()? {
+            DMLTokenType::Identifier(value) => value,
+            _ => return None,
+        };
+        self.next_token();
+        self.parse_end_of_statement()?;
+
+        Some(DMLFetchStatement { name, count })
+    }
+}
+
+/// Replaces the `old.<column>` and `new.<column>` references of a trigger
+/// statement by parameters. Returns the rewritten statement and the row and
+/// column every parameter stands for, `$1` first.
+pub fn row_references(statement: &str) -> Result<(String, Vec<(TriggerRow, String)>), Error> {
+    let chars: Vec<char> = statement.chars().collect();
+    let mut lexer = DMLLexer::new(statement.to_string());
+    let mut rewritten = String::new();
+    let mut position = 0;
+    let mut references = Vec::new();
+    while let Some(token) = lexer.next_token() {
+        let reference = match &token.token_type {
+            DMLTokenType::Parameter(_) => {
+                return Err(Error::new(ErrorCode::FeatureNotSupported, "Trigger statements cannot have parameters")
+                    .with_hint("Read the changed row with old.<column> and new.<column>"));
+            }
+            DMLTokenType::Identifier(word) => match word.split_once('.') {
+                Some((row, column)) if row.eq_ignore_ascii_case("old") => (TriggerRow::Old, column.to_string()),
+                Some((row, column)) if row.eq_ignore_ascii_case("new") => (TriggerRow::New, column.to_string()),
+                _ => continue,
+            },
+            _ => continue,
+        };
+        rewritten.extend(&chars[position..token.span.start]);
+        references.push(reference);
+        rewritten.push_str(&format!("${}", references.len()));
+        position = token.span.end;
+    }
+    if let Some(error) = lexer.error {
+        return Err(error);
+    }
+    rewritten.extend(&chars[position..]);
+    Ok((rewritten, references))
+}
+
+pub fn parse(query: &str) -> Result<DMLStatement, Error> {
+    let mut parser = DMLParser::new(query.to_string());
+    let statement = parser.parse();
+    if let Some(error) = parser.lexer.error {
+        return Err(error);
+    }
+    match statement {
+        Some(statement) => Ok(statement),
+        None => Err(parser.syntax_error(query)),
+    }
+}
+
+// Analyzer
+// - Check if the table exists
+// - Check if the columns exist
+// - Check if the values are of the correct type
+// - Check if the values are not null if the column is not nullable
+// - Check if the primary key is unique
+// - Check if the foreign key exists
+// - Check if the foreign key is not null
 // - Check if the foreign key is unique
 // - Check if the foreign key is of the correct type
 // - Check if the foreign key is not the primary key
 
-struct DMLAnalyzer {
+struct DMLAnalyzer<'a> {
+    db: &'a Database,
     statements: Vec<DMLStatement>,
 }
 
-impl DMLAnalyzer {
-    fn new(statements: Vec<DMLStatement>) -> DMLAnalyzer {
-        DMLAnalyzer { statements }
+impl DMLAnalyzer<'_> {
+    fn new(db: &Database, statements: Vec<DMLStatement>) -> DMLAnalyzer<'_> {
+        DMLAnalyzer { db, statements }
     }
 
-    async fn analyze(&self) -> Vec<String> {
+    async fn analyze(&self) -> Vec<Error> {
         let mut errors = Vec::new();
         errors.extend(self.check_table_exists().await);
         errors.extend(self.check_columns_exist().await);
+        errors.extend(self.check_system_versioning().await);
+        errors.extend(self.check_join_conditions().await);
+        errors.extend(self.check_grouping().await);
+        errors.extend(self.check_values_correct_type().await);
+        errors.extend(self.check_values_not_null().await);
+        errors.extend(self.check_sequences().await);
+        errors.extend(self.check_array_operations().await);
+        errors.extend(self.check_json_operations().await);
+        errors.extend(self.check_time_travel().await);
         errors
     }
 
-    async fn check_table_exists(&self) -> Vec<String> {
+    async fn check_table_exists(&self) -> Vec<Error> {
         let mut errors = Vec::new();
         for statement in self.statements.iter() {
-            match statement {
-                DMLStatement::Insert(insert_statement) => {
-                    let catalogue = crate::get_catalogue().lock().await;
-                    if !catalogue.table_exists(&insert_statement.table_name) {
-                        errors.push(format!(
-                            "Table {} does not exist",
-                            insert_statement.table_name
+            if let Some((table_names, _)) = referenced_columns(statement) {
+                let catalogue = self.db.catalogue.lock().await;
+                for table_name in table_names {
+                    if !catalogue.table_exists(table_name) {
+                        errors.push(Error::new(
+                            ErrorCode::UndefinedTable,
+                            format!("Table {} does not exist", table_name),
                         ));
                     }
-                }   
-                _ => {}
+                }
+            }
+        }
+        errors
+    }
+
+    async fn check_columns_exist(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for statement in self.statements.iter() {
+            if let Some((table_names, columns)) = referenced_columns(statement) {
+                let catalogue = self.db.catalogue.lock().await;
+                let Some(tables) = table_names
+                    .iter()
+                    .map(|table_name| catalogue.get_table(table_name))
+                    .collect::<Option<Vec<_>>>()
+                else {
+                    continue;
+                };
+                for column in columns {
+                    if let Err(error) = plan::resolve_column(column, &tables) {
+                        errors.push(error);
+                    }
+                }
+            }
+        }
+        errors
+    }
+
+    async fn check_join_conditions(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for statement in self.statements.iter() {
+            let (DMLStatement::Select(select)
+            | DMLStatement::Explain(DMLExplainStatement {
+                statement: select, ..
+            })
+            | DMLStatement::Declare(DMLDeclareStatement {
+                statement: select, ..
+            })) = statement
+            else {
+                continue;
+            };
+            let catalogue = self.db.catalogue.lock().await;
+            let Some(tables) = select
+                .table_names()
+                .map(|table_name| catalogue.get_table(table_name))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            for (position, join) in select.joins.iter().enumerate() {
+                let joined = position + 1;
+                let left = plan::resolve_column(&join.left_column, &tables[..=joined]);
+                let right = plan::resolve_column(&join.right_column, &tables[..=joined]);
+                let (Ok((left, _)), Ok((right, _))) = (left, right) else {
+                    // Reported by `check_columns_exist`.
+                    continue;
+                };
+                if (left == joined) == (right == joined) {
+                    errors.push(Error::new(
+                        ErrorCode::InvalidJoin,
+                        format!(
+                            "Join condition {} = {} must compare a column of {} with a column of an earlier table",
+                            join.left_column, join.right_column, join.table_name
+                        ),
+                    ));
+                }
             }
         }
         errors
     }
 
-    async fn check_columns_exist(&self) -> Vec<String> {
+    /// Checks that a grouped select only selects the columns it groups by,
+    /// besides its aggregates, and that it sums and averages numbers.
+    async fn check_grouping(&self) -> Vec<Error> {
         let mut errors = Vec::new();
         for statement in self.statements.iter() {
-            match statement {
-                DMLStatement::Insert(insert_statement) => {
-                    let catalogue = crate::get_catalogue().lock().await;
-                    let table = catalogue.get_table(&insert_statement.table_name).unwrap();
-                    for column in insert_statement.columns.iter() {
-                        if !table.column_exists(column) {
-                            errors.push(format!(
-                                "Column {} does not exist in table {}",
-                                column, insert_statement.table_name
-                            ));
+            let (DMLStatement::Select(select)
+            | DMLStatement::Explain(DMLExplainStatement {
+                statement: select, ..
+            })
+            | DMLStatement::Declare(DMLDeclareStatement {
+                statement: select, ..
+            })) = statement
+            else {
+                continue;
+            };
+            if !select.is_grouped() {
+                continue;
+            }
+            let catalogue = self.db.catalogue.lock().await;
+            let Some(tables) = select
+                .table_names()
+                .map(|table_name| catalogue.get_table(table_name))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            if select.columns.is_empty() {
+                errors.push(
+                    Error::new(ErrorCode::GroupingError, "A grouped select cannot select *")
+                        .with_hint("Select the columns of the group by clause and aggregates"),
+                );
+            }
+            for column in select.columns.iter() {
+                match column.aggregate {
+                    Some(aggregate @ (DMLAggregate::Sum | DMLAggregate::Avg)) => {
+                        let Some(data_type) = plan::column_type(&column.name, select, &tables)
+                        else {
+                            // Reported by `check_columns_exist`.
+                            continue;
+                        };
+                        if !matches!(
+                            data_type,
+                            BasicType::Int | BasicType::Float | BasicType::Decimal { .. }
+                        ) {
+                            errors.push(
+                                Error::new(
+                                    ErrorCode::DatatypeMismatch,
+                                    format!(
+                                        "{} needs a number, but column {} is {}",
+                                        aggregate.name(),
+                                        column.name,
+                                        data_type
+                                    ),
+                                )
+                                .with_hint("Sum and average Int, Float or Decimal columns"),
+                            );
+                        }
+                    }
+                    Some(_) => {}
+                    None => {
+                        let grouped = column.target == DMLTarget::Value
+                            && plan::group_position(&column.name, &select.group_by, &tables)
+                                .is_some();
+                        if !grouped {
+                            errors.push(
+                                Error::new(
+                                    ErrorCode::GroupingError,
+                                    format!(
+                                        "Column {} must appear in the group by clause or be used in an aggregate",
+                                        column.name
+                                    ),
+                                )
+                                .with_hint(format!("Add group by {}", column.name)),
+                            );
                         }
                     }
                 }
-                _ => {}
             }
         }
         errors
     }
 
-    // TODO: Implement check if values are of the correct type
-    async fn check_values_correct_type(&self) -> Vec<String> {
-        vec![]
+    /// Checks that the literal values an insert or update writes are
+    /// values of their columns' types. Parameters are checked when bound.
+    async fn check_values_correct_type(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for statement in self.statements.iter() {
+            let (table_name, values): (&str, Vec<(&str, &DMLValue)>) = match statement {
+                DMLStatement::Insert(insert) => (
+                    &insert.table_name,
+                    insert
+                        .columns
+                        .iter()
+                        .map(|column| column.as_str())
+                        .zip(insert.values.iter())
+                        .collect(),
+                ),
+                DMLStatement::Update(update) => (
+                    &update.table_name,
+                    update
+                        .assignments
+                        .iter()
+                        .map(|(column, value)| (column.as_str(), value))
+                        .collect(),
+                ),
+                _ => continue,
+            };
+            let catalogue = self.db.catalogue.lock().await;
+            let Some(table) = catalogue.get_table(table_name) else {
+                continue;
+            };
+            for (column, value) in values {
+                if let DMLValue::Literal(literal) = value {
+                    if let Err(error) = super::column_value(table, column, literal) {
+                        errors.push(error);
+                    }
+                }
+            }
+        }
+        errors
     }
 
-    // TODO: Implement check if values are not null if the column is not nullable
-    async fn check_values_not_null(&self) -> Vec<String> {
-        vec![]
+    /// Checks that an insert gives a value to every column that cannot be
+    /// NULL and has no default.
+    async fn check_values_not_null(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for statement in self.statements.iter() {
+            let DMLStatement::Insert(insert) = statement else {
+                continue;
+            };
+            let catalogue = self.db.catalogue.lock().await;
+            let Some(table) = catalogue.get_table(&insert.table_name) else {
+                continue;
+            };
+            errors.extend(super::not_null_violations(table, &insert.columns));
+        }
+        errors
+    }
+
+    /// Checks that the sequences `nextval` advances exist, and that those
+    /// `create sequence` creates do not.
+    async fn check_sequences(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for statement in self.statements.iter() {
+            let catalogue = self.db.catalogue.lock().await;
+            if let DMLStatement::CreateSequence(create) = statement {
+                if catalogue.sequence_exists(&create.name) {
+                    errors.push(super::duplicate_sequence(&create.name));
+                }
+                if create.increment == 0 {
+                    errors.push(Error::new(
+                        ErrorCode::NumericValueOutOfRange,
+                        format!("Increment of sequence {} must not be zero", create.name),
+                    ));
+                }
+                continue;
+            }
+            let mut names = Vec::new();
+            if let DMLStatement::NextValue(name) = statement {
+                names.push(name);
+            }
+            for value in statement.values() {
+                if let DMLValue::NextValue(name) = value {
+                    names.push(name);
+                }
+            }
+            for name in names {
+                if !catalogue.sequence_exists(name) {
+                    errors.push(super::undefined_sequence(name));
+                }
+            }
+        }
+        errors
+    }
+
+    /// Checks that the timestamps of `as of` and `for system_time` clauses
+    /// are timestamps, and that `for system_time` reads a system-versioned
+    /// table.
+    async fn check_time_travel(&self) -> Vec<Error> {
+        let invalid_timestamp = |clause: &str, timestamp: &str| {
+            Error::new(
+                ErrorCode::InvalidTextRepresentation,
+                format!("Invalid timestamp in {}: {}", clause, timestamp),
+            )
+            .with_hint("Write timestamps as 2024-01-31 12:30:00+02")
+        };
+        let mut errors = Vec::new();
+        for statement in self.statements.iter() {
+            let (DMLStatement::Select(select)
+            | DMLStatement::Explain(DMLExplainStatement {
+                statement: select, ..
+            })
+            | DMLStatement::Declare(DMLDeclareStatement {
+                statement: select, ..
+            })) = statement
+            else {
+                continue;
+            };
+            if let Some(DMLAsOf::Timestamp(timestamp)) = &select.as_of {
+                if basic_types::parse_timestamp(timestamp).is_none() {
+                    errors.push(invalid_timestamp("as of", timestamp));
+                }
+            }
+            let Some(system_time) = &select.system_time else {
+                continue;
+            };
+            for timestamp in [&system_time.from, &system_time.to] {
+                if basic_types::parse_timestamp(timestamp).is_none() {
+                    errors.push(invalid_timestamp("for system_time", timestamp));
+                }
+            }
+            let catalogue = self.db.catalogue.lock().await;
+            if let Some(table) = catalogue.get_table(&select.table_name) {
+                if table.history_table().is_none() {
+                    errors.push(
+                        Error::new(
+                            ErrorCode::WrongObjectType,
+                            format!("Table {} is not system-versioned", table.name()),
+                        )
+                        .with_hint("Declare its model with @@temporal to keep its history"),
+                    );
+                }
+            }
+        }
+        errors
+    }
+
+    /// Checks that writes leave history tables and the system columns of
+    /// system-versioned tables to the database.
+    async fn check_system_versioning(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for statement in self.statements.iter() {
+            let (table_name, columns): (&str, Vec<&str>) = match statement {
+                DMLStatement::Insert(insert) => (
+                    &insert.table_name,
+                    insert.columns.iter().map(String::as_str).collect(),
+                ),
+                DMLStatement::Update(update) => (
+                    &update.table_name,
+                    update
+                        .assignments
+                        .iter()
+                        .map(|(column, _)| column.as_str())
+                        .collect(),
+                ),
+                DMLStatement::Delete(delete) => (&delete.table_name, Vec::new()),
+                _ => continue,
+            };
+            let catalogue = self.db.catalogue.lock().await;
+            if let Some(table) = catalogue.history_of(table_name) {
+                errors.push(
+                    Error::new(
+                        ErrorCode::WrongObjectType,
+                        format!("Cannot write to history table {}", table_name),
+                    )
+                    .with_hint(format!(
+                        "Write to {}, which keeps its history",
+                        table.name()
+                    )),
+                );
+                continue;
+            }
+            let is_versioned = catalogue
+                .get_table(table_name)
+                .is_some_and(|table| table.history_table().is_some());
+            for column in columns {
+                if is_versioned && catalogue::is_system_column(column) {
+                    errors.push(
+                        Error::new(
+                            ErrorCode::GeneratedAlways,
+                            format!(
+                                "Column {} of table {} is set by the database",
+                                column, table_name
+                            ),
+                        )
+                        .with_hint(format!(
+                            "Leave {} and {} out of inserts and updates",
+                            catalogue::VALID_FROM,
+                            catalogue::VALID_TO
+                        )),
+                    );
+                }
+            }
+        }
+        errors
+    }
+
+    /// Checks that `ANY`, `array_length`, `unnest` and the array operators
+    /// are used on array columns, and that the literals they compare with
+    /// are values of the right type.
+    async fn check_array_operations(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for statement in self.statements.iter() {
+            let (select, table_names, predicates): (_, Vec<&str>, _) = match statement {
+                DMLStatement::Select(select)
+                | DMLStatement::Explain(DMLExplainStatement {
+                    statement: select, ..
+                })
+                | DMLStatement::Declare(DMLDeclareStatement {
+                    statement: select, ..
+                }) => (
+                    Some(select),
+                    select.table_names().collect(),
+                    &select.predicates,
+                ),
+                DMLStatement::Update(update) => {
+                    (None, vec![&update.table_name], &update.predicates)
+                }
+                DMLStatement::Delete(delete) => {
+                    (None, vec![&delete.table_name], &delete.predicates)
+                }
+                _ => continue,
+            };
+            let catalogue = self.db.catalogue.lock().await;
+            let Some(tables) = table_names
+                .iter()
+                .map(|table_name| catalogue.get_table(table_name))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            let column_type = |column: &str| match select {
+                Some(select) => plan::column_type(column, select, &tables),
+                None => tables[0]
+                    .get_column(column)
+                    .map(|column| column.data_type()),
+            };
+
+            let mut uses: Vec<(&str, String)> = Vec::new();
+            if let Some(select) = select {
+                for column in select.columns.iter() {
+                    if column.target == DMLTarget::Length {
+                        uses.push((&column.name, "array_length".to_string()));
+                    }
+                }
+                for unnest in select.unnests.iter() {
+                    uses.push((&unnest.column, "unnest".to_string()));
+                }
+            }
+            for predicate in predicates.iter() {
+                match predicate.target {
+                    DMLTarget::Value if predicate.operator.is_array_operator() => uses.push((
+                        &predicate.column,
+                        format!("Operator {}", predicate.operator),
+                    )),
+                    DMLTarget::Value | DMLTarget::Path(_) => {}
+                    DMLTarget::AnyElement => uses.push((&predicate.column, "ANY".to_string())),
+                    DMLTarget::Length => uses.push((&predicate.column, "array_length".to_string())),
+                }
+            }
+            for (column, operation) in uses {
+                // Unknown columns are reported by `check_columns_exist`.
+                let Some(data_type) = column_type(column) else {
+                    continue;
+                };
+                if data_type.element_type().is_none() {
+                    errors.push(
+                        Error::new(
+                            ErrorCode::DatatypeMismatch,
+                            format!(
+                                "{} needs an array, but column {} is {}",
+                                operation, column, data_type
+                            ),
+                        )
+                        .with_hint("Array fields are declared with [], as in Tags String[]"),
+                    );
+                }
+            }
+
+            for predicate in predicates.iter() {
+                let is_array_target =
+                    matches!(predicate.target, DMLTarget::AnyElement | DMLTarget::Length);
+                if !is_array_target && !predicate.operator.is_array_operator() {
+                    continue;
+                }
+                let DMLValue::Literal(literal) = &predicate.value else {
+                    continue;
+                };
+                let Some(data_type) = column_type(&predicate.column) else {
+                    continue;
+                };
+                if data_type.element_type().is_none() {
+                    continue;
+                }
+                let value_type = predicate.value_type(data_type);
+                if let Err(error) = super::typed_value(&value_type, &predicate.column, literal) {
+                    errors.push(error);
+                }
+            }
+        }
+        errors
+    }
+
+    /// Checks that `->`, `->>`, `@?` and `@@` are used on Json columns, and
+    /// that the JSON paths `@?` and `@@` apply are valid.
+    async fn check_json_operations(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for statement in self.statements.iter() {
+            let (select, table_names, predicates): (_, Vec<&str>, _) = match statement {
+                DMLStatement::Select(select)
+                | DMLStatement::Explain(DMLExplainStatement {
+                    statement: select, ..
+                })
+                | DMLStatement::Declare(DMLDeclareStatement {
+                    statement: select, ..
+                }) => (
+                    Some(select),
+                    select.table_names().collect(),
+                    &select.predicates,
+                ),
+                DMLStatement::Update(update) => {
+                    (None, vec![&update.table_name], &update.predicates)
+                }
+                DMLStatement::Delete(delete) => {
+                    (None, vec![&delete.table_name], &delete.predicates)
+                }
+                _ => continue,
+            };
+            let catalogue = self.db.catalogue.lock().await;
+            let Some(tables) = table_names
+                .iter()
+                .map(|table_name| catalogue.get_table(table_name))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            let column_type = |column: &str| match select {
+                Some(select) => plan::column_type(column, select, &tables),
+                None => tables[0]
+                    .get_column(column)
+                    .map(|column| column.data_type()),
+            };
+
+            let mut uses: Vec<(&str, String)> = Vec::new();
+            if let Some(select) = select {
+                for column in select.columns.iter() {
+                    if let DMLTarget::Path(access) = &column.target {
+                        uses.push((&column.name, format!("Operator {}", access)));
+                    }
+                }
+            }
+            for predicate in predicates.iter() {
+                if let DMLTarget::Path(access) = &predicate.target {
+                    uses.push((&predicate.column, format!("Operator {}", access)));
+                } else if predicate.operator.is_path_operator() {
+                    uses.push((
+                        &predicate.column,
+                        format!("Operator {}", predicate.operator),
+                    ));
+                }
+            }
+            for (column, operation) in uses {
+                // Unknown columns are reported by `check_columns_exist`.
+                let Some(data_type) = column_type(column) else {
+                    continue;
+                };
+                if data_type != BasicType::Json {
+                    errors.push(
+                        Error::new(
+                            ErrorCode::DatatypeMismatch,
+                            format!(
+                                "{} needs a Json value, but column {} is {}",
+                                operation, column, data_type
+                            ),
+                        )
+                        .with_hint("Json fields are declared as Doc Json"),
+                    );
+                }
+            }
+
+            for predicate in predicates.iter() {
+                if !predicate.operator.is_path_operator() {
+                    continue;
+                }
+                let DMLValue::Literal(literal) = &predicate.value else {
+                    continue;
+                };
+                if let Err(error) = JsonPath::parse(literal) {
+                    errors.push(
+                        Error::new(
+                            ErrorCode::InvalidTextRepresentation,
+                            format!("Invalid JSON path \"{}\": {}", literal, error),
+                        )
+                        .with_hint("Write JSON paths as $.items[*] ? (@.qty > 2)"),
+                    );
+                }
+            }
+        }
+        errors
     }
+}
 
+// Checks that are not wired into `analyze` yet.
+#[allow(dead_code)]
+impl DMLAnalyzer<'_> {
     // TODO: Implement check if the primary key is unique
-    async fn check_primary_key_unique(&self) -> Vec<String> {
+    async fn check_primary_key_unique(&self) -> Vec<Error> {
         vec![]
     }
 
     // TODO: Implement check if the foreign key exists
-    fn check_foreign_key_exists(&self) -> Vec<String> {
+    fn check_foreign_key_exists(&self) -> Vec<Error> {
         vec![]
     }
 
     // TODO: Implement check if the foreign key is not null
-    fn check_foreign_key_not_null(&self) -> Vec<String> {
+    fn check_foreign_key_not_null(&self) -> Vec<Error> {
         vec![]
     }
 
     // TODO: Implement check if the foreign key is unique
-    fn check_foreign_key_unique(&self) -> Vec<String> {
+    fn check_foreign_key_unique(&self) -> Vec<Error> {
         vec![]
     }
 
     // TODO: Implement check if the foreign key is of the correct type
-    fn check_foreign_key_correct_type(&self) -> Vec<String> {
+    fn check_foreign_key_correct_type(&self) -> Vec<Error> {
         vec![]
     }
 
     // TODO: Implement check if the foreign key is not the primary key
-    fn check_foreign_key_not_primary_key(&self) -> Vec<String> {
+    fn check_foreign_key_not_primary_key(&self) -> Vec<Error> {
         vec![]
     }
 }
 
-pub async fn analyze(statement: DMLStatement) -> Vec<String> {
-    let analyzer = DMLAnalyzer::new(vec![statement]);
-    analyzer.analyze().await
+/// The tables a statement operates on and the columns it mentions.
+fn referenced_columns(statement: &DMLStatement) -> Option<(Vec<&str>, Vec<&str>)> {
+    match statement {
+        DMLStatement::Insert(insert_statement) => Some((
+            vec![&insert_statement.table_name],
+            insert_statement
+                .columns
+                .iter()
+                .map(|c| c.as_str())
+                .collect(),
+        )),
+        DMLStatement::Select(select_statement)
+        | DMLStatement::Explain(DMLExplainStatement {
+            statement: select_statement,
+            ..
+        })
+        | DMLStatement::Declare(DMLDeclareStatement {
+            statement: select_statement,
+            ..
+        }) => {
+            let mut columns: Vec<&str> = select_statement
+                .columns
+                .iter()
+                .map(|c| c.name.as_str())
+                .filter(|name| *name != "*")
+                .collect();
+            columns.extend(
+                select_statement
+                    .predicates
+                    .iter()
+                    .map(|p| p.column.as_str()),
+            );
+            columns.extend(select_statement.group_by.iter().map(|c| c.as_str()));
+            for join in select_statement.joins.iter() {
+                columns.push(&join.left_column);
+                columns.push(&join.right_column);
+            }
+            // The columns unnests add are not columns of the tables.
+            columns.retain(|column| select_statement.unnest_of(column).is_none());
+            columns.extend(select_statement.unnests.iter().map(|u| u.column.as_str()));
+            Some((select_statement.table_names().collect(), columns))
+        }
+        DMLStatement::Update(update_statement) => {
+            let mut columns: Vec<&str> = update_statement
+                .assignments
+                .iter()
+                .map(|(column, _)| column.as_str())
+                .collect();
+            columns.extend(
+                update_statement
+                    .predicates
+                    .iter()
+                    .map(|p| p.column.as_str()),
+            );
+            Some((vec![&update_statement.table_name], columns))
+        }
+        DMLStatement::Delete(delete_statement) => Some((
+            vec![&delete_statement.table_name],
+            delete_statement
+                .predicates
+                .iter()
+                .map(|p| p.column.as_str())
+                .collect(),
+        )),
+        _ => None,
+    }
+}
+
+/// Checks that a statement only refers to tables and columns that exist.
+/// Returns every problem found.
+pub async fn analyze(db: &Database, statement: DMLStatement) -> Result<(), Error> {
+    let analyzer = DMLAnalyzer::new(db, vec![statement]);
+    let errors = analyzer.analyze().await;
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::many(errors))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::DMLTokenType;
+    use super::{DMLColumn, DMLTarget, DMLTokenType};
+
+    fn names(columns: &[DMLColumn]) -> Vec<String> {
+        columns.iter().map(ToString::to_string).collect()
+    }
 
     #[test]
     fn test_dml_lexer() {
@@ -484,7 +2814,14 @@ mod tests {
                     insert_statement.columns,
                     vec!["EmployeeID", "FirstName", "LastName"]
                 );
-                assert_eq!(insert_statement.values, vec!["1", "John", "Doe"]);
+                assert_eq!(
+                    insert_statement.values,
+                    vec![
+                        super::DMLValue::Literal("1".to_string()),
+                        super::DMLValue::Literal("John".to_string()),
+                        super::DMLValue::Literal("Doe".to_string()),
+                    ]
+                );
             }
             _ => {
                 assert!(false);
@@ -501,4 +2838,433 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_dml_parser_select_statement() {
+        let input = "SELECT FirstName, LastName FROM Employee WHERE HireDate >= '2021-01-01' AND DepartmentID<>3;";
+        let mut parser = super::DMLParser::new(input.to_string());
+        match parser.parse() {
+            Some(super::DMLStatement::Select(select_statement)) => {
+                assert_eq!(select_statement.table_name, "Employee");
+                assert_eq!(
+                    names(&select_statement.columns),
+                    vec!["FirstName", "LastName"]
+                );
+                assert_eq!(
+                    select_statement.predicates,
+                    vec![
+                        super::DMLPredicate {
+                            column: "HireDate".to_string(),
+                            target: super::DMLTarget::Value,
+                            operator: super::DMLOperator::GreaterThanOrEquals,
+                            value: super::DMLValue::Literal("2021-01-01".to_string()),
+                        },
+                        super::DMLPredicate {
+                            column: "DepartmentID".to_string(),
+                            target: super::DMLTarget::Value,
+                            operator: super::DMLOperator::NotEquals,
+                            value: super::DMLValue::Literal("3".to_string()),
+                        },
+                    ]
+                );
+            }
+            _ => panic!("expected a select statement"),
+        }
+    }
+
+    #[test]
+    fn test_dml_parser_explain_statement() {
+        let mut parser = super::DMLParser::new("explain select * from Employee".to_string());
+        match parser.parse() {
+            Some(super::DMLStatement::Explain(explain_statement)) => {
+                assert!(!explain_statement.analyze);
+                assert!(explain_statement.statement.columns.is_empty());
+            }
+            _ => panic!("expected an explain statement"),
+        }
+
+        let mut parser = super::DMLParser::new(
+            "EXPLAIN ANALYZE select * from Employee where EmployeeID = '1';".to_string(),
+        );
+        match parser.parse() {
+            Some(super::DMLStatement::Explain(explain_statement)) => {
+                assert!(explain_statement.analyze);
+                assert_eq!(explain_statement.statement.predicates.len(), 1);
+            }
+            _ => panic!("expected an explain analyze statement"),
+        }
+    }
+
+    #[test]
+    fn test_dml_parser_join() {
+        let input = "select Employee.FirstName, Amount from Employee join Salary on Employee.EmployeeID = Salary.EmployeeID where Amount > 1000;";
+        let mut parser = super::DMLParser::new(input.to_string());
+        match parser.parse() {
+            Some(super::DMLStatement::Select(select_statement)) => {
+                assert_eq!(
+                    names(&select_statement.columns),
+                    vec!["Employee.FirstName", "Amount"]
+                );
+                assert_eq!(select_statement.joins.len(), 1);
+                let join = &select_statement.joins[0];
+                assert_eq!(join.table_name, "Salary");
+                assert_eq!(join.left_column, "Employee.EmployeeID");
+                assert_eq!(join.right_column, "Salary.EmployeeID");
+                assert_eq!(select_statement.predicates.len(), 1);
+                assert_eq!(
+                    select_statement.table_names().collect::<Vec<_>>(),
+                    vec!["Employee", "Salary"]
+                );
+            }
+            _ => panic!("expected a select statement"),
+        }
+    }
+
+    #[test]
+    fn test_dml_parser_parameters() {
+        let input = "update Employee set LastName = $2 where EmployeeID = $1 and DepartmentID > $3;";
+        let mut statement = super::parse(input).unwrap();
+        assert_eq!(statement.parameter_count(), 3);
+        let super::DMLStatement::Update(update) = &statement else {
+            panic!("expected an update statement");
+        };
+        assert_eq!(update.assignments[0].1, super::DMLValue::Parameter(2));
+        assert_eq!(update.predicates[0].to_string(), "EmployeeID = $1");
+
+        statement.bind(&["7".to_string(), "O'Brien".to_string(), "2".to_string()]);
+        let super::DMLStatement::Update(update) = &statement else {
+            panic!("expected an update statement");
+        };
+        assert_eq!(update.assignments[0].1.literal(), "O'Brien");
+        assert_eq!(update.predicates[1].value.literal(), "2");
+        assert_eq!(statement.parameter_count(), 0);
+    }
+
+    #[test]
+    fn test_dml_parser_cursors() {
+        let input = "declare recent cursor for select FirstName from Employee where HireDate > $1;";
+        let Ok(super::DMLStatement::Declare(declare)) = super::parse(input) else {
+            panic!("expected a declare statement");
+        };
+        assert_eq!(declare.name, "recent");
+        assert_eq!(names(&declare.statement.columns), vec!["FirstName"]);
+        assert_eq!(declare.statement.predicates.len(), 1);
+
+        let Ok(super::DMLStatement::Fetch(fetch)) = super::parse("fetch 50 from recent;") else {
+            panic!("expected a fetch statement");
+        };
+        assert_eq!((fetch.name.as_str(), fetch.count), ("recent", Some(50)));
+        let Ok(super::DMLStatement::Fetch(fetch)) = super::parse("fetch all recent") else {
+            panic!("expected a fetch statement");
+        };
+        assert_eq!(fetch.count, None);
+        let Ok(super::DMLStatement::Fetch(fetch)) = super::parse("fetch recent") else {
+            panic!("expected a fetch statement");
+        };
+        assert_eq!(fetch.count, Some(1));
+
+        assert!(matches!(
+            super::parse("close recent;"),
+            Ok(super::DMLStatement::Close(name)) if name == "recent"
+        ));
+        assert!(super::parse("declare recent for select * from Employee").is_err());
+    }
+
+    #[test]
+    fn test_dml_parser_arrays() {
+        let input = "select Title, array_length(Tags, 1), Tag from Post, unnest(Tags) as Tag \
+                     where 'rust' = any(Tags) and Tags @> '{db}' and Tag <> 'db';";
+        let Ok(super::DMLStatement::Select(select)) = super::parse(input) else {
+            panic!("expected a select statement");
+        };
+        assert_eq!(
+            names(&select.columns),
+            vec!["Title", "array_length(Tags, 1)", "Tag"]
+        );
+        assert_eq!(select.columns[1].output_name(), "array_length");
+        assert_eq!(select.unnests.len(), 1);
+        assert_eq!(select.unnest_of("Tag").unwrap().column, "Tags");
+        let predicates: Vec<String> = select.predicates.iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            predicates,
+            vec!["'rust' = ANY(Tags)", "Tags @> '{db}'", "Tag <> 'db'"]
+        );
+        assert_eq!(select.predicates[0].target, DMLTarget::AnyElement);
+        assert_eq!(select.predicates[1].operator, super::DMLOperator::Contains);
+
+        let input = "delete from Post where array_length(Tags, 1) > 3 and Tags && $1;";
+        let Ok(super::DMLStatement::Delete(delete)) = super::parse(input) else {
+            panic!("expected a delete statement");
+        };
+        assert_eq!(delete.predicates[0].target, DMLTarget::Length);
+        assert_eq!(delete.predicates[1].operator, super::DMLOperator::Overlaps);
+
+        assert!(super::parse("select * from Post where 'a' @> any(Tags);").is_err());
+        assert!(super::parse("select array_length(Tags, 2) from Post;").is_err());
+    }
+
+    #[test]
+    fn test_dml_parser_json() {
+        let input = "select Doc->'customer'->>'name', Doc->'items'->0 from Orders \
+                     where Doc->>'status' = 'paid' and Doc @? '$.items[*] ? (@.qty > 2)' \
+                     and Doc @@ '$.total >= 100';";
+        let Ok(super::DMLStatement::Select(select)) = super::parse(input) else {
+            panic!("expected a select statement");
+        };
+        assert_eq!(
+            names(&select.columns),
+            vec!["Doc->'customer'->>'name'", "Doc->'items'->0"]
+        );
+        assert_eq!(select.columns[0].output_name(), "name");
+        assert_eq!(select.columns[1].output_name(), "?column?");
+        let predicates: Vec<String> = select.predicates.iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            predicates,
+            vec![
+                "Doc->>'status' = 'paid'",
+                "Doc @? '$.items[*] ? (@.qty > 2)'",
+                "Doc @@ '$.total >= 100'"
+            ]
+        );
+        assert_eq!(select.predicates[1].operator, super::DMLOperator::PathExists);
+        assert_eq!(select.predicates[2].operator, super::DMLOperator::PathMatches);
+
+        assert!(super::parse("select Doc->>'a'->'b' from Orders;").is_err());
+        assert!(super::parse("select * from Orders where Doc->'a' @? '$';").is_err());
+    }
+
+    #[test]
+    fn test_dml_parser_sequences() {
+        let input = "create sequence Invoices start with 100 increment by 10;";
+        let Ok(super::DMLStatement::CreateSequence(create)) = super::parse(input) else {
+            panic!("expected a create sequence statement");
+        };
+        assert_eq!((create.name.as_str(), create.start, create.increment), ("Invoices", 100, 10));
+        let Ok(super::DMLStatement::CreateSequence(create)) = super::parse("create sequence s;")
+        else {
+            panic!("expected a create sequence statement");
+        };
+        assert_eq!((create.start, create.increment), (1, 1));
+
+        let Ok(super::DMLStatement::NextValue(name)) = super::parse("select nextval('s');") else {
+            panic!("expected a nextval statement");
+        };
+        assert_eq!(name, "s");
+
+        let input = "insert into Invoice (InvoiceID, Total) values (nextval('s'), 12);";
+        let Ok(super::DMLStatement::Insert(insert)) = super::parse(input) else {
+            panic!("expected an insert statement");
+        };
+        assert_eq!(
+            insert.values,
+            vec![
+                super::DMLValue::NextValue("s".to_string()),
+                super::DMLValue::Literal("12".to_string())
+            ]
+        );
+        let input = "update Invoice set Number = nextval('s'), Total = 3 where InvoiceID = 1;";
+        let Ok(super::DMLStatement::Update(update)) = super::parse(input) else {
+            panic!("expected an update statement");
+        };
+        assert_eq!(update.assignments[0].1, super::DMLValue::NextValue("s".to_string()));
+
+        assert!(super::parse("create sequence s start with 'a';").is_err());
+        assert!(super::parse("select * from Invoice where InvoiceID = nextval('s');").is_err());
+    }
+
+    #[test]
+    fn test_dml_parser_views() {
+        let input = "create incremental materialized view Sales as select LastName as Name from Employee where DepartmentID = 7;";
+        let Ok(super::DMLStatement::CreateView(create)) = super::parse(input) else {
+            panic!("expected a create view statement");
+        };
+        assert_eq!(create.name, "Sales");
+        assert_eq!(create.query, "select LastName as Name from Employee where DepartmentID = 7");
+        assert_eq!(create.materialized, Some(super::Refresh::Incremental));
+        assert_eq!(create.statement.columns[0].name, "LastName");
+        assert_eq!(create.statement.columns[0].output_name(), "Name");
+
+        let Ok(super::DMLStatement::CreateView(create)) = super::parse("create view V as select * from T") else {
+            panic!("expected a create view statement");
+        };
+        assert_eq!(create.materialized, None);
+        let Ok(super::DMLStatement::RefreshView(name)) = super::parse("refresh materialized view V;") else {
+            panic!("expected a refresh statement");
+        };
+        assert_eq!(name, "V");
+
+        let input = "create materialized view Headcount as select DepartmentID, COUNT(*), avg(Salary) as Pay from Employee where HireDate > '2020-01-01' group by DepartmentID;";
+        let Ok(super::DMLStatement::CreateView(create)) = super::parse(input) else {
+            panic!("expected a create view statement");
+        };
+        let select = &create.statement;
+        assert_eq!(
+            names(&select.columns),
+            ["DepartmentID", "count(*)", "avg(Salary) as Pay"]
+        );
+        assert_eq!(
+            select.columns[1].aggregate,
+            Some(super::DMLAggregate::Count)
+        );
+        assert_eq!(select.columns[1].output_name(), "count");
+        assert_eq!(select.columns[2].output_name(), "Pay");
+        assert_eq!(select.group_by, ["DepartmentID"]);
+        assert_eq!(select.predicates.len(), 1);
+        assert!(select.is_grouped());
+
+        assert!(super::parse("select sum(*) from T;").is_err());
+        assert!(super::parse("select count(*) from T group by;").is_err());
+        assert!(super::parse("create incremental view V as select * from T;").is_err());
+        assert!(super::parse("create view V select * from T;").is_err());
+        assert!(super::parse("refresh view V;").is_err());
+    }
+
+    #[test]
+    fn test_dml_parser_triggers() {
+        let input = "create trigger audit after update on Employee for each row begin insert into Audit (EmployeeID, Salary) values (old.EmployeeID, new.Salary); delete from Pending where EmployeeID = old.EmployeeID; end;";
+        let Ok(super::DMLStatement::CreateTrigger(create)) = super::parse(input) else {
+            panic!("expected a create trigger statement");
+        };
+        assert_eq!(create.name, "audit");
+        assert_eq!(create.table_name, "Employee");
+        assert_eq!(create.timing, super::TriggerTiming::After);
+        assert_eq!(create.event, super::TriggerEvent::Update);
+        assert_eq!(create.statements, vec![
+            "insert into Audit (EmployeeID, Salary) values (old.EmployeeID, new.Salary)".to_string(),
+            "delete from Pending where EmployeeID = old.EmployeeID".to_string(),
+        ]);
+
+        let (text, references) = super::row_references(&create.statements[0]).unwrap();
+        assert_eq!(text, "insert into Audit (EmployeeID, Salary) values ($1, $2)");
+        assert_eq!(references, vec![
+            (super::TriggerRow::Old, "EmployeeID".to_string()),
+            (super::TriggerRow::New, "Salary".to_string()),
+        ]);
+        assert!(super::row_references("delete from T where A = $1").is_err());
+
+        assert!(super::parse("create trigger t before insert on T begin delete from T; end;").is_ok());
+        assert!(super::parse("create trigger t during insert on T begin delete from T; end;").is_err());
+        assert!(super::parse("create trigger t after insert on T delete from T;").is_err());
+    }
+
+    #[test]
+    fn test_dml_parser_as_of() {
+        let input = "select Amount from Salary as of timestamp '2026-10-01T12:00' \
+                     join Employee on Salary.EmployeeID = Employee.EmployeeID where Amount > 10;";
+        let Ok(super::DMLStatement::Select(select)) = super::parse(input) else {
+            panic!("expected a select statement");
+        };
+        assert_eq!(
+            select.as_of,
+            Some(super::DMLAsOf::Timestamp("2026-10-01T12:00".to_string()))
+        );
+        assert_eq!(select.joins.len(), 1);
+        assert_eq!(select.predicates.len(), 1);
+
+        let Ok(super::DMLStatement::Select(select)) =
+            super::parse("SELECT * FROM Salary AS OF TRANSACTION 42;")
+        else {
+            panic!("expected a select statement");
+        };
+        let as_of = select.as_of.unwrap();
+        assert_eq!(as_of, super::DMLAsOf::Transaction(42));
+        assert_eq!(as_of.to_string(), "as of transaction 42");
+        let Ok(super::DMLStatement::Select(select)) = super::parse("select * from Salary;") else {
+            panic!("expected a select statement");
+        };
+        assert_eq!(select.as_of, None);
+
+        assert!(super::parse("select * from Salary as of transaction -1;").is_err());
+        assert!(super::parse("select * from Salary as of timestamp 5;").is_err());
+        assert!(super::parse("select * from Salary as of yesterday;").is_err());
+    }
+
+    #[test]
+    fn test_dml_parser_system_time() {
+        let input = "select * from Salary for system_time between '2026-01-01' and '2026-07-01' \
+                     where Amount > 10;";
+        let Ok(super::DMLStatement::Select(select)) = super::parse(input) else {
+            panic!("expected a select statement");
+        };
+        let system_time = select.system_time.unwrap();
+        assert_eq!(system_time.from, "2026-01-01");
+        assert_eq!(system_time.to, "2026-07-01");
+        assert_eq!(
+            system_time.to_string(),
+            "for system_time between '2026-01-01' and '2026-07-01'"
+        );
+        assert_eq!(select.predicates.len(), 1);
+
+        let Ok(super::DMLStatement::Select(select)) = super::parse(
+            "SELECT * FROM Salary FOR SYSTEM_TIME BETWEEN '2026-01-01' AND '2026-07-01';",
+        ) else {
+            panic!("expected a select statement");
+        };
+        assert!(select.system_time.is_some());
+
+        assert!(
+            super::parse("select * from Salary for system_time between '2026-01-01';").is_err()
+        );
+        assert!(super::parse("select * from Salary for system_time from 1 to 2;").is_err());
+    }
+
+    #[test]
+    fn test_dml_parser_syntax_errors() {
+        let error = super::parse("select * fro Employee").unwrap_err();
+        assert_eq!(error.code(), super::ErrorCode::SyntaxError);
+        assert_eq!(error.message(), "Syntax error at or near \"fro\"");
+        assert_eq!(error.span(), Some(super::Span { start: 9, end: 12 }));
+
+        let error = super::parse("insert into Employee (EmployeeID) values (1)").unwrap_err();
+        assert_eq!(error.message(), "Unexpected end of statement");
+        assert_eq!(error.hint(), Some("Statements end with ;"));
+
+        let error = super::parse("  selec * from Employee").unwrap_err();
+        assert_eq!(error.span(), Some(super::Span { start: 2, end: 7 }));
+        assert!(error.hint().unwrap().starts_with("Statements start with select"));
+
+        let error = super::parse("delete from Employee where EmployeeID = 1x;").unwrap_err();
+        assert_eq!(error.code(), super::ErrorCode::SyntaxError);
+        assert_eq!(error.message(), "Invalid number \"1x\"");
+        assert_eq!(error.span(), Some(super::Span { start: 40, end: 42 }));
+
+        let error = super::parse("select * from Employee where LastName = 'abc;").unwrap_err();
+        assert_eq!(error.code(), super::ErrorCode::SyntaxError);
+        assert_eq!(error.message(), "Unterminated string");
+        assert_eq!(error.span(), Some(super::Span { start: 40, end: 45 }));
+
+        // Positions count characters, not bytes.
+        let error = super::parse("select * from Employee where LastName = 'é' fro;").unwrap_err();
+        assert_eq!(error.message(), "Syntax error at or near \"fro\"");
+        assert_eq!(error.span(), Some(super::Span { start: 44, end: 47 }));
+        assert!(super::parse("select * from Employee where LastName = 'Müller';").is_ok());
+
+        let error =
+            super::parse("insert into Employee (EmployeeID, LastName) values (5);").unwrap_err();
+        assert_eq!(error.code(), super::ErrorCode::SyntaxError);
+        assert_eq!(error.message(), "INSERT has more columns than values");
+        assert_eq!(error.span(), Some(super::Span { start: 34, end: 42 }));
+        let error =
+            super::parse("insert into Employee (EmployeeID, LastName) values ($1, $2, $3);")
+                .unwrap_err();
+        assert_eq!(error.message(), "INSERT has more values than columns");
+        assert_eq!(error.span(), Some(super::Span { start: 60, end: 62 }));
+        let error = super::parse("insert into Employee (EmployeeID, EmployeeID) values (1, 2);")
+            .unwrap_err();
+        assert_eq!(error.code(), super::ErrorCode::DuplicateColumn);
+        assert_eq!(error.span(), Some(super::Span { start: 34, end: 44 }));
+
+        // Quotes inside strings are written twice.
+        match super::parse("insert into Employee (EmployeeID, LastName) values (3, 'O''Brien');") {
+            Ok(super::DMLStatement::Insert(insert)) => assert_eq!(
+                insert.values[1],
+                super::DMLValue::Literal("O'Brien".to_string())
+            ),
+            other => panic!("expected an insert statement, got {:?}", other),
+        }
+        assert_eq!(
+            super::DMLValue::Literal("O'Brien".to_string()).to_string(),
+            "'O''Brien'"
+        );
+    }
 }
@@ -0,0 +1,446 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::time::Instant;
+
+use super::dml::{DMLOperator, DMLTarget};
+use super::plan::{self, ActualStats, Operator, PlanNode, TypedPredicate};
+use crate::catalogue::basic_types::{parse_array, BasicType};
+use crate::catalogue::json_path::JsonPath;
+use crate::storage::Row;
+use crate::Database;
+
+/// A hash join whose build side has this many times more rows than
+/// estimated is re-planned before it is probed.
+const REOPTIMIZATION_THRESHOLD: f64 = 100.0;
+
+/// Executes a plan bottom-up, recording the actual statistics on every node.
+pub fn execute_plan(db: &Database, node: &mut PlanNode) -> Result<Vec<Row>, std::io::Error> {
+    let start = Instant::now();
+    let mut inputs = Vec::new();
+    let mut reoptimized = None;
+    if matches!(node.operator, Operator::HashJoin { .. }) {
+        // The build side is a pipeline breaker: once it is materialized its
+        // true size is known, and the rest of the join can be re-planned.
+        let estimated_build_rows = node.children[0].estimated_rows;
+        let first = execute_plan(db, &mut node.children[0])?;
+        let observed_build_rows = first.len() as f64;
+        if observed_build_rows > REOPTIMIZATION_THRESHOLD * estimated_build_rows {
+            reoptimized = Some(plan::replan_hash_join(node, observed_build_rows));
+        }
+        if node.children[0].actual.is_some() {
+            // The inputs kept their places.
+            let second = execute_plan(db, &mut node.children[1])?;
+            inputs.extend([first, second]);
+        } else {
+            let second = execute_plan(db, &mut node.children[0])?;
+            inputs.extend([second, first]);
+        }
+    } else {
+        for child in node.children.iter_mut() {
+            inputs.push(execute_plan(db, child)?);
+        }
+    }
+
+    let mut pages_read = 0;
+    let mut values_cracked = None;
+    let mut segments_skipped = None;
+    let rows = match &node.operator {
+        Operator::SeqScan { table } => {
+            let table = db.storage.scan(table)?;
+            pages_read = table.page_count();
+            table.rows
+        }
+        Operator::ColumnScan {
+            table,
+            columns,
+            predicates,
+            ..
+        } => {
+            // Each predicate rules out the segments whose zone map lies
+            // outside of it on its own.
+            let ranges: Vec<_> = predicates
+                .iter()
+                .map(|p| {
+                    let range = plan::range_of(std::slice::from_ref(p));
+                    (p.predicate.column.clone(), p.data_type.clone(), range)
+                })
+                .collect();
+            let scan = db.storage.column_scan(table, columns, &ranges)?;
+            pages_read = scan.pages_read;
+            segments_skipped = Some(scan.segments_skipped);
+            scan.rows
+        }
+        Operator::IndexScan {
+            table,
+            index: index_name,
+            column,
+            path,
+            data_type,
+            predicates,
+            ..
+        } => {
+            let table_data = db.storage.scan(table)?;
+            let range = plan::range_of(predicates);
+            let mut row_indices = db.indexes.lookup(
+                table,
+                column,
+                path.as_ref(),
+                data_type.clone(),
+                &table_data.rows,
+                &range,
+            );
+            row_indices.sort_unstable();
+            let row_pages = table_data.row_pages();
+            let mut pages: Vec<usize> = row_indices.iter().map(|row| row_pages[*row]).collect();
+            pages.dedup();
+            pages_read = pages.len();
+            db.advisor.record_index_use(table, index_name);
+            let mut rows = table_data.rows;
+            row_indices
+                .into_iter()
+                .map(|index| std::mem::take(&mut rows[index]))
+                .collect()
+        }
+        Operator::InvertedIndexScan {
+            table,
+            index: index_name,
+            column,
+            predicates,
+            ..
+        } => {
+            let table_data = db.storage.scan(table)?;
+            let mut candidates: Option<BTreeSet<usize>> = None;
+            for predicate in predicates.iter() {
+                let Some(found) = element_rows(db, table, column, &table_data.rows, predicate)
+                else {
+                    continue;
+                };
+                candidates = Some(match candidates {
+                    Some(candidates) => candidates.intersection(&found).copied().collect(),
+                    None => found,
+                });
+            }
+            let row_indices: Vec<usize> = match candidates {
+                Some(candidates) => candidates.into_iter().collect(),
+                None => (0..table_data.rows.len()).collect(),
+            };
+            let row_pages = table_data.row_pages();
+            let mut pages: Vec<usize> = row_indices.iter().map(|row| row_pages[*row]).collect();
+            pages.dedup();
+            pages_read = pages.len();
+            db.advisor.record_index_use(table, index_name);
+            // The index only narrows down the rows of some predicates, such
+            // as those looking for an empty array.
+            let mut rows = table_data.rows;
+            row_indices
+                .into_iter()
+                .map(|index| std::mem::take(&mut rows[index]))
+                .filter(|row| predicates.iter().all(|predicate| evaluate(predicate, row)))
+                .collect()
+        }
+        Operator::CrackerScan {
+            table,
+            column,
+            data_type,
+            predicates,
+            ..
+        } => {
+            // Read before the rows, so a write in between makes the next
+            // selection start over rather than go unnoticed.
+            let version = db.storage.version(table);
+            let table_data = db.storage.scan(table)?;
+            pages_read = table_data.page_count();
+            let range = plan::range_of(predicates);
+            let selection = db.cracking.select(
+                table,
+                column,
+                data_type.clone(),
+                &table_data.rows,
+                version,
+                &range,
+            );
+            values_cracked = Some(selection.values_touched);
+            let mut row_indices = selection.row_indices;
+            // Keep the table order rather than the order of the cracked column.
+            row_indices.sort_unstable();
+            let mut rows = table_data.rows;
+            row_indices
+                .into_iter()
+                .map(|index| std::mem::take(&mut rows[index]))
+                .collect()
+        }
+        Operator::HashJoin {
+            build_key,
+            probe_key,
+            build_table,
+            probe_table,
+            ..
+        } => {
+            // The keys are qualified, as in `Table.column`.
+            for key in [build_key, probe_key] {
+                if let Some((table, column)) = key.split_once('.') {
+                    db.advisor.record_join(table, column);
+                }
+            }
+            let probe = qualify(inputs.pop().unwrap(), probe_table.as_deref());
+            let build = qualify(inputs.pop().unwrap(), build_table.as_deref());
+            hash_join(build, build_key, probe, probe_key)
+        }
+        Operator::Values {
+            rows, predicates, ..
+        } => rows
+            .iter()
+            .filter(|row| predicates.iter().all(|predicate| evaluate(predicate, row)))
+            .cloned()
+            .collect(),
+        Operator::Filter { predicates, .. } => inputs
+            .remove(0)
+            .into_iter()
+            .filter(|row| predicates.iter().all(|predicate| evaluate(predicate, row)))
+            .collect(),
+        Operator::Unnest { column, alias } => {
+            let mut rows = Vec::new();
+            for row in inputs.remove(0) {
+                let Some(elements) = row
+                    .get(column)
+                    .and_then(|value| value.as_str())
+                    .and_then(parse_array)
+                else {
+                    continue;
+                };
+                for element in elements {
+                    let mut row = row.clone();
+                    let element = element
+                        .map(serde_json::Value::String)
+                        .unwrap_or(serde_json::Value::Null);
+                    row.insert(alias.clone(), element);
+                    rows.push(row);
+                }
+            }
+            rows
+        }
+        Operator::Projection { columns, sources } => inputs
+            .remove(0)
+            .into_iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .zip(sources.iter())
+                    .map(|(column, (source, target))| {
+                        let value = row.get(source).and_then(|value| value.as_str());
+                        let value = match (value, target) {
+                            (Some(value), DMLTarget::Length) => match array_length(value) {
+                                Some(length) => serde_json::Value::String(length.to_string()),
+                                None => serde_json::Value::Null,
+                            },
+                            (Some(value), DMLTarget::Path(access)) => match access.extract(value) {
+                                Some(value) => serde_json::Value::String(value),
+                                None => serde_json::Value::Null,
+                            },
+                            (Some(value), _) => serde_json::Value::String(value.to_string()),
+                            (None, _) => serde_json::Value::Null,
+                        };
+                        (column.clone(), value)
+                    })
+                    .collect()
+            })
+            .collect(),
+    };
+
+    node.actual = Some(ActualStats {
+        rows: rows.len(),
+        loops: 1,
+        time_ms: start.elapsed().as_secs_f64() * 1000.0,
+        pages_read,
+        segments_skipped,
+        values_cracked,
+        reoptimized,
+    });
+
+    if let Some(feedback) = &node.feedback {
+        let input_rows = feedback.input_rows.unwrap_or_else(|| {
+            node.children
+                .iter()
+                .filter_map(|child| child.actual.as_ref())
+                .map(|actual| actual.rows as f64)
+                .fold(0.0, f64::max)
+        });
+        if input_rows > 0.0 {
+            db.statistics
+                .record(&feedback.shape, rows.len() as f64 / input_rows);
+        }
+    }
+    Ok(rows)
+}
+
+/// Prefixes the columns of rows read from `table` with the table name.
+fn qualify(rows: Vec<Row>, table: Option<&str>) -> Vec<Row> {
+    let Some(table) = table else {
+        return rows;
+    };
+    rows.into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|(column, value)| (format!("{}.{}", table, column), value))
+                .collect()
+        })
+        .collect()
+}
+
+/// Joins the rows whose keys are equal. NULL keys never join.
+fn hash_join(build: Vec<Row>, build_key: &str, probe: Vec<Row>, probe_key: &str) -> Vec<Row> {
+    let mut hash_table: HashMap<&str, Vec<&Row>> = HashMap::new();
+    for row in build.iter() {
+        if let Some(key) = row.get(build_key).and_then(|value| value.as_str()) {
+            hash_table.entry(key).or_default().push(row);
+        }
+    }
+
+    let mut rows = Vec::new();
+    for probe_row in probe.iter() {
+        let Some(key) = probe_row.get(probe_key).and_then(|value| value.as_str()) else {
+            continue;
+        };
+        for build_row in hash_table.get(key).into_iter().flatten() {
+            let mut row = (*build_row).clone();
+            row.extend(probe_row.clone());
+            rows.push(row);
+        }
+    }
+    rows
+}
+
+/// The rows of `table` an inverted index on `column` finds for a predicate
+/// that looks for elements, or `None` if it would have to find all of them.
+fn element_rows(
+    db: &Database,
+    table: &str,
+    column: &str,
+    rows: &[Row],
+    predicate: &TypedPredicate,
+) -> Option<BTreeSet<usize>> {
+    let element_type = predicate
+        .data_type
+        .element_type()
+        .cloned()
+        .unwrap_or(BasicType::String);
+    let literal = predicate.predicate.value.literal();
+    let elements = match predicate.predicate.target {
+        DMLTarget::AnyElement => vec![Some(literal.to_string())],
+        _ => parse_array(literal).unwrap_or_default(),
+    };
+    let mut lookup = |element: &Option<String>| -> BTreeSet<usize> {
+        let Some(element) = element else {
+            return BTreeSet::new();
+        };
+        // The index holds elements in their stored form.
+        let element = element_type
+            .parse_value(element)
+            .unwrap_or_else(|_| element.clone());
+        db.indexes
+            .lookup_element(table, column, rows, &element)
+            .into_iter()
+            .collect()
+    };
+    match predicate.predicate.operator {
+        DMLOperator::Overlaps => Some(elements.iter().flat_map(&mut lookup).collect()),
+        _ => elements
+            .iter()
+            .map(lookup)
+            .reduce(|found, other| found.intersection(&other).copied().collect()),
+    }
+}
+
+/// Number of elements of an array. Like PostgreSQL, an empty array has no
+/// length.
+fn array_length(value: &str) -> Option<usize> {
+    parse_array(value)
+        .map(|elements| elements.len())
+        .filter(|length| *length > 0)
+}
+
+/// Evaluates a predicate against a row. Comparisons with NULL never hold.
+pub fn evaluate(predicate: &TypedPredicate, row: &Row) -> bool {
+    let Some(value) = row
+        .get(&predicate.predicate.column)
+        .and_then(|v| v.as_str())
+    else {
+        return false;
+    };
+    let literal = predicate.predicate.value.literal();
+    let operator = predicate.predicate.operator;
+    let data_type = &predicate.data_type;
+    match &predicate.predicate.target {
+        DMLTarget::Value if operator.is_path_operator() => {
+            let (Ok(path), Ok(document)) = (JsonPath::parse(literal), serde_json::from_str(value))
+            else {
+                return false;
+            };
+            match operator {
+                DMLOperator::PathExists => path.exists(&document),
+                _ => path.matches(&document),
+            }
+        }
+        DMLTarget::Value if operator.is_array_operator() => {
+            let element_type = data_type.element_type().unwrap_or(&BasicType::String);
+            match (parse_array(value), parse_array(literal)) {
+                (Some(value), Some(literal)) => match operator {
+                    DMLOperator::Contains => contains(element_type, &value, &literal),
+                    DMLOperator::ContainedBy => contains(element_type, &literal, &value),
+                    _ => literal.iter().any(|element| {
+                        contains(element_type, &value, std::slice::from_ref(element))
+                    }),
+                },
+                _ => false,
+            }
+        }
+        DMLTarget::Value => match data_type.compare(value, literal) {
+            Some(ordering) => operator.matches(ordering),
+            None => false,
+        },
+        DMLTarget::AnyElement => {
+            let element_type = data_type.element_type().unwrap_or(&BasicType::String);
+            parse_array(value).unwrap_or_default().iter().flatten().any(
+                |element| match element_type.compare(literal, element) {
+                    Some(ordering) => operator.matches(ordering),
+                    None => false,
+                },
+            )
+        }
+        DMLTarget::Length => match array_length(value) {
+            Some(length) => match BasicType::Int.compare(&length.to_string(), literal) {
+                Some(ordering) => operator.matches(ordering),
+                None => false,
+            },
+            None => false,
+        },
+        DMLTarget::Path(access) => {
+            let value_type = access.value_type();
+            match access
+                .extract(value)
+                .and_then(|value| value_type.compare(&value, literal))
+            {
+                Some(ordering) => operator.matches(ordering),
+                None => false,
+            }
+        }
+    }
+}
+
+/// Whether every element of `elements` is in `array`. NULL elements are in no
+/// array.
+fn contains(
+    element_type: &BasicType,
+    array: &[Option<String>],
+    elements: &[Option<String>],
+) -> bool {
+    elements.iter().all(|element| {
+        let Some(element) = element else {
+            return false;
+        };
+        array
+            .iter()
+            .flatten()
+            .any(|candidate| element_type.compare(candidate, element) == Some(Ordering::Equal))
+    })
+}
@@ -0,0 +1,259 @@
+//! Time travel: the rows tables had at a past point, rebuilt from their
+//! current rows by undoing the changes the change log holds since then.
+//! Tables with a retention keep that much history; older changes of them
+//! are pruned from the log.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::dml::{DMLAsOf, DMLSelectStatement};
+use crate::catalogue::basic_types::{self, BasicType};
+use crate::storage::changelog::{Change, Operation};
+use crate::storage::Row;
+use crate::{Database, Error, ErrorCode};
+
+/// How often changes older than the retention of their table are pruned.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+
+/// The rows of every table `select` reads, as they were at `as_of`.
+pub(crate) async fn rows_as_of(
+    db: &Database,
+    select: &DMLSelectStatement,
+    as_of: &DMLAsOf,
+) -> Result<Vec<(String, Vec<Row>)>, Error> {
+    let catalogue = db.catalogue.lock().await;
+    let changes = db.changes(0, usize::MAX)?;
+    // When the point is, if the log still knows.
+    let point = match as_of {
+        DMLAsOf::Timestamp(timestamp) => {
+            Some(micros(timestamp).expect("Analyzing should have caught this error"))
+        }
+        DMLAsOf::Transaction(transaction) => {
+            let last = db.changes.last_transaction();
+            if *transaction > last {
+                return Err(Error::new(
+                    ErrorCode::InvalidParameter,
+                    format!("Transaction {} has not committed", transaction),
+                )
+                .with_hint(format!("The last committed transaction is {}", last)));
+            }
+            changes
+                .iter()
+                .find(|change| change.transaction == *transaction)
+                .and_then(|change| micros(&change.committed_at))
+        }
+    };
+    let is_after = |change: &Change| match as_of {
+        DMLAsOf::Timestamp(_) => micros(&change.committed_at) > point,
+        DMLAsOf::Transaction(transaction) => change.transaction > *transaction,
+    };
+
+    let mut tables: Vec<(String, Vec<Row>)> = Vec::new();
+    for table_name in select.table_names() {
+        if tables.iter().any(|(name, _)| name == table_name) {
+            continue;
+        }
+        if catalogue.get_view(table_name).is_some() {
+            return Err(Error::new(
+                ErrorCode::FeatureNotSupported,
+                format!("Materialized view {} keeps no history", table_name),
+            )
+            .with_hint("Select from the tables it reads as of the point instead"));
+        }
+        let table = catalogue
+            .get_table(table_name)
+            .expect("Analyzing should have caught this error");
+        if let Some(retention) = table.retention() {
+            let horizon = now() - retention as i64 * MICROS_PER_SECOND;
+            if point.is_none_or(|point| point < horizon) {
+                return Err(Error::new(
+                    ErrorCode::SnapshotTooOld,
+                    format!(
+                        "Table {} keeps {} of history, too little to read it {}",
+                        table_name,
+                        describe(retention),
+                        as_of
+                    ),
+                )
+                .with_hint("Raise the @@retention of its model to keep more"));
+            }
+        }
+
+        let mut rows = db.storage.scan(table_name)?.rows;
+        for change in changes
+            .iter()
+            .rev()
+            .filter(|change| change.table == table_name && is_after(change))
+        {
+            undo(&mut rows, change)?;
+        }
+        tables.push((table_name.to_string(), rows));
+    }
+    Ok(tables)
+}
+
+/// Reverts the row change `change` made. Fails if the row it left is not
+/// among `rows`, as then the log no longer describes the table.
+fn undo(rows: &mut Vec<Row>, change: &Change) -> Result<(), Error> {
+    let position = change
+        .after
+        .as_ref()
+        .map(|after| rows.iter().position(|row| row == after));
+    match (change.op, position, &change.before) {
+        (Operation::Insert, Some(Some(position)), _) => {
+            rows.remove(position);
+        }
+        (Operation::Update, Some(Some(position)), Some(before)) => rows[position] = before.clone(),
+        (Operation::Delete, None, Some(before)) => rows.push(before.clone()),
+        _ => return Err(diverged(change)),
+    }
+    Ok(())
+}
+
+/// The error for a change of the log that does not match the rows of its
+/// table.
+pub(crate) fn diverged(change: &Change) -> Error {
+    Error::new(
+        ErrorCode::DataCorrupted,
+        format!(
+            "Change {} of the change log does not match the rows of table {}",
+            change.lsn, change.table
+        ),
+    )
+    .with_hint("Restore the database from a backup")
+}
+
+/// Removes the changes older than the retention of their table from the
+/// change log. Returns how many were removed.
+pub async fn prune(db: &Database) -> Result<usize, Error> {
+    let retentions: HashMap<String, u64> = {
+        let catalogue = db.catalogue.lock().await;
+        catalogue
+            .tables()
+            .iter()
+            .filter_map(|table| Some((table.name().to_string(), table.retention()?)))
+            .collect()
+    };
+    if retentions.is_empty() {
+        return Ok(0);
+    }
+    let now = now();
+    // Changes within the shortest retention are kept whatever their table.
+    let shortest = retentions.values().min().copied().unwrap_or_default();
+    let removed = db.changes.prune(
+        &db.storage,
+        |change| {
+            micros(&change.committed_at)
+                .is_none_or(|committed_at| committed_at < now - shortest as i64 * MICROS_PER_SECOND)
+        },
+        |change| {
+            let Some(retention) = retentions.get(&change.table) else {
+                return true;
+            };
+            micros(&change.committed_at).is_some_and(|committed_at| {
+                committed_at >= now - *retention as i64 * MICROS_PER_SECOND
+            })
+        },
+    )?;
+    Ok(removed)
+}
+
+/// Periodically prunes the changes older than the retention of their table.
+pub async fn run_pruning(db: Arc<Database>) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match prune(&db).await {
+            Ok(0) => {}
+            Ok(removed) => tracing::debug!("Pruned {} changes past their retention", removed),
+            Err(error) => tracing::error!("Pruning the change log failed: {}", error),
+        }
+    }
+}
+
+/// Microseconds since 1970-01-01 00:00 UTC of a timestamp.
+pub(crate) fn micros(timestamp: &str) -> Option<i64> {
+    basic_types::parse_timestamp(timestamp).map(|(micros, offset)| micros - offset)
+}
+
+fn now() -> i64 {
+    let now = basic_types::now(&BasicType::TimestampTz).unwrap();
+    micros(&now).unwrap()
+}
+
+/// A retention in the largest unit that measures it exactly, as `30 days`.
+fn describe(seconds: u64) -> String {
+    let units = [
+        ("week", 7 * 24 * 60 * 60),
+        ("day", 24 * 60 * 60),
+        ("hour", 60 * 60),
+        ("minute", 60),
+    ];
+    let (unit, size) = units
+        .into_iter()
+        .find(|(_, size)| seconds > 0 && seconds.is_multiple_of(*size))
+        .unwrap_or(("second", 1));
+    let count = seconds / size;
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: &str, name: &str) -> Row {
+        let mut row = Row::new();
+        row.insert("id".to_string(), serde_json::json!(id));
+        row.insert("name".to_string(), serde_json::json!(name));
+        row
+    }
+
+    fn change(op: Operation, before: Option<Row>, after: Option<Row>) -> Change {
+        Change {
+            lsn: 7,
+            transaction: 3,
+            committed_at: "2026-10-01 12:00:00+00".to_string(),
+            table: "t".to_string(),
+            op,
+            before,
+            after,
+        }
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut rows = vec![row("1", "Poe"), row("2", "Roe")];
+        let update = change(
+            Operation::Update,
+            Some(row("1", "Doe")),
+            Some(row("1", "Poe")),
+        );
+        undo(&mut rows, &update).unwrap();
+        assert_eq!(rows, [row("1", "Doe"), row("2", "Roe")]);
+        undo(
+            &mut rows,
+            &change(Operation::Delete, Some(row("3", "Zoe")), None),
+        )
+        .unwrap();
+        undo(
+            &mut rows,
+            &change(Operation::Insert, None, Some(row("2", "Roe"))),
+        )
+        .unwrap();
+        assert_eq!(rows, [row("1", "Doe"), row("3", "Zoe")]);
+
+        // Rows the log does not account for are an error, not a guess.
+        let error = undo(&mut rows, &update).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::DataCorrupted);
+        assert_eq!(
+            error.message(),
+            "Change 7 of the change log does not match the rows of table t"
+        );
+        let insert = change(Operation::Insert, None, Some(row("2", "Roe")));
+        assert!(undo(&mut rows, &insert).is_err());
+        assert_eq!(rows, [row("1", "Doe"), row("3", "Zoe")]);
+    }
+}
@@ -1,53 +1,988 @@
+pub mod cursor;
 pub mod ddl;
 pub mod dml;
+pub mod executor;
+pub mod history;
+pub mod plan;
+pub mod prepared;
 pub mod sql;
+pub mod subscription;
+pub mod temporal;
+pub mod trigger;
+pub mod view;
 
-pub fn execute(statement: dml::DMLStatement) -> serde_json::Value {
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::time::Instant;
+
+use crate::adaptive::advisor;
+use crate::catalogue::basic_types::{self, BasicType, ValueError};
+use crate::catalogue::check::CheckExpression;
+use crate::catalogue::{
+    self, json, Catalogue, Check, Column, ColumnDefault, ForeignKey, Sequence, Table,
+    TriggerEvent, TriggerTiming, Unique,
+};
+use crate::database::{QueryResult, Rows};
+use crate::storage::{self, changelog};
+use crate::{Database, Error, ErrorCode};
+
+/// The writes of a statement and of the triggers it fires, which commit
+/// together.
+#[derive(Default)]
+pub(crate) struct Transaction {
+    /// The number of triggers the running statement runs in.
+    depth: usize,
+    changes: Vec<changelog::PendingChange>,
+    /// The time system-versioned tables record the writes of the
+    /// transaction at, once one needs it.
+    system_time: Option<String>,
+}
+
+impl Transaction {
+    /// The time the rows the transaction writes to system-versioned tables
+    /// become current, and the rows they replace stop being current.
+    fn system_time(&mut self) -> String {
+        self.system_time
+            .get_or_insert_with(|| basic_types::now(&BasicType::TimestampTz).unwrap())
+            .clone()
+    }
+
+    fn record(
+        &mut self,
+        table_name: &str,
+        op: changelog::Operation,
+        before: Option<&storage::Row>,
+        after: Option<&storage::Row>,
+    ) {
+        self.changes.push(changelog::PendingChange {
+            table: table_name.to_string(),
+            op,
+            before: before.cloned(),
+            after: after.cloned(),
+        });
+    }
+}
+
+/// Executes a statement. Selects run `plan` if given, which must be a plan
+/// for the statement. The rows it changes are added to the change log.
+pub async fn execute(
+    db: &Database,
+    statement: dml::DMLStatement,
+    plan: Option<plan::PlanNode>,
+) -> Result<QueryResult, Error> {
+    // Statements that write run one at a time, from their first write until
+    // their changes are in the log, so the log lists changes in the order
+    // they were made.
+    let (_read, _write);
+    if statement.is_read_only() {
+        _read = db.statements.read().await;
+    } else {
+        _write = db.statements.write().await;
+    }
+    let mut transaction = Transaction::default();
+    let result = execute_statement(db, statement, plan, &mut transaction).await?;
+    db.changes.commit(&db.storage, transaction.changes)?;
+    Ok(result)
+}
+
+/// Executes a statement as part of `transaction`.
+async fn execute_statement(
+    db: &Database,
+    statement: dml::DMLStatement,
+    plan: Option<plan::PlanNode>,
+    transaction: &mut Transaction,
+) -> Result<QueryResult, Error> {
     match statement {
-        dml::DMLStatement::Insert(insert) => serde_json::json!({
-            "result": handle_insert(insert)
-        }),
-        _ => todo!("Implement other DML statements"),
+        dml::DMLStatement::Insert(insert) => handle_insert(db, insert, transaction)
+            .await
+            .map(QueryResult::Insert),
+        dml::DMLStatement::Update(update) => handle_update(db, update, transaction)
+            .await
+            .map(QueryResult::Update),
+        dml::DMLStatement::Delete(delete) => handle_delete(db, delete, transaction)
+            .await
+            .map(QueryResult::Delete),
+        dml::DMLStatement::Select(select) => {
+            let columns = output_columns(db, &select).await.unwrap_or_default();
+            let rows = select_rows(db, &select, plan).await?;
+            Ok(QueryResult::Select(Rows::new(columns, rows)))
+        }
+        dml::DMLStatement::Explain(explain) => handle_explain(db, explain, plan)
+            .await
+            .map(QueryResult::Explain),
+        dml::DMLStatement::Declare(declare) => {
+            handle_declare(db, declare, plan).await?;
+            Ok(QueryResult::Declare)
+        }
+        dml::DMLStatement::Fetch(fetch) => {
+            let columns = match db.cursors.statement(&fetch.name) {
+                Some(select) => output_columns(db, &select).await.unwrap_or_default(),
+                None => Vec::new(),
+            };
+            let rows = db.cursors.fetch(&fetch.name, fetch.count)?;
+            Ok(QueryResult::Fetch(Rows::new(columns, rows)))
+        }
+        dml::DMLStatement::Close(name) => {
+            if db.cursors.close(&name) {
+                Ok(QueryResult::Close)
+            } else {
+                Err(cursor::undefined_cursor(&name))
+            }
+        }
+        // Every statement commits on its own, so there is nothing left to do.
+        dml::DMLStatement::Commit => Ok(QueryResult::Commit),
+        dml::DMLStatement::CreateSequence(create) => {
+            let mut catalogue = db.catalogue.lock().await;
+            if catalogue.sequence_exists(&create.name) {
+                return Err(duplicate_sequence(&create.name));
+            }
+            catalogue.add_sequence(Sequence::new(create.name, create.start, create.increment));
+            catalogue.save(&db.storage)?;
+            Ok(QueryResult::CreateSequence)
+        }
+        dml::DMLStatement::NextValue(name) => {
+            let mut catalogue = db.catalogue.lock().await;
+            let value = next_value(db, &mut catalogue, &name)?;
+            let mut row = storage::Row::new();
+            row.insert("nextval".to_string(), serde_json::Value::String(value));
+            Ok(QueryResult::Select(Rows::new(
+                vec![("nextval".to_string(), BasicType::Int)],
+                vec![row],
+            )))
+        }
+        dml::DMLStatement::CreateView(create) => {
+            view::create(db, create).await?;
+            Ok(QueryResult::CreateView)
+        }
+        dml::DMLStatement::RefreshView(name) => {
+            view::refresh(db, &name).await?;
+            Ok(QueryResult::RefreshView)
+        }
+        dml::DMLStatement::CreateTrigger(create) => {
+            trigger::create(db, create).await?;
+            Ok(QueryResult::CreateTrigger)
+        }
     }
 }
 
-fn handle_insert(insert_statement: dml::DMLInsertStatement) -> serde_json::Value {
-    if !std::path::Path::new(&global_file_path()).exists() {
-        let _ = std::fs::create_dir_all(".adaptivedb");
-        let _ = std::fs::write(&global_file_path(), "[{
-            \"name\": \"Employee\",
-            \"rows\": []
-        }]");
+pub(crate) fn duplicate_sequence(name: &str) -> Error {
+    Error::new(
+        ErrorCode::DuplicateTable,
+        format!("Sequence {} already exists", name),
+    )
+}
+
+/// Advances the sequence `name`. The catalogue is saved right away, so a
+/// restart never hands out a value twice.
+fn next_value(db: &Database, catalogue: &mut Catalogue, name: &str) -> Result<String, Error> {
+    let sequence = catalogue
+        .get_sequence_mut(name)
+        .ok_or_else(|| undefined_sequence(name))?;
+    let value = sequence.next_value().ok_or_else(|| {
+        Error::new(
+            ErrorCode::NumericValueOutOfRange,
+            format!("Sequence {} reached its maximum value", name),
+        )
+    })?;
+    catalogue.save(&db.storage)?;
+    Ok(value.to_string())
+}
+
+pub(crate) fn undefined_sequence(name: &str) -> Error {
+    Error::new(
+        ErrorCode::UndefinedTable,
+        format!("Sequence {} does not exist", name),
+    )
+    .with_hint(format!("Create it with create sequence {};", name))
+}
+
+/// The literal `value` stands for, advancing its sequence for a
+/// `nextval('name')`.
+fn resolve_value(
+    db: &Database,
+    catalogue: &mut Catalogue,
+    value: &dml::DMLValue,
+) -> Result<String, Error> {
+    match value {
+        dml::DMLValue::NextValue(name) => next_value(db, catalogue, name),
+        value => Ok(value.literal().to_string()),
     }
+}
+
+/// The name and type of every column a select returns, or `None` if one of
+/// its tables no longer exists.
+pub async fn output_columns(
+    db: &Database,
+    select: &dml::DMLSelectStatement,
+) -> Option<Vec<(String, BasicType)>> {
+    let catalogue = db.catalogue.lock().await;
+    let tables: Option<Vec<&Table>> = select
+        .table_names()
+        .map(|table_name| catalogue.get_table(table_name))
+        .collect();
+    Some(plan::output_columns(select, &tables?))
+}
+
+async fn handle_insert(
+    db: &Database,
+    insert_statement: dml::DMLInsertStatement,
+    transaction: &mut Transaction,
+) -> Result<usize, Error> {
+    let mut row = storage::Row::new();
+    let triggers = {
+        let mut catalogue = db.catalogue.lock().await;
+        let table = catalogue
+            .get_table(&insert_statement.table_name)
+            .expect("Analyzing should have caught this error");
+        let table_name = table.name().to_string();
+        let is_versioned = table.history_table().is_some();
+        // Columns the insert leaves out get their default, if they have one.
+        let defaults: Vec<(String, BasicType, ColumnDefault)> = table
+            .columns()
+            .iter()
+            .filter(|column| !insert_statement.columns.iter().any(|c| c == column.name()))
+            .filter_map(|column| {
+                let default = column.default()?.clone();
+                Some((column.name().to_string(), column.data_type(), default))
+            })
+            .collect();
+
+        let mut values = Vec::new();
+        for (column, value) in insert_statement
+            .columns
+            .iter()
+            .zip(insert_statement.values.iter())
+        {
+            values.push((column.clone(), resolve_value(db, &mut catalogue, value)?));
+        }
+        for (column, data_type, default) in defaults {
+            let value = match default {
+                ColumnDefault::Now if is_versioned && column == catalogue::VALID_FROM => {
+                    transaction.system_time()
+                }
+                ColumnDefault::Value(value) => value,
+                ColumnDefault::Now => basic_types::now(&data_type).unwrap_or_default(),
+                ColumnDefault::Uuid => basic_types::random_uuid(),
+                ColumnDefault::Autoincrement => {
+                    let sequence = catalogue::sequence_name(&table_name, &column);
+                    next_value(db, &mut catalogue, &sequence)?
+                }
+            };
+            values.push((column, value));
+        }
 
-    let mut map = std::collections::HashMap::new();
-    for i in 0..insert_statement.columns.len() {
-        map.insert(
-            insert_statement.columns[i].clone(),
-            insert_statement.values[i].clone(),
-        );
+        let table = catalogue
+            .get_table(&table_name)
+            .expect("Analyzing should have caught this error");
+        for (column, value) in values {
+            let value = column_value(table, &column, &value)?;
+            row.insert(column, serde_json::Value::String(value));
+        }
+        check_not_null(table, &row)?;
+        trigger::Triggers::of(db, &catalogue, table, TriggerEvent::Insert)
+    };
+
+    let table_name = &insert_statement.table_name;
+    let savepoint = trigger::Savepoint::take(&triggers, transaction);
+    let result = async {
+        triggers
+            .fire(db, TriggerTiming::Before, None, Some(&row), transaction)
+            .await?;
+        {
+            let catalogue = db.catalogue.lock().await;
+            let table = catalogue
+                .get_table(table_name)
+                .expect("Analyzing should have caught this error");
+            let added = [row.clone()];
+            db.storage
+                .try_append_rows(table_name, vec![row.clone()], |rows, appended| {
+                    let changed: Vec<usize> = appended.collect();
+                    let violations = table_constraint_violations(db, table, rows, &changed)?;
+                    if violations.is_empty() {
+                        Ok(())
+                    } else {
+                        Err(Error::many(violations))
+                    }
+                })??;
+            transaction.record(table_name, changelog::Operation::Insert, None, Some(&row));
+            view::maintain(db, &catalogue, table_name, &[], &added)?;
+        }
+        triggers
+            .fire(db, TriggerTiming::After, None, Some(&row), transaction)
+            .await
+    }
+    .await;
+    if let Err(error) = result {
+        if let Some(savepoint) = savepoint {
+            savepoint.roll_back(db, transaction).await?;
+        }
+        return Err(error);
     }
 
-    let data = std::fs::read_to_string(&global_file_path()).unwrap();
-    let mut data: serde_json::Value = serde_json::from_str(&data).unwrap();
-    let table_name = insert_statement.table_name;
-    let table_index = data
-        .as_array()
-        .unwrap()
+    record_write(db, table_name, advisor::Write::Insert, 1);
+    Ok(1)
+}
+
+/// Checks that an insert sets every column of `table` that cannot be NULL
+/// and has no default.
+/// Fails for every column of `table` that cannot be NULL but is NULL in
+/// `row`, the row an insert built with its defaults.
+fn check_not_null(table: &Table, row: &storage::Row) -> Result<(), Error> {
+    let errors: Vec<Error> = table
+        .columns()
+        .iter()
+        .filter(|column| {
+            !column.is_nullable()
+                && row
+                    .get(column.name())
+                    .is_none_or(serde_json::Value::is_null)
+        })
+        .map(|column| not_null_violation(table, column))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::many(errors))
+    }
+}
+
+/// An error for every column of `table` that cannot be NULL, has no
+/// default and is not among the `columns` an insert sets.
+pub(crate) fn not_null_violations(table: &Table, columns: &[String]) -> Vec<Error> {
+    table
+        .columns()
+        .iter()
+        .filter(|column| {
+            !column.is_nullable()
+                && column.default().is_none()
+                && !columns.iter().any(|c| c == column.name())
+        })
+        .map(|column| not_null_violation(table, column))
+        .collect()
+}
+
+fn not_null_violation(table: &Table, column: &Column) -> Error {
+    Error::new(
+        ErrorCode::NotNullViolation,
+        format!(
+            "Column {} of table {} cannot be NULL",
+            column.name(),
+            table.name()
+        ),
+    )
+    .with_hint(format!(
+        "Give {} a value in the insert, or a @default in the schema",
+        column.name()
+    ))
+}
+
+/// An error for every constraint of `table` that one of the rows at
+/// `changed` in `rows` violates: its checks, unique keys, primary key and
+/// foreign keys.
+fn table_constraint_violations(
+    db: &Database,
+    table: &Table,
+    rows: &[storage::Row],
+    changed: &[usize],
+) -> Result<Vec<Error>, Error> {
+    let mut uniques: Vec<_> = table.primary_key_constraint().into_iter().collect();
+    uniques.extend(table.uniques().iter().cloned());
+    let mut errors = constraint_violations(table, table.checks(), &uniques, rows, changed);
+    errors.extend(foreign_key_violations(
+        db,
+        table,
+        table.foreign_keys(),
+        rows,
+        changed,
+    )?);
+    Ok(errors)
+}
+
+/// The values of `columns` in `row`, or None if one of them is NULL.
+fn key_of(row: &storage::Row, columns: &[String]) -> Option<Vec<String>> {
+    columns
         .iter()
-        .position(|x| x["name"] == table_name)
-        .unwrap();
-    let table = &mut data[table_index]["rows"];
-    table.as_array_mut().unwrap().push(serde_json::json!(map));
-    let data = serde_json::to_string_pretty(&data).unwrap();
-    let _ = std::fs::write(&global_file_path(), data);
+        .map(|column| Some(row.get(column)?.as_str()?.to_string()))
+        .collect()
+}
 
+/// The rows of `table_name`, which are `rows` if it is `table` itself.
+fn rows_of<'a>(
+    db: &Database,
+    table: &Table,
+    rows: &'a [storage::Row],
+    table_name: &str,
+) -> Result<Cow<'a, [storage::Row]>, Error> {
+    if table_name == table.name() {
+        return Ok(Cow::Borrowed(rows));
+    }
+    Ok(Cow::Owned(db.storage.scan(table_name)?.rows))
+}
 
-    serde_json::json!({
-        "success": true
+/// An error for every one of `foreign_keys` of `table` that one of the rows
+/// at `changed` in `rows` violates, by holding a key no row of the
+/// referenced table has. Keys with a NULL reference nothing.
+pub(crate) fn foreign_key_violations(
+    db: &Database,
+    table: &Table,
+    foreign_keys: &[ForeignKey],
+    rows: &[storage::Row],
+    changed: &[usize],
+) -> Result<Vec<Error>, Error> {
+    let mut errors = Vec::new();
+    for foreign_key in foreign_keys {
+        let referenced_rows = rows_of(db, table, rows, foreign_key.table())?;
+        let keys: HashSet<Vec<String>> = referenced_rows
+            .iter()
+            .filter_map(|row| key_of(row, foreign_key.referenced_columns()))
+            .collect();
+        let missing = changed
+            .iter()
+            .filter_map(|&index| key_of(&rows[index], foreign_key.columns()))
+            .find(|key| !keys.contains(key));
+        if let Some(missing) = missing {
+            errors.push(
+                Error::new(
+                    ErrorCode::ForeignKeyViolation,
+                    format!(
+                        "Key ({})=({}) of table {} violates foreign key constraint {}: it is not present in table {}",
+                        foreign_key.columns().join(", "),
+                        missing.join(", "),
+                        table.name(),
+                        foreign_key.name(),
+                        foreign_key.table()
+                    ),
+                )
+                .with_hint(format!("Insert the row of {} first", foreign_key.table())),
+            );
+        }
+    }
+    Ok(errors)
+}
+
+/// An error for every foreign key referencing `table` that a row of its
+/// table holds a key of, which is not among `rows` anymore.
+fn referencing_violations(
+    db: &Database,
+    catalogue: &Catalogue,
+    table: &Table,
+    rows: &[storage::Row],
+) -> Result<Vec<Error>, Error> {
+    let mut errors = Vec::new();
+    for referencing in catalogue.tables() {
+        let foreign_keys = referencing
+            .foreign_keys()
+            .iter()
+            .filter(|foreign_key| foreign_key.table() == table.name());
+        for foreign_key in foreign_keys {
+            let keys: HashSet<Vec<String>> = rows
+                .iter()
+                .filter_map(|row| key_of(row, foreign_key.referenced_columns()))
+                .collect();
+            let referencing_rows = rows_of(db, table, rows, referencing.name())?;
+            let missing = referencing_rows
+                .iter()
+                .filter_map(|row| key_of(row, foreign_key.columns()))
+                .find(|key| !keys.contains(key));
+            if let Some(missing) = missing {
+                errors.push(
+                    Error::new(
+                        ErrorCode::ForeignKeyViolation,
+                        format!(
+                            "Update or delete on table {} violates foreign key constraint {} of table {}: key ({})=({}) is still referenced",
+                            table.name(),
+                            foreign_key.name(),
+                            referencing.name(),
+                            foreign_key.referenced_columns().join(", "),
+                            missing.join(", ")
+                        ),
+                    )
+                    .with_hint(format!(
+                        "Delete or change the rows of {} that reference it first",
+                        referencing.name()
+                    )),
+                );
+            }
+        }
+    }
+    Ok(errors)
+}
+
+/// An error for every one of `checks` and `uniques` that one of the rows
+/// at `changed` in `rows` violates. Checks fail only if they are false, not
+/// if they are unknown, and unique keys with a NULL never clash.
+pub(crate) fn constraint_violations(
+    table: &Table,
+    checks: &[Check],
+    uniques: &[Unique],
+    rows: &[storage::Row],
+    changed: &[usize],
+) -> Vec<Error> {
+    let mut errors = Vec::new();
+    let column_type = |column: &str| table.get_column(column).map(|column| column.data_type());
+    for check in checks {
+        let Ok(expression) = CheckExpression::parse(check.expression()) else {
+            continue;
+        };
+        let violated = changed.iter().any(|&index| {
+            let value = |column: &str| rows[index].get(column).and_then(|value| value.as_str());
+            expression.holds(&value, &column_type) == Some(false)
+        });
+        if violated {
+            errors.push(
+                Error::new(
+                    ErrorCode::CheckViolation,
+                    format!(
+                        "Row of table {} violates check constraint {}: {}",
+                        table.name(),
+                        check.name(),
+                        check.expression()
+                    ),
+                )
+                .with_hint(format!(
+                    "Write values for which {} holds",
+                    check.expression()
+                )),
+            );
+        }
+    }
+
+    for unique in uniques {
+        let key = |row: &storage::Row| -> Option<Vec<String>> {
+            unique
+                .columns()
+                .iter()
+                .map(|column| Some(row.get(column)?.as_str()?.to_string()))
+                .collect()
+        };
+        let mut counts: std::collections::HashMap<Vec<String>, usize> =
+            std::collections::HashMap::new();
+        for row in rows {
+            if let Some(key) = key(row) {
+                *counts.entry(key).or_default() += 1;
+            }
+        }
+        let duplicate = changed
+            .iter()
+            .filter_map(|&index| key(&rows[index]))
+            .find(|key| counts[key] > 1);
+        if let Some(duplicate) = duplicate {
+            errors.push(
+                Error::new(
+                    ErrorCode::UniqueViolation,
+                    format!(
+                        "Duplicate key ({})=({}) violates unique constraint {} of table {}",
+                        unique.columns().join(", "),
+                        duplicate.join(", "),
+                        unique.name(),
+                        table.name()
+                    ),
+                )
+                .with_hint("Another row already has these values"),
+            );
+        }
+    }
+    errors
+}
+
+/// The stored form of `value` for a column of `table`: the value written
+/// the way its type stores it. Columns that do not exist keep the value as
+/// is, since analyzing reports them.
+/// Json values must also satisfy the JSON Schema of their column, if any.
+pub(crate) fn column_value(table: &Table, column: &str, value: &str) -> Result<String, Error> {
+    let Some(table_column) = table.get_column(column) else {
+        return Ok(value.to_string());
+    };
+    let stored = typed_value(&table_column.data_type(), column, value)?;
+    if let Some(schema) = table_column.json_schema() {
+        let document = serde_json::from_str(&stored).unwrap_or_default();
+        if let Err(violation) = json::validate(schema, &document) {
+            return Err(Error::new(
+                ErrorCode::CheckViolation,
+                format!(
+                    "Value of column {} does not match its JSON Schema: {}",
+                    column, violation
+                ),
+            ));
+        }
+    }
+    Ok(stored)
+}
+
+/// The stored form of a value of a type, compared with or written to
+/// `column`.
+pub(crate) fn typed_value(
+    data_type: &BasicType,
+    column: &str,
+    value: &str,
+) -> Result<String, Error> {
+    data_type.parse_value(value).map_err(|error| match error {
+        ValueError::Invalid => {
+            let error = Error::new(
+                ErrorCode::InvalidTextRepresentation,
+                format!(
+                    "Invalid {} value for column {}: \"{}\"",
+                    data_type, column, value
+                ),
+            );
+            match value_example(data_type) {
+                Some(example) => error.with_hint(example),
+                None => error,
+            }
+        }
+        ValueError::OutOfRange => Error::new(
+            ErrorCode::NumericValueOutOfRange,
+            format!(
+                "Value {} of column {} is out of range for {}",
+                value, column, data_type
+            ),
+        ),
     })
 }
 
-fn global_file_path() -> std::path::PathBuf {
-    ".adaptivedb/data.json".into()
+/// How values of a type are written, for types whose literals are easy to
+/// get wrong.
+fn value_example(data_type: &BasicType) -> Option<String> {
+    let example = match data_type {
+        BasicType::Bool => "Write booleans as true or false",
+        BasicType::Date => "Write dates as 2024-01-31",
+        BasicType::Timestamp => "Write timestamps as 2024-01-31 12:30:00",
+        BasicType::TimestampTz => "Write timestamps as 2024-01-31 12:30:00+02",
+        BasicType::Time => "Write times as 12:30:00",
+        BasicType::Uuid => "Write UUIDs as a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11",
+        BasicType::Array(_) => "Write arrays as {a,b,c}",
+        BasicType::Enum(enum_type) => {
+            return Some(format!(
+                "{} is one of {}",
+                enum_type.name(),
+                enum_type.labels().join(", ")
+            ))
+        }
+        _ => return None,
+    };
+    Some(example.to_string())
+}
+
+/// Applies `change` to the rows matching `predicates`, and returns the
+/// number of rows that matched. Unless `change` removes them, the changed
+/// rows must still satisfy the constraints of the table, or the table is
+/// left as it was. The triggers of the table for `event` fire for every
+/// matching row.
+async fn modify_rows(
+    db: &Database,
+    table_name: &str,
+    event: TriggerEvent,
+    predicates: &[dml::DMLPredicate],
+    transaction: &mut Transaction,
+    mut change: impl FnMut(&mut Vec<storage::Row>, usize),
+) -> Result<usize, Error> {
+    db.advisor.record_predicates(table_name, predicates);
+    let triggers = {
+        let catalogue = db.catalogue.lock().await;
+        let table = catalogue
+            .get_table(table_name)
+            .expect("Analyzing should have caught this error");
+        trigger::Triggers::of(db, &catalogue, table, event)
+    };
+
+    let savepoint = trigger::Savepoint::take(&triggers, transaction);
+    let result = async {
+        if triggers.has(TriggerTiming::Before) {
+            // The rows as the write will find and leave them.
+            let old: Vec<storage::Row> = {
+                let catalogue = db.catalogue.lock().await;
+                let table = catalogue
+                    .get_table(table_name)
+                    .expect("Analyzing should have caught this error");
+                let predicates = plan::type_predicates(predicates, table);
+                let rows = db.storage.scan(table_name)?.rows;
+                let matching = matching_rows(&rows, &predicates);
+                matching.iter().map(|index| rows[*index].clone()).collect()
+            };
+            let mut new = old.clone();
+            for index in (0..new.len()).rev() {
+                change(&mut new, index);
+            }
+            for (position, row) in old.iter().enumerate() {
+                let new = if new.len() == old.len() {
+                    Some(&new[position])
+                } else {
+                    None
+                };
+                triggers
+                    .fire(db, TriggerTiming::Before, Some(row), new, transaction)
+                    .await?;
+            }
+        }
+
+        let (removed, added) = write_rows(db, table_name, predicates, &mut change).await?;
+        let op = if added.is_empty() {
+            changelog::Operation::Delete
+        } else {
+            changelog::Operation::Update
+        };
+        for (position, row) in removed.iter().enumerate() {
+            transaction.record(table_name, op, Some(row), added.get(position));
+        }
+        {
+            let catalogue = db.catalogue.lock().await;
+            view::maintain(db, &catalogue, table_name, &removed, &added)?;
+            let table = catalogue
+                .get_table(table_name)
+                .expect("Analyzing should have caught this error");
+            temporal::archive(db, &catalogue, table, &removed, transaction)?;
+        }
+        for (position, row) in removed.iter().enumerate() {
+            triggers
+                .fire(
+                    db,
+                    TriggerTiming::After,
+                    Some(row),
+                    added.get(position),
+                    transaction,
+                )
+                .await?;
+        }
+        Ok(removed.len())
+    }
+    .await;
+    if result.is_err() {
+        if let Some(savepoint) = savepoint {
+            savepoint.roll_back(db, transaction).await?;
+        }
+    }
+    result
+}
+
+/// The positions of the rows matching `predicates`.
+fn matching_rows(rows: &[storage::Row], predicates: &[plan::TypedPredicate]) -> Vec<usize> {
+    rows.iter()
+        .enumerate()
+        .filter(|(_, row)| predicates.iter().all(|p| executor::evaluate(p, row)))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Applies `change` to the rows matching `predicates` as one write. Returns
+/// the matching rows as they were, and as they are unless `change` removed
+/// them.
+async fn write_rows(
+    db: &Database,
+    table_name: &str,
+    predicates: &[dml::DMLPredicate],
+    change: &mut impl FnMut(&mut Vec<storage::Row>, usize),
+) -> Result<(Vec<storage::Row>, Vec<storage::Row>), Error> {
+    let catalogue = db.catalogue.lock().await;
+    let table = catalogue
+        .get_table(table_name)
+        .expect("Analyzing should have caught this error");
+    let predicates = plan::type_predicates(predicates, table);
+
+    let (removed, added) = db.storage.try_update_rows(table_name, |rows| {
+        let row_count = rows.len();
+        let matching = matching_rows(rows, &predicates);
+        let removed: Vec<storage::Row> =
+            matching.iter().map(|index| rows[*index].clone()).collect();
+        // Go backwards so removing a row does not shift the ones still to visit.
+        for index in matching.iter().rev() {
+            change(rows, *index);
+        }
+        let mut violations = Vec::new();
+        let mut added = Vec::new();
+        if rows.len() == row_count {
+            violations = table_constraint_violations(db, table, rows, &matching)?;
+            added = matching.iter().map(|index| rows[*index].clone()).collect();
+        }
+        // Keys other rows reference must still be there.
+        violations.extend(referencing_violations(db, &catalogue, table, rows)?);
+        if !violations.is_empty() {
+            return Err(Error::many(violations));
+        }
+        Ok((removed, added))
+    })??;
+
+    db.invalidate_table(table_name);
+    Ok((removed, added))
+}
+
+async fn handle_update(
+    db: &Database,
+    update: dml::DMLUpdateStatement,
+    transaction: &mut Transaction,
+) -> Result<usize, Error> {
+    let assignments = {
+        let mut catalogue = db.catalogue.lock().await;
+        // Sequences advance once per statement, not once per row.
+        let values = update
+            .assignments
+            .iter()
+            .map(|(_, value)| resolve_value(db, &mut catalogue, value))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let table = catalogue
+            .get_table(&update.table_name)
+            .expect("Analyzing should have caught this error");
+        let mut assignments = update
+            .assignments
+            .iter()
+            .zip(values)
+            .map(|((column, _), value)| Ok((column.clone(), column_value(table, column, &value)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        // The new versions of rows of a system-versioned table are current
+        // from now on.
+        if table.history_table().is_some() {
+            assignments.push((catalogue::VALID_FROM.to_string(), transaction.system_time()));
+        }
+        assignments
+    };
+    let affected_rows = modify_rows(
+        db,
+        &update.table_name,
+        TriggerEvent::Update,
+        &update.predicates,
+        transaction,
+        |rows, index| {
+            for (column, value) in assignments.iter() {
+                rows[index].insert(column.clone(), serde_json::Value::String(value.clone()));
+            }
+        },
+    )
+    .await?;
+    record_write(
+        db,
+        &update.table_name,
+        advisor::Write::Update,
+        affected_rows,
+    );
+    Ok(affected_rows)
+}
+
+async fn handle_delete(
+    db: &Database,
+    delete: dml::DMLDeleteStatement,
+    transaction: &mut Transaction,
+) -> Result<usize, Error> {
+    let affected_rows = modify_rows(
+        db,
+        &delete.table_name,
+        TriggerEvent::Delete,
+        &delete.predicates,
+        transaction,
+        |rows, index| {
+            rows.remove(index);
+        },
+    )
+    .await?;
+    record_write(
+        db,
+        &delete.table_name,
+        advisor::Write::Delete,
+        affected_rows,
+    );
+    Ok(affected_rows)
+}
+
+/// Tells the adaptive components how many rows a write changed.
+fn record_write(db: &Database, table_name: &str, write: advisor::Write, rows: usize) {
+    db.advisor.record_write(table_name, write, rows);
+    db.layout.record_write(table_name, rows);
+}
+
+async fn plan(db: &Database, select: &dml::DMLSelectStatement) -> plan::PlanNode {
+    let sizes: Vec<plan::TableSize> = select
+        .table_names()
+        .map(|table_name| {
+            let table_data = db
+                .storage
+                .scan(table_name)
+                .unwrap_or_else(|_| storage::TableData::new(table_name.to_string()));
+            plan::TableSize::of(&table_data)
+        })
+        .collect();
+
+    let catalogue = db.catalogue.lock().await;
+    let tables: Vec<&Table> = select
+        .table_names()
+        .map(|table_name| {
+            catalogue
+                .get_table(table_name)
+                .expect("Analyzing should have caught this error")
+        })
+        .collect();
+    plan::plan_select(db, select, &tables, &sizes)
+}
+
+/// Tells the adaptive components which tables a select reads and how.
+async fn record_reads(db: &Database, select: &dml::DMLSelectStatement) {
+    let catalogue = db.catalogue.lock().await;
+    let tables: Vec<&Table> = select
+        .table_names()
+        .map(|table_name| {
+            catalogue
+                .get_table(table_name)
+                .expect("Analyzing should have caught this error")
+        })
+        .collect();
+    let predicates = plan::split_predicates(select, &tables);
+    for (table, predicates) in tables.iter().zip(predicates) {
+        db.advisor.record_predicates(table.name(), &predicates);
+        db.layout.record_read(table.name());
+    }
+}
+
+/// Runs a select and returns its rows. Selects run `plan` if given, which
+/// must be a plan for the select. Selects `as of` a past point read the
+/// rows the tables had then, and selects `for system_time` the versions of
+/// rows current in its period.
+pub async fn select_rows(
+    db: &Database,
+    select: &dml::DMLSelectStatement,
+    plan: Option<plan::PlanNode>,
+) -> Result<Vec<storage::Row>, Error> {
+    record_reads(db, select).await;
+    let mut plan = match plan {
+        Some(plan) => plan,
+        None => self::plan(db, select).await,
+    };
+    if let Some(as_of) = &select.as_of {
+        for (table_name, rows) in history::rows_as_of(db, select, as_of).await? {
+            plan::substitute(&mut plan, &table_name, &rows);
+        }
+    }
+    if let Some(period) = &select.system_time {
+        let rows = temporal::rows_between(db, &select.table_name, period).await?;
+        plan::substitute(&mut plan, &select.table_name, &rows);
+    }
+    Ok(executor::execute_plan(db, &mut plan)?)
+}
+
+/// Runs the select of a cursor and keeps its rows for fetching.
+async fn handle_declare(
+    db: &Database,
+    declare: dml::DMLDeclareStatement,
+    plan: Option<plan::PlanNode>,
+) -> Result<(), Error> {
+    let rows = select_rows(db, &declare.statement, plan).await?;
+    db.cursors.declare(&declare.name, declare.statement, rows)
+}
+
+async fn handle_explain(
+    db: &Database,
+    explain: dml::DMLExplainStatement,
+    plan: Option<plan::PlanNode>,
+) -> Result<serde_json::Value, Error> {
+    let mut plan = match plan {
+        Some(plan) => plan,
+        None => self::plan(db, &explain.statement).await,
+    };
+    if !explain.analyze {
+        return Ok(serde_json::json!({ "plan": plan }));
+    }
+
+    record_reads(db, &explain.statement).await;
+    let start = Instant::now();
+    executor::execute_plan(db, &mut plan)?;
+    Ok(serde_json::json!({
+        "plan": plan,
+        "execution_time_ms": start.elapsed().as_secs_f64() * 1000.0,
+    }))
 }
@@ -0,0 +1,1234 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use super::dml::{
+    DMLAggregate, DMLColumn, DMLOperator, DMLPredicate, DMLSelectStatement, DMLTarget, DMLValue,
+};
+use crate::catalogue::basic_types::{BasicType, MAX_DECIMAL_PRECISION};
+use crate::catalogue::{json::JsonAccess, IndexKind, Layout, Table};
+use crate::storage::{self, index, TableData};
+use crate::{Database, Error, ErrorCode};
+
+// Cost units follow the usual convention of one sequential page read = 1.0.
+const SEQ_PAGE_COST: f64 = 1.0;
+const RANDOM_PAGE_COST: f64 = 4.0;
+const CPU_TUPLE_COST: f64 = 0.01;
+const CPU_OPERATOR_COST: f64 = 0.0025;
+
+// Default selectivities, used until we keep statistics on column values.
+const EQUALITY_SELECTIVITY: f64 = 0.005;
+const INEQUALITY_SELECTIVITY: f64 = 1.0 - EQUALITY_SELECTIVITY;
+const RANGE_SELECTIVITY: f64 = 1.0 / 3.0;
+
+/// Elements an unnested array is assumed to have, until we keep statistics
+/// on arrays.
+const UNNEST_ELEMENTS: f64 = 10.0;
+
+/// Physical operator of a plan node.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "operator")]
+pub enum Operator {
+    SeqScan {
+        table: String,
+    },
+    /// Range selection through a secondary index, on the values of a column
+    /// or on what `path` reads out of them.
+    IndexScan {
+        table: String,
+        index: String,
+        column: String,
+        condition: String,
+        #[serde(skip)]
+        path: Option<JsonAccess>,
+        #[serde(skip)]
+        data_type: BasicType,
+        /// The predicates the index answers, which bound a single range.
+        #[serde(skip)]
+        predicates: Vec<TypedPredicate>,
+    },
+    /// Scan of a column layout table that reads only `columns` (all if
+    /// empty) and skips segments whose zone maps rule out `condition`.
+    ColumnScan {
+        table: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        columns: Vec<String>,
+        #[serde(skip_serializing_if = "String::is_empty")]
+        condition: String,
+        /// The range predicates whose zone maps are checked.
+        #[serde(skip)]
+        predicates: Vec<TypedPredicate>,
+    },
+    /// Selection through an inverted index of the rows whose array holds
+    /// the elements `predicates` look for. The selected rows are checked
+    /// against `predicates` again, as some predicates only narrow them down.
+    InvertedIndexScan {
+        table: String,
+        index: String,
+        column: String,
+        condition: String,
+        #[serde(skip)]
+        predicates: Vec<TypedPredicate>,
+    },
+    /// Range selection through the cracker column of an adaptive column.
+    CrackerScan {
+        table: String,
+        column: String,
+        condition: String,
+        #[serde(skip)]
+        data_type: BasicType,
+        #[serde(skip)]
+        predicates: Vec<TypedPredicate>,
+    },
+    /// Equi-join that hashes the rows of its first child and probes the hash
+    /// table with the rows of its second child. Keys are qualified column
+    /// names, as in `Employee.EmployeeID`.
+    HashJoin {
+        condition: String,
+        build_key: String,
+        probe_key: String,
+        /// Table of the build side if it is a single table, whose rows still
+        /// need their columns qualified.
+        #[serde(skip)]
+        build_table: Option<String>,
+        #[serde(skip)]
+        probe_table: Option<String>,
+    },
+    Filter {
+        condition: String,
+        #[serde(skip)]
+        predicates: Vec<TypedPredicate>,
+    },
+    /// Repeats every row for each element of the array in `column`, which
+    /// is the `alias` column of the repeated row.
+    Unnest {
+        column: String,
+        alias: String,
+    },
+    Projection {
+        columns: Vec<String>,
+        /// The column each output column reads, and what it reads of it.
+        #[serde(skip)]
+        sources: Vec<(String, DMLTarget)>,
+    },
+    /// Rows given in place of those of `table`, filtered by the predicates
+    /// the scan it replaces answered. Incremental views run their plans over
+    /// the rows a write changed this way.
+    Values {
+        table: String,
+        #[serde(skip)]
+        rows: Vec<storage::Row>,
+        #[serde(skip)]
+        predicates: Vec<TypedPredicate>,
+    },
+}
+
+/// A where clause predicate together with the type of the column it compares.
+#[derive(Debug, Clone)]
+pub struct TypedPredicate {
+    pub predicate: DMLPredicate,
+    pub data_type: BasicType,
+}
+
+/// Measurements taken while executing a plan node.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ActualStats {
+    pub rows: usize,
+    pub loops: usize,
+    pub time_ms: f64,
+    pub pages_read: usize,
+    /// Segments whose rows a column scan left out thanks to their zone
+    /// maps. Their pages are still read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments_skipped: Option<usize>,
+    /// Values a cracker scan looked at while cracking its column.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub values_cracked: Option<usize>,
+    /// How the executor changed the plan after a misestimate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reoptimized: Option<String>,
+}
+
+/// Identifies the logical result of a plan node, so that the cardinality the
+/// executor observes for it improves the estimates of later plans.
+#[derive(Debug, Clone)]
+pub struct Feedback {
+    pub shape: String,
+    /// Rows the observed selectivity is relative to: the size of the table
+    /// for an access path, or `None` for the larger input of a join.
+    pub input_rows: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanNode {
+    #[serde(flatten)]
+    pub operator: Operator,
+    pub estimated_rows: f64,
+    pub estimated_cost: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<ActualStats>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<PlanNode>,
+    #[serde(skip)]
+    pub feedback: Option<Feedback>,
+}
+
+impl PlanNode {
+    fn new(operator: Operator, estimated_rows: f64, estimated_cost: f64) -> PlanNode {
+        PlanNode {
+            operator,
+            estimated_rows,
+            estimated_cost,
+            actual: None,
+            children: Vec::new(),
+            feedback: None,
+        }
+    }
+
+    fn with_child(mut self, child: PlanNode) -> PlanNode {
+        self.children.push(child);
+        self
+    }
+}
+
+/// Size of a table as seen by the planner.
+pub struct TableSize {
+    pub rows: usize,
+    pub pages: usize,
+    /// Bytes taken up by each column, for costing column scans.
+    pub column_bytes: HashMap<String, usize>,
+}
+
+impl TableSize {
+    pub fn of(table: &TableData) -> TableSize {
+        TableSize {
+            rows: table.rows.len(),
+            pages: table.page_count(),
+            column_bytes: table.column_bytes(),
+        }
+    }
+
+    /// Pages a column scan reads, those of every column.
+    fn column_pages(&self) -> usize {
+        let bytes: usize = self.column_bytes.values().sum();
+        bytes.div_ceil(storage::PAGE_SIZE)
+    }
+}
+
+pub fn plan_select(
+    db: &Database,
+    select: &DMLSelectStatement,
+    tables: &[&Table],
+    sizes: &[TableSize],
+) -> PlanNode {
+    let joined = !select.joins.is_empty();
+    let predicates = split_predicates(select, tables);
+
+    // Scans only need the projected columns and the join keys, or all
+    // columns for `select *`.
+    let mut columns = vec![Vec::new(); tables.len()];
+    let mut note_column = |column: &str| {
+        if select.unnest_of(column).is_some() {
+            return;
+        }
+        let (position, column) = resolve(column, tables);
+        if !columns[position].contains(&column) {
+            columns[position].push(column);
+        }
+    };
+    if !select.columns.is_empty() {
+        for column in select.columns.iter() {
+            note_column(&column.name);
+        }
+        for join in select.joins.iter() {
+            note_column(&join.left_column);
+            note_column(&join.right_column);
+        }
+        for unnest in select.unnests.iter() {
+            note_column(&unnest.column);
+        }
+    }
+
+    let mut plan = plan_access(db, tables[0], &predicates[0], &columns[0], &sizes[0]);
+    let mut shape = access_shape(tables[0], &predicates[0]);
+    let mut plan_table = Some(tables[0].name().to_string());
+    for (position, join) in select.joins.iter().enumerate() {
+        let joined_position = position + 1;
+        let table = tables[joined_position];
+        let inner = plan_access(
+            db,
+            table,
+            &predicates[joined_position],
+            &columns[joined_position],
+            &sizes[joined_position],
+        );
+        let inner_shape = access_shape(table, &predicates[joined_position]);
+
+        let left = resolve(&join.left_column, &tables[..=joined_position]);
+        let right = resolve(&join.right_column, &tables[..=joined_position]);
+        let (inner_key, outer_key) = if left.0 == joined_position {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        let inner_key = qualify(tables[inner_key.0], &inner_key.1);
+        let outer_key = qualify(tables[outer_key.0], &outer_key.1);
+        let condition = format!("{} = {}", outer_key, inner_key);
+        shape = format!("({} join {} on {})", shape, inner_shape, condition);
+
+        plan = plan_hash_join(
+            db,
+            (plan, plan_table.take(), outer_key),
+            (inner, Some(table.name().to_string()), inner_key),
+            condition,
+            shape.clone(),
+        );
+    }
+
+    // The name of a column in the rows of the plan.
+    let row_column = |column: &str| {
+        if select.unnest_of(column).is_some() {
+            return column.to_string();
+        }
+        let (position, column) = resolve(column, tables);
+        if joined {
+            qualify(tables[position], &column)
+        } else {
+            column
+        }
+    };
+
+    for unnest in select.unnests.iter() {
+        let estimated_rows = plan.estimated_rows * UNNEST_ELEMENTS;
+        let cost = plan.estimated_cost + estimated_rows * CPU_TUPLE_COST;
+        plan = PlanNode::new(
+            Operator::Unnest {
+                column: row_column(&unnest.column),
+                alias: unnest.alias.clone(),
+            },
+            estimated_rows,
+            cost,
+        )
+        .with_child(plan);
+    }
+    // Predicates on unnested elements apply once there are elements.
+    let predicates: Vec<TypedPredicate> = select
+        .predicates
+        .iter()
+        .filter(|p| select.unnest_of(&p.column).is_some())
+        .map(|p| TypedPredicate {
+            predicate: p.clone(),
+            data_type: column_type(&p.column, select, tables).unwrap_or(BasicType::String),
+        })
+        .collect();
+    if !predicates.is_empty() {
+        let selectivity: f64 = predicates
+            .iter()
+            .map(|p| selectivity(p.predicate.operator))
+            .product();
+        let cost =
+            plan.estimated_cost + plan.estimated_rows * CPU_OPERATOR_COST * predicates.len() as f64;
+        let estimated_rows = (plan.estimated_rows * selectivity).max(1.0);
+        plan = PlanNode::new(
+            Operator::Filter {
+                condition: describe(&predicates),
+                predicates,
+            },
+            estimated_rows,
+            cost,
+        )
+        .with_child(plan);
+    }
+
+    if !select.columns.is_empty() {
+        let sources: Vec<(String, DMLTarget)> = select
+            .columns
+            .iter()
+            .map(|column| (row_column(&column.name), column.target.clone()))
+            .collect();
+        let columns = select
+            .columns
+            .iter()
+            .zip(sources.iter())
+            .map(|(column, (source, _))| match column.target {
+                DMLTarget::Value if column.alias.is_none() => source.clone(),
+                _ => column.output_name().to_string(),
+            })
+            .collect();
+        let cost = plan.estimated_cost + plan.estimated_rows * CPU_TUPLE_COST;
+        plan = PlanNode::new(
+            Operator::Projection { columns, sources },
+            plan.estimated_rows,
+            cost,
+        )
+        .with_child(plan);
+    }
+
+    plan
+}
+
+/// Names and types of the columns a select returns, in order. Columns of
+/// joined selects are qualified with their table, as in the result rows.
+pub fn output_columns(select: &DMLSelectStatement, tables: &[&Table]) -> Vec<(String, BasicType)> {
+    let joined = !select.joins.is_empty();
+    let name = |table: &Table, column: &str| {
+        if joined {
+            qualify(table, column)
+        } else {
+            column.to_string()
+        }
+    };
+    let unnested = select.unnests.iter().map(|unnest| {
+        let data_type = column_type(&unnest.alias, select, tables);
+        (unnest.alias.clone(), data_type.unwrap_or(BasicType::String))
+    });
+    if select.columns.is_empty() {
+        return tables
+            .iter()
+            .flat_map(|table| {
+                table
+                    .columns()
+                    .iter()
+                    .map(|column| (name(table, column.name()), column.data_type()))
+            })
+            .chain(unnested)
+            .collect();
+    }
+    select
+        .columns
+        .iter()
+        .map(|column| {
+            let (name, data_type) = output_column(select, column, tables, &name);
+            match &column.alias {
+                Some(alias) => (alias.clone(), data_type),
+                None => (name, data_type),
+            }
+        })
+        .collect()
+}
+
+/// Name and type of the item `column` of the select list of `select`,
+/// leaving aside its alias. `name` names the columns of tables.
+fn output_column(
+    select: &DMLSelectStatement,
+    column: &DMLColumn,
+    tables: &[&Table],
+    name: &dyn Fn(&Table, &str) -> String,
+) -> (String, BasicType) {
+    if let Some(aggregate) = column.aggregate {
+        let argument = column_type(&column.name, select, tables);
+        return (
+            aggregate.name().to_string(),
+            aggregate_type(aggregate, argument),
+        );
+    }
+    match &column.target {
+        DMLTarget::Length => return ("array_length".to_string(), BasicType::Int),
+        DMLTarget::Path(access) => return (access.output_name().to_string(), access.value_type()),
+        DMLTarget::Value | DMLTarget::AnyElement => {}
+    }
+    let data_type = column_type(&column.name, select, tables).unwrap_or(BasicType::String);
+    if select.unnest_of(&column.name).is_some() {
+        return (column.name.clone(), data_type);
+    }
+    let (position, column) = resolve(&column.name, tables);
+    (name(tables[position], &column), data_type)
+}
+
+/// The type of the values `aggregate` computes from a column of type
+/// `argument`, which `count(*)` does not have. Sums of decimals get the
+/// largest precision.
+pub fn aggregate_type(aggregate: DMLAggregate, argument: Option<BasicType>) -> BasicType {
+    match (aggregate, argument) {
+        (DMLAggregate::Count, _) => BasicType::Int,
+        (DMLAggregate::Avg, Some(BasicType::Int | BasicType::Float)) => BasicType::Float,
+        (DMLAggregate::Sum | DMLAggregate::Avg, Some(BasicType::Decimal { scale, .. })) => {
+            BasicType::Decimal {
+                precision: MAX_DECIMAL_PRECISION,
+                scale,
+            }
+        }
+        (_, argument) => argument.unwrap_or(BasicType::String),
+    }
+}
+
+/// The position in `group_by` of a column of a grouped select, which may
+/// name it with or without its table.
+pub fn group_position(column: &str, group_by: &[String], tables: &[&Table]) -> Option<usize> {
+    let resolved = resolve_column(column, tables).ok();
+    group_by.iter().position(|group| {
+        group == column || (resolved.is_some() && resolve_column(group, tables).ok() == resolved)
+    })
+}
+
+/// The type of a possibly qualified column of a select, or of a column one
+/// of its unnests adds.
+pub fn column_type(
+    column: &str,
+    select: &DMLSelectStatement,
+    tables: &[&Table],
+) -> Option<BasicType> {
+    if let Some(unnest) = select.unnest_of(column) {
+        let array_type = column_type(&unnest.column, select, tables)?;
+        return array_type.element_type().cloned();
+    }
+    let (position, column) = resolve_column(column, tables).ok()?;
+    tables[position]
+        .get_column(&column)
+        .map(|column| column.data_type())
+}
+
+/// Finds the table of a possibly qualified column among `tables`. Returns
+/// the position of the table and the unqualified column name.
+pub fn resolve_column(column: &str, tables: &[&Table]) -> Result<(usize, String), Error> {
+    if let Some((table_name, column_name)) = column.split_once('.') {
+        let Some(position) = tables.iter().position(|table| table.name() == table_name) else {
+            let names: Vec<&str> = tables.iter().map(|table| table.name()).collect();
+            return Err(Error::new(
+                ErrorCode::UndefinedTable,
+                format!("Table {} is not part of the query", table_name),
+            )
+            .with_hint(format!("The query reads {}", names.join(", "))));
+        };
+        if !tables[position].column_exists(column_name) {
+            return Err(Error::new(
+                ErrorCode::UndefinedColumn,
+                format!(
+                    "Column {} does not exist in table {}",
+                    column_name, table_name
+                ),
+            )
+            .with_hint(columns_hint(&tables[position..=position])));
+        }
+        return Ok((position, column_name.to_string()));
+    }
+
+    let mut matches = tables
+        .iter()
+        .enumerate()
+        .filter(|(_, table)| table.column_exists(column));
+    match (matches.next(), matches.next()) {
+        (Some((position, _)), None) => Ok((position, column.to_string())),
+        (Some((first, _)), Some(_)) => Err(Error::new(
+            ErrorCode::AmbiguousColumn,
+            format!("Column {} is ambiguous", column),
+        )
+        .with_hint(format!(
+            "Qualify it with its table, as in {}",
+            qualify(tables[first], column)
+        ))),
+        (None, _) => {
+            let names: Vec<&str> = tables.iter().map(|table| table.name()).collect();
+            Err(Error::new(
+                ErrorCode::UndefinedColumn,
+                format!(
+                    "Column {} does not exist in table {}",
+                    column,
+                    names.join(", ")
+                ),
+            )
+            .with_hint(columns_hint(tables)))
+        }
+    }
+}
+
+/// Names the columns of `tables`, for hints on columns that do not exist.
+fn columns_hint(tables: &[&Table]) -> String {
+    let columns: Vec<String> = tables
+        .iter()
+        .map(|table| {
+            let names: Vec<&str> = table.columns().iter().map(|column| column.name()).collect();
+            format!("{} has columns {}", table.name(), names.join(", "))
+        })
+        .collect();
+    columns.join("; ")
+}
+
+fn resolve(column: &str, tables: &[&Table]) -> (usize, String) {
+    resolve_column(column, tables).expect("Analyzing should have caught this error")
+}
+
+fn qualify(table: &Table, column: &str) -> String {
+    format!("{}.{}", table.name(), column)
+}
+
+/// Splits the where clause of a select into the predicates on each of its
+/// tables, with unqualified column names. Predicates on the columns unnests
+/// add are left out.
+pub fn split_predicates(select: &DMLSelectStatement, tables: &[&Table]) -> Vec<Vec<DMLPredicate>> {
+    let mut predicates = vec![Vec::new(); tables.len()];
+    for predicate in select.predicates.iter() {
+        if select.unnest_of(&predicate.column).is_some() {
+            continue;
+        }
+        let (position, column) = resolve(&predicate.column, tables);
+        predicates[position].push(DMLPredicate {
+            column,
+            ..predicate.clone()
+        });
+    }
+    predicates
+}
+
+/// Plans the cheapest way to read the rows of a table that satisfy
+/// `predicates`. `columns` are the columns the query needs, empty for all.
+fn plan_access(
+    db: &Database,
+    table: &Table,
+    predicates: &[DMLPredicate],
+    columns: &[String],
+    size: &TableSize,
+) -> PlanNode {
+    let rows = size.rows as f64;
+    let shape = access_shape(table, predicates);
+    let mut predicates = type_predicates(predicates, table);
+
+    let mut plan = match plan_cracker_scan(db, table, &mut predicates, size) {
+        Some(plan) => plan,
+        None if table.layout() == Layout::Column => {
+            plan_column_scan(table, columns, &predicates, size)
+        }
+        None => PlanNode::new(
+            Operator::SeqScan {
+                table: table.name().to_string(),
+            },
+            rows,
+            seq_scan_cost(size),
+        ),
+    };
+    if matches!(
+        plan.operator,
+        Operator::SeqScan { .. } | Operator::ColumnScan { .. }
+    ) {
+        if let Some(index_scan) = plan_index_scan(table, &mut predicates, size, &plan) {
+            plan = index_scan;
+        }
+    }
+    if matches!(
+        plan.operator,
+        Operator::SeqScan { .. } | Operator::ColumnScan { .. }
+    ) {
+        if let Some(index_scan) = plan_inverted_index_scan(table, &mut predicates, size, &plan) {
+            plan = index_scan;
+        }
+    }
+
+    if !predicates.is_empty() {
+        let selectivity: f64 = predicates
+            .iter()
+            .map(|p| selectivity(p.predicate.operator))
+            .product();
+        let condition = describe(&predicates);
+        let cost =
+            plan.estimated_cost + plan.estimated_rows * CPU_OPERATOR_COST * predicates.len() as f64;
+        let estimated_rows = (plan.estimated_rows * selectivity).max(1.0).min(rows);
+        plan = PlanNode::new(
+            Operator::Filter {
+                condition,
+                predicates,
+            },
+            estimated_rows,
+            cost,
+        )
+        .with_child(plan);
+    }
+
+    if let Some(selectivity) = db.statistics.selectivity(&shape) {
+        plan.estimated_rows = (rows * selectivity).max(1.0).min(rows);
+    }
+    plan.feedback = Some(Feedback {
+        shape,
+        input_rows: Some(rows),
+    });
+    plan
+}
+
+/// Shape of the rows of a table that satisfy `predicates`, whatever the
+/// compared values.
+fn access_shape(table: &Table, predicates: &[DMLPredicate]) -> String {
+    let mut conditions: Vec<String> = predicates
+        .iter()
+        .map(|p| format!("{} {}", p.column, p.operator))
+        .collect();
+    conditions.sort();
+    format!("{}[{}]", table.name(), conditions.join(", "))
+}
+
+/// Joins two inputs, given with their table if they read a single table and
+/// their join key, hashing the smaller one.
+fn plan_hash_join(
+    db: &Database,
+    outer: (PlanNode, Option<String>, String),
+    inner: (PlanNode, Option<String>, String),
+    condition: String,
+    shape: String,
+) -> PlanNode {
+    // Without statistics on join keys every row is assumed to find one
+    // partner on the other side, as when joining along a foreign key.
+    let input_rows = outer.0.estimated_rows.max(inner.0.estimated_rows);
+    let selectivity = db.statistics.selectivity(&shape).unwrap_or(1.0);
+    let estimated_rows = (input_rows * selectivity).max(1.0);
+
+    let (build, probe) = if inner.0.estimated_rows <= outer.0.estimated_rows {
+        (inner, outer)
+    } else {
+        (outer, inner)
+    };
+    let cost = build.0.estimated_cost
+        + probe.0.estimated_cost
+        + hash_join_cost(build.0.estimated_rows, probe.0.estimated_rows, estimated_rows);
+    let mut plan = PlanNode::new(
+        Operator::HashJoin {
+            condition,
+            build_key: build.2,
+            probe_key: probe.2,
+            build_table: build.1,
+            probe_table: probe.1,
+        },
+        estimated_rows,
+        cost,
+    )
+    .with_child(build.0)
+    .with_child(probe.0);
+    plan.feedback = Some(Feedback {
+        shape,
+        input_rows: None,
+    });
+    plan
+}
+
+/// Re-plans a hash join whose build side turned out to have
+/// `observed_build_rows` rows: the estimates of the join are redone with the
+/// observed count, and the inputs trade places if hashing the probe side is
+/// now cheaper. Returns a description of the change.
+pub fn replan_hash_join(node: &mut PlanNode, observed_build_rows: f64) -> String {
+    let estimated_build_rows = node.children[0].estimated_rows;
+    let probe_rows = node.children[1].estimated_rows;
+    let selectivity = node.estimated_rows / estimated_build_rows.max(probe_rows);
+    node.estimated_rows = (observed_build_rows.max(probe_rows) * selectivity).max(1.0);
+
+    let inputs_cost = node.children[0].estimated_cost + node.children[1].estimated_cost;
+    let keep_cost = hash_join_cost(observed_build_rows, probe_rows, node.estimated_rows);
+    let swap_cost = hash_join_cost(probe_rows, observed_build_rows, node.estimated_rows);
+    let mut description = format!(
+        "build side has {} rows, estimated {}",
+        observed_build_rows, estimated_build_rows
+    );
+    if swap_cost < keep_cost {
+        node.children.swap(0, 1);
+        if let Operator::HashJoin {
+            build_key,
+            probe_key,
+            build_table,
+            probe_table,
+            ..
+        } = &mut node.operator
+        {
+            std::mem::swap(build_key, probe_key);
+            std::mem::swap(build_table, probe_table);
+        }
+        description.push_str("; swapped build and probe sides");
+    }
+    node.estimated_cost = inputs_cost + keep_cost.min(swap_cost);
+    description
+}
+
+pub fn seq_scan_cost(size: &TableSize) -> f64 {
+    size.pages as f64 * SEQ_PAGE_COST + size.rows as f64 * CPU_TUPLE_COST
+}
+
+/// Cost of fetching `estimated_rows` rows through a secondary index. Every
+/// fetched row is assumed to need a random page read, up to the table size.
+pub fn index_scan_cost(size: &TableSize, estimated_rows: f64) -> f64 {
+    let search = (size.rows as f64 + 1.0).log2() * CPU_OPERATOR_COST;
+    let pages = estimated_rows.min(size.pages as f64);
+    search + pages * RANDOM_PAGE_COST + estimated_rows * CPU_TUPLE_COST
+}
+
+/// Cost of keeping an index up to date for a single written row. Index pages
+/// are assumed to stay cached, so a write pays for the search and the entry.
+pub fn index_maintenance_cost(size: &TableSize) -> f64 {
+    (size.rows as f64 + 1.0).log2() * CPU_OPERATOR_COST + CPU_TUPLE_COST
+}
+
+/// Cost of a hash join on top of its inputs: hashing every build row,
+/// probing with every probe row and emitting the joined rows.
+pub fn hash_join_cost(build_rows: f64, probe_rows: f64, output_rows: f64) -> f64 {
+    build_rows * (CPU_OPERATOR_COST + CPU_TUPLE_COST)
+        + probe_rows * CPU_OPERATOR_COST
+        + output_rows * CPU_TUPLE_COST
+}
+
+pub fn estimate_rows(size: &TableSize, operators: &[DMLOperator]) -> f64 {
+    let rows = size.rows as f64;
+    let selectivity: f64 = operators.iter().map(|operator| selectivity(*operator)).product();
+    (rows * selectivity).max(1.0).min(rows)
+}
+
+pub fn type_predicates(predicates: &[DMLPredicate], table: &Table) -> Vec<TypedPredicate> {
+    predicates
+        .iter()
+        .map(|predicate| TypedPredicate {
+            predicate: predicate.clone(),
+            data_type: table
+                .get_column(&predicate.column)
+                .map(|column| column.data_type())
+                .unwrap_or(BasicType::String),
+        })
+        .collect()
+}
+
+fn describe(predicates: &[TypedPredicate]) -> String {
+    predicates
+        .iter()
+        .map(|p| p.predicate.to_string())
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+/// Whether a predicate can be answered by a range selection on its column,
+/// or on what a path reads out of it. Parameters are bound to values of the
+/// compared type, so they always can.
+fn is_range_predicate(predicate: &TypedPredicate) -> bool {
+    let value_type = predicate.predicate.value_type(predicate.data_type.clone());
+    predicate.predicate.operator != DMLOperator::NotEquals
+        && predicate.predicate.operator.is_comparison()
+        && matches!(
+            predicate.predicate.target,
+            DMLTarget::Value | DMLTarget::Path(_)
+        )
+        && match &predicate.predicate.value {
+            DMLValue::Literal(value) => value_type.compare(value, value).is_some(),
+            DMLValue::Parameter(_) => true,
+            DMLValue::NextValue(_) => false,
+        }
+}
+
+/// Takes the range predicates on `target` of `column` that bound a single
+/// range out of `predicates`.
+fn take_range(
+    predicates: &mut Vec<TypedPredicate>,
+    column: &str,
+    target: &DMLTarget,
+) -> Vec<TypedPredicate> {
+    // Whether a predicate fits depends only on the sides already bounded,
+    // not on the values, which parameters do not have yet.
+    let mut range = index::Range::default();
+    let mut answered = Vec::new();
+    predicates.retain(|p| {
+        let in_range = p.predicate.column == column
+            && p.predicate.target == *target
+            && is_range_predicate(p)
+            && range.restrict(p.predicate.operator, "");
+        if in_range {
+            answered.push(p.clone());
+        }
+        !in_range
+    });
+    answered
+}
+
+/// The range bounded by predicates taken by `take_range`, once bound.
+pub fn range_of(predicates: &[TypedPredicate]) -> index::Range {
+    let mut range = index::Range::default();
+    for predicate in predicates {
+        range.restrict(predicate.predicate.operator, predicate.predicate.value.literal());
+    }
+    range
+}
+
+fn is_bound(predicates: &[TypedPredicate]) -> bool {
+    predicates
+        .iter()
+        .all(|p| matches!(p.predicate.value, DMLValue::Literal(_)))
+}
+
+/// Replaces the parameters in the predicates of a plan by their values in
+/// `parameters`, so that a plan cached for a prepared statement can run.
+pub fn bind(node: &mut PlanNode, parameters: &[String]) {
+    match &mut node.operator {
+        Operator::IndexScan { predicates, .. }
+        | Operator::InvertedIndexScan { predicates, .. }
+        | Operator::ColumnScan { predicates, .. }
+        | Operator::CrackerScan { predicates, .. }
+        | Operator::Filter { predicates, .. }
+        | Operator::Values { predicates, .. } => {
+            for predicate in predicates.iter_mut() {
+                predicate.predicate.value.bind(parameters);
+            }
+        }
+        Operator::SeqScan { .. }
+        | Operator::HashJoin { .. }
+        | Operator::Unnest { .. }
+        | Operator::Projection { .. } => {}
+    }
+    for child in node.children.iter_mut() {
+        bind(child, parameters);
+    }
+}
+
+/// Replaces the scans of `table` in a plan by `rows`, so that the plan
+/// computes its result over those rows instead of the whole table. The
+/// plan no longer measures the table, so it gives no feedback.
+pub fn substitute(node: &mut PlanNode, table: &str, rows: &[storage::Row]) {
+    node.feedback = None;
+    let predicates = match &node.operator {
+        Operator::SeqScan { table: scanned } if scanned == table => Vec::new(),
+        Operator::IndexScan {
+            table: scanned,
+            predicates,
+            ..
+        }
+        | Operator::InvertedIndexScan {
+            table: scanned,
+            predicates,
+            ..
+        }
+        | Operator::ColumnScan {
+            table: scanned,
+            predicates,
+            ..
+        }
+        | Operator::CrackerScan {
+            table: scanned,
+            predicates,
+            ..
+        } if scanned == table => predicates.clone(),
+        _ => {
+            for child in node.children.iter_mut() {
+                substitute(child, table, rows);
+            }
+            return;
+        }
+    };
+    node.operator = Operator::Values {
+        table: table.to_string(),
+        rows: rows.to_vec(),
+        predicates,
+    };
+}
+
+/// Plans a cracker scan if a predicate restricts an adaptive column to a
+/// range. The predicates the cracker scan answers are removed from
+/// `predicates`.
+fn plan_cracker_scan(
+    db: &Database,
+    table: &Table,
+    predicates: &mut Vec<TypedPredicate>,
+    size: &TableSize,
+) -> Option<PlanNode> {
+    let predicate = predicates.iter().find(|p| {
+        is_range_predicate(p)
+            && p.predicate.target == DMLTarget::Value
+            && table
+                .get_column(&p.predicate.column)
+                .is_some_and(|column| column.is_adaptive())
+    })?;
+    let column = predicate.predicate.column.clone();
+    let data_type = predicate.data_type.clone();
+    let cracked = take_range(predicates, &column, &DMLTarget::Value);
+
+    let operators: Vec<DMLOperator> = cracked.iter().map(|p| p.predicate.operator).collect();
+    let estimated_rows = estimate_rows(size, &operators);
+    let values_touched = if is_bound(&cracked) {
+        let range = range_of(&cracked);
+        db.cracking.estimate_values_touched(
+            table.name(),
+            &column,
+            &range,
+            size.rows,
+            db.storage.version(table.name()),
+        )
+    } else {
+        size.rows
+    };
+    let cost = size.pages as f64 * SEQ_PAGE_COST
+        + values_touched as f64 * CPU_OPERATOR_COST
+        + estimated_rows * CPU_TUPLE_COST;
+
+    Some(PlanNode::new(
+        Operator::CrackerScan {
+            table: table.name().to_string(),
+            column,
+            condition: describe(&cracked),
+            data_type,
+            predicates: cracked,
+        },
+        estimated_rows,
+        cost,
+    ))
+}
+
+/// Plans a scan of a column layout table. It returns `columns` and those
+/// the filter needs, and prunes segments on the range predicates, which
+/// stay in `predicates` since pruning does not remove every non-matching row.
+/// The scan reads every column of every segment all the same.
+fn plan_column_scan(
+    table: &Table,
+    columns: &[String],
+    predicates: &[TypedPredicate],
+    size: &TableSize,
+) -> PlanNode {
+    let mut columns = columns.to_vec();
+    if !columns.is_empty() {
+        for predicate in predicates {
+            if !columns.contains(&predicate.predicate.column) {
+                columns.push(predicate.predicate.column.clone());
+            }
+        }
+    }
+
+    let pruning: Vec<TypedPredicate> = predicates
+        .iter()
+        .filter(|p| is_range_predicate(p) && p.predicate.target == DMLTarget::Value)
+        .cloned()
+        .collect();
+    let cost = size.column_pages() as f64 * SEQ_PAGE_COST + size.rows as f64 * CPU_TUPLE_COST;
+    PlanNode::new(
+        Operator::ColumnScan {
+            table: table.name().to_string(),
+            columns,
+            condition: describe(&pruning),
+            predicates: pruning,
+        },
+        size.rows as f64,
+        cost,
+    )
+}
+
+/// Plans the cheapest index scan over the indexed columns or paths that
+/// predicates restrict to a range, if it is cheaper than `scan`. The
+/// predicates the index scan answers are removed from `predicates`.
+fn plan_index_scan(
+    table: &Table,
+    predicates: &mut Vec<TypedPredicate>,
+    size: &TableSize,
+    scan: &PlanNode,
+) -> Option<PlanNode> {
+    let mut best: Option<(f64, &crate::catalogue::Index, DMLTarget, BasicType)> = None;
+    for index in table.indexes() {
+        if index.kind() != IndexKind::BTree {
+            continue;
+        }
+        let target = match index.path() {
+            Some(path) => DMLTarget::Path(path.clone()),
+            None => DMLTarget::Value,
+        };
+        let indexed: Vec<&TypedPredicate> = predicates
+            .iter()
+            .filter(|p| p.predicate.column == index.column() && p.predicate.target == target)
+            .collect();
+        let operators: Vec<DMLOperator> = indexed
+            .iter()
+            .filter(|p| is_range_predicate(p))
+            .map(|p| p.predicate.operator)
+            .collect();
+        let Some(data_type) = indexed
+            .first()
+            .map(|p| p.predicate.value_type(p.data_type.clone()))
+        else {
+            continue;
+        };
+        if operators.is_empty() {
+            continue;
+        }
+        let cost = index_scan_cost(size, estimate_rows(size, &operators));
+        if best.as_ref().is_none_or(|(best_cost, ..)| cost < *best_cost) {
+            best = Some((cost, index, target, data_type));
+        }
+    }
+
+    let (cost, index, target, data_type) = best?;
+    if cost >= scan.estimated_cost {
+        return None;
+    }
+    let answered = take_range(predicates, index.column(), &target);
+    let operators: Vec<DMLOperator> = answered.iter().map(|p| p.predicate.operator).collect();
+
+    Some(PlanNode::new(
+        Operator::IndexScan {
+            table: table.name().to_string(),
+            index: index.name().to_string(),
+            column: index.column().to_string(),
+            condition: describe(&answered),
+            path: index.path().cloned(),
+            data_type,
+            predicates: answered,
+        },
+        estimate_rows(size, &operators),
+        index_scan_cost(size, estimate_rows(size, &operators)),
+    ))
+}
+
+/// Whether an inverted index on its column narrows down the rows that
+/// satisfy a predicate: those whose arrays hold an element the predicate
+/// looks for.
+pub fn is_element_predicate(predicate: &DMLPredicate) -> bool {
+    match predicate.target {
+        DMLTarget::Value => matches!(
+            predicate.operator,
+            DMLOperator::Contains | DMLOperator::Overlaps
+        ),
+        DMLTarget::AnyElement => predicate.operator == DMLOperator::Equals,
+        DMLTarget::Length | DMLTarget::Path(_) => false,
+    }
+}
+
+/// Plans a scan of an inverted index for the predicates on its column that
+/// look for elements, if it is cheaper than `scan`. The predicates the scan
+/// answers are removed from `predicates`.
+fn plan_inverted_index_scan(
+    table: &Table,
+    predicates: &mut Vec<TypedPredicate>,
+    size: &TableSize,
+    scan: &PlanNode,
+) -> Option<PlanNode> {
+    let index = table.indexes().iter().find(|index| {
+        index.kind() == IndexKind::Inverted
+            && predicates
+                .iter()
+                .any(|p| p.predicate.column == index.column() && is_element_predicate(&p.predicate))
+    })?;
+    let answered: Vec<TypedPredicate> = predicates
+        .iter()
+        .filter(|p| p.predicate.column == index.column() && is_element_predicate(&p.predicate))
+        .cloned()
+        .collect();
+    let operators: Vec<DMLOperator> = answered.iter().map(|_| DMLOperator::Equals).collect();
+    let estimated_rows = estimate_rows(size, &operators);
+    let cost = index_scan_cost(size, estimated_rows);
+    if cost >= scan.estimated_cost {
+        return None;
+    }
+    predicates
+        .retain(|p| !(p.predicate.column == index.column() && is_element_predicate(&p.predicate)));
+
+    Some(PlanNode::new(
+        Operator::InvertedIndexScan {
+            table: table.name().to_string(),
+            index: index.name().to_string(),
+            column: index.column().to_string(),
+            condition: describe(&answered),
+            predicates: answered,
+        },
+        estimated_rows,
+        cost,
+    ))
+}
+
+fn selectivity(operator: DMLOperator) -> f64 {
+    match operator {
+        DMLOperator::Equals | DMLOperator::Contains | DMLOperator::Overlaps => EQUALITY_SELECTIVITY,
+        DMLOperator::ContainedBy | DMLOperator::PathExists | DMLOperator::PathMatches => {
+            RANGE_SELECTIVITY
+        }
+        DMLOperator::NotEquals => INEQUALITY_SELECTIVITY,
+        DMLOperator::LessThan
+        | DMLOperator::LessThanOrEquals
+        | DMLOperator::GreaterThan
+        | DMLOperator::GreaterThanOrEquals => RANGE_SELECTIVITY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalogue::Column;
+    use crate::queryprocessing::dml;
+
+    fn employee_table() -> Table {
+        Table::new(
+            "Employee".to_string(),
+            vec![
+                Column::new("EmployeeID".to_string(), BasicType::String, false),
+                Column::new("DepartmentID".to_string(), BasicType::Int, true),
+            ],
+            vec![0],
+        )
+    }
+
+    #[test]
+    fn test_plan_select_with_filter_and_projection() {
+        let statement = dml::parse("select EmployeeID from Employee where DepartmentID > 2");
+        let Ok(dml::DMLStatement::Select(select)) = statement else {
+            panic!("expected a select statement");
+        };
+        let size = TableSize {
+            rows: 300,
+            pages: 10,
+            column_bytes: HashMap::new(),
+        };
+        let db = Database::temporary("plan_select");
+        let plan = plan_select(&db, &select, &[&employee_table()], &[size]);
+
+        assert!(matches!(plan.operator, Operator::Projection { .. }));
+        let filter = &plan.children[0];
+        assert!(matches!(filter.operator, Operator::Filter { .. }));
+        assert_eq!(filter.estimated_rows, 100.0);
+        let scan = &filter.children[0];
+        assert!(matches!(scan.operator, Operator::SeqScan { .. }));
+        assert_eq!(scan.estimated_rows, 300.0);
+        assert_eq!(scan.estimated_cost, 13.0);
+        assert!(plan.estimated_cost > filter.estimated_cost);
+    }
+
+    #[test]
+    fn test_plan_hash_join_and_replan() {
+        let salary = Table::new(
+            "Salary".to_string(),
+            vec![
+                Column::new("SalaryID".to_string(), BasicType::Int, false),
+                Column::new("EmployeeID".to_string(), BasicType::String, false),
+                Column::new("Amount".to_string(), BasicType::Float, false),
+            ],
+            vec![0],
+        );
+        let statement = dml::parse(
+            "select DepartmentID, Amount from Employee join Salary on Employee.EmployeeID = Salary.EmployeeID where Amount > 1000",
+        );
+        let Ok(dml::DMLStatement::Select(select)) = statement else {
+            panic!("expected a select statement");
+        };
+        let sizes = [
+            TableSize {
+                rows: 300,
+                pages: 10,
+                column_bytes: HashMap::new(),
+            },
+            TableSize {
+                rows: 3000,
+                pages: 100,
+                column_bytes: HashMap::new(),
+            },
+        ];
+        let db = Database::temporary("plan_hash_join");
+        let mut plan = plan_select(&db, &select, &[&employee_table(), &salary], &sizes);
+
+        let Operator::Projection { columns, .. } = &plan.operator else {
+            panic!("expected a projection");
+        };
+        assert_eq!(columns, &vec!["Employee.DepartmentID", "Salary.Amount"]);
+        let join = &mut plan.children[0];
+        let Operator::HashJoin {
+            build_key,
+            build_table,
+            ..
+        } = &join.operator
+        else {
+            panic!("expected a hash join");
+        };
+        // The 300 employees are hashed rather than the ~1000 salaries.
+        assert_eq!(build_key, "Employee.EmployeeID");
+        assert_eq!(build_table.as_deref(), Some("Employee"));
+        assert_eq!(join.estimated_rows, 1000.0);
+
+        let description = replan_hash_join(join, 30000.0);
+        assert!(description.contains("swapped"));
+        let Operator::HashJoin { build_key, .. } = &join.operator else {
+            panic!("expected a hash join");
+        };
+        assert_eq!(build_key, "Salary.EmployeeID");
+        assert!(matches!(join.children[0].operator, Operator::Filter { .. }));
+        assert_eq!(join.estimated_rows, 30000.0);
+    }
+}
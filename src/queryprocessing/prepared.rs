@@ -0,0 +1,391 @@
+//! Prepared statements. A statement is parsed, analyzed and, if it is a
+//! select, planned once. It can then run any number of times with values
+//! bound to its `$n` parameters. Parameters are bound after parsing, so their
+//! values never pass through the lexer and need no quoting.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use super::dml::{self, DMLDeclareStatement, DMLExplainStatement, DMLStatement, DMLValue};
+use super::plan::{self, PlanNode};
+use super::view;
+use crate::catalogue::{
+    basic_types::{format_array, BasicType, ValueError},
+    Catalogue, Table,
+};
+use crate::{Database, Error, ErrorCode};
+
+#[derive(Clone)]
+struct PreparedStatement {
+    statement: DMLStatement,
+    parameter_types: Vec<BasicType>,
+    /// Plan of a select, and the catalogue entries of its tables when it was
+    /// planned. The plan is redone once they change, as when an index is
+    /// created.
+    plan: Option<(PlanNode, String)>,
+}
+
+/// The prepared statements of a database, by handle.
+pub struct PreparedStatements {
+    statements: Mutex<HashMap<String, PreparedStatement>>,
+    next_handle: AtomicU64,
+}
+
+impl Default for PreparedStatements {
+    fn default() -> PreparedStatements {
+        PreparedStatements {
+            statements: Mutex::new(HashMap::new()),
+            next_handle: AtomicU64::new(1),
+        }
+    }
+}
+
+impl PreparedStatements {
+    /// Forgets a prepared statement. Returns whether it existed.
+    pub fn deallocate(&self, handle: &str) -> bool {
+        self.statements.lock().unwrap().remove(handle).is_some()
+    }
+}
+
+#[derive(Serialize)]
+pub struct Prepared {
+    pub handle: String,
+    /// Type of every parameter, starting with `$1`.
+    pub parameters: Vec<BasicType>,
+    #[serde(skip)]
+    pub statement: DMLStatement,
+}
+
+/// Parses, analyzes and plans a statement, and caches it under a new handle.
+pub async fn prepare(db: &Database, query: &str) -> Result<Prepared, Error> {
+    let mut statement = dml::parse(query)?;
+    view::expand(db, &mut statement).await?;
+    dml::analyze(db, statement.clone()).await?;
+
+    let parameter_types = {
+        let catalogue = db.catalogue.lock().await;
+        parameter_types(&statement, &catalogue)?
+    };
+    let plan = match select_of(&statement) {
+        Some(select) => {
+            let plan = super::plan(db, select).await;
+            let catalogue = db.catalogue.lock().await;
+            Some((plan, fingerprint(select, &catalogue)))
+        }
+        None => None,
+    };
+
+    let number = db.prepared.next_handle.fetch_add(1, Ordering::Relaxed);
+    let handle = format!("s{}", number);
+    let prepared = Prepared {
+        handle: handle.clone(),
+        parameters: parameter_types.clone(),
+        statement: statement.clone(),
+    };
+    db.prepared.statements.lock().unwrap().insert(
+        handle,
+        PreparedStatement {
+            statement,
+            parameter_types,
+            plan,
+        },
+    );
+    Ok(prepared)
+}
+
+/// Binds `parameters` to a prepared statement. Returns the statement, and its
+/// plan if one is cached and still valid.
+pub async fn bind_prepared(
+    db: &Database,
+    handle: &str,
+    parameters: &[serde_json::Value],
+) -> Result<(DMLStatement, Option<PlanNode>), Error> {
+    let prepared = db.prepared.statements.lock().unwrap().get(handle).cloned();
+    let Some(prepared) = prepared else {
+        return Err(Error::new(
+            ErrorCode::UndefinedPreparedStatement,
+            format!("Prepared statement {} does not exist", handle),
+        )
+        .with_hint("Prepare it again with POST /prepare"));
+    };
+    let values = bind_parameters(&prepared.parameter_types, parameters)?;
+
+    let mut statement = prepared.statement;
+    statement.bind(&values);
+    let plan = match (select_of(&statement), prepared.plan) {
+        (Some(select), Some((mut plan, planned_against))) => {
+            let catalogue = db.catalogue.lock().await;
+            if fingerprint(select, &catalogue) == planned_against {
+                plan::bind(&mut plan, &values);
+                Some(plan)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    Ok((statement, plan))
+}
+
+/// Binds `parameters` to a statement that was just parsed and analyzed.
+pub async fn bind(
+    db: &Database,
+    statement: &mut DMLStatement,
+    parameters: &[serde_json::Value],
+) -> Result<(), Error> {
+    let parameter_types = {
+        let catalogue = db.catalogue.lock().await;
+        parameter_types(statement, &catalogue)?
+    };
+    let values = bind_parameters(&parameter_types, parameters)?;
+    statement.bind(&values);
+    Ok(())
+}
+
+fn select_of(statement: &DMLStatement) -> Option<&dml::DMLSelectStatement> {
+    match statement {
+        DMLStatement::Select(select)
+        | DMLStatement::Explain(DMLExplainStatement {
+            statement: select, ..
+        })
+        | DMLStatement::Declare(DMLDeclareStatement {
+            statement: select, ..
+        }) => Some(select),
+        _ => None,
+    }
+}
+
+fn fingerprint(select: &dml::DMLSelectStatement, catalogue: &Catalogue) -> String {
+    let tables: Vec<Option<&Table>> = select
+        .table_names()
+        .map(|table_name| catalogue.get_table(table_name))
+        .collect();
+    serde_json::to_string(&tables).unwrap()
+}
+
+/// Infers the type of every parameter from the column it is assigned to or
+/// compared with.
+fn parameter_types(
+    statement: &DMLStatement,
+    catalogue: &Catalogue,
+) -> Result<Vec<BasicType>, Error> {
+    let mut types: Vec<Option<BasicType>> = vec![None; statement.parameter_count()];
+    let mut errors = Vec::new();
+    let mut note = |value: &DMLValue, data_type: BasicType| {
+        let DMLValue::Parameter(number) = value else {
+            return;
+        };
+        match &types[number - 1] {
+            None => types[number - 1] = Some(data_type),
+            Some(earlier) if *earlier != data_type => errors.push(Error::new(
+                ErrorCode::AmbiguousParameter,
+                format!(
+                    "Parameter ${} is used as both {} and {}",
+                    number, earlier, data_type
+                ),
+            )),
+            Some(_) => {}
+        }
+    };
+    let table = |table_name: &str| {
+        catalogue
+            .get_table(table_name)
+            .expect("Analyzing should have caught this error")
+    };
+    let column_type = |table: &Table, column: &str| {
+        table
+            .get_column(column)
+            .map(|column| column.data_type())
+            .unwrap_or(BasicType::String)
+    };
+
+    match statement {
+        DMLStatement::Insert(insert) => {
+            let table = table(&insert.table_name);
+            for (column, value) in insert.columns.iter().zip(insert.values.iter()) {
+                note(value, column_type(table, column));
+            }
+        }
+        DMLStatement::Update(update) => {
+            let table = table(&update.table_name);
+            for (column, value) in update.assignments.iter() {
+                note(value, column_type(table, column));
+            }
+            for predicate in update.predicates.iter() {
+                let data_type = column_type(table, &predicate.column);
+                note(&predicate.value, predicate.value_type(data_type));
+            }
+        }
+        DMLStatement::Delete(delete) => {
+            let table = table(&delete.table_name);
+            for predicate in delete.predicates.iter() {
+                let data_type = column_type(table, &predicate.column);
+                note(&predicate.value, predicate.value_type(data_type));
+            }
+        }
+        DMLStatement::Select(select)
+        | DMLStatement::Explain(DMLExplainStatement {
+            statement: select, ..
+        })
+        | DMLStatement::Declare(DMLDeclareStatement {
+            statement: select, ..
+        }) => {
+            let tables: Vec<&Table> = select.table_names().map(table).collect();
+            for predicate in select.predicates.iter() {
+                if let Some(data_type) = plan::column_type(&predicate.column, select, &tables) {
+                    note(&predicate.value, predicate.value_type(data_type));
+                }
+            }
+        }
+        DMLStatement::Fetch(_)
+        | DMLStatement::Close(_)
+        | DMLStatement::Commit
+        | DMLStatement::CreateSequence(_)
+        | DMLStatement::NextValue(_)
+        | DMLStatement::CreateView(_)
+        | DMLStatement::RefreshView(_)
+        | DMLStatement::CreateTrigger(_) => {}
+    }
+
+    for (position, data_type) in types.iter().enumerate() {
+        if data_type.is_none() {
+            errors.push(Error::new(
+                ErrorCode::UnusedParameter,
+                format!("Parameter ${} is not used", position + 1),
+            ));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(Error::many(errors));
+    }
+    Ok(types.into_iter().flatten().collect())
+}
+
+/// Converts parameters to the stored form of their types.
+fn bind_parameters(
+    types: &[BasicType],
+    parameters: &[serde_json::Value],
+) -> Result<Vec<String>, Error> {
+    if parameters.len() != types.len() {
+        return Err(Error::new(
+            ErrorCode::WrongParameterCount,
+            format!(
+                "Expected {} parameters, got {}",
+                types.len(),
+                parameters.len()
+            ),
+        ));
+    }
+
+    let mut values = Vec::new();
+    let mut errors = Vec::new();
+    for (position, (value, data_type)) in parameters.iter().zip(types).enumerate() {
+        match bind_parameter(position + 1, value, data_type) {
+            Ok(value) => values.push(value),
+            Err(error) => errors.push(error),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(Error::many(errors));
+    }
+    Ok(values)
+}
+
+fn bind_parameter(
+    number: usize,
+    value: &serde_json::Value,
+    data_type: &BasicType,
+) -> Result<String, Error> {
+    let text = match value {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Object(_) | serde_json::Value::Array(_)
+            if *data_type == BasicType::Json =>
+        {
+            value.to_string()
+        }
+        serde_json::Value::Array(elements) if data_type.element_type().is_some() => {
+            let elements: Vec<Option<String>> = elements
+                .iter()
+                .map(|element| match element {
+                    serde_json::Value::Null => None,
+                    serde_json::Value::String(text) => Some(text.clone()),
+                    element => Some(element.to_string()),
+                })
+                .collect();
+            format_array(&elements)
+        }
+        serde_json::Value::Number(number) => number.to_string(),
+        serde_json::Value::Bool(boolean) => boolean.to_string(),
+        serde_json::Value::Null => {
+            return Err(Error::new(
+                ErrorCode::NullParameter,
+                format!("Parameter ${} is null", number),
+            ))
+        }
+        _ => {
+            return Err(Error::new(
+                ErrorCode::InvalidParameter,
+                format!(
+                    "Parameter ${} must be a string, number, boolean or array",
+                    number
+                ),
+            ))
+        }
+    };
+    data_type.parse_value(&text).map_err(|error| match error {
+        ValueError::Invalid => Error::new(
+            ErrorCode::InvalidParameter,
+            format!(
+                "Parameter ${} is not a valid {}: {}",
+                number, data_type, value
+            ),
+        ),
+        ValueError::OutOfRange => Error::new(
+            ErrorCode::NumericValueOutOfRange,
+            format!(
+                "Parameter ${} is out of range for {}: {}",
+                number, data_type, value
+            ),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_bind_parameters() {
+        let types = [BasicType::Int, BasicType::String, BasicType::Float];
+        let values = bind_parameters(&types, &[json!(7), json!("O'Brien"), json!("2.5")]);
+        assert_eq!(values.unwrap(), vec!["7", "O'Brien", "2.5"]);
+
+        let error = bind_parameters(&types, &[json!(1.5), json!(null), json!("x")]).unwrap_err();
+        let codes: Vec<ErrorCode> = error.all().map(Error::code).collect();
+        assert_eq!(
+            codes,
+            vec![
+                ErrorCode::InvalidParameter,
+                ErrorCode::NullParameter,
+                ErrorCode::InvalidParameter
+            ]
+        );
+        assert!(error.message().starts_with("Parameter $1 is not a valid Int"));
+
+        let error = bind_parameters(&types, &[json!(1)]).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::WrongParameterCount);
+        assert_eq!(error.message(), "Expected 3 parameters, got 1");
+
+        let types = [BasicType::Array(Box::new(BasicType::Int))];
+        let values = bind_parameters(&types, &[json!([3, "04", null])]);
+        assert_eq!(values.unwrap(), vec!["{3,4,NULL}"]);
+        let values = bind_parameters(&types, &[json!("{1,2}")]);
+        assert_eq!(values.unwrap(), vec!["{1,2}"]);
+        let error = bind_parameters(&types, &[json!(["x"])]).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::InvalidParameter);
+    }
+}
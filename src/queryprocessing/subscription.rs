@@ -0,0 +1,184 @@
+//! Live queries: selects whose result is kept up to date by following the
+//! change log, sending the rows that committed writes add to and remove
+//! from it.
+
+use serde::Serialize;
+
+use super::dml::{self, DMLSelectStatement, DMLStatement};
+use super::{prepared, view};
+use crate::storage::Row;
+use crate::{Database, Error, ErrorCode};
+
+/// A select of a single table, and the position in the change log its
+/// result is up to date with.
+pub struct Subscription {
+    select: DMLSelectStatement,
+    lsn: u64,
+}
+
+/// A change to the result of a subscription.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Diff {
+    Insert { row: Row },
+    Update { before: Row, after: Row },
+    Delete { row: Row },
+}
+
+/// The changes one transaction made to the result of a subscription.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransactionDiff {
+    pub transaction: u64,
+    /// The LSN of the last change of the transaction.
+    pub lsn: u64,
+    pub committed_at: String,
+    pub diffs: Vec<Diff>,
+}
+
+impl Subscription {
+    /// Subscribes to a select with the values of its `$n` parameters.
+    /// Returns the subscription and the rows of the select.
+    pub async fn new(
+        db: &Database,
+        sql: &str,
+        params: &[serde_json::Value],
+    ) -> Result<(Subscription, Vec<Row>), Error> {
+        let mut statement = dml::parse(sql)?;
+        view::expand(db, &mut statement).await?;
+        dml::analyze(db, statement.clone()).await?;
+        prepared::bind(db, &mut statement, params).await?;
+        let DMLStatement::Select(select) = statement else {
+            return Err(Error::new(
+                ErrorCode::FeatureNotSupported,
+                "Only selects can be subscribed to",
+            ));
+        };
+        if !select.joins.is_empty() {
+            return Err(Error::new(
+                ErrorCode::FeatureNotSupported,
+                "Subscriptions cannot join tables",
+            )
+            .with_hint("Subscribe to every table, each with its own select"));
+        }
+        if select.as_of.is_some() || select.system_time.is_some() {
+            return Err(Error::new(
+                ErrorCode::FeatureNotSupported,
+                "Subscriptions cannot read past versions of tables",
+            )
+            .with_hint("Select the past versions, and subscribe to the current rows"));
+        }
+
+        // Writes store their rows before they commit them to the change
+        // log, so none may be halfway done while the rows are read.
+        let _read = db.statements.read().await;
+        let catalogue = db.catalogue.lock().await;
+        if catalogue.get_view(&select.table_name).is_some() {
+            return Err(Error::new(
+                ErrorCode::FeatureNotSupported,
+                format!(
+                    "Materialized view {} cannot be subscribed to",
+                    select.table_name
+                ),
+            )
+            .with_hint("Subscribe to the tables it reads instead"));
+        }
+        let lsn = db.changes.last_lsn();
+        let rows = view::evaluate(db, &catalogue, &select, None)?;
+        Ok((Subscription { select, lsn }, rows))
+    }
+
+    /// The LSN of the last change the subscription has seen.
+    pub fn lsn(&self) -> u64 {
+        self.lsn
+    }
+
+    /// The changes to the result of the select made by the transactions
+    /// that committed since the last call, oldest first. Transactions that
+    /// left the result as it was are left out.
+    pub async fn poll(&mut self, db: &Database) -> Result<Vec<TransactionDiff>, Error> {
+        let changes = db.changes(self.lsn, usize::MAX)?;
+        let catalogue = db.catalogue.lock().await;
+        let table_name = self.select.table_name.as_str();
+        let mut transactions: Vec<TransactionDiff> = Vec::new();
+        for change in changes {
+            self.lsn = change.lsn;
+            if change.table != table_name {
+                continue;
+            }
+            let result = |row: Option<Row>| match row {
+                Some(row) => {
+                    view::evaluate(db, &catalogue, &self.select, Some((table_name, &[row])))
+                }
+                None => Ok(Vec::new()),
+            };
+            let before = result(change.before)?;
+            let after = result(change.after)?;
+            if before == after {
+                continue;
+            }
+            let diffs = match (<[Row; 1]>::try_from(before), <[Row; 1]>::try_from(after)) {
+                (Ok([before]), Ok([after])) => vec![Diff::Update { before, after }],
+                (before, after) => {
+                    let before = before.map_or_else(|rows| rows, Vec::from);
+                    let after = after.map_or_else(|rows| rows, Vec::from);
+                    let deletes = before.into_iter().map(|row| Diff::Delete { row });
+                    deletes
+                        .chain(after.into_iter().map(|row| Diff::Insert { row }))
+                        .collect()
+                }
+            };
+            match transactions.last_mut() {
+                Some(last) if last.transaction == change.transaction => {
+                    last.lsn = change.lsn;
+                    last.diffs.extend(diffs);
+                }
+                _ => transactions.push(TransactionDiff {
+                    transaction: change.transaction,
+                    lsn: change.lsn,
+                    committed_at: change.committed_at,
+                    diffs,
+                }),
+            }
+        }
+        Ok(transactions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::changelog::{Operation, PendingChange};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_subscribe_during_write() {
+        let db = Arc::new(Database::temporary("subscribe_during_write"));
+        db.apply_schema("model C {\n  Id Int @id\n}").await.unwrap();
+
+        // A write that has stored its row but not yet logged it.
+        let write = db.statements.write().await;
+        let row: Row = serde_json::from_value(serde_json::json!({"Id": "1"})).unwrap();
+        db.storage.append_rows("C", vec![row.clone()]).unwrap();
+        let subscribing = tokio::spawn({
+            let db = db.clone();
+            async move { Subscription::new(&db, "select * from C;", &[]).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!subscribing.is_finished());
+        let change = PendingChange {
+            table: "C".to_string(),
+            op: Operation::Insert,
+            before: None,
+            after: Some(row.clone()),
+        };
+        db.changes.commit(&db.storage, vec![change]).unwrap();
+        drop(write);
+
+        // The row is in the result once, and not sent again as a change.
+        let (mut subscription, rows) = subscribing.await.unwrap().unwrap();
+        assert_eq!(rows, [row]);
+        assert_eq!(subscription.lsn(), 1);
+        assert!(subscription.poll(&db).await.unwrap().is_empty());
+    }
+}
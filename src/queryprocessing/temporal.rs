@@ -0,0 +1,86 @@
+//! System-versioned tables: the tables of `@@temporal` models, whose rows
+//! carry the period they are current in, `valid_from` until `valid_to`.
+//! Updates and deletes move the versions of rows they replace to the
+//! history table of the table, where `for system_time` selects find them.
+
+use std::cmp::Ordering;
+
+use super::dml::DMLSystemTime;
+use super::{view, Transaction};
+use crate::catalogue::basic_types::BasicType;
+use crate::catalogue::{self, Catalogue, Table};
+use crate::storage::changelog::Operation;
+use crate::storage::Row;
+use crate::{Database, Error};
+
+/// Adds the rows an update or delete of `table` replaced to its history
+/// table, current until the system time of `transaction`. Does nothing
+/// unless `table` is system-versioned.
+pub(super) fn archive(
+    db: &Database,
+    catalogue: &Catalogue,
+    table: &Table,
+    removed: &[Row],
+    transaction: &mut Transaction,
+) -> Result<(), Error> {
+    let Some(history_table) = table.history_table() else {
+        return Ok(());
+    };
+    if removed.is_empty() {
+        return Ok(());
+    }
+    let valid_to = serde_json::Value::String(transaction.system_time());
+    let versions: Vec<Row> = removed
+        .iter()
+        .map(|row| {
+            let mut version = row.clone();
+            version.insert(catalogue::VALID_TO.to_string(), valid_to.clone());
+            version
+        })
+        .collect();
+    db.storage.append_rows(history_table, versions.clone())?;
+    for version in versions.iter() {
+        transaction.record(history_table, Operation::Insert, None, Some(version));
+    }
+    view::maintain(db, catalogue, history_table, &[], &versions)?;
+    Ok(())
+}
+
+/// Every version of the rows of a system-versioned table that was current
+/// at some time of `period`: those of the table that became current by its
+/// end, and those of its history table that were still current after its
+/// start.
+pub(crate) async fn rows_between(
+    db: &Database,
+    table_name: &str,
+    period: &DMLSystemTime,
+) -> Result<Vec<Row>, Error> {
+    let history_table = {
+        let catalogue = db.catalogue.lock().await;
+        let table = catalogue
+            .get_table(table_name)
+            .expect("Analyzing should have caught this error");
+        table
+            .history_table()
+            .expect("Analyzing should have caught this error")
+            .to_string()
+    };
+    let mut rows = db.storage.scan(table_name)?.rows;
+    rows.extend(db.storage.scan(&history_table)?.rows);
+
+    let timestamp = BasicType::TimestampTz;
+    let compare = |row: &Row, column: &str, value: &str| {
+        row.get(column)
+            .and_then(|stored| stored.as_str())
+            .and_then(|stored| timestamp.compare(stored, value))
+    };
+    rows.retain(|row| {
+        let started = compare(row, catalogue::VALID_FROM, &period.to) != Some(Ordering::Greater);
+        let ended = match row.get(catalogue::VALID_TO) {
+            Some(_) => compare(row, catalogue::VALID_TO, &period.from) != Some(Ordering::Greater),
+            None => false,
+        };
+        started && !ended
+    });
+    Ok(rows)
+}
@@ -0,0 +1,381 @@
+//! Triggers: statements and Rust callbacks run for every row an insert,
+//! update or delete changes, before or after the row is written.
+//!
+//! A write with triggers runs as one unit with them. If a trigger, the
+//! write itself or a write of a trigger fails, the rows they changed are
+//! put back as they were.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::dml::{self, DMLCreateTriggerStatement, DMLStatement, DMLValue, TriggerRow};
+use super::{view, Transaction};
+use crate::catalogue::{Catalogue, Table, Trigger, TriggerEvent, TriggerTiming};
+use crate::storage::changelog::PendingChange;
+use crate::storage::Row;
+use crate::{Database, Error, ErrorCode, Record};
+
+/// Triggers may write to tables with triggers, up to this many levels deep.
+const MAX_DEPTH: usize = 16;
+
+/// A Rust trigger. An error rejects the write.
+pub type Callback = Arc<dyn Fn(&RowChange) -> Result<(), Error> + Send + Sync>;
+
+/// A row an insert, update or delete changes, as a trigger sees it.
+pub struct RowChange {
+    table: String,
+    timing: TriggerTiming,
+    event: TriggerEvent,
+    old: Option<Record>,
+    new: Option<Record>,
+}
+
+impl RowChange {
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn timing(&self) -> TriggerTiming {
+        self.timing
+    }
+
+    pub fn event(&self) -> TriggerEvent {
+        self.event
+    }
+
+    /// The row before an update or delete.
+    pub fn old_row(&self) -> Option<&Record> {
+        self.old.as_ref()
+    }
+
+    /// The row after an insert or update.
+    pub fn new_row(&self) -> Option<&Record> {
+        self.new.as_ref()
+    }
+}
+
+/// Callbacks with the timing they run at.
+type Timed = Vec<(TriggerTiming, Callback)>;
+
+/// The Rust triggers registered with a database, by table and event.
+#[derive(Default)]
+pub struct Callbacks {
+    callbacks: Mutex<HashMap<(String, TriggerEvent), Timed>>,
+}
+
+impl Callbacks {
+    pub fn register(
+        &self,
+        table_name: &str,
+        timing: TriggerTiming,
+        event: TriggerEvent,
+        callback: Callback,
+    ) {
+        let mut callbacks = self.callbacks.lock().unwrap();
+        callbacks
+            .entry((table_name.to_string(), event))
+            .or_default()
+            .push((timing, callback));
+    }
+
+    fn of(&self, table_name: &str, event: TriggerEvent) -> Timed {
+        let callbacks = self.callbacks.lock().unwrap();
+        callbacks
+            .get(&(table_name.to_string(), event))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// The triggers a write to a table fires.
+pub(crate) struct Triggers {
+    table: String,
+    event: TriggerEvent,
+    columns: Arc<[String]>,
+    /// Timing and statements of every SQL trigger.
+    statements: Vec<(TriggerTiming, Vec<String>)>,
+    callbacks: Timed,
+}
+
+impl Triggers {
+    /// The triggers of `table` for `event`, copied out of the catalogue so
+    /// that they can run once it is unlocked.
+    pub(crate) fn of(
+        db: &Database,
+        catalogue: &Catalogue,
+        table: &Table,
+        event: TriggerEvent,
+    ) -> Triggers {
+        Triggers {
+            table: table.name().to_string(),
+            event,
+            columns: table
+                .columns()
+                .iter()
+                .map(|column| column.name().to_string())
+                .collect(),
+            statements: catalogue
+                .triggers_of(table.name(), event)
+                .map(|trigger| (trigger.timing(), trigger.statements().to_vec()))
+                .collect(),
+            callbacks: db.triggers.of(table.name(), event),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.statements.is_empty() && self.callbacks.is_empty()
+    }
+
+    pub(crate) fn has(&self, timing: TriggerTiming) -> bool {
+        self.statements.iter().any(|(t, _)| *t == timing)
+            || self.callbacks.iter().any(|(t, _)| *t == timing)
+    }
+
+    /// Runs the triggers with `timing` for one changed row, SQL triggers
+    /// first, as part of the transaction of the write.
+    pub(crate) async fn fire(
+        &self,
+        db: &Database,
+        timing: TriggerTiming,
+        old: Option<&Row>,
+        new: Option<&Row>,
+        transaction: &mut Transaction,
+    ) -> Result<(), Error> {
+        if !self.has(timing) {
+            return Ok(());
+        }
+        if transaction.depth >= MAX_DEPTH {
+            return Err(Error::new(
+                ErrorCode::ProgramLimitExceeded,
+                format!(
+                    "Triggers on {} nest more than {} levels deep",
+                    self.table, MAX_DEPTH
+                ),
+            )
+            .with_hint("Check for triggers that write to each other's tables"));
+        }
+        for (_, statements) in self.statements.iter().filter(|(t, _)| *t == timing) {
+            for statement in statements {
+                let statement = bind(db, statement, old, new).await?;
+                transaction.depth += 1;
+                let result =
+                    Box::pin(super::execute_statement(db, statement, None, transaction)).await;
+                transaction.depth -= 1;
+                result?;
+            }
+        }
+        let change = RowChange {
+            table: self.table.clone(),
+            timing,
+            event: self.event,
+            old: old.map(|row| Record::new(self.columns.clone(), row.clone())),
+            new: new.map(|row| Record::new(self.columns.clone(), row.clone())),
+        };
+        for (_, callback) in self.callbacks.iter().filter(|(t, _)| *t == timing) {
+            callback(&change)?;
+        }
+        Ok(())
+    }
+}
+
+/// A trigger statement with its row references replaced by the values of
+/// the changed row, ready to run. Inserts leave the columns of NULLs out;
+/// comparing with or assigning a NULL fails, as statements have no NULL
+/// values.
+async fn bind(
+    db: &Database,
+    statement: &str,
+    old: Option<&Row>,
+    new: Option<&Row>,
+) -> Result<DMLStatement, Error> {
+    let (text, references) = dml::row_references(statement)?;
+    let mut statement = dml::parse(&format!("{};", text))?;
+    let values: Vec<Option<String>> = references
+        .iter()
+        .map(|(row, column)| {
+            let row = match row {
+                TriggerRow::Old => old,
+                TriggerRow::New => new,
+            };
+            row.and_then(|row| row.get(column))
+                .and_then(|value| value.as_str())
+                .map(str::to_string)
+        })
+        .collect();
+    let is_null = |value: &DMLValue| matches!(value, DMLValue::Parameter(number) if values[number - 1].is_none());
+    if let DMLStatement::Insert(insert) = &mut statement {
+        let (columns, insert_values) = insert
+            .columns
+            .drain(..)
+            .zip(insert.values.drain(..))
+            .filter(|(_, value)| !is_null(value))
+            .unzip();
+        insert.columns = columns;
+        insert.values = insert_values;
+    }
+    if let Some(DMLValue::Parameter(number)) = statement.values().into_iter().find(|v| is_null(v)) {
+        let (row, column) = &references[number - 1];
+        let row = match row {
+            TriggerRow::Old => "old",
+            TriggerRow::New => "new",
+        };
+        return Err(Error::new(
+            ErrorCode::NullParameter,
+            format!(
+                "Trigger statements cannot compare with or assign {}.{}, which is NULL",
+                row, column
+            ),
+        )
+        .with_hint("Triggers can only compare with and assign values that are set"));
+    }
+    let values: Vec<String> = values.into_iter().map(Option::unwrap_or_default).collect();
+    statement.bind(&values);
+    view::expand(db, &mut statement).await?;
+    dml::analyze(db, statement.clone()).await?;
+    Ok(statement)
+}
+
+/// Creates a trigger, after checking that its statements parse and read
+/// rows the event has.
+pub async fn create(db: &Database, create: DMLCreateTriggerStatement) -> Result<(), Error> {
+    for statement in create.statements.iter() {
+        let (text, references) = dml::row_references(statement)?;
+        let mut parsed = dml::parse(&format!("{};", text))?;
+        if !matches!(
+            parsed,
+            DMLStatement::Insert(_) | DMLStatement::Update(_) | DMLStatement::Delete(_)
+        ) {
+            return Err(Error::new(
+                ErrorCode::FeatureNotSupported,
+                format!(
+                    "Trigger {} can only run insert, update and delete statements",
+                    create.name
+                ),
+            ));
+        }
+        view::expand(db, &mut parsed).await?;
+        dml::analyze(db, parsed).await?;
+        let catalogue = db.catalogue.lock().await;
+        let table = table_of(&catalogue, &create.table_name)?;
+        for (row, column) in references {
+            let missing = match row {
+                TriggerRow::Old => create.event == TriggerEvent::Insert,
+                TriggerRow::New => create.event == TriggerEvent::Delete,
+            };
+            if missing {
+                let (name, event) = match row {
+                    TriggerRow::Old => ("old", "Insert"),
+                    TriggerRow::New => ("new", "Delete"),
+                };
+                return Err(Error::new(
+                    ErrorCode::UndefinedColumn,
+                    format!("{} triggers have no {} row", event, name),
+                ));
+            }
+            if !table.column_exists(&column) {
+                return Err(Error::new(
+                    ErrorCode::UndefinedColumn,
+                    format!("Column {} does not exist in table {}", column, table.name()),
+                ));
+            }
+        }
+    }
+
+    let mut catalogue = db.catalogue.lock().await;
+    table_of(&catalogue, &create.table_name)?;
+    if catalogue.trigger_exists(&create.name) {
+        return Err(Error::new(
+            ErrorCode::DuplicateObject,
+            format!("Trigger {} already exists", create.name),
+        ));
+    }
+    catalogue.add_trigger(Trigger::new(
+        create.name,
+        create.table_name,
+        create.timing,
+        create.event,
+        create.statements,
+    ));
+    catalogue.save(&db.storage)?;
+    Ok(())
+}
+
+fn table_of<'a>(catalogue: &'a Catalogue, table_name: &str) -> Result<&'a Table, Error> {
+    if catalogue.get_view(table_name).is_some() {
+        return Err(Error::new(
+            ErrorCode::WrongObjectType,
+            format!("Triggers cannot be created on view {}", table_name),
+        ));
+    }
+    catalogue.get_table(table_name).ok_or_else(|| {
+        Error::new(
+            ErrorCode::UndefinedTable,
+            format!("Table {} does not exist", table_name),
+        )
+    })
+}
+
+/// The point of a transaction before a write with triggers, to roll the
+/// transaction back to if the write fails.
+pub(crate) struct Savepoint(usize);
+
+impl Savepoint {
+    /// Marks the changes `transaction` has made so far, if `triggers` fire
+    /// at all.
+    pub(crate) fn take(triggers: &Triggers, transaction: &Transaction) -> Option<Savepoint> {
+        if triggers.is_empty() {
+            return None;
+        }
+        Some(Savepoint(transaction.changes.len()))
+    }
+
+    /// Reverts the changes `transaction` made since the savepoint, latest
+    /// first, and drops them from it. The statement holds the write lock,
+    /// so the rows are still as the changes left them.
+    pub(crate) async fn roll_back(
+        self,
+        db: &Database,
+        transaction: &mut Transaction,
+    ) -> Result<(), Error> {
+        let changes = transaction.changes.split_off(self.0);
+        let catalogue = db.catalogue.lock().await;
+        for change in changes.iter().rev() {
+            db.storage
+                .try_update_rows(&change.table, |rows| undo(rows, change))??;
+            db.invalidate_table(&change.table);
+            view::maintain(
+                db,
+                &catalogue,
+                &change.table,
+                change.after.as_slice(),
+                change.before.as_slice(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Reverts the row change `change` made to `rows`.
+fn undo(rows: &mut Vec<Row>, change: &PendingChange) -> Result<(), Error> {
+    let position = change
+        .after
+        .as_ref()
+        .and_then(|after| rows.iter().position(|row| row == after));
+    match (position, &change.before) {
+        (Some(position), None) => {
+            rows.remove(position);
+        }
+        (Some(position), Some(before)) => rows[position] = before.clone(),
+        (None, Some(before)) if change.after.is_none() => rows.push(before.clone()),
+        _ => {
+            return Err(Error::new(
+                ErrorCode::DataCorrupted,
+                format!(
+                    "A row of table {} changed while its write was rolled back",
+                    change.table
+                ),
+            ))
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,876 @@
+//! Views, selects stored in the catalogue under a name.
+//!
+//! Selects from a plain view are expanded into selects from the tables the
+//! view reads before they are analyzed, so they are planned like any other
+//! select. A materialized view keeps the rows of its select in a table of
+//! the same name instead. `refresh materialized view` recomputes them, and
+//! an incremental view is kept up to date by every write to the tables it
+//! reads: its plan runs once over the rows the write removed and once over
+//! those it added, and the difference is applied to the view.
+//!
+//! Aggregates and group by clauses are only supported in materialized
+//! views. Their selects read the grouped values of every row, which are
+//! folded into a row per group. An incremental view recomputes the groups
+//! the rows of a write fall in.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use super::dml::{
+    self, DMLAggregate, DMLColumn, DMLCreateViewStatement, DMLOperator, DMLPredicate,
+    DMLSelectStatement, DMLStatement, DMLTarget, DMLValue,
+};
+use super::{executor, plan};
+use crate::catalogue::basic_types::{self, BasicType, MAX_DECIMAL_PRECISION};
+use crate::catalogue::{Catalogue, Column, Refresh, Table, View};
+use crate::storage::{self, Row};
+use crate::{Database, Error, ErrorCode};
+
+/// The values of the group by columns of a row, NULL where they are None.
+type GroupKey = Vec<Option<String>>;
+
+/// Expands the plain views a statement reads into their selects, and
+/// rejects writes to views.
+pub async fn expand(db: &Database, statement: &mut DMLStatement) -> Result<(), Error> {
+    if matches!(statement, DMLStatement::CreateView(_)) && statement.parameter_count() > 0 {
+        return Err(Error::new(
+            ErrorCode::FeatureNotSupported,
+            "Views cannot have parameters",
+        )
+        .with_hint("Write the values into the select of the view"));
+    }
+    let grouped = match statement {
+        DMLStatement::Select(select)
+        | DMLStatement::Explain(dml::DMLExplainStatement {
+            statement: select, ..
+        })
+        | DMLStatement::Declare(dml::DMLDeclareStatement {
+            statement: select, ..
+        }) => select.is_grouped(),
+        DMLStatement::CreateView(create) => {
+            create.materialized.is_none() && create.statement.is_grouped()
+        }
+        _ => false,
+    };
+    if grouped {
+        return Err(Error::new(
+            ErrorCode::FeatureNotSupported,
+            "Aggregates and group by are only supported in materialized views",
+        )
+        .with_hint("Create a materialized view of the select and select from it"));
+    }
+    let catalogue = db.catalogue.lock().await;
+    match statement {
+        DMLStatement::Insert(dml::DMLInsertStatement { table_name, .. })
+        | DMLStatement::Update(dml::DMLUpdateStatement { table_name, .. })
+        | DMLStatement::Delete(dml::DMLDeleteStatement { table_name, .. }) => {
+            match catalogue.get_view(table_name) {
+                Some(view) => Err(read_only_view(view)),
+                None => Ok(()),
+            }
+        }
+        DMLStatement::Select(select)
+        | DMLStatement::Explain(dml::DMLExplainStatement {
+            statement: select, ..
+        })
+        | DMLStatement::Declare(dml::DMLDeclareStatement {
+            statement: select, ..
+        })
+        | DMLStatement::CreateView(DMLCreateViewStatement {
+            statement: select, ..
+        }) => expand_select(&catalogue, select),
+        DMLStatement::Fetch(_)
+        | DMLStatement::Close(_)
+        | DMLStatement::Commit
+        | DMLStatement::CreateSequence(_)
+        | DMLStatement::NextValue(_)
+        | DMLStatement::RefreshView(_)
+        | DMLStatement::CreateTrigger(_) => Ok(()),
+    }
+}
+
+fn read_only_view(view: &View) -> Error {
+    let error = Error::new(
+        ErrorCode::WrongObjectType,
+        format!("Cannot write to view {}", view.name()),
+    );
+    match view.materialized() {
+        Some(_) => error.with_hint(format!(
+            "Write to the tables it reads, and refresh it with refresh materialized view {};",
+            view.name()
+        )),
+        None => error.with_hint("Write to the tables it reads"),
+    }
+}
+
+/// Replaces a plain view in the from clause of `select` by the select of
+/// the view, merged with the rest of `select`. Views of views are expanded
+/// all the way down.
+fn expand_select(catalogue: &Catalogue, select: &mut DMLSelectStatement) -> Result<(), Error> {
+    for join in select.joins.iter() {
+        if catalogue
+            .get_view(&join.table_name)
+            .is_some_and(|view| view.materialized().is_none())
+        {
+            return Err(Error::new(
+                ErrorCode::FeatureNotSupported,
+                format!("View {} cannot be joined", join.table_name),
+            )
+            .with_hint(format!(
+                "Select from {} and join the tables to it",
+                join.table_name
+            )));
+        }
+    }
+    let Some(view) = catalogue.get_view(&select.table_name) else {
+        return Ok(());
+    };
+    if view.materialized().is_some() {
+        return Ok(());
+    }
+
+    let mut inner = view_select(view)?;
+    expand_select(catalogue, &mut inner)?;
+    // Joined tables may have columns of the same name as the view's table.
+    let qualified = !inner.joins.is_empty() || !select.joins.is_empty();
+    if qualified {
+        qualify_columns(&mut inner);
+    }
+    let view_columns = view_columns(catalogue, &inner, qualified);
+    let outer = std::mem::replace(select, inner);
+    select.as_of = outer.as_of;
+    select.system_time = outer.system_time;
+
+    // Columns of the tables the outer select joins to the view, which keep
+    // their names.
+    let joined: Vec<&Table> = outer
+        .joins
+        .iter()
+        .filter_map(|join| catalogue.get_table(&join.table_name))
+        .collect();
+    let unnested: Vec<String> = outer
+        .unnests
+        .iter()
+        .map(|unnest| unnest.alias.clone())
+        .collect();
+    let is_outer_column = |column: &str| {
+        joined.iter().any(|table| table.column_exists(column))
+            || unnested.iter().any(|alias| alias == column)
+    };
+    let resolve = |column: &str, target: &DMLTarget| -> Result<(String, DMLTarget), Error> {
+        let name = match column.split_once('.') {
+            Some((table_name, name)) if table_name == view.name() => name,
+            Some(_) => return Ok((column.to_string(), target.clone())),
+            None => column,
+        };
+        match view_columns
+            .iter()
+            .find(|view_column| view_column.output_name() == name)
+        {
+            Some(view_column) => compose(view, view_column, target),
+            None if !column.contains('.') && is_outer_column(column) => {
+                Ok((column.to_string(), target.clone()))
+            }
+            None => Err(Error::new(
+                ErrorCode::UndefinedColumn,
+                format!("Column {} does not exist in view {}", name, view.name()),
+            )
+            .with_hint(format!(
+                "View {} has the columns {}",
+                view.name(),
+                view_columns
+                    .iter()
+                    .map(DMLColumn::output_name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        }
+    };
+    // Joins and unnests take plain columns.
+    let resolve_value = |column: &str| -> Result<String, Error> {
+        match resolve(column, &DMLTarget::Value)? {
+            (column, DMLTarget::Value) => Ok(column),
+            _ => Err(computed_column(view, column)),
+        }
+    };
+
+    for mut join in outer.joins {
+        join.left_column = resolve_value(&join.left_column)?;
+        join.right_column = resolve_value(&join.right_column)?;
+        select.joins.push(join);
+    }
+    for mut unnest in outer.unnests {
+        unnest.column = resolve_value(&unnest.column)?;
+        select.unnests.push(unnest);
+    }
+    for mut predicate in outer.predicates {
+        (predicate.column, predicate.target) = resolve(&predicate.column, &predicate.target)?;
+        select.predicates.push(predicate);
+    }
+    select.group_by = outer
+        .group_by
+        .iter()
+        .map(|column| resolve_value(column))
+        .collect::<Result<_, Error>>()?;
+
+    if outer.columns.is_empty() {
+        // The columns of the view, then those the outer select adds.
+        if !select.columns.is_empty() || !select.joins.is_empty() {
+            select.columns = view_columns.clone();
+            for table in joined.iter() {
+                select
+                    .columns
+                    .extend(table.columns().iter().map(|column| DMLColumn {
+                        name: format!("{}.{}", table.name(), column.name()),
+                        target: DMLTarget::Value,
+                        alias: None,
+                        aggregate: None,
+                    }));
+            }
+            select
+                .columns
+                .extend(unnested.iter().map(|alias| DMLColumn {
+                    name: alias.clone(),
+                    target: DMLTarget::Value,
+                    alias: None,
+                    aggregate: None,
+                }));
+        }
+    } else {
+        select.columns = outer
+            .columns
+            .into_iter()
+            .map(|column| {
+                let output_name = column.output_name().to_string();
+                // count(*) reads no column.
+                let (name, target) = match column.name.as_str() {
+                    "*" => (column.name.clone(), column.target.clone()),
+                    name => resolve(name, &column.target)?,
+                };
+                Ok(DMLColumn {
+                    name,
+                    target,
+                    alias: Some(output_name),
+                    aggregate: column.aggregate,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+    }
+    Ok(())
+}
+
+/// What `target` reads of the view column `view_column`, in terms of the
+/// tables of the view.
+fn compose(
+    view: &View,
+    view_column: &DMLColumn,
+    target: &DMLTarget,
+) -> Result<(String, DMLTarget), Error> {
+    match (&view_column.target, target) {
+        (view_target, DMLTarget::Value) => Ok((view_column.name.clone(), view_target.clone())),
+        (DMLTarget::Value, target) => Ok((view_column.name.clone(), target.clone())),
+        _ => Err(computed_column(view, view_column.output_name())),
+    }
+}
+
+fn computed_column(view: &View, column: &str) -> Error {
+    Error::new(
+        ErrorCode::FeatureNotSupported,
+        format!(
+            "Column {} of view {} is computed and can only be read as it is",
+            column,
+            view.name()
+        ),
+    )
+}
+
+/// Parses the select of a view.
+fn view_select(view: &View) -> Result<DMLSelectStatement, Error> {
+    match dml::parse(view.query())? {
+        DMLStatement::Select(select) => Ok(select),
+        _ => Err(Error::new(
+            ErrorCode::InvalidTableDefinition,
+            format!("View {} is not defined by a select", view.name()),
+        )),
+    }
+}
+
+/// Qualifies the columns a select without joins reads with its table, so
+/// that they stay unambiguous once tables are joined to it.
+fn qualify_columns(select: &mut DMLSelectStatement) {
+    if !select.joins.is_empty() {
+        return;
+    }
+    let table_name = select.table_name.clone();
+    let unnested: Vec<String> = select
+        .unnests
+        .iter()
+        .map(|unnest| unnest.alias.clone())
+        .collect();
+    let qualify = |column: &mut String| {
+        if !column.contains('.') && !unnested.contains(column) {
+            *column = format!("{}.{}", table_name, column);
+        }
+    };
+    select
+        .columns
+        .iter_mut()
+        .for_each(|column| qualify(&mut column.name));
+    select
+        .unnests
+        .iter_mut()
+        .for_each(|unnest| qualify(&mut unnest.column));
+    select
+        .predicates
+        .iter_mut()
+        .for_each(|predicate| qualify(&mut predicate.column));
+    select.group_by.iter_mut().for_each(qualify);
+}
+
+/// The columns of an expanded view select, each named after the column of
+/// the view it is. Columns of tables are `qualified` with their table.
+fn view_columns(
+    catalogue: &Catalogue,
+    select: &DMLSelectStatement,
+    qualified: bool,
+) -> Vec<DMLColumn> {
+    let unqualified = |name: &str| match name.split_once('.') {
+        Some((_, name)) => name.to_string(),
+        None => name.to_string(),
+    };
+    if !select.columns.is_empty() {
+        return select
+            .columns
+            .iter()
+            .map(|column| DMLColumn {
+                alias: Some(unqualified(column.output_name())),
+                ..column.clone()
+            })
+            .collect();
+    }
+    let tables = select
+        .table_names()
+        .filter_map(|table_name| catalogue.get_table(table_name));
+    tables
+        .flat_map(|table| {
+            table.columns().iter().map(move |column| DMLColumn {
+                name: match qualified {
+                    true => format!("{}.{}", table.name(), column.name()),
+                    false => column.name().to_string(),
+                },
+                target: DMLTarget::Value,
+                alias: Some(column.name().to_string()),
+                aggregate: None,
+            })
+        })
+        .chain(select.unnests.iter().map(|unnest| DMLColumn {
+            name: unnest.alias.clone(),
+            target: DMLTarget::Value,
+            alias: Some(unnest.alias.clone()),
+            aggregate: None,
+        }))
+        .collect()
+}
+
+/// Creates a view. A materialized view gets a table holding the rows of its
+/// select right away.
+pub async fn create(db: &Database, create: DMLCreateViewStatement) -> Result<(), Error> {
+    if create.statement.as_of.is_some() || create.statement.system_time.is_some() {
+        return Err(Error::new(
+            ErrorCode::FeatureNotSupported,
+            "Views cannot read past versions of tables",
+        )
+        .with_hint("Select from the view with as of or for system_time instead"));
+    }
+    dml::analyze(db, DMLStatement::Select(create.statement.clone())).await?;
+    let mut catalogue = db.catalogue.lock().await;
+    if catalogue.table_exists(&create.name) || catalogue.get_view(&create.name).is_some() {
+        return Err(Error::new(
+            ErrorCode::DuplicateTable,
+            format!("Table or view {} already exists", create.name),
+        ));
+    }
+
+    let tables = tables_of(&catalogue, &create.statement);
+    let mut columns: Vec<Column> = Vec::new();
+    for (name, data_type) in plan::output_columns(&create.statement, &tables) {
+        let name = unqualified(&name).to_string();
+        if columns.iter().any(|column| column.name() == name) {
+            return Err(Error::new(
+                ErrorCode::InvalidTableDefinition,
+                format!(
+                    "Column {} appears more than once in view {}",
+                    name, create.name
+                ),
+            )
+            .with_hint("Give the columns of the select distinct names with as"));
+        }
+        columns.push(Column::new(name, data_type, true));
+    }
+    if create.materialized == Some(Refresh::Incremental) {
+        // Maintenance finds the stored row of a group by its columns.
+        let statement = &create.statement;
+        let unselected = (0..statement.group_by.len()).find(|position| {
+            !statement.columns.iter().any(|column| {
+                column.aggregate.is_none()
+                    && plan::group_position(&column.name, &statement.group_by, &tables)
+                        == Some(*position)
+            })
+        });
+        if let Some(position) = unselected {
+            return Err(Error::new(
+                ErrorCode::FeatureNotSupported,
+                format!(
+                    "Incremental view {} must select its group by column {}",
+                    create.name, statement.group_by[position]
+                ),
+            )
+            .with_hint("Select every column of the group by clause"));
+        }
+    }
+
+    if create.materialized.is_some() {
+        let rows = evaluate(db, &catalogue, &create.statement, None)?;
+        db.storage
+            .update_rows(&create.name, |stored| *stored = rows)?;
+        catalogue.add_table(Table::new(create.name.clone(), columns, Vec::new()));
+        db.cracking.reset_table(&create.name);
+    }
+    catalogue.add_view(View::new(create.name, create.query, create.materialized));
+    catalogue.save(&db.storage)?;
+    Ok(())
+}
+
+/// Recomputes the rows of a materialized view.
+pub async fn refresh(db: &Database, name: &str) -> Result<(), Error> {
+    let catalogue = db.catalogue.lock().await;
+    let Some(view) = catalogue.get_view(name) else {
+        return Err(Error::new(
+            ErrorCode::UndefinedTable,
+            format!("View {} does not exist", name),
+        ));
+    };
+    if view.materialized().is_none() {
+        return Err(Error::new(
+            ErrorCode::WrongObjectType,
+            format!("View {} is not materialized", name),
+        )
+        .with_hint("Plain views are computed whenever they are read"));
+    }
+    recompute(db, &catalogue, view)
+}
+
+/// Replaces the rows of a materialized view by those its select returns
+/// now, and passes the change on to the views reading it.
+fn recompute(db: &Database, catalogue: &Catalogue, view: &View) -> Result<(), Error> {
+    let mut select = view_select(view)?;
+    expand_select(catalogue, &mut select)?;
+    let rows = evaluate(db, catalogue, &select, None)?;
+    let removed = db.storage.update_rows(view.name(), |stored| {
+        std::mem::replace(stored, rows.clone())
+    })?;
+    db.invalidate_table(view.name());
+    maintain(db, catalogue, view.name(), &removed, &rows)
+}
+
+/// Keeps the incremental views reading `table_name` up to date after a
+/// write to it removed the rows `removed` and added the rows `added`. An
+/// update removes the old rows and adds the new ones.
+///
+/// Views reading the table more than once, or reading it together with
+/// another materialized view, are recomputed, since the difference of their
+/// rows does not follow from that of the table alone.
+pub(crate) fn maintain(
+    db: &Database,
+    catalogue: &Catalogue,
+    table_name: &str,
+    removed: &[Row],
+    added: &[Row],
+) -> Result<(), Error> {
+    if removed.is_empty() && added.is_empty() {
+        return Ok(());
+    }
+    for view in catalogue.views() {
+        if view.materialized() != Some(Refresh::Incremental) {
+            continue;
+        }
+        let mut select = view_select(view)?;
+        expand_select(catalogue, &mut select)?;
+        let reads = select
+            .table_names()
+            .filter(|name| *name == table_name)
+            .count();
+        if reads == 0 {
+            continue;
+        }
+        let reads_views = select
+            .table_names()
+            .filter(|name| catalogue.get_view(name).is_some())
+            .count();
+        if reads > 1 || (reads_views > 0 && select.table_names().count() > 1) {
+            recompute(db, catalogue, view)?;
+            continue;
+        }
+        if select.is_grouped() {
+            maintain_groups(db, catalogue, view, &select, table_name, removed, added)?;
+            continue;
+        }
+
+        let old = evaluate(db, catalogue, &select, Some((table_name, removed)))?;
+        let new = evaluate(db, catalogue, &select, Some((table_name, added)))?;
+        if old.is_empty() && new.is_empty() {
+            continue;
+        }
+        db.storage.update_rows(view.name(), |rows| {
+            // Rows may repeat, so only one copy goes per removed row.
+            for row in old.iter() {
+                if let Some(position) = rows.iter().position(|stored| stored == row) {
+                    rows.remove(position);
+                }
+            }
+            rows.extend(new.iter().cloned());
+        })?;
+        db.invalidate_table(view.name());
+        maintain(db, catalogue, view.name(), &old, &new)?;
+    }
+    Ok(())
+}
+
+/// Recomputes the groups of a grouped incremental view that the rows
+/// `removed` and `added` of `table_name` fall in, and passes the change on
+/// to the views reading it.
+fn maintain_groups(
+    db: &Database,
+    catalogue: &Catalogue,
+    view: &View,
+    select: &DMLSelectStatement,
+    table_name: &str,
+    removed: &[Row],
+    added: &[Row],
+) -> Result<(), Error> {
+    let tables = tables_of(catalogue, select);
+    let input = grouped_input(select);
+    let mut keys: Vec<GroupKey> = Vec::new();
+    for rows in [removed, added] {
+        for row in evaluate(db, catalogue, &input, Some((table_name, rows)))? {
+            let key = group_key(select, &row);
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+    if keys.is_empty() {
+        return Ok(());
+    }
+
+    let mut new = Vec::new();
+    for key in keys.iter() {
+        let mut group = input.clone();
+        for (column, value) in select.group_by.iter().zip(key) {
+            if let (Some(value), None) = (value, select.unnest_of(column)) {
+                group.predicates.push(DMLPredicate {
+                    column: column.clone(),
+                    target: DMLTarget::Value,
+                    operator: DMLOperator::Equals,
+                    value: DMLValue::Literal(value.clone()),
+                });
+            }
+        }
+        // NULLs are not equal to anything, so they are matched here.
+        let rows: Vec<Row> = evaluate(db, catalogue, &group, None)?
+            .into_iter()
+            .filter(|row| group_key(select, row) == *key)
+            .collect();
+        if select.group_by.is_empty() || !rows.is_empty() {
+            new.push(group_row(select, &tables, key, &rows)?);
+        }
+    }
+
+    // The stored row of a group has the values of its columns.
+    let names: Vec<String> = (0..select.group_by.len())
+        .map(|position| {
+            select
+                .columns
+                .iter()
+                .find(|column| {
+                    column.aggregate.is_none()
+                        && plan::group_position(&column.name, &select.group_by, &tables)
+                            == Some(position)
+                })
+                .map(|column| unqualified(column.output_name()).to_string())
+                .expect("Creating the view should have caught this error")
+        })
+        .collect();
+    let stored_key = |row: &Row| -> GroupKey {
+        names
+            .iter()
+            .map(|name| {
+                row.get(name)
+                    .and_then(|value| value.as_str())
+                    .map(str::to_string)
+            })
+            .collect()
+    };
+    let old = db.storage.update_rows(view.name(), |stored| {
+        let (old, kept): (Vec<Row>, Vec<Row>) = std::mem::take(stored)
+            .into_iter()
+            .partition(|row| keys.contains(&stored_key(row)));
+        *stored = kept;
+        stored.extend(new.iter().cloned());
+        old
+    })?;
+    db.invalidate_table(view.name());
+    maintain(db, catalogue, view.name(), &old, &new)
+}
+
+/// The select reading the values a grouped select folds: its group by
+/// columns, then the column of each of its aggregates, each named after its
+/// position among them. `count(*)` reads no column.
+fn grouped_input(select: &DMLSelectStatement) -> DMLSelectStatement {
+    let groups = select.group_by.iter().enumerate();
+    let arguments = select
+        .columns
+        .iter()
+        .enumerate()
+        .filter(|(_, column)| column.aggregate.is_some() && column.name != "*")
+        .map(|(position, column)| (select.group_by.len() + position, &column.name));
+    DMLSelectStatement {
+        columns: groups
+            .chain(arguments)
+            .map(|(position, name)| DMLColumn {
+                name: name.clone(),
+                target: DMLTarget::Value,
+                alias: Some(position.to_string()),
+                aggregate: None,
+            })
+            .collect(),
+        group_by: Vec::new(),
+        ..select.clone()
+    }
+}
+
+/// The group of a row of the `grouped_input` of `select`.
+fn group_key(select: &DMLSelectStatement, row: &Row) -> GroupKey {
+    (0..select.group_by.len())
+        .map(|position| {
+            row.get(&position.to_string())
+                .and_then(|value| value.as_str())
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+/// Folds the rows of the `grouped_input` of `select` into a row per group.
+/// Groups keep the order they first appear in. Without a group by clause
+/// there is a single group, even of no rows.
+fn aggregate(
+    select: &DMLSelectStatement,
+    tables: &[&Table],
+    rows: Vec<Row>,
+) -> Result<Vec<Row>, Error> {
+    let mut keys: Vec<GroupKey> = Vec::new();
+    let mut groups: HashMap<GroupKey, Vec<Row>> = HashMap::new();
+    if select.group_by.is_empty() {
+        keys.push(Vec::new());
+        groups.insert(Vec::new(), Vec::new());
+    }
+    for row in rows {
+        let key = group_key(select, &row);
+        groups
+            .entry(key)
+            .or_insert_with_key(|key| {
+                keys.push(key.clone());
+                Vec::new()
+            })
+            .push(row);
+    }
+    keys.iter()
+        .map(|key| group_row(select, tables, key, &groups[key]))
+        .collect()
+}
+
+/// The row of `select` for the group `key`, whose `grouped_input` rows are
+/// `rows`.
+fn group_row(
+    select: &DMLSelectStatement,
+    tables: &[&Table],
+    key: &GroupKey,
+    rows: &[Row],
+) -> Result<Row, Error> {
+    let mut row = Row::new();
+    for (position, column) in select.columns.iter().enumerate() {
+        let value = match column.aggregate {
+            None => plan::group_position(&column.name, &select.group_by, tables)
+                .and_then(|position| key[position].clone()),
+            Some(_) if column.name == "*" => Some(rows.len().to_string()),
+            Some(aggregate) => {
+                let argument = (select.group_by.len() + position).to_string();
+                let values: Vec<&str> = rows
+                    .iter()
+                    .filter_map(|row| row.get(&argument)?.as_str())
+                    .collect();
+                let data_type =
+                    plan::column_type(&column.name, select, tables).unwrap_or(BasicType::String);
+                fold(aggregate, &data_type, &values)?
+            }
+        };
+        // NULL columns are left out of stored rows.
+        if let Some(value) = value {
+            row.insert(
+                unqualified(column.output_name()).to_string(),
+                serde_json::Value::String(value),
+            );
+        }
+    }
+    Ok(row)
+}
+
+/// Folds the values of a column of type `data_type` that are not NULL. Only
+/// `count` has a value when there are none.
+fn fold(
+    aggregate: DMLAggregate,
+    data_type: &BasicType,
+    values: &[&str],
+) -> Result<Option<String>, Error> {
+    if aggregate == DMLAggregate::Count {
+        return Ok(Some(values.len().to_string()));
+    }
+    if values.is_empty() {
+        return Ok(None);
+    }
+    let out_of_range = || {
+        Error::new(
+            ErrorCode::NumericValueOutOfRange,
+            format!(
+                "{} is out of range for type {}",
+                aggregate.name(),
+                plan::aggregate_type(aggregate, Some(data_type.clone()))
+            ),
+        )
+    };
+    let count = values.len();
+    let value = match (aggregate, data_type) {
+        (DMLAggregate::Min | DMLAggregate::Max, _) => {
+            let order = |left: &&&str, right: &&&str| {
+                data_type.compare(left, right).unwrap_or(Ordering::Equal)
+            };
+            let value = match aggregate {
+                DMLAggregate::Min => values.iter().min_by(order),
+                _ => values.iter().max_by(order),
+            };
+            value.map(|value| value.to_string())
+        }
+        (_, BasicType::Int) => {
+            let sum: i128 = values
+                .iter()
+                .filter_map(|value| value.parse::<i64>().ok())
+                .map(i128::from)
+                .sum();
+            match aggregate {
+                DMLAggregate::Sum => {
+                    Some(i64::try_from(sum).map_err(|_| out_of_range())?.to_string())
+                }
+                _ => Some((sum as f64 / count as f64).to_string()),
+            }
+        }
+        (_, BasicType::Float) => {
+            let sum: f64 = values
+                .iter()
+                .filter_map(|value| value.parse::<f64>().ok())
+                .sum();
+            match aggregate {
+                DMLAggregate::Sum => Some(sum.to_string()),
+                _ => Some((sum / count as f64).to_string()),
+            }
+        }
+        (_, BasicType::Decimal { scale, .. }) => {
+            let mut sum: i128 = 0;
+            for value in values {
+                let value =
+                    basic_types::parse_decimal(value, *scale).map_err(|_| out_of_range())?;
+                sum = sum.checked_add(value).ok_or_else(out_of_range)?;
+            }
+            let value = match aggregate {
+                DMLAggregate::Sum => sum,
+                // Rounded half away from zero, as decimals are.
+                _ => {
+                    let count = count as i128;
+                    match (sum % count).abs() * 2 >= count {
+                        true => sum / count + sum.signum(),
+                        false => sum / count,
+                    }
+                }
+            };
+            if value.unsigned_abs() >= 10u128.pow(MAX_DECIMAL_PRECISION as u32) {
+                return Err(out_of_range());
+            }
+            Some(basic_types::format_decimal(value, *scale))
+        }
+        _ => {
+            return Err(Error::new(
+                ErrorCode::DatatypeMismatch,
+                format!("{} needs a number, but got {}", aggregate.name(), data_type),
+            ))
+        }
+    };
+    Ok(value)
+}
+
+/// Runs a view select and returns its rows as the table of the view stores
+/// them. With `rows`, the select reads those rows in place of the table
+/// they belong to.
+pub(crate) fn evaluate(
+    db: &Database,
+    catalogue: &Catalogue,
+    select: &DMLSelectStatement,
+    rows: Option<(&str, &[Row])>,
+) -> Result<Vec<Row>, Error> {
+    if select.is_grouped() {
+        let input = evaluate(db, catalogue, &grouped_input(select), rows)?;
+        return aggregate(select, &tables_of(catalogue, select), input);
+    }
+    let tables = tables_of(catalogue, select);
+    let sizes: Vec<plan::TableSize> = select
+        .table_names()
+        .map(|table_name| {
+            let table_data = db
+                .storage
+                .scan(table_name)
+                .unwrap_or_else(|_| storage::TableData::new(table_name.to_string()));
+            plan::TableSize::of(&table_data)
+        })
+        .collect();
+    let mut plan = plan::plan_select(db, select, &tables, &sizes);
+    if let Some((table_name, rows)) = rows {
+        plan::substitute(&mut plan, table_name, rows);
+    }
+    let rows = executor::execute_plan(db, &mut plan)?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            // NULL columns are left out of stored rows.
+            row.into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(column, value)| (unqualified(&column).to_string(), value))
+                .collect()
+        })
+        .collect())
+}
+
+fn tables_of<'a>(catalogue: &'a Catalogue, select: &DMLSelectStatement) -> Vec<&'a Table> {
+    select
+        .table_names()
+        .map(|table_name| {
+            catalogue
+                .get_table(table_name)
+                .expect("Analyzing should have caught this error")
+        })
+        .collect()
+}
+
+fn unqualified(column: &str) -> &str {
+    match column.split_once('.') {
+        Some((_, column)) => column,
+        None => column,
+    }
+}
@@ -0,0 +1,392 @@
+//! The change log: every row a committed statement inserted, updated or
+//! deleted, in commit order. Each change has a log sequence number (LSN),
+//! counting up from 1, and the id of the transaction that made it, which is
+//! shared by the changes of one statement and of the triggers it fired.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use super::{Row, Storage};
+use crate::catalogue::basic_types::{self, BasicType};
+
+/// Name of the file holding the change log, one JSON change per line.
+const LOG_FILE: &str = "changes.log";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// A change to one row of a table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    pub lsn: u64,
+    pub transaction: u64,
+    /// When the transaction committed, as a `Timestamptz` value.
+    pub committed_at: String,
+    pub table: String,
+    pub op: Operation,
+    /// The row before an update or delete.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<Row>,
+    /// The row after an insert or update.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<Row>,
+}
+
+/// A change of a transaction that has not committed yet.
+#[derive(Debug, Clone)]
+pub struct PendingChange {
+    pub table: String,
+    pub op: Operation,
+    pub before: Option<Row>,
+    pub after: Option<Row>,
+}
+
+/// Positions of the next change and transaction, and of the changes in
+/// the log file.
+struct Positions {
+    lsn: u64,
+    transaction: u64,
+    /// The LSN of every change in the log with the byte offset its line
+    /// starts at, in LSN order.
+    offsets: Vec<(u64, u64)>,
+    /// Length of the log file.
+    end: u64,
+}
+
+/// The positions of a change, read without the rest of it.
+#[derive(Deserialize)]
+struct Position {
+    lsn: u64,
+    transaction: u64,
+}
+
+pub struct ChangeLog {
+    next: Mutex<Positions>,
+    /// The LSN of the last change, for waiting on new ones.
+    last_lsn: watch::Sender<u64>,
+}
+
+impl ChangeLog {
+    /// Opens the change log of a database, continuing after its last change.
+    /// Reads the positions of every change once, so later reads can go
+    /// straight to the changes they want.
+    pub fn open(storage: &Storage) -> Result<ChangeLog, std::io::Error> {
+        let mut offsets = Vec::new();
+        let mut end = 0;
+        let mut last = None;
+        let path = storage.path(LOG_FILE);
+        if path.exists() {
+            let mut reader = BufReader::new(File::open(path)?);
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                let length = reader.read_until(b'\n', &mut line)? as u64;
+                if length == 0 {
+                    break;
+                }
+                if !line.trim_ascii().is_empty() {
+                    let position: Position = serde_json::from_slice(&line)?;
+                    offsets.push((position.lsn, end));
+                    last = Some(position);
+                }
+                end += length;
+            }
+        }
+        let (lsn, transaction) = last.map_or((0, 0), |last| (last.lsn, last.transaction));
+        Ok(ChangeLog {
+            next: Mutex::new(Positions {
+                lsn: lsn + 1,
+                transaction: transaction + 1,
+                offsets,
+                end,
+            }),
+            last_lsn: watch::Sender::new(lsn),
+        })
+    }
+
+    /// Appends the changes of a transaction. Returns the id of the
+    /// transaction, or None if it changed nothing.
+    pub fn commit(
+        &self,
+        storage: &Storage,
+        changes: Vec<PendingChange>,
+    ) -> Result<Option<u64>, std::io::Error> {
+        if changes.is_empty() {
+            return Ok(None);
+        }
+        let count = changes.len() as u64;
+        let mut next = self.next.lock().unwrap();
+        let transaction = next.transaction;
+        let committed_at = basic_types::now(&BasicType::TimestampTz).unwrap();
+        let mut lines = Vec::new();
+        let mut offsets = Vec::new();
+        for (offset, change) in changes.into_iter().enumerate() {
+            let lsn = next.lsn + offset as u64;
+            offsets.push((lsn, next.end + lines.len() as u64));
+            let change = Change {
+                lsn,
+                transaction,
+                committed_at: committed_at.clone(),
+                table: change.table,
+                op: change.op,
+                before: change.before,
+                after: change.after,
+            };
+            serde_json::to_writer(&mut lines, &change)?;
+            lines.push(b'\n');
+        }
+        storage.append_file(LOG_FILE, &lines)?;
+        next.offsets.extend(offsets);
+        next.end += lines.len() as u64;
+        next.lsn += count;
+        next.transaction += 1;
+        self.last_lsn.send_replace(next.lsn - 1);
+        Ok(Some(transaction))
+    }
+
+    /// Appends changes that committed elsewhere, such as in the database a
+    /// backup was taken of, keeping their positions. The log continues after
+    /// the last of them.
+    pub fn append(&self, storage: &Storage, changes: &[Change]) -> Result<(), std::io::Error> {
+        let Some(last) = changes.last() else {
+            return Ok(());
+        };
+        let mut next = self.next.lock().unwrap();
+        let mut lines = Vec::new();
+        let mut offsets = Vec::new();
+        for change in changes {
+            offsets.push((change.lsn, next.end + lines.len() as u64));
+            serde_json::to_writer(&mut lines, change)?;
+            lines.push(b'\n');
+        }
+        storage.append_file(LOG_FILE, &lines)?;
+        next.offsets.extend(offsets);
+        next.end += lines.len() as u64;
+        next.lsn = last.lsn + 1;
+        next.transaction = last.transaction + 1;
+        self.last_lsn.send_replace(last.lsn);
+        Ok(())
+    }
+
+    /// Copies the log to the data directory of `to`, between commits.
+    pub fn copy(&self, storage: &Storage, to: &Storage) -> Result<(), std::io::Error> {
+        let _next = self.next.lock().unwrap();
+        storage.copy_file(LOG_FILE, to)
+    }
+
+    /// The LSN of the last change, 0 if there is none.
+    pub fn last_lsn(&self) -> u64 {
+        *self.last_lsn.borrow()
+    }
+
+    /// The id of the last transaction that committed changes, 0 if there
+    /// is none.
+    pub fn last_transaction(&self) -> u64 {
+        self.next.lock().unwrap().transaction - 1
+    }
+
+    /// Removes the changes `keep` rejects, except the last one, which
+    /// holds the positions the log continues from. Only the changes `old`
+    /// accepts are looked at: changes are in commit order, so the log is
+    /// kept as it is from the first one `old` rejects. Returns how many
+    /// were removed.
+    pub fn prune(
+        &self,
+        storage: &Storage,
+        old: impl Fn(&Change) -> bool,
+        keep: impl Fn(&Change) -> bool,
+    ) -> Result<usize, std::io::Error> {
+        // No transaction commits while the log is rewritten.
+        let mut next = self.next.lock().unwrap();
+        let Some(&(last_lsn, _)) = next.offsets.last() else {
+            return Ok(0);
+        };
+        let mut reader = BufReader::new(File::open(storage.path(LOG_FILE))?);
+        let mut lines = Vec::new();
+        let mut offsets = Vec::new();
+        let mut removed = 0;
+        // Where the part of the log that is kept as it is starts.
+        let mut rest = 0;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let length = reader.read_until(b'\n', &mut line)? as u64;
+            if length == 0 {
+                break;
+            }
+            if !line.trim_ascii().is_empty() {
+                let change: Change = serde_json::from_slice(&line)?;
+                if !old(&change) {
+                    break;
+                }
+                if change.lsn == last_lsn || keep(&change) {
+                    offsets.push((change.lsn, lines.len() as u64));
+                    lines.extend_from_slice(line.trim_ascii_end());
+                    lines.push(b'\n');
+                } else {
+                    removed += 1;
+                }
+            }
+            rest += length;
+        }
+        if removed > 0 {
+            let shift = rest - lines.len() as u64;
+            let kept = next.offsets.partition_point(|(_, offset)| *offset < rest);
+            offsets.extend(
+                next.offsets[kept..]
+                    .iter()
+                    .map(|(lsn, offset)| (*lsn, offset - shift)),
+            );
+            reader.seek(SeekFrom::Start(rest))?;
+            reader.read_to_end(&mut lines)?;
+            // Readers see either the old log or the new one.
+            let pruned = format!("{}.pruned", LOG_FILE);
+            storage.write_file(&pruned, &lines)?;
+            std::fs::rename(storage.path(&pruned), storage.path(LOG_FILE))?;
+            next.offsets = offsets;
+            next.end = lines.len() as u64;
+        }
+        Ok(removed)
+    }
+
+    /// Up to `limit` changes after `since`, in LSN order.
+    pub fn since(
+        &self,
+        storage: &Storage,
+        since: u64,
+        limit: usize,
+    ) -> Result<Vec<Change>, std::io::Error> {
+        let (file, start, end) = {
+            let next = self.next.lock().unwrap();
+            let first = next.offsets.partition_point(|(lsn, _)| *lsn <= since);
+            let Some(&(_, start)) = next.offsets.get(first) else {
+                return Ok(Vec::new());
+            };
+            // A log that is pruned later is renamed over this one, which
+            // stays readable through the open file.
+            (File::open(storage.path(LOG_FILE))?, start, next.end)
+        };
+        let mut reader = BufReader::new(file);
+        reader.seek(SeekFrom::Start(start))?;
+        let mut changes = Vec::new();
+        for line in reader.take(end - start).lines() {
+            if changes.len() == limit {
+                break;
+            }
+            let line = line?;
+            if !line.trim().is_empty() {
+                changes.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Waits until there are changes after `since`.
+    pub async fn wait_for(&self, since: u64) {
+        let mut last_lsn = self.last_lsn.subscribe();
+        // The sender lives as long as the log, so this only ends once it has.
+        let _ = last_lsn.wait_for(|lsn| *lsn > since).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FsyncPolicy;
+
+    fn insert(table: &str, id: &str) -> PendingChange {
+        let mut row = Row::new();
+        row.insert("id".to_string(), serde_json::json!(id));
+        PendingChange {
+            table: table.to_string(),
+            op: Operation::Insert,
+            before: None,
+            after: Some(row),
+        }
+    }
+
+    #[test]
+    fn test_change_log_positions() {
+        let path =
+            std::env::temp_dir().join(format!("adaptivedb-changelog-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let storage = Storage::new(path.clone(), FsyncPolicy::default());
+        let log = ChangeLog::open(&storage).unwrap();
+        assert_eq!(log.commit(&storage, Vec::new()).unwrap(), None);
+        assert_eq!(
+            log.commit(&storage, vec![insert("t", "1"), insert("u", "2")])
+                .unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            log.commit(&storage, vec![insert("t", "3")]).unwrap(),
+            Some(2)
+        );
+        assert_eq!(log.last_lsn(), 3);
+
+        // A reopened log continues where it left off.
+        let log = ChangeLog::open(&storage).unwrap();
+        assert_eq!(
+            log.commit(&storage, vec![insert("t", "4")]).unwrap(),
+            Some(3)
+        );
+        let changes = log.since(&storage, 1, 2).unwrap();
+        let positions: Vec<(u64, u64)> = changes
+            .iter()
+            .map(|change| (change.lsn, change.transaction))
+            .collect();
+        assert_eq!(positions, [(2, 1), (3, 2)]);
+        assert_eq!(changes[0].table, "u");
+        assert_eq!(log.last_transaction(), 3);
+
+        // Pruning keeps the last change, so the log continues after it.
+        assert_eq!(
+            log.prune(&storage, |_| true, |change| change.table == "u")
+                .unwrap(),
+            2
+        );
+        let lsns: Vec<u64> = log
+            .since(&storage, 0, 10)
+            .unwrap()
+            .iter()
+            .map(|change| change.lsn)
+            .collect();
+        assert_eq!(lsns, [2, 4]);
+        let log = ChangeLog::open(&storage).unwrap();
+        assert_eq!(log.last_lsn(), 4);
+        assert_eq!(log.last_transaction(), 3);
+
+        // Changes committed after a prune are found where they were written,
+        // and changes from the first one that is not old are kept unread.
+        log.commit(&storage, vec![insert("t", "5"), insert("t", "6")])
+            .unwrap();
+        assert_eq!(
+            log.prune(&storage, |change| change.lsn < 5, |_| false)
+                .unwrap(),
+            2
+        );
+        let lsns: Vec<u64> = log
+            .since(&storage, 0, 10)
+            .unwrap()
+            .iter()
+            .map(|change| change.lsn)
+            .collect();
+        assert_eq!(lsns, [5, 6]);
+        let changes = log.since(&storage, 5, 10).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].after, insert("t", "6").after);
+        log.commit(&storage, vec![insert("t", "7")]).unwrap();
+        assert_eq!(log.since(&storage, 6, 10).unwrap()[0].lsn, 7);
+        assert_eq!(log.since(&storage, 7, 10).unwrap(), []);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
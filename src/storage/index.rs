@@ -0,0 +1,277 @@
+//! Secondary indexes. An index keeps the values of one column, or what a path
+//! reads out of a Json column, sorted together with the rows they belong
+//! to, and an inverted index the elements of an
+//! array column together with the rows whose arrays hold them. Index contents
+//! live in memory and are built from the table on first use; the catalogue
+//! records which indexes exist.
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use super::Row;
+use crate::catalogue::basic_types::{parse_array, BasicType};
+use crate::catalogue::json::JsonAccess;
+use crate::queryprocessing::dml::DMLOperator;
+
+/// Bounds of a range selection. Each bound is a value and whether it is
+/// included in the range.
+#[derive(Debug, Clone, Default)]
+pub struct Range {
+    pub lower: Option<(String, bool)>,
+    pub upper: Option<(String, bool)>,
+}
+
+impl Range {
+    /// Narrows the range by a predicate. Returns `false` if the predicate does
+    /// not describe a range, or bounds the same side as an earlier predicate.
+    pub fn restrict(&mut self, operator: DMLOperator, value: &str) -> bool {
+        let bound = |included| Some((value.to_string(), included));
+        match operator {
+            DMLOperator::GreaterThan if self.lower.is_none() => self.lower = bound(false),
+            DMLOperator::GreaterThanOrEquals if self.lower.is_none() => self.lower = bound(true),
+            DMLOperator::LessThan if self.upper.is_none() => self.upper = bound(false),
+            DMLOperator::LessThanOrEquals if self.upper.is_none() => self.upper = bound(true),
+            DMLOperator::Equals if self.lower.is_none() && self.upper.is_none() => {
+                self.lower = bound(true);
+                self.upper = bound(true);
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+pub struct SecondaryIndex {
+    data_type: BasicType,
+    /// Reads the indexed value out of a Json column.
+    path: Option<JsonAccess>,
+    /// Column values sorted by `data_type`, paired with their row index.
+    entries: Vec<(String, usize)>,
+    /// Number of table rows that have been added to the index.
+    rows_seen: usize,
+}
+
+impl SecondaryIndex {
+    pub fn new(data_type: BasicType) -> SecondaryIndex {
+        SecondaryIndex {
+            data_type,
+            path: None,
+            entries: Vec::new(),
+            rows_seen: 0,
+        }
+    }
+
+    pub fn with_path(mut self, path: Option<JsonAccess>) -> SecondaryIndex {
+        self.path = path;
+        self
+    }
+
+    /// Adds rows that were appended to the table since the last lookup.
+    pub fn refresh(&mut self, column: &str, rows: &[Row]) {
+        if rows.len() < self.rows_seen {
+            *self = SecondaryIndex::new(self.data_type.clone()).with_path(self.path.take());
+        }
+        for (row_index, row) in rows.iter().enumerate().skip(self.rows_seen) {
+            let Some(value) = row.get(column).and_then(|value| value.as_str()) else {
+                continue;
+            };
+            let value = match &self.path {
+                Some(path) => match path.extract(value) {
+                    Some(value) => Cow::Owned(value),
+                    None => continue,
+                },
+                None => Cow::Borrowed(value),
+            };
+            if self.data_type.compare(&value, &value).is_none() {
+                continue;
+            }
+            let position = self.partition_point(&value, true);
+            self.entries.insert(position, (value.to_string(), row_index));
+        }
+        self.rows_seen = rows.len();
+    }
+
+    /// Position of the first entry that is above `value`, or at least `value`
+    /// if `included` is false.
+    fn partition_point(&self, value: &str, included: bool) -> usize {
+        self.entries
+            .partition_point(|(entry, _)| match self.data_type.compare(entry, value) {
+                Some(Ordering::Less) => true,
+                Some(Ordering::Equal) => included,
+                _ => false,
+            })
+    }
+
+    pub fn lookup(&self, range: &Range) -> Vec<usize> {
+        let start = match &range.lower {
+            Some((value, included)) => self.partition_point(value, !included),
+            None => 0,
+        };
+        let end = match &range.upper {
+            Some((value, included)) => self.partition_point(value, *included),
+            None => self.entries.len(),
+        };
+        if start >= end {
+            return Vec::new();
+        }
+        self.entries[start..end].iter().map(|(_, row)| *row).collect()
+    }
+}
+
+#[derive(Default)]
+pub struct InvertedIndex {
+    /// Rows whose arrays hold each element, in table order.
+    postings: HashMap<String, Vec<usize>>,
+    /// Number of table rows that have been added to the index.
+    rows_seen: usize,
+}
+
+impl InvertedIndex {
+    /// Adds rows that were appended to the table since the last lookup.
+    pub fn refresh(&mut self, column: &str, rows: &[Row]) {
+        if rows.len() < self.rows_seen {
+            *self = InvertedIndex::default();
+        }
+        for (row_index, row) in rows.iter().enumerate().skip(self.rows_seen) {
+            let Some(elements) = row
+                .get(column)
+                .and_then(|value| value.as_str())
+                .and_then(parse_array)
+            else {
+                continue;
+            };
+            let elements: HashSet<String> = elements.into_iter().flatten().collect();
+            for element in elements {
+                self.postings.entry(element).or_default().push(row_index);
+            }
+        }
+        self.rows_seen = rows.len();
+    }
+
+    /// Rows whose arrays hold `element`.
+    pub fn lookup(&self, element: &str) -> &[usize] {
+        self.postings
+            .get(element)
+            .map(|rows| rows.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+type IndexKey = (String, String);
+
+/// The contents of the indexes of a database.
+#[derive(Default)]
+pub struct Indexes {
+    indexes: Mutex<HashMap<IndexKey, SecondaryIndex>>,
+    inverted: Mutex<HashMap<IndexKey, InvertedIndex>>,
+}
+
+impl Indexes {
+    /// Looks up the rows of `table` whose `column` value, or what `path`
+    /// reads out of it, lies in `range`.
+    pub fn lookup(
+        &self,
+        table: &str,
+        column: &str,
+        path: Option<&JsonAccess>,
+        data_type: BasicType,
+        rows: &[Row],
+        range: &Range,
+    ) -> Vec<usize> {
+        let mut indexes = self.indexes.lock().unwrap();
+        let key = match path {
+            Some(path) => format!("{}{}", column, path),
+            None => column.to_string(),
+        };
+        let index = indexes
+            .entry((table.to_string(), key))
+            .or_insert_with(|| SecondaryIndex::new(data_type).with_path(path.cloned()));
+        index.refresh(column, rows);
+        index.lookup(range)
+    }
+
+    /// Looks up the rows of `table` whose array in `column` holds `element`.
+    pub fn lookup_element(
+        &self,
+        table: &str,
+        column: &str,
+        rows: &[Row],
+        element: &str,
+    ) -> Vec<usize> {
+        let mut inverted = self.inverted.lock().unwrap();
+        let index = inverted
+            .entry((table.to_string(), column.to_string()))
+            .or_default();
+        index.refresh(column, rows);
+        index.lookup(element).to_vec()
+    }
+
+    /// Forgets the contents of all indexes on a table. They are rebuilt on
+    /// their next use. Needed whenever rows are changed or removed rather than
+    /// appended.
+    pub fn invalidate_table(&self, table: &str) {
+        let mut indexes = self.indexes.lock().unwrap();
+        indexes.retain(|(indexed_table, _), _| indexed_table != table);
+        let mut inverted = self.inverted.lock().unwrap();
+        inverted.retain(|(indexed_table, _), _| indexed_table != table);
+    }
+
+    /// Forgets the contents of a dropped index.
+    pub fn drop_index(&self, table: &str, column: &str) {
+        let mut indexes = self.indexes.lock().unwrap();
+        indexes.remove(&(table.to_string(), column.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secondary_index_lookup() {
+        let rows: Vec<Row> = ["30", "10", "20", "10", "40"]
+            .iter()
+            .map(|value| {
+                let mut row = Row::new();
+                row.insert("x".to_string(), serde_json::json!(value));
+                row
+            })
+            .collect();
+        let mut index = SecondaryIndex::new(BasicType::Int);
+        index.refresh("x", &rows);
+
+        let mut range = Range::default();
+        range.restrict(DMLOperator::Equals, "10");
+        let mut found = index.lookup(&range);
+        found.sort();
+        assert_eq!(found, vec![1, 3]);
+
+        let mut range = Range::default();
+        range.restrict(DMLOperator::GreaterThan, "10");
+        range.restrict(DMLOperator::LessThanOrEquals, "30");
+        let mut found = index.lookup(&range);
+        found.sort();
+        assert_eq!(found, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_inverted_index_lookup() {
+        let mut rows: Vec<Row> = ["{a,b}", "{b,b,c}", "{}", "{NULL,a}"]
+            .iter()
+            .map(|value| {
+                let mut row = Row::new();
+                row.insert("x".to_string(), serde_json::json!(value));
+                row
+            })
+            .collect();
+        rows.push(Row::new());
+        let mut index = InvertedIndex::default();
+        index.refresh("x", &rows);
+
+        assert_eq!(index.lookup("a"), &[0, 3]);
+        assert_eq!(index.lookup("b"), &[0, 1]);
+        assert_eq!(index.lookup("d"), &[] as &[usize]);
+    }
+}
@@ -0,0 +1,614 @@
+pub mod changelog;
+pub mod index;
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::catalogue::{basic_types::BasicType, Layout};
+use crate::config::FsyncPolicy;
+
+/// A stored row, keyed by column name. Missing columns are NULL.
+pub type Row = serde_json::Map<String, serde_json::Value>;
+
+/// Size of a page in bytes. Tables are accounted for in pages of this size.
+pub const PAGE_SIZE: usize = 4096;
+
+/// Number of rows in a segment of a columnar table.
+pub const SEGMENT_ROWS: usize = 1024;
+
+/// Name of the file holding the rows of all tables.
+const DATA_FILE: &str = "data.json";
+
+/// The files of a database, kept in its data directory.
+pub struct Storage {
+    data_dir: PathBuf,
+    fsync: FsyncPolicy,
+    /// Serializes read-modify-write cycles on the data file.
+    lock: Mutex<()>,
+    /// Per table, the number of writes that changed or removed rows it
+    /// already had, since the database was opened.
+    versions: Mutex<HashMap<String, u64>>,
+}
+
+/// A table with its rows, independent of the layout it is stored in.
+pub struct TableData {
+    pub name: String,
+    pub layout: Layout,
+    /// Column types of a columnar table, needed to maintain its zone maps.
+    pub column_types: BTreeMap<String, BasicType>,
+    pub rows: Vec<Row>,
+}
+
+/// A table as it is laid out in the data file.
+#[derive(Serialize, Deserialize)]
+struct StoredTable {
+    name: String,
+    #[serde(default)]
+    layout: Layout,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    column_types: BTreeMap<String, BasicType>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    rows: Vec<Row>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    segments: Vec<Segment>,
+}
+
+/// A run of consecutive rows of a columnar table, stored column by column.
+#[derive(Serialize, Deserialize)]
+struct Segment {
+    rows: usize,
+    columns: BTreeMap<String, ColumnChunk>,
+}
+
+/// The values of one column in a segment, with a zone map of the smallest
+/// and largest non-null value.
+#[derive(Serialize, Deserialize)]
+struct ColumnChunk {
+    min: Option<String>,
+    max: Option<String>,
+    values: Vec<serde_json::Value>,
+}
+
+impl ColumnChunk {
+    fn new(values: Vec<serde_json::Value>, data_type: Option<BasicType>) -> ColumnChunk {
+        let mut min: Option<&str> = None;
+        let mut max: Option<&str> = None;
+        if let Some(data_type) = data_type {
+            for value in values.iter().filter_map(|value| value.as_str()) {
+                if data_type.compare(value, value).is_none() {
+                    continue;
+                }
+                if min.is_none_or(|min| data_type.compare(value, min).is_some_and(|o| o.is_lt())) {
+                    min = Some(value);
+                }
+                if max.is_none_or(|max| data_type.compare(value, max).is_some_and(|o| o.is_gt())) {
+                    max = Some(value);
+                }
+            }
+        }
+        ColumnChunk {
+            min: min.map(|min| min.to_string()),
+            max: max.map(|max| max.to_string()),
+            values,
+        }
+    }
+
+    /// Whether the zone map rules out any value of the chunk lying in `range`.
+    fn excludes(&self, data_type: &BasicType, range: &index::Range) -> bool {
+        let (Some(min), Some(max)) = (&self.min, &self.max) else {
+            // Only NULLs, which never lie in a range.
+            return true;
+        };
+        if let Some((lower, included)) = &range.lower {
+            match data_type.compare(max, lower) {
+                Some(std::cmp::Ordering::Less) => return true,
+                Some(std::cmp::Ordering::Equal) if !included => return true,
+                _ => {}
+            }
+        }
+        if let Some((upper, included)) = &range.upper {
+            match data_type.compare(min, upper) {
+                Some(std::cmp::Ordering::Greater) => return true,
+                Some(std::cmp::Ordering::Equal) if !included => return true,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    fn bytes(&self) -> usize {
+        serde_json::to_vec(&self.values).map(|v| v.len()).unwrap_or(0)
+    }
+}
+
+impl StoredTable {
+    fn into_table_data(self) -> TableData {
+        let rows = match self.layout {
+            Layout::Row => self.rows,
+            Layout::Column => self
+                .segments
+                .into_iter()
+                .flat_map(|segment| segment_rows(segment, None))
+                .collect(),
+        };
+        TableData {
+            name: self.name,
+            layout: self.layout,
+            column_types: self.column_types,
+            rows,
+        }
+    }
+
+    fn from_table_data(table: TableData) -> StoredTable {
+        let (rows, segments) = match table.layout {
+            Layout::Row => (table.rows, Vec::new()),
+            Layout::Column => (Vec::new(), build_segments(table.rows, &table.column_types)),
+        };
+        StoredTable {
+            name: table.name,
+            layout: table.layout,
+            column_types: table.column_types,
+            rows,
+            segments,
+        }
+    }
+}
+
+fn build_segments(rows: Vec<Row>, column_types: &BTreeMap<String, BasicType>) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut rows = rows.into_iter().peekable();
+    while rows.peek().is_some() {
+        let chunk: Vec<Row> = rows.by_ref().take(SEGMENT_ROWS).collect();
+        let mut names: Vec<&String> = column_types.keys().collect();
+        for row in chunk.iter() {
+            names.extend(row.keys().filter(|name| !column_types.contains_key(*name)));
+        }
+        names.sort();
+        names.dedup();
+        let columns = names
+            .into_iter()
+            .map(|name| {
+                let values = chunk
+                    .iter()
+                    .map(|row| row.get(name).cloned().unwrap_or(serde_json::Value::Null))
+                    .collect();
+                let data_type = column_types.get(name).cloned();
+                (name.clone(), ColumnChunk::new(values, data_type))
+            })
+            .collect();
+        segments.push(Segment {
+            rows: chunk.len(),
+            columns,
+        });
+    }
+    segments
+}
+
+/// Reassembles the rows of a segment, keeping only `columns` if given.
+fn segment_rows(segment: Segment, columns: Option<&[String]>) -> Vec<Row> {
+    let mut rows = vec![Row::new(); segment.rows];
+    for (name, chunk) in segment.columns {
+        if columns.is_some_and(|columns| !columns.contains(&name)) {
+            continue;
+        }
+        for (row, value) in rows.iter_mut().zip(chunk.values) {
+            if !value.is_null() {
+                row.insert(name.clone(), value);
+            }
+        }
+    }
+    rows
+}
+
+impl TableData {
+    pub fn new(name: String) -> TableData {
+        TableData {
+            name,
+            layout: Layout::Row,
+            column_types: BTreeMap::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn page_count(&self) -> usize {
+        let bytes: usize = self
+            .rows
+            .iter()
+            .map(|row| serde_json::to_vec(row).map(|v| v.len()).unwrap_or(0))
+            .sum();
+        bytes.div_ceil(PAGE_SIZE)
+    }
+
+    /// Bytes taken up by the values of every column.
+    pub fn column_bytes(&self) -> HashMap<String, usize> {
+        let mut bytes = HashMap::new();
+        for row in self.rows.iter() {
+            for (name, value) in row.iter() {
+                let size = serde_json::to_vec(value).map(|v| v.len()).unwrap_or(0);
+                *bytes.entry(name.clone()).or_insert(0) += size;
+            }
+        }
+        bytes
+    }
+
+    /// Page number of every row, for counting the pages a lookup touches.
+    pub fn row_pages(&self) -> Vec<usize> {
+        let mut offset = 0;
+        self.rows
+            .iter()
+            .map(|row| {
+                let page = offset / PAGE_SIZE;
+                offset += serde_json::to_vec(row).map(|v| v.len()).unwrap_or(0);
+                page
+            })
+            .collect()
+    }
+}
+
+/// Result of a scan over a columnar table.
+pub struct ColumnScan {
+    pub rows: Vec<Row>,
+    pub pages_read: usize,
+    pub segments_skipped: usize,
+}
+
+impl Storage {
+    pub fn new(data_dir: PathBuf, fsync: FsyncPolicy) -> Storage {
+        Storage {
+            data_dir,
+            fsync,
+            lock: Mutex::new(()),
+            versions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Path of a file in the data directory.
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.data_dir.join(name)
+    }
+
+    /// Writes a file in the data directory, flushing it to disk if the fsync
+    /// policy asks for it.
+    pub fn write_file(&self, name: &str, contents: &[u8]) -> Result<(), std::io::Error> {
+        let path = self.path(name);
+        std::fs::create_dir_all(&self.data_dir)?;
+        std::fs::write(&path, contents)?;
+        if self.fsync == FsyncPolicy::Always {
+            std::fs::File::open(&path)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Appends to a file in the data directory, flushing it to disk if the
+    /// fsync policy asks for it.
+    pub fn append_file(&self, name: &str, contents: &[u8]) -> Result<(), std::io::Error> {
+        use std::io::Write;
+
+        std::fs::create_dir_all(&self.data_dir)?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(name))?;
+        file.write_all(contents)?;
+        if self.fsync == FsyncPolicy::Always {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn load_stored(&self) -> Result<Vec<StoredTable>, std::io::Error> {
+        let data_file = self.path(DATA_FILE);
+        if !data_file.exists() {
+            return Ok(Vec::new());
+        }
+        let data = std::fs::read_to_string(data_file)?;
+        let tables = serde_json::from_str(&data)?;
+        Ok(tables)
+    }
+
+    pub fn load(&self) -> Result<Vec<TableData>, std::io::Error> {
+        let tables = self.load_stored()?;
+        Ok(tables
+            .into_iter()
+            .map(StoredTable::into_table_data)
+            .collect())
+    }
+
+    pub fn save(&self, tables: Vec<TableData>) -> Result<(), std::io::Error> {
+        let tables: Vec<StoredTable> = tables
+            .into_iter()
+            .map(StoredTable::from_table_data)
+            .collect();
+        let data = serde_json::to_string_pretty(&tables)?;
+        self.write_file(DATA_FILE, data.as_bytes())
+    }
+
+    /// Reads all rows of a table. Tables without any inserted rows are empty.
+    pub fn scan(&self, table_name: &str) -> Result<TableData, std::io::Error> {
+        let tables = self.load()?;
+        let table = tables
+            .into_iter()
+            .find(|table| table.name == table_name)
+            .unwrap_or_else(|| TableData::new(table_name.to_string()));
+        Ok(table)
+    }
+
+    /// Scans a table for the values of `columns` (all columns if empty),
+    /// leaving out the rows of segments whose zone maps rule out one of
+    /// `ranges`. Callers must still filter the returned rows. Every table
+    /// lives in the data file, so the scan reads the table whole whatever
+    /// it leaves out; zone maps only spare it reassembling those rows.
+    pub fn column_scan(
+        &self,
+        table_name: &str,
+        columns: &[String],
+        ranges: &[(String, BasicType, index::Range)],
+    ) -> Result<ColumnScan, std::io::Error> {
+        let Some(table) = self
+            .load_stored()?
+            .into_iter()
+            .find(|table| table.name == table_name)
+        else {
+            return Ok(ColumnScan {
+                rows: Vec::new(),
+                pages_read: 0,
+                segments_skipped: 0,
+            });
+        };
+        if table.layout == Layout::Row {
+            let table = table.into_table_data();
+            return Ok(ColumnScan {
+                pages_read: table.page_count(),
+                rows: table.rows,
+                segments_skipped: 0,
+            });
+        }
+
+        let columns = (!columns.is_empty()).then_some(columns);
+        let mut rows = Vec::new();
+        let mut bytes_read = 0;
+        let mut segments_skipped = 0;
+        for segment in table.segments {
+            bytes_read += segment
+                .columns
+                .values()
+                .map(ColumnChunk::bytes)
+                .sum::<usize>();
+            let excluded = ranges.iter().any(|(column, data_type, range)| {
+                match segment.columns.get(column) {
+                    Some(chunk) => chunk.excludes(data_type, range),
+                    // The column is NULL in every row of the segment.
+                    None => true,
+                }
+            });
+            if excluded {
+                segments_skipped += 1;
+                continue;
+            }
+            rows.extend(segment_rows(segment, columns));
+        }
+        Ok(ColumnScan {
+            rows,
+            pages_read: bytes_read.div_ceil(PAGE_SIZE),
+            segments_skipped,
+        })
+    }
+
+    /// Loads a table, lets `change` modify it, and stores it again while
+    /// holding the data file lock. Changes that may touch the rows the
+    /// table already had are `rewrites`, and move its version on.
+    fn modify<R>(
+        &self,
+        table_name: &str,
+        rewrites: bool,
+        change: impl FnOnce(&mut TableData) -> R,
+    ) -> Result<R, std::io::Error> {
+        let result = self.try_modify(table_name, rewrites, |table| {
+            Ok::<R, std::convert::Infallible>(change(table))
+        })?;
+        Ok(result.unwrap_or_else(|never| match never {}))
+    }
+
+    /// Like `modify`, but leaves the table as it was if `change` fails.
+    fn try_modify<R, E>(
+        &self,
+        table_name: &str,
+        rewrites: bool,
+        change: impl FnOnce(&mut TableData) -> Result<R, E>,
+    ) -> Result<Result<R, E>, std::io::Error> {
+        let _guard = self.lock.lock().unwrap();
+        let mut tables = self.load()?;
+        let position = match tables.iter().position(|table| table.name == table_name) {
+            Some(position) => position,
+            None => {
+                tables.push(TableData::new(table_name.to_string()));
+                tables.len() - 1
+            }
+        };
+        let result = change(&mut tables[position]);
+        if result.is_ok() {
+            self.save(tables)?;
+            if rewrites {
+                let mut versions = self.versions.lock().unwrap();
+                *versions.entry(table_name.to_string()).or_default() += 1;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Copies the data file to the data directory of `to`, as no write is
+    /// halfway through it.
+    pub fn copy_data(&self, to: &Storage) -> Result<(), std::io::Error> {
+        let _guard = self.lock.lock().unwrap();
+        self.copy_file(DATA_FILE, to)
+    }
+
+    /// Copies a file of the data directory to that of `to`, if it exists.
+    pub fn copy_file(&self, name: &str, to: &Storage) -> Result<(), std::io::Error> {
+        let path = self.path(name);
+        if !path.exists() {
+            return Ok(());
+        }
+        to.write_file(name, &std::fs::read(path)?)
+    }
+
+    /// Changes whenever a write changes or removes rows of a table, but not
+    /// when rows are appended to it.
+    pub fn version(&self, table_name: &str) -> u64 {
+        let versions = self.versions.lock().unwrap();
+        versions.get(table_name).copied().unwrap_or_default()
+    }
+
+    pub fn insert(&self, table_name: &str, row: Row) -> Result<(), std::io::Error> {
+        self.append_rows(table_name, vec![row])
+    }
+
+    /// Appends `rows` to a table.
+    pub fn append_rows(&self, table_name: &str, rows: Vec<Row>) -> Result<(), std::io::Error> {
+        self.modify(table_name, false, |table| table.rows.extend(rows))
+    }
+
+    /// Appends `rows` to a table, keeping the table as it was if `check`
+    /// fails. `check` is given all rows of the table and the positions of
+    /// the appended ones.
+    pub fn try_append_rows<R, E>(
+        &self,
+        table_name: &str,
+        rows: Vec<Row>,
+        check: impl FnOnce(&[Row], std::ops::Range<usize>) -> Result<R, E>,
+    ) -> Result<Result<R, E>, std::io::Error> {
+        self.try_modify(table_name, false, |table| {
+            let start = table.rows.len();
+            table.rows.extend(rows);
+            check(&table.rows, start..table.rows.len())
+        })
+    }
+
+    /// Lets `change` modify the rows of a table in place.
+    pub fn update_rows<R>(
+        &self,
+        table_name: &str,
+        change: impl FnOnce(&mut Vec<Row>) -> R,
+    ) -> Result<R, std::io::Error> {
+        self.modify(table_name, true, |table| change(&mut table.rows))
+    }
+
+    /// Lets `change` modify the rows of a table in place, keeping the rows
+    /// as they were if it fails.
+    pub fn try_update_rows<R, E>(
+        &self,
+        table_name: &str,
+        change: impl FnOnce(&mut Vec<Row>) -> Result<R, E>,
+    ) -> Result<Result<R, E>, std::io::Error> {
+        self.try_modify(table_name, true, |table| change(&mut table.rows))
+    }
+
+    /// Rewrites a table in another layout. Row order is preserved, so row
+    /// positions known to indexes stay valid.
+    pub fn set_layout(
+        &self,
+        table_name: &str,
+        layout: Layout,
+        column_types: BTreeMap<String, BasicType>,
+    ) -> Result<(), std::io::Error> {
+        self.modify(table_name, false, |table| {
+            table.layout = layout;
+            table.column_types = match layout {
+                Layout::Row => BTreeMap::new(),
+                Layout::Column => column_types,
+            };
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(values: std::ops::Range<i64>) -> Vec<Row> {
+        values
+            .map(|value| {
+                let mut row = Row::new();
+                row.insert("id".to_string(), serde_json::json!(value.to_string()));
+                row.insert("name".to_string(), serde_json::json!(format!("n{}", value)));
+                row
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_columnar_round_trip() {
+        let column_types = BTreeMap::from([
+            ("id".to_string(), BasicType::Int),
+            ("name".to_string(), BasicType::String),
+        ]);
+        let table = TableData {
+            name: "t".to_string(),
+            layout: Layout::Column,
+            column_types,
+            rows: rows(0..2500),
+        };
+        let stored = StoredTable::from_table_data(table);
+        assert!(stored.rows.is_empty());
+        assert_eq!(stored.segments.len(), 3);
+        let id = &stored.segments[1].columns["id"];
+        assert_eq!(id.min.as_deref(), Some("1024"));
+        assert_eq!(id.max.as_deref(), Some("2047"));
+
+        let table = stored.into_table_data();
+        assert_eq!(table.rows, rows(0..2500));
+    }
+
+    #[test]
+    fn test_column_scan_reads_skipped_segments() {
+        let dir =
+            std::env::temp_dir().join(format!("adaptivedb-column-scan-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = Storage::new(dir.clone(), FsyncPolicy::Never);
+        let column_types = BTreeMap::from([
+            ("id".to_string(), BasicType::Int),
+            ("name".to_string(), BasicType::String),
+        ]);
+        storage
+            .update_rows("t", |stored| stored.extend(rows(0..2500)))
+            .unwrap();
+        storage
+            .set_layout("t", Layout::Column, column_types)
+            .unwrap();
+
+        let all = storage.column_scan("t", &[], &[]).unwrap();
+        assert_eq!(all.rows.len(), 2500);
+        let mut range = index::Range::default();
+        range.restrict(
+            crate::queryprocessing::dml::DMLOperator::GreaterThan,
+            "2100",
+        );
+        let ranges = [("id".to_string(), BasicType::Int, range)];
+        let columns = ["name".to_string()];
+        let scan = storage.column_scan("t", &columns, &ranges).unwrap();
+        assert_eq!(scan.rows.len(), 2500 - 2048);
+        assert_eq!(scan.rows[0].keys().collect::<Vec<_>>(), ["name"]);
+        assert_eq!(scan.segments_skipped, 2);
+        // The skipped segments and columns are read all the same.
+        assert_eq!(scan.pages_read, all.pages_read);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_zone_map_excludes() {
+        let chunk = ColumnChunk::new(
+            vec![serde_json::json!("5"), serde_json::json!("20")],
+            Some(BasicType::Int),
+        );
+        let mut range = index::Range::default();
+        range.restrict(crate::queryprocessing::dml::DMLOperator::GreaterThan, "20");
+        assert!(chunk.excludes(&BasicType::Int, &range));
+
+        let mut range = index::Range::default();
+        range.restrict(crate::queryprocessing::dml::DMLOperator::LessThanOrEquals, "5");
+        assert!(!chunk.excludes(&BasicType::Int, &range));
+    }
+}
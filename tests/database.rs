@@ -0,0 +1,1774 @@
+use adaptivedb::catalogue::{TriggerEvent, TriggerTiming};
+use adaptivedb::queryprocessing::subscription::{Diff, Subscription};
+use adaptivedb::storage::changelog::Operation;
+use adaptivedb::{Database, Error, ErrorCode, ErrorKind, QueryResult};
+use serde_json::json;
+
+const SCHEMA: &str = "
+model Employee {
+    EmployeeID Int @id
+    LastName String
+    DepartmentID Int?
+    HireDate Date
+}
+";
+
+fn open(name: &str) -> Database {
+    let path = std::env::temp_dir().join(format!("adaptivedb-it-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    Database::open(path).unwrap()
+}
+
+fn code<T: std::fmt::Debug>(result: Result<T, Error>) -> ErrorCode {
+    result.unwrap_err().code()
+}
+
+#[tokio::test]
+async fn test_execute_and_typed_rows() {
+    let db = open("typed_rows");
+    assert_eq!(db.apply_schema(SCHEMA).await.unwrap(), vec!["Employee"]);
+
+    let insert = "insert into Employee (EmployeeID, LastName, DepartmentID, HireDate) values ($1, $2, $3, $4);";
+    let result = db
+        .execute(
+            insert,
+            &[json!(1), json!("O'Brien"), json!(7), json!("2021-01-01")],
+        )
+        .await
+        .unwrap();
+    assert_eq!(result.affected_rows(), Some(1));
+    db.execute(
+        "insert into Employee (EmployeeID, LastName, HireDate) values (2, 'Doe', '2022-03-01');",
+        &[],
+    )
+    .await
+    .unwrap();
+
+    let rows = db
+        .query(
+            "select EmployeeID, LastName, DepartmentID from Employee where HireDate > $1;",
+            &[json!("2020-06-01")],
+        )
+        .await
+        .unwrap();
+    let columns: Vec<&str> = rows
+        .columns()
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(columns, vec!["EmployeeID", "LastName", "DepartmentID"]);
+    let employees: Vec<(i64, String, Option<i64>)> = rows
+        .map(|record| {
+            (
+                record.get("EmployeeID").unwrap(),
+                record.get("LastName").unwrap(),
+                record.get("DepartmentID").unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        employees,
+        vec![
+            (1, "O'Brien".to_string(), Some(7)),
+            (2, "Doe".to_string(), None)
+        ]
+    );
+
+    let record = db
+        .query("select * from Employee where EmployeeID = 2;", &[])
+        .await
+        .unwrap()
+        .next()
+        .unwrap();
+    assert_eq!(
+        code(record.get::<i64>("DepartmentID")),
+        ErrorCode::DatatypeMismatch
+    );
+    assert_eq!(code(record.get::<i64>("Salary")), ErrorCode::UndefinedColumn);
+
+    let result = db
+        .execute(
+            "update Employee set LastName = 'Smith' where EmployeeID = 2;",
+            &[],
+        )
+        .await
+        .unwrap();
+    assert!(matches!(result, QueryResult::Update(1)));
+}
+
+#[tokio::test]
+async fn test_errors() {
+    let db = open("errors");
+    db.apply_schema(SCHEMA).await.unwrap();
+
+    assert_eq!(
+        code(db.apply_schema(SCHEMA).await),
+        ErrorCode::DuplicateTable
+    );
+    assert_eq!(
+        code(db.execute("select * from Missing;", &[]).await),
+        ErrorCode::UndefinedTable
+    );
+    assert_eq!(
+        code(db.execute("close missing;", &[]).await),
+        ErrorCode::UndefinedCursor
+    );
+
+    let error = db.execute("selec * from Employee;", &[]).await.unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Parse);
+    assert_eq!(error.code(), ErrorCode::SyntaxError);
+    assert_eq!(error.message(), "Syntax error at or near \"selec\"");
+    assert_eq!(error.span().map(|span| (span.start, span.end)), Some((0, 5)));
+
+    let error = db
+        .execute("insert into Employee (EmployeeID, LastName) values (1, 'Doe');", &[])
+        .await
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Constraint);
+    assert_eq!(error.code(), ErrorCode::NotNullViolation);
+    assert_eq!(
+        error.message(),
+        "Column HireDate of table Employee cannot be NULL"
+    );
+    // A column named without a value is not stored as NULL.
+    assert_eq!(
+        code(
+            db.execute(
+                "insert into Employee (EmployeeID, LastName, HireDate) values (5, '2021-01-01');",
+                &[],
+            )
+            .await
+        ),
+        ErrorCode::SyntaxError
+    );
+    assert_eq!(
+        db.query("select * from Employee;", &[])
+            .await
+            .unwrap()
+            .len(),
+        0
+    );
+    assert!(matches!(
+        db.execute("commit;", &[]).await,
+        Ok(QueryResult::Commit)
+    ));
+}
+
+#[tokio::test]
+async fn test_databases_are_independent() {
+    let first = open("independent_first");
+    let second = open("independent_second");
+    first.apply_schema(SCHEMA).await.unwrap();
+
+    assert!(second.catalogue().await.tables().is_empty());
+    assert_eq!(
+        second
+            .execute("select * from Employee;", &[])
+            .await
+            .unwrap_err()
+            .code(),
+        ErrorCode::UndefinedTable
+    );
+
+    // A reopened database reads the catalogue and rows back from disk.
+    first
+        .execute(
+            "insert into Employee (EmployeeID, LastName, HireDate) values (1, 'Doe', '2021-01-01');",
+            &[],
+        )
+        .await
+        .unwrap();
+    let path = first.config().data_dir.clone();
+    drop(first);
+    let reopened = Database::open(path).unwrap();
+    let rows = reopened
+        .query("select * from Employee;", &[])
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+}
+
+#[tokio::test]
+async fn test_types() {
+    let db = open("types");
+    db.apply_schema(
+        "
+        enum Role { guest member admin }
+
+        model Account {
+            AccountID Uuid @id
+            Balance Decimal(8, 2)
+            Role Role
+            Opened Timestamptz
+            Settings Json?
+        }",
+    )
+    .await
+    .unwrap();
+
+    db.execute(
+        "insert into Account (AccountID, Balance, Role, Opened, Settings) values \
+         ('A0EEBC99-9C0B-4EF8-BB6D-6BB9BD380A11', '12.345', 'admin', '2024-01-31 12:30:00+02', '{\"b\": 1, \"a\": 2}');",
+        &[],
+    )
+    .await
+    .unwrap();
+    db.execute(
+        "insert into Account (AccountID, Balance, Role, Opened) values ($1, $2, $3, $4);",
+        &[
+            json!("b0eebc999c0b4ef8bb6d6bb9bd380a12"),
+            json!("9.5"),
+            json!("guest"),
+            json!("2024-01-31 09:00:00Z"),
+        ],
+    )
+    .await
+    .unwrap();
+
+    let rows = db
+        .query("select * from Account where Role > 'guest';", &[])
+        .await
+        .unwrap();
+    let accounts: Vec<(String, String, String, String)> = rows
+        .map(|record| {
+            (
+                record.get("AccountID").unwrap(),
+                record.get("Balance").unwrap(),
+                record.get("Opened").unwrap(),
+                record.get("Settings").unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        accounts,
+        vec![(
+            "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11".to_string(),
+            "12.35".to_string(),
+            "2024-01-31 10:30:00+00".to_string(),
+            r#"{"a":2,"b":1}"#.to_string(),
+        )]
+    );
+    let rows = db
+        .query("select AccountID from Account where Balance < '10';", &[])
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+
+    let insert = "insert into Account (AccountID, Balance, Role, Opened) values ($1, $2, $3, $4);";
+    let account = |balance: &str, role: &str| {
+        [
+            json!("c0eebc99-9c0b-4ef8-bb6d-6bb9bd380a13"),
+            json!(balance),
+            json!(role),
+            json!("2024-01-31 09:00:00"),
+        ]
+    };
+    assert_eq!(
+        code(db.execute(insert, &account("1", "owner")).await),
+        ErrorCode::InvalidParameter
+    );
+    assert_eq!(
+        code(db.execute(insert, &account("1000000", "guest")).await),
+        ErrorCode::NumericValueOutOfRange
+    );
+    let error = db
+        .execute(
+            "update Account set Role = 'owner' where Balance > '0';",
+            &[],
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::InvalidTextRepresentation);
+    assert_eq!(error.hint(), Some("Role is one of guest, member, admin"));
+
+    assert_eq!(
+        code(db.apply_schema("enum Role { a }").await),
+        ErrorCode::DuplicateType
+    );
+    assert_eq!(
+        code(
+            db.apply_schema("model Other { OtherID Int @id Kind Kind }")
+                .await
+        ),
+        ErrorCode::UndefinedType
+    );
+}
+
+#[tokio::test]
+async fn test_arrays() {
+    let db = open("arrays");
+    db.apply_schema(
+        "
+        model Post {
+            PostID Int @id
+            Tags String[]
+            Scores Int[]?
+            @@index(Tags, gin)
+        }",
+    )
+    .await
+    .unwrap();
+
+    let insert = "insert into Post (PostID, Tags, Scores) values ($1, $2, $3);";
+    db.execute(insert, &[json!(1), json!(["rust", "db"]), json!([3, 1])])
+        .await
+        .unwrap();
+    db.execute(
+        "insert into Post (PostID, Tags) values (2, '{go,\"web dev\"}');",
+        &[],
+    )
+    .await
+    .unwrap();
+    db.execute(
+        "insert into Post (PostID, Tags, Scores) values (3, '{rust}', '{10, 02}');",
+        &[],
+    )
+    .await
+    .unwrap();
+
+    let ids = |sql: &'static str| {
+        let db = &db;
+        async move {
+            let rows = db.query(sql, &[]).await.unwrap();
+            rows.map(|record| record.get::<i64>("PostID").unwrap())
+                .collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(
+        ids("select PostID from Post where Tags @> '{rust}';").await,
+        [1, 3]
+    );
+    assert_eq!(
+        ids("select PostID from Post where Tags && '{db,go}';").await,
+        [1, 2]
+    );
+    assert_eq!(
+        ids("select PostID from Post where Tags <@ '{rust,db,c}';").await,
+        [1, 3]
+    );
+    assert_eq!(
+        ids("select PostID from Post where 'web dev' = any(Tags);").await,
+        [2]
+    );
+    assert_eq!(
+        ids("select PostID from Post where 5 < any(Scores);").await,
+        [3]
+    );
+    assert_eq!(
+        ids("select PostID from Post where array_length(Tags, 1) = 2;").await,
+        [1, 2]
+    );
+
+    let rows = db
+        .query(
+            "select PostID, Tag from Post, unnest(Tags) as Tag where Tag <> 'db';",
+            &[],
+        )
+        .await
+        .unwrap();
+    let tags: Vec<(i64, String)> = rows
+        .map(|record| (record.get("PostID").unwrap(), record.get("Tag").unwrap()))
+        .collect();
+    assert_eq!(
+        tags,
+        vec![
+            (1, "rust".to_string()),
+            (2, "go".to_string()),
+            (2, "web dev".to_string()),
+            (3, "rust".to_string())
+        ]
+    );
+    let rows = db
+        .query("select Scores, array_length(Scores, 1) from Post;", &[])
+        .await
+        .unwrap();
+    let scores: Vec<(Option<String>, Option<i64>)> = rows
+        .map(|record| {
+            (
+                record.get("Scores").unwrap(),
+                record.get("array_length").unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        scores,
+        vec![
+            (Some("{3,1}".to_string()), Some(2)),
+            (None, None),
+            (Some("{10,2}".to_string()), Some(2))
+        ]
+    );
+
+    let rows = db
+        .query(
+            "select PostID from Post where Tags @> $1;",
+            &[json!(["db"])],
+        )
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(
+        code(
+            db.query("select PostID from Post where PostID @> '{1}';", &[])
+                .await
+        ),
+        ErrorCode::DatatypeMismatch
+    );
+    assert_eq!(
+        code(
+            db.query("select PostID from Post where Scores @> '{x}';", &[])
+                .await
+        ),
+        ErrorCode::InvalidTextRepresentation
+    );
+    assert_eq!(
+        code(
+            db.apply_schema("model Other { OtherID Int @id @@index(OtherID, gin) }")
+                .await
+        ),
+        ErrorCode::InvalidTableDefinition
+    );
+}
+
+#[tokio::test]
+async fn test_json() {
+    let db = open("json");
+    db.apply_schema(
+        r#"
+        model Orders {
+            OrderID Int @id
+            Doc Json @schema("{\"type\": \"object\", \"required\": [\"status\"], \"properties\": {\"status\": {\"enum\": [\"open\", \"paid\"]}}}")
+            @@index(Doc->>'status')
+        }"#,
+    )
+    .await
+    .unwrap();
+
+    let insert = "insert into Orders (OrderID, Doc) values ($1, $2);";
+    let documents = [
+        json!({"status": "paid", "customer": {"name": "Ada"}, "items": [{"qty": 1}, {"qty": 3}]}),
+        json!({"status": "open", "customer": {"name": "Bob"}, "items": [{"qty": 2}]}),
+        json!({"status": "paid", "items": []}),
+    ];
+    for (position, document) in documents.iter().enumerate() {
+        db.execute(insert, &[json!(position + 1), document.clone()])
+            .await
+            .unwrap();
+    }
+
+    let ids = |sql: &'static str| {
+        let db = &db;
+        async move {
+            let rows = db.query(sql, &[]).await.unwrap();
+            rows.map(|record| record.get::<i64>("OrderID").unwrap())
+                .collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(
+        ids("select OrderID from Orders where Doc->>'status' = 'paid';").await,
+        [1, 3]
+    );
+    assert_eq!(
+        ids("select OrderID from Orders where Doc->'customer'->>'name' = 'Bob';").await,
+        [2]
+    );
+    assert_eq!(
+        ids("select OrderID from Orders where Doc @? '$.items[*] ? (@.qty > 2)';").await,
+        [1]
+    );
+    assert_eq!(
+        ids("select OrderID from Orders where Doc @@ '$.items[*].qty >= 2';").await,
+        [1, 2]
+    );
+
+    let rows = db
+        .query(
+            "select Doc->'customer'->>'name', Doc->'items'->-1 from Orders where OrderID = 1;",
+            &[],
+        )
+        .await
+        .unwrap();
+    let values: Vec<(String, String)> = rows
+        .map(|record| (record.get("name").unwrap(), record.get("?column?").unwrap()))
+        .collect();
+    assert_eq!(values, vec![("Ada".to_string(), r#"{"qty":3}"#.to_string())]);
+
+    assert_eq!(
+        code(db.execute(insert, &[json!(4), json!({"status": "lost"})]).await),
+        ErrorCode::CheckViolation
+    );
+    assert_eq!(
+        code(
+            db.execute(
+                "update Orders set Doc = '{\"items\": []}' where OrderID = 1;",
+                &[]
+            )
+            .await
+        ),
+        ErrorCode::CheckViolation
+    );
+    assert_eq!(
+        code(
+            db.query("select OrderID from Orders where OrderID @? '$.a';", &[])
+                .await
+        ),
+        ErrorCode::DatatypeMismatch
+    );
+    assert_eq!(
+        code(
+            db.query("select OrderID from Orders where Doc @? '$.a ==';", &[])
+                .await
+        ),
+        ErrorCode::InvalidTextRepresentation
+    );
+    assert_eq!(
+        code(
+            db.apply_schema("model Other { OtherID Int @id Name String @schema(\"{}\") }")
+                .await
+        ),
+        ErrorCode::InvalidTableDefinition
+    );
+    assert_eq!(
+        code(
+            db.apply_schema("model Other { OtherID Int @id Doc Json @schema(\"{\\\"type\\\": 1}\") }")
+                .await
+        ),
+        ErrorCode::InvalidTableDefinition
+    );
+}
+
+#[tokio::test]
+async fn test_defaults() {
+    let db = open("defaults");
+    db.apply_schema(
+        r#"
+        enum Status { open closed }
+
+        model Ticket {
+            TicketID Int @id @default(autoincrement())
+            Key Uuid @default(uuid())
+            Title String
+            Priority Int @default(3)
+            Status Status @default(open)
+            Opened Timestamptz @default(now())
+        }"#,
+    )
+    .await
+    .unwrap();
+
+    db.execute("insert into Ticket (Title) values ('first');", &[])
+        .await
+        .unwrap();
+    db.execute(
+        "insert into Ticket (Title, Priority, Status) values ('second', 1, 'closed');",
+        &[],
+    )
+    .await
+    .unwrap();
+    let rows: Vec<_> = db
+        .query("select * from Ticket;", &[])
+        .await
+        .unwrap()
+        .collect();
+    let tickets: Vec<(i64, i64, String)> = rows
+        .iter()
+        .map(|record| {
+            (
+                record.get("TicketID").unwrap(),
+                record.get("Priority").unwrap(),
+                record.get("Status").unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        tickets,
+        vec![(1, 3, "open".to_string()), (2, 1, "closed".to_string())]
+    );
+    let keys: Vec<String> = rows.iter().map(|record| record.get("Key").unwrap()).collect();
+    assert_ne!(keys[0], keys[1]);
+    assert_eq!(&keys[0][14..15], "4");
+    let opened: String = rows[0].get("Opened").unwrap();
+    assert!(opened.ends_with("+00"));
+
+    assert_eq!(
+        code(db.execute("insert into Ticket (Priority) values (1);", &[]).await),
+        ErrorCode::NotNullViolation
+    );
+
+    db.execute("create sequence Invoices start with 100 increment by 10;", &[])
+        .await
+        .unwrap();
+    let next = |sql: &'static str| {
+        let db = &db;
+        async move {
+            let mut rows = db.query(sql, &[]).await.unwrap();
+            rows.next().unwrap().get::<i64>("nextval").unwrap()
+        }
+    };
+    assert_eq!(next("select nextval('Invoices');").await, 100);
+    assert_eq!(next("select nextval('Invoices');").await, 110);
+    db.execute(
+        "insert into Ticket (TicketID, Title) values (nextval('Invoices'), 'third');",
+        &[],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        db.query("select Title from Ticket where TicketID = 120;", &[])
+            .await
+            .unwrap()
+            .count(),
+        1
+    );
+    assert_eq!(next("select nextval('ticket_ticketid_seq');").await, 3);
+
+    assert_eq!(
+        code(db.execute("create sequence Invoices;", &[]).await),
+        ErrorCode::DuplicateTable
+    );
+    assert_eq!(
+        code(db.query("select nextval('Missing');", &[]).await),
+        ErrorCode::UndefinedTable
+    );
+    assert_eq!(
+        code(
+            db.apply_schema("model Other { OtherID Int @id Name String @default(now()) }")
+                .await
+        ),
+        ErrorCode::InvalidTableDefinition
+    );
+}
+
+#[tokio::test]
+async fn test_constraints() {
+    let db = open("constraints");
+    db.apply_schema(
+        r#"
+        model Employee {
+            EmployeeID Int @id
+            FirstName String
+            LastName String
+            Salary Int @check("Salary > 0")
+            Nickname String?
+            @@unique([FirstName, LastName])
+        }"#,
+    )
+    .await
+    .unwrap();
+
+    let insert = "insert into Employee (EmployeeID, FirstName, LastName, Salary) values ($1, $2, $3, $4);";
+    db.execute(insert, &[json!(1), json!("Ada"), json!("Lovelace"), json!(100)])
+        .await
+        .unwrap();
+    db.execute(insert, &[json!(2), json!("Ada"), json!("Byron"), json!(90)])
+        .await
+        .unwrap();
+    let error = db
+        .execute(insert, &[json!(3), json!("Ada"), json!("Lovelace"), json!(80)])
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::UniqueViolation);
+    assert_eq!(error.kind(), ErrorKind::Constraint);
+    assert_eq!(
+        error.message(),
+        "Duplicate key (FirstName, LastName)=(Ada, Lovelace) violates unique constraint employee_firstname_lastname_key of table Employee"
+    );
+    assert_eq!(
+        code(db.execute(insert, &[json!(3), json!("Grace"), json!("Hopper"), json!(0)]).await),
+        ErrorCode::CheckViolation
+    );
+    assert_eq!(
+        code(
+            db.execute("update Employee set LastName = 'Lovelace' where EmployeeID = 2;", &[])
+                .await
+        ),
+        ErrorCode::UniqueViolation
+    );
+    assert_eq!(
+        code(db.execute("update Employee set Salary = -1;", &[]).await),
+        ErrorCode::CheckViolation
+    );
+    let salaries: Vec<i64> = db
+        .query("select Salary from Employee;", &[])
+        .await
+        .unwrap()
+        .map(|record| record.get("Salary").unwrap())
+        .collect();
+    assert_eq!(salaries, [100, 90]);
+
+    // Nicknames are not unique yet, and two rows share one.
+    db.execute("update Employee set Nickname = 'Countess';", &[])
+        .await
+        .unwrap();
+    let migration = |constraint: &str| {
+        format!(
+            r#"
+            model Employee {{
+                EmployeeID Int @id
+                FirstName String
+                LastName String
+                Salary Int @check("Salary > 0")
+                Nickname String?
+                @@unique([FirstName, LastName])
+                {}
+            }}"#,
+            constraint
+        )
+    };
+    assert_eq!(
+        code(db.migrate_schema(&migration("@@unique([Nickname])")).await),
+        ErrorCode::UniqueViolation
+    );
+    assert_eq!(
+        code(db.migrate_schema(&migration(r#"@@check("Salary >= 95")"#)).await),
+        ErrorCode::CheckViolation
+    );
+    assert_eq!(
+        db.migrate_schema(&migration(r#"@@check("Salary < 1000")"#))
+            .await
+            .unwrap(),
+        vec!["Employee"]
+    );
+    assert_eq!(
+        code(db.execute(insert, &[json!(3), json!("Grace"), json!("Hopper"), json!(5000)]).await),
+        ErrorCode::CheckViolation
+    );
+    assert_eq!(
+        code(
+            db.migrate_schema("model Employee { EmployeeID Int @id Name String }")
+                .await
+        ),
+        ErrorCode::InvalidTableDefinition
+    );
+}
+
+#[tokio::test]
+async fn test_failed_schema_changes_leave_the_catalogue() {
+    let db = open("failed_schema");
+    let data_file = std::env::temp_dir()
+        .join(format!(
+            "adaptivedb-it-failed_schema-{}",
+            std::process::id()
+        ))
+        .join("data.json");
+    let schema = "enum Level { low high }\nmodel Metric {\n  MetricID Int @id @default(autoincrement())\n  Level Level\n  @@layout(column)\n}";
+
+    // Storing the layout of the table fails, so nothing is created.
+    let _ = std::fs::remove_file(&data_file);
+    std::fs::create_dir_all(&data_file).unwrap();
+    assert_eq!(code(db.apply_schema(schema).await), ErrorCode::IoError);
+    assert_eq!(code(db.migrate_schema(schema).await), ErrorCode::IoError);
+    assert_eq!(
+        code(db.query("select * from Metric;", &[]).await),
+        ErrorCode::UndefinedTable
+    );
+
+    std::fs::remove_dir(&data_file).unwrap();
+    assert_eq!(db.apply_schema(schema).await.unwrap(), vec!["Metric"]);
+    db.execute("insert into Metric (Level) values ('high');", &[])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_composite_keys() {
+    let db = open("composite_keys");
+    db.apply_schema(
+        "
+        model Bonus {
+            BonusID Int @id
+            EmployeeID Int?
+            FromDate Date?
+            @@references([EmployeeID, FromDate], SalaryHistory)
+        }
+        model SalaryHistory {
+            EmployeeID Int
+            FromDate Date
+            Amount Int
+            @@id([EmployeeID, FromDate])
+        }",
+    )
+    .await
+    .unwrap();
+
+    let salary = "insert into SalaryHistory (EmployeeID, FromDate, Amount) values ($1, $2, $3);";
+    db.execute(salary, &[json!(1), json!("2020-01-01"), json!(100)])
+        .await
+        .unwrap();
+    db.execute(salary, &[json!(1), json!("2021-01-01"), json!(110)])
+        .await
+        .unwrap();
+    let error = db
+        .execute(salary, &[json!(1), json!("2020-01-01"), json!(120)])
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::UniqueViolation);
+    assert_eq!(
+        error.message(),
+        "Duplicate key (EmployeeID, FromDate)=(1, 2020-01-01) violates unique constraint salaryhistory_pkey of table SalaryHistory"
+    );
+
+    let bonus = "insert into Bonus (BonusID, EmployeeID, FromDate) values ($1, $2, $3);";
+    db.execute(bonus, &[json!(1), json!(1), json!("2021-01-01")])
+        .await
+        .unwrap();
+    // A key with a NULL references nothing.
+    db.execute(
+        "insert into Bonus (BonusID, EmployeeID) values (2, 1);",
+        &[],
+    )
+    .await
+    .unwrap();
+    let error = db
+        .execute(bonus, &[json!(3), json!(2), json!("2021-01-01")])
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ForeignKeyViolation);
+    assert_eq!(
+        error.message(),
+        "Key (EmployeeID, FromDate)=(2, 2021-01-01) of table Bonus violates foreign key constraint bonus_employeeid_fromdate_fkey: it is not present in table SalaryHistory"
+    );
+    assert_eq!(
+        code(
+            db.execute(
+                "update Bonus set FromDate = '2022-01-01' where BonusID = 1;",
+                &[]
+            )
+            .await
+        ),
+        ErrorCode::ForeignKeyViolation
+    );
+    assert_eq!(
+        code(
+            db.execute(
+                "delete from SalaryHistory where FromDate = '2021-01-01';",
+                &[]
+            )
+            .await
+        ),
+        ErrorCode::ForeignKeyViolation
+    );
+    let result = db
+        .execute(
+            "delete from SalaryHistory where FromDate = '2020-01-01';",
+            &[],
+        )
+        .await;
+    assert!(matches!(result, Ok(QueryResult::Delete(1))));
+
+    // Later schemas reference tables of the catalogue, by their whole key.
+    let raise = |reference: &str| {
+        format!(
+            "model Raise {{ RaiseID Int @id EmployeeID Int FromDate Date {} }}",
+            reference
+        )
+    };
+    assert_eq!(
+        code(
+            db.apply_schema(&raise("@@references([EmployeeID], SalaryHistory)"))
+                .await
+        ),
+        ErrorCode::InvalidForeignKey
+    );
+    db.apply_schema(&raise(
+        "@@references([EmployeeID, FromDate], SalaryHistory)",
+    ))
+    .await
+    .unwrap();
+    let catalogue = db.catalogue().await;
+    let table = catalogue.get_table("SalaryHistory").unwrap();
+    let key: Vec<&str> = table
+        .primary_key()
+        .iter()
+        .map(|column| column.name())
+        .collect();
+    assert_eq!(key, ["EmployeeID", "FromDate"]);
+}
+
+#[tokio::test]
+async fn test_single_column_key_catalogue_loads() {
+    let path =
+        std::env::temp_dir().join(format!("adaptivedb-it-legacy_key-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    // Catalogues stored the position of a single primary key column.
+    std::fs::write(
+        path.join("catalogue.json"),
+        r#"{"tables": [{"name": "T", "primary_key": 0, "columns": [{"name": "TID", "data_type": "Int", "nullable": false}]}]}"#,
+    )
+    .unwrap();
+    let db = Database::open(&path).unwrap();
+    db.execute("insert into T (TID) values (1);", &[])
+        .await
+        .unwrap();
+    assert_eq!(
+        code(db.execute("insert into T (TID) values (1);", &[]).await),
+        ErrorCode::UniqueViolation
+    );
+}
+
+/// The `LastName` of every row of a select, in order.
+async fn last_names(db: &Database, query: &str) -> Vec<String> {
+    db.query(query, &[])
+        .await
+        .unwrap()
+        .map(|record| record.get("LastName").unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_views() {
+    let db = open("views");
+    db.apply_schema(SCHEMA).await.unwrap();
+    db.apply_schema("model Department {\n  DepartmentID Int @id\n  Name String\n}")
+        .await
+        .unwrap();
+    for statement in [
+        "insert into Department (DepartmentID, Name) values (7, 'Sales');",
+        "insert into Employee (EmployeeID, LastName, DepartmentID, HireDate) values (1, 'Doe', 7, '2021-01-01');",
+        "insert into Employee (EmployeeID, LastName, DepartmentID, HireDate) values (2, 'Roe', 8, '2022-01-01');",
+        "create view Sales as select EmployeeID, LastName, DepartmentID from Employee where DepartmentID = 7;",
+        "create materialized view Hires as select LastName, HireDate from Employee;",
+        "create incremental materialized view Staff as select Employee.LastName, Department.Name from Employee join Department on Employee.DepartmentID = Department.DepartmentID;",
+    ] {
+        db.execute(statement, &[]).await.unwrap();
+    }
+
+    // Plain views are expanded into their select.
+    assert_eq!(
+        last_names(&db, "select LastName from Sales;").await,
+        ["Doe"]
+    );
+    assert_eq!(
+        last_names(&db, "select * from Sales where EmployeeID = 2;").await,
+        Vec::<String>::new()
+    );
+    let rows = db
+        .query(
+            "select LastName as Employee, Name from Sales join Department on DepartmentID = Department.DepartmentID;",
+            &[],
+        )
+        .await
+        .unwrap();
+    let columns: Vec<&str> = rows
+        .columns()
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(columns, ["Employee", "Name"]);
+    assert_eq!(
+        code(db.execute("select Name from Sales;", &[]).await),
+        ErrorCode::UndefinedColumn
+    );
+    assert_eq!(
+        code(db.execute("delete from Sales;", &[]).await),
+        ErrorCode::WrongObjectType
+    );
+
+    // Manual views only change when refreshed.
+    db.execute(
+        "insert into Employee (EmployeeID, LastName, DepartmentID, HireDate) values (3, 'Poe', 7, '2023-01-01');",
+        &[],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        last_names(&db, "select * from Hires;").await,
+        ["Doe", "Roe"]
+    );
+    db.execute("refresh materialized view Hires;", &[])
+        .await
+        .unwrap();
+    assert_eq!(
+        last_names(&db, "select * from Hires;").await,
+        ["Doe", "Roe", "Poe"]
+    );
+    assert_eq!(
+        code(db.execute("refresh materialized view Sales;", &[]).await),
+        ErrorCode::WrongObjectType
+    );
+
+    // Incremental views follow every write.
+    assert_eq!(
+        last_names(&db, "select LastName from Staff;").await,
+        ["Doe", "Poe"]
+    );
+    db.execute(
+        "update Employee set DepartmentID = 7 where EmployeeID = 2;",
+        &[],
+    )
+    .await
+    .unwrap();
+    db.execute("delete from Employee where EmployeeID = 1;", &[])
+        .await
+        .unwrap();
+    assert_eq!(
+        last_names(&db, "select LastName from Staff;").await,
+        ["Poe", "Roe"]
+    );
+    db.execute("update Department set Name = 'Ops';", &[])
+        .await
+        .unwrap();
+    let rows = db.query("select Name from Staff;", &[]).await.unwrap();
+    let names: Vec<String> = rows.map(|record| record.get("Name").unwrap()).collect();
+    assert_eq!(names, ["Ops", "Ops"]);
+}
+
+/// The rows of the view Totals, ordered by region.
+async fn totals(db: &Database) -> Vec<String> {
+    let rows = db.query("select * from Totals;", &[]).await.unwrap();
+    let mut totals: Vec<String> = rows
+        .map(|record| {
+            let region: Option<String> = record.get("Region").unwrap();
+            let price: Option<String> = record.get("Price").unwrap();
+            let counts: Vec<String> = ["Sales", "Priced", "Units", "Least", "Most"]
+                .iter()
+                .map(|column| record.get::<i64>(column).unwrap().to_string())
+                .collect();
+            format!(
+                "{} {} {}",
+                region.unwrap_or("NULL".to_string()),
+                counts.join(" "),
+                price.unwrap_or("NULL".to_string())
+            )
+        })
+        .collect();
+    totals.sort();
+    totals
+}
+
+/// The sales and revenue of the view Overall.
+async fn overall(db: &Database) -> (i64, Option<String>) {
+    let mut rows = db.query("select * from Overall;", &[]).await.unwrap();
+    let record = rows.next().unwrap();
+    (record.get("Sales").unwrap(), record.get("Revenue").unwrap())
+}
+
+#[tokio::test]
+async fn test_aggregate_views() {
+    let db = open("aggregate_views");
+    db.apply_schema(
+        "model Sale {\n  SaleID Int @id\n  Region String?\n  Units Int\n  Price Decimal(8, 2)?\n}",
+    )
+    .await
+    .unwrap();
+    for statement in [
+        "insert into Sale (SaleID, Region, Units, Price) values (1, 'North', 2, '1.50');",
+        "insert into Sale (SaleID, Region, Units, Price) values (2, 'North', 5, '2.25');",
+        "insert into Sale (SaleID, Region, Units) values (3, 'South', 1);",
+        "create incremental materialized view Totals as select Region, count(*) as Sales, count(Price) as Priced, sum(Units) as Units, min(Units) as Least, max(Units) as Most, avg(Price) as Price from Sale group by Region;",
+        "create materialized view Overall as select count(*) as Sales, sum(Price) as Revenue from Sale;",
+    ] {
+        db.execute(statement, &[]).await.unwrap();
+    }
+    assert_eq!(
+        totals(&db).await,
+        ["North 2 2 7 2 5 1.88", "South 1 0 1 1 1 NULL"]
+    );
+
+    // Incremental views recompute the groups a write touches.
+    for statement in [
+        "insert into Sale (SaleID, Region, Units, Price) values (4, 'South', 3, '4.00');",
+        "update Sale set Units = 1 where SaleID = 2;",
+        "delete from Sale where SaleID = 1;",
+        "insert into Sale (SaleID, Units) values (5, 6);",
+    ] {
+        db.execute(statement, &[]).await.unwrap();
+    }
+    assert_eq!(
+        totals(&db).await,
+        [
+            "NULL 1 0 6 6 6 NULL",
+            "North 1 1 1 1 1 2.25",
+            "South 2 1 4 1 3 4.00"
+        ]
+    );
+    db.execute("delete from Sale where Region = 'South';", &[])
+        .await
+        .unwrap();
+    assert_eq!(
+        totals(&db).await,
+        ["NULL 1 0 6 6 6 NULL", "North 1 1 1 1 1 2.25"]
+    );
+
+    // A select without group by has a row even when there are no rows.
+    assert_eq!(overall(&db).await, (3, Some("3.75".to_string())));
+    db.execute("delete from Sale;", &[]).await.unwrap();
+    db.execute("refresh materialized view Overall;", &[])
+        .await
+        .unwrap();
+    assert_eq!(overall(&db).await, (0, None));
+    assert_eq!(totals(&db).await, Vec::<String>::new());
+
+    for (statement, expected) in [
+        (
+            "select Region, count(*) from Sale group by Region;",
+            ErrorCode::FeatureNotSupported,
+        ),
+        (
+            "create view Counts as select count(*) from Sale;",
+            ErrorCode::FeatureNotSupported,
+        ),
+        (
+            "create materialized view Bad as select Region, Units, count(*) from Sale group by Region;",
+            ErrorCode::GroupingError,
+        ),
+        (
+            "create materialized view Bad as select sum(Region) from Sale;",
+            ErrorCode::DatatypeMismatch,
+        ),
+        (
+            "create incremental materialized view Bad as select count(*) from Sale group by Region;",
+            ErrorCode::FeatureNotSupported,
+        ),
+    ] {
+        assert_eq!(code(db.execute(statement, &[]).await), expected, "{}", statement);
+    }
+}
+
+#[tokio::test]
+async fn test_triggers() {
+    let db = open("triggers");
+    db.apply_schema(SCHEMA).await.unwrap();
+    db.apply_schema(
+        "model Audit {\n  AuditID Int @id @default(autoincrement())\n  EmployeeID Int\n  OldName String?\n  NewName String\n}\nmodel Chain {\n  ChainID Int @id @default(autoincrement())\n  N Int\n}",
+    )
+    .await
+    .unwrap();
+    db.execute("create trigger audit after update on Employee for each row begin insert into Audit (EmployeeID, OldName, NewName) values (old.EmployeeID, old.LastName, new.LastName); end;", &[])
+        .await
+        .unwrap();
+    db.register_trigger(
+        "Employee",
+        TriggerTiming::Before,
+        TriggerEvent::Insert,
+        |change| {
+            let name: String = change.new_row().unwrap().get("LastName")?;
+            if name == "Nobody" {
+                return Err(Error::new(
+                    ErrorCode::CheckViolation,
+                    "Employees need a name",
+                ));
+            }
+            Ok(())
+        },
+    );
+
+    let insert =
+        "insert into Employee (EmployeeID, LastName, HireDate) values ($1, $2, '2021-01-01');";
+    db.execute(insert, &[json!(1), json!("Doe")]).await.unwrap();
+    let result = db.execute(insert, &[json!(2), json!("Nobody")]).await;
+    assert_eq!(code(result), ErrorCode::CheckViolation);
+    assert_eq!(
+        last_names(&db, "select LastName from Employee;").await,
+        ["Doe"]
+    );
+
+    // SQL triggers read the old and new row.
+    db.execute(
+        "update Employee set LastName = 'Roe' where EmployeeID = 1;",
+        &[],
+    )
+    .await
+    .unwrap();
+    let audit: Vec<(i64, String, String)> = db
+        .query("select * from Audit;", &[])
+        .await
+        .unwrap()
+        .map(|record| {
+            (
+                record.get("AuditID").unwrap(),
+                record.get("OldName").unwrap(),
+                record.get("NewName").unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(audit, [(1, "Doe".to_string(), "Roe".to_string())]);
+
+    // A failing trigger undoes the write and the writes of other triggers.
+    db.execute("create trigger gone after delete on Employee begin insert into Audit (AuditID, EmployeeID, NewName) values (1, old.EmployeeID, 'gone'); end;", &[])
+        .await
+        .unwrap();
+    let result = db.execute("delete from Employee;", &[]).await;
+    assert_eq!(code(result), ErrorCode::UniqueViolation);
+    assert_eq!(
+        last_names(&db, "select LastName from Employee;").await,
+        ["Roe"]
+    );
+    assert_eq!(
+        db.query("select * from Audit;", &[]).await.unwrap().count(),
+        1
+    );
+    assert_eq!(db.changes(0, 10).unwrap().len(), 3);
+
+    // Statements comparing with or assigning a NULL fail the write.
+    db.execute("create trigger moved after update on Employee begin update Chain set N = new.DepartmentID; end;", &[])
+        .await
+        .unwrap();
+    let result = db
+        .execute(
+            "update Employee set LastName = 'Moe' where EmployeeID = 1;",
+            &[],
+        )
+        .await;
+    assert_eq!(code(result), ErrorCode::NullParameter);
+    assert_eq!(
+        last_names(&db, "select LastName from Employee;").await,
+        ["Roe"]
+    );
+
+    // Triggers that fire each other stop at a limit.
+    db.execute("create trigger chain after insert on Chain begin insert into Chain (N) values (new.N); end;", &[])
+        .await
+        .unwrap();
+    let result = db.execute("insert into Chain (N) values (1);", &[]).await;
+    assert_eq!(code(result), ErrorCode::ProgramLimitExceeded);
+    assert_eq!(
+        db.query("select * from Chain;", &[]).await.unwrap().count(),
+        0
+    );
+
+    for (statement, expected) in [
+        (
+            "create trigger audit after insert on Chain begin delete from Chain; end;",
+            ErrorCode::DuplicateObject,
+        ),
+        (
+            "create trigger t after insert on Missing begin delete from Chain; end;",
+            ErrorCode::UndefinedTable,
+        ),
+        (
+            "create trigger t after insert on Chain begin delete from Chain where N = old.N; end;",
+            ErrorCode::UndefinedColumn,
+        ),
+        (
+            "create trigger t after insert on Chain begin select * from Chain; end;",
+            ErrorCode::FeatureNotSupported,
+        ),
+    ] {
+        assert_eq!(
+            code(db.execute(statement, &[]).await),
+            expected,
+            "{}",
+            statement
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_change_log() {
+    let db = open("change_log");
+    db.apply_schema(SCHEMA).await.unwrap();
+    db.apply_schema(
+        "model Audit {\n  AuditID Int @id @default(autoincrement())\n  EmployeeID Int\n}",
+    )
+    .await
+    .unwrap();
+    db.execute("create trigger audit after delete on Employee begin insert into Audit (EmployeeID) values (old.EmployeeID); end;", &[])
+        .await
+        .unwrap();
+    for statement in [
+        "insert into Employee (EmployeeID, LastName, HireDate) values (1, 'Doe', '2021-01-01');",
+        "insert into Employee (EmployeeID, LastName, HireDate) values (2, 'Roe', '2022-01-01');",
+        "update Employee set LastName = 'Poe' where EmployeeID = 1;",
+        "delete from Employee where EmployeeID = 2;",
+        "select * from Employee;",
+    ] {
+        db.execute(statement, &[]).await.unwrap();
+    }
+    // Failed statements leave nothing in the log.
+    let insert =
+        "insert into Employee (EmployeeID, LastName, HireDate) values (1, 'Doe', '2021-01-01');";
+    assert_eq!(
+        code(db.execute(insert, &[]).await),
+        ErrorCode::UniqueViolation
+    );
+
+    let changes = db.changes(0, 100).unwrap();
+    let summary: Vec<(u64, u64, &str, Operation)> = changes
+        .iter()
+        .map(|change| {
+            (
+                change.lsn,
+                change.transaction,
+                change.table.as_str(),
+                change.op,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (1, 1, "Employee", Operation::Insert),
+            (2, 2, "Employee", Operation::Insert),
+            (3, 3, "Employee", Operation::Update),
+            (4, 4, "Employee", Operation::Delete),
+            (5, 4, "Audit", Operation::Insert),
+        ]
+    );
+    assert_eq!(changes[2].before.as_ref().unwrap()["LastName"], "Doe");
+    assert_eq!(changes[2].after.as_ref().unwrap()["LastName"], "Poe");
+    assert!(changes[3].after.is_none());
+    assert_eq!(changes[4].committed_at, changes[3].committed_at);
+
+    // Readers resume after the last change they saw.
+    let lsns: Vec<u64> = db
+        .changes(3, 1)
+        .unwrap()
+        .iter()
+        .map(|change| change.lsn)
+        .collect();
+    assert_eq!(lsns, [4]);
+    let waiting = db.wait_for_changes(5);
+    db.execute("delete from Audit;", &[]).await.unwrap();
+    waiting.await;
+    assert_eq!(db.changes(5, 100).unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_subscriptions() {
+    let db = open("subscriptions");
+    db.apply_schema(SCHEMA).await.unwrap();
+    let insert = "insert into Employee (EmployeeID, LastName, DepartmentID, HireDate) values ($1, $2, $3, '2021-01-01');";
+    db.execute(insert, &[json!(1), json!("Doe"), json!(7)])
+        .await
+        .unwrap();
+    let (mut subscription, rows) = Subscription::new(
+        &db,
+        "select EmployeeID, LastName from Employee where DepartmentID = $1;",
+        &[json!(7)],
+    )
+    .await
+    .unwrap();
+    assert_eq!(rows, [row(&[("EmployeeID", "1"), ("LastName", "Doe")])]);
+
+    for statement in [
+        // Outside the result.
+        "insert into Employee (EmployeeID, LastName, DepartmentID, HireDate) values (2, 'Roe', 8, '2021-01-01');",
+        // Moves into it.
+        "update Employee set DepartmentID = 7 where EmployeeID = 2;",
+        // Changes a column the select does not read.
+        "update Employee set HireDate = '2022-01-01' where EmployeeID = 1;",
+        "update Employee set LastName = 'Poe' where EmployeeID = 1;",
+        "delete from Employee where EmployeeID = 2;",
+    ] {
+        db.execute(statement, &[]).await.unwrap();
+    }
+    let transactions = subscription.poll(&db).await.unwrap();
+    let diffs: Vec<&Diff> = transactions
+        .iter()
+        .flat_map(|transaction| transaction.diffs.iter())
+        .collect();
+    assert_eq!(
+        diffs,
+        [
+            &Diff::Insert {
+                row: row(&[("EmployeeID", "2"), ("LastName", "Roe")])
+            },
+            &Diff::Update {
+                before: row(&[("EmployeeID", "1"), ("LastName", "Doe")]),
+                after: row(&[("EmployeeID", "1"), ("LastName", "Poe")]),
+            },
+            &Diff::Delete {
+                row: row(&[("EmployeeID", "2"), ("LastName", "Roe")])
+            },
+        ]
+    );
+    assert_eq!(transactions.len(), 3);
+    assert!(subscription.poll(&db).await.unwrap().is_empty());
+
+    db.apply_schema("model Department {\n  DepartmentID Int @id\n}")
+        .await
+        .unwrap();
+    let result = Subscription::new(
+        &db,
+        "select * from Employee join Department on Employee.DepartmentID = Department.DepartmentID;",
+        &[],
+    )
+    .await;
+    assert_eq!(
+        result.err().map(|error| error.code()),
+        Some(ErrorCode::FeatureNotSupported)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_change_log_order_under_concurrent_writes() {
+    let db = std::sync::Arc::new(open("change_log_order"));
+    db.apply_schema("model C {\n  Id Int @id\n  N Int\n}")
+        .await
+        .unwrap();
+    db.execute("insert into C (Id, N) values (1, 0);", &[])
+        .await
+        .unwrap();
+    let mut updates = Vec::new();
+    for i in 1..=200 {
+        let db = db.clone();
+        updates.push(tokio::spawn(async move {
+            db.execute("update C set N = $1 where Id = 1;", &[json!(i)])
+                .await
+                .unwrap();
+        }));
+    }
+    for update in updates {
+        update.await.unwrap();
+    }
+
+    // Every change starts from the row the change before it left.
+    let changes = db.changes(0, 1000).unwrap();
+    assert_eq!(changes.len(), 201);
+    for pair in changes.windows(2) {
+        assert_eq!(pair[1].before, pair[0].after, "change {}", pair[1].lsn);
+    }
+    let rows = db.query("select N from C;", &[]).await.unwrap();
+    let n: Vec<i64> = rows.map(|record| record.get("N").unwrap()).collect();
+    assert_eq!(
+        json!(n[0].to_string()),
+        changes.last().unwrap().after.as_ref().unwrap()["N"]
+    );
+}
+#[tokio::test]
+async fn test_time_travel() {
+    let db = open("time_travel");
+    db.apply_schema(SCHEMA).await.unwrap();
+    for statement in [
+        "insert into Employee (EmployeeID, LastName, HireDate) values (1, 'Doe', '2021-01-01');",
+        "insert into Employee (EmployeeID, LastName, HireDate) values (2, 'Roe', '2022-01-01');",
+        "update Employee set LastName = 'Poe' where EmployeeID = 1;",
+        "delete from Employee where EmployeeID = 2;",
+    ] {
+        db.execute(statement, &[]).await.unwrap();
+    }
+    let names = |sql: &'static str| {
+        let db = &db;
+        async move {
+            let rows = db.query(sql, &[]).await.unwrap();
+            let mut names: Vec<String> = rows
+                .map(|record| record.get::<String>("LastName").unwrap())
+                .collect();
+            names.sort();
+            names
+        }
+    };
+    assert_eq!(
+        names("select LastName from Employee as of transaction 1;").await,
+        ["Doe"]
+    );
+    assert_eq!(
+        names("select LastName from Employee as of transaction 3;").await,
+        ["Poe", "Roe"]
+    );
+    assert!(names("select LastName from Employee as of transaction 0;")
+        .await
+        .is_empty());
+    assert_eq!(
+        names("select LastName from Employee as of transaction 4 where EmployeeID = 1;").await,
+        ["Poe"]
+    );
+
+    // A timestamp reads what had committed by then.
+    let committed_at = db.changes(0, 100).unwrap()[1].committed_at.clone();
+    let rows = db
+        .query(
+            &format!(
+                "select LastName from Employee as of timestamp '{}' where EmployeeID = 2;",
+                committed_at
+            ),
+            &[],
+        )
+        .await
+        .unwrap();
+    assert_eq!(rows.count(), 1);
+    assert_eq!(
+        code(
+            db.query("select * from Employee as of transaction 5;", &[])
+                .await
+        ),
+        ErrorCode::InvalidParameter
+    );
+    assert_eq!(
+        code(
+            db.query("select * from Employee as of timestamp 'yesterday';", &[])
+                .await
+        ),
+        ErrorCode::InvalidTextRepresentation
+    );
+
+    // History older than the retention of a table is gone.
+    db.apply_schema(
+        "model Salary {\n  EmployeeID Int @id\n  Amount Int\n  @@retention(\"1 second\")\n}",
+    )
+    .await
+    .unwrap();
+    db.execute(
+        "insert into Salary (EmployeeID, Amount) values (1, 100);",
+        &[],
+    )
+    .await
+    .unwrap();
+    db.execute("update Salary set Amount = 200 where EmployeeID = 1;", &[])
+        .await
+        .unwrap();
+    let sql = "select Amount from Salary as of transaction 5;";
+    assert_eq!(db.query(sql, &[]).await.unwrap().count(), 1);
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    assert_eq!(code(db.query(sql, &[]).await), ErrorCode::SnapshotTooOld);
+    assert_eq!(db.prune_history().await.unwrap(), 1);
+    assert_eq!(db.changes(0, 100).unwrap().len(), 5);
+    assert_eq!(
+        names("select LastName from Employee as of transaction 1;").await,
+        ["Doe"]
+    );
+}
+
+#[tokio::test]
+async fn test_temporal_tables() {
+    let db = open("temporal_tables");
+    db.apply_schema("model Salary {\n  EmployeeID Int @id\n  Amount Int\n  @@temporal\n}")
+        .await
+        .unwrap();
+    db.execute(
+        "insert into Salary (EmployeeID, Amount) values (1, 100);",
+        &[],
+    )
+    .await
+    .unwrap();
+    db.execute(
+        "insert into Salary (EmployeeID, Amount) values (2, 500);",
+        &[],
+    )
+    .await
+    .unwrap();
+    let versions = |sql: String| {
+        let db = &db;
+        async move {
+            let rows = db.query(&sql, &[]).await.unwrap();
+            let mut versions: Vec<(i64, String, Option<String>)> = rows
+                .map(|record| {
+                    (
+                        record.get::<i64>("Amount").unwrap(),
+                        record.get::<String>("valid_from").unwrap(),
+                        record.get::<Option<String>>("valid_to").unwrap(),
+                    )
+                })
+                .collect();
+            versions.sort();
+            versions
+        }
+    };
+    let inserted = versions("select * from Salary where EmployeeID = 1;".to_string()).await;
+    assert_eq!(inserted.len(), 1);
+    assert_eq!(inserted[0].2, None);
+    let inserted_at = inserted[0].1.clone();
+
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    db.execute("update Salary set Amount = 200 where EmployeeID = 1;", &[])
+        .await
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    db.execute("delete from Salary where EmployeeID = 2;", &[])
+        .await
+        .unwrap();
+
+    // The table holds the current versions, the history table the replaced ones.
+    let current = versions("select * from Salary;".to_string()).await;
+    assert_eq!(current.len(), 1);
+    let (amount, updated_at, valid_to) = current[0].clone();
+    assert_eq!((amount, valid_to), (200, None));
+    assert!(updated_at > inserted_at);
+    let history = versions("select * from SalaryHistory;".to_string()).await;
+    assert_eq!(history.len(), 2);
+    assert_eq!(
+        history[0],
+        (100, inserted_at.clone(), Some(updated_at.clone()))
+    );
+    assert_eq!(history[1].0, 500);
+    let deleted_at = history[1].2.clone().unwrap();
+
+    // Every version current at some time of the period.
+    let between = |from: &str, to: &str| {
+        format!(
+            "select * from Salary for system_time between '{}' and '{}' where EmployeeID = 1;",
+            from, to
+        )
+    };
+    let amounts = |versions: Vec<(i64, String, Option<String>)>| {
+        versions
+            .into_iter()
+            .map(|(amount, _, _)| amount)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        amounts(versions(between(&inserted_at, &inserted_at)).await),
+        [100]
+    );
+    assert_eq!(
+        amounts(versions(between(&inserted_at, &updated_at)).await),
+        [100, 200]
+    );
+    assert_eq!(
+        amounts(versions(between(&updated_at, &deleted_at)).await),
+        [200]
+    );
+    assert!(versions(between("2000-01-01", "2000-12-31"))
+        .await
+        .is_empty());
+
+    // System columns and history tables are written by the database alone.
+    for (statement, expected) in [
+        (
+            "insert into Salary (EmployeeID, Amount, valid_from) values (3, 1, '2020-01-01');",
+            ErrorCode::GeneratedAlways,
+        ),
+        (
+            "update Salary set valid_to = '2020-01-01' where EmployeeID = 1;",
+            ErrorCode::GeneratedAlways,
+        ),
+        (
+            "insert into SalaryHistory (EmployeeID, Amount) values (3, 1);",
+            ErrorCode::WrongObjectType,
+        ),
+        ("delete from SalaryHistory;", ErrorCode::WrongObjectType),
+    ] {
+        assert_eq!(code(db.execute(statement, &[]).await), expected);
+    }
+    db.apply_schema(SCHEMA).await.unwrap();
+    assert_eq!(
+        code(
+            db.query(
+                "select * from Employee for system_time between '2020-01-01' and '2021-01-01';",
+                &[]
+            )
+            .await
+        ),
+        ErrorCode::WrongObjectType
+    );
+}
+#[tokio::test]
+async fn test_backup_and_restore() {
+    let db = open("backup");
+    db.apply_schema(
+        "model Ticket {\n  TicketID Int @id @default(autoincrement())\n  Title String\n}",
+    )
+    .await
+    .unwrap();
+    db.execute("insert into Ticket (Title) values ('before');", &[])
+        .await
+        .unwrap();
+    let dir = |name: &str| {
+        let path = std::env::temp_dir().join(format!(
+            "adaptivedb-it-backup-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    };
+    let backup_dir = dir("backup");
+    let backup = db.backup(&backup_dir).await.unwrap();
+    assert_eq!(backup.lsn, 1);
+    assert_eq!(
+        code(db.backup(&backup_dir).await),
+        ErrorCode::InvalidParameter
+    );
+
+    // Changes committed after the backup are archived to it.
+    db.execute("insert into Ticket (Title) values ('first');", &[])
+        .await
+        .unwrap();
+    db.execute(
+        "update Ticket set Title = 'second' where TicketID = 2;",
+        &[],
+    )
+    .await
+    .unwrap();
+    assert_eq!(db.archive_changes().unwrap(), 2);
+    assert_eq!(db.archive_changes().unwrap(), 0);
+    db.execute("delete from Ticket where TicketID = 1;", &[])
+        .await
+        .unwrap();
+    assert_eq!(db.archive_changes().unwrap(), 1);
+
+    let titles = |db: Database| async move {
+        let rows = db.query("select Title from Ticket;", &[]).await.unwrap();
+        let mut titles: Vec<String> = rows
+            .map(|record| record.get::<String>("Title").unwrap())
+            .collect();
+        titles.sort();
+        titles
+    };
+    let restored_dir = dir("all");
+    let restored = Database::restore(&backup_dir, &restored_dir, None)
+        .await
+        .unwrap();
+    assert_eq!((restored.lsn, restored.replayed), (4, 3));
+    let restored = Database::open(&restored_dir).unwrap();
+    assert_eq!(restored.changes(0, 10).unwrap().len(), 4);
+    // The sequence continues after the restored rows.
+    restored
+        .execute("insert into Ticket (Title) values ('third');", &[])
+        .await
+        .unwrap();
+    assert_eq!(titles(restored).await, ["second", "third"]);
+
+    // A restore point replays the transactions up to it.
+    let restored_dir = dir("lsn");
+    Database::restore(&backup_dir, &restored_dir, Some("2"))
+        .await
+        .unwrap();
+    assert_eq!(
+        titles(Database::open(&restored_dir).unwrap()).await,
+        ["before", "first"]
+    );
+    let committed_at = db.changes(0, 10).unwrap()[2].committed_at.clone();
+    let restored_dir = dir("timestamp");
+    Database::restore(&backup_dir, &restored_dir, Some(&committed_at))
+        .await
+        .unwrap();
+    assert_eq!(
+        titles(Database::open(&restored_dir).unwrap()).await,
+        ["before", "second"]
+    );
+
+    assert_eq!(
+        code(Database::restore(&backup_dir, &restored_dir, None).await),
+        ErrorCode::InvalidParameter
+    );
+    assert_eq!(
+        code(Database::restore(&backup_dir, dir("early"), Some("2000-01-01")).await),
+        ErrorCode::InvalidParameter
+    );
+    assert_eq!(
+        code(Database::restore(&backup_dir, dir("invalid"), Some("yesterday")).await),
+        ErrorCode::InvalidParameter
+    );
+
+    // Archived changes that do not match the rows fail the restore.
+    let log = backup_dir.join("changes.log");
+    let archived: Vec<String> = std::fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            if line.contains("\"update\"") {
+                line.replace("\"first\"", "\"other\"")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    std::fs::write(&log, archived.join("\n")).unwrap();
+    let restored_dir = dir("diverged");
+    assert_eq!(
+        code(Database::restore(&backup_dir, &restored_dir, None).await),
+        ErrorCode::DataCorrupted
+    );
+    assert!(!restored_dir.exists());
+}
+
+/// A row with the given canonical values.
+fn row(values: &[(&str, &str)]) -> serde_json::Map<String, serde_json::Value> {
+    values
+        .iter()
+        .map(|(column, value)| (column.to_string(), json!(value)))
+        .collect()
+}